@@ -8,9 +8,29 @@
 
 // 声明 `task_handler` 子模块，该模块包含了任务相关 API 的具体实现。
 pub mod task_handler;
+// 声明 `auth_handler` 子模块，提供登录 (用户名密码校验与访问令牌签发) 接口。
+pub mod auth_handler;
 
 // 预留注释：后续随着项目功能的扩展，可能会在这里添加更多的 handler 子模块，
 // 例如：
 // pub mod project_handler; // 用于处理项目管理相关的 API
-// pub mod user_handler;    // 用于处理用户认证和管理相关的 API
-// 等等。 
\ No newline at end of file
+// 等等。
+
+use anyhow::Context;
+use axum::Router;
+use log::info;
+
+/// 在指定地址上启动 HTTP API 服务，并持续运行直到出错。
+///
+/// # 参数
+/// * `addr`: 监听地址字符串，例如 "127.0.0.1:8089"。
+/// * `router`: 已组装好的 axum 路由 (例如 `auth_handler::auth_router` 的返回值)。
+pub async fn start_api_server(addr: String, router: Router) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("[HTTP API] 绑定地址 '{}' 失败", addr))?;
+    info!("[HTTP API] HTTP API 服务正在监听地址: {}", addr);
+    axum::serve(listener, router)
+        .await
+        .with_context(|| format!("[HTTP API] 地址 '{}' 上的 HTTP API 服务异常终止", addr))
+}
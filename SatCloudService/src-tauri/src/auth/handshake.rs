@@ -0,0 +1,93 @@
+// SatCloudService/src-tauri/src/auth/handshake.rs
+
+//! 基于访问令牌的 WebSocket 握手认证器。
+
+use crate::auth::token::TokenService;
+use log::warn;
+use rust_websocket_utils::server::auth::{
+    extract_bearer_token, AuthenticatedPrincipal, HandshakeAuthenticator, HandshakeRejection,
+};
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+/// 在 WebSocket 握手阶段校验访问令牌的认证器。
+///
+/// 令牌可以通过 `Authorization: Bearer <token>` 请求头或 `token` 查询参数传递。
+/// - 令牌有效：握手通过，令牌中的用户信息成为连接的已认证主体。
+/// - 令牌无效或过期：握手以 401 拒绝。
+/// - 未携带令牌：`allow_anonymous` 为 `false` 时以 401 拒绝，否则作为匿名连接接受
+///   (匿名连接只能以 `Observer` 角色注册，见 `ConnectionManager::join_group`)。
+#[derive(Debug)]
+pub struct TokenHandshakeAuthenticator {
+    token_service: Arc<TokenService>,
+    allow_anonymous: bool,
+}
+
+impl TokenHandshakeAuthenticator {
+    /// 创建认证器。
+    pub fn new(token_service: Arc<TokenService>, allow_anonymous: bool) -> Self {
+        Self { token_service, allow_anonymous }
+    }
+}
+
+impl HandshakeAuthenticator for TokenHandshakeAuthenticator {
+    fn authenticate(&self, request: &Request) -> Result<Option<AuthenticatedPrincipal>, HandshakeRejection> {
+        match extract_bearer_token(request) {
+            Some(token) => match self.token_service.verify(&token) {
+                Ok(claims) => Ok(Some(claims.to_principal())),
+                Err(e) => {
+                    warn!("[握手认证] 访问令牌校验失败: {}", e);
+                    Err(HandshakeRejection::Unauthorized("访问令牌无效或已过期，请重新登录".to_string()))
+                }
+            },
+            None if !self.allow_anonymous => {
+                Err(HandshakeRejection::Unauthorized("缺少访问令牌，请先登录".to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::UserAccount;
+    use chrono::{Duration, Utc};
+    use common_models::enums::{ClientRole, UserRole};
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(()).unwrap()
+    }
+
+    #[test]
+    fn test_token_handshake_authenticator() {
+        let token_service = Arc::new(TokenService::new(b"secret", Duration::hours(1)));
+        let account = UserAccount {
+            user_id: "u-1".to_string(),
+            username: "field".to_string(),
+            roles: vec![UserRole::FieldEngineer],
+            allowed_projects: vec!["*".to_string()],
+            is_active: true,
+            created_at: Utc::now(),
+        };
+        let (token, _) = token_service.issue(&account).unwrap();
+
+        let strict = TokenHandshakeAuthenticator::new(token_service.clone(), false);
+        let principal = strict
+            .authenticate(&request(&format!("/ws?token={}", token)))
+            .expect("有效令牌应通过认证")
+            .expect("应附带已认证主体");
+        assert_eq!(principal.user_id, "u-1");
+        assert_eq!(principal.allowed_roles, vec![ClientRole::OnSiteMobile]);
+
+        assert!(matches!(strict.authenticate(&request("/ws")), Err(HandshakeRejection::Unauthorized(_))));
+        assert!(matches!(
+            strict.authenticate(&request("/ws?token=garbage")),
+            Err(HandshakeRejection::Unauthorized(_))
+        ));
+
+        let lenient = TokenHandshakeAuthenticator::new(token_service, true);
+        assert_eq!(lenient.authenticate(&request("/ws")), Ok(None));
+        assert!(lenient.authenticate(&request("/ws?token=garbage")).is_err(), "无效令牌即使允许匿名连接也应被拒绝");
+    }
+}
@@ -0,0 +1,62 @@
+// SatCloudService/src-tauri/src/auth/mod.rs
+
+//! 用户认证与授权模块。
+//!
+//! 本模块把用户账户 (`db::user_repo`) 与 WebSocket 连接的身份联系起来：
+//! - `token`: 访问令牌的签发与校验 (HS256 签名的 JWT，带过期时间)。
+//!   REST 登录接口 (`api::auth_handler`) 在校验用户名密码后通过它签发令牌。
+//! - `handshake`: 实现 `rust_websocket_utils` 的 `HandshakeAuthenticator`，
+//!   在 WebSocket 握手阶段校验令牌，并把令牌中的用户信息转换为 `AuthenticatedPrincipal`，
+//!   最终存储在 `ClientSession::principal` 上，供 `ConnectionManager::join_group` 做角色检查。
+
+pub mod handshake;
+pub mod token;
+
+pub use handshake::TokenHandshakeAuthenticator;
+pub use token::{TokenClaims, TokenService};
+
+use crate::config::{AuthConfig, DatabaseConfig};
+use crate::db::UserRepository;
+use crate::error::AppError;
+use common_models::enums::UserRole;
+use log::{info, warn};
+use std::sync::Arc;
+
+/// 根据配置初始化用户仓库和令牌服务。
+///
+/// - 打开 (或创建) 配置中指定的 SQLite 数据库；
+/// - 若用户表为空且配置了引导管理员账户，则创建该管理员；
+/// - 使用配置的签名密钥创建令牌服务，密钥为空时随机生成一个 (服务重启后旧令牌全部失效)。
+pub fn init_auth(
+    auth_config: &AuthConfig,
+    database_config: &DatabaseConfig,
+) -> Result<(Arc<UserRepository>, Arc<TokenService>), AppError> {
+    let user_repository = UserRepository::open(&database_config.path)?;
+
+    if user_repository.user_count()? == 0 {
+        match (&auth_config.bootstrap_admin_username, &auth_config.bootstrap_admin_password) {
+            (Some(username), Some(password)) => {
+                user_repository.create_user(username, password, &[UserRole::Admin], &[
+                    rust_websocket_utils::server::auth::ANY_PROJECT.to_string(),
+                ])?;
+                info!("[认证模块] 用户表为空，已根据配置创建引导管理员账户 '{}'。", username);
+            }
+            _ => warn!(
+                "[认证模块] 用户表为空且未配置引导管理员账户 (auth.bootstrap_admin_username / bootstrap_admin_password)，当前没有任何用户可以登录。"
+            ),
+        }
+    }
+
+    let secret = if auth_config.token_secret.is_empty() {
+        warn!("[认证模块] 未配置 auth.token_secret，将使用随机生成的签名密钥。服务重启后之前签发的令牌将全部失效。");
+        format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+    } else {
+        auth_config.token_secret.clone()
+    };
+    let token_service = TokenService::new(
+        secret.as_bytes(),
+        chrono::Duration::seconds(auth_config.token_ttl_seconds as i64),
+    );
+
+    Ok((Arc::new(user_repository), Arc::new(token_service)))
+}
@@ -1,10 +1,14 @@
 // SatCloudService/src-tauri/src/config.rs
 
 use log::{info, warn};
+use crate::ws_server::heartbeat_monitor::HeartbeatPolicy;
+use common_models::enums::ClientRole;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::OnceLock;
+use std::time::Duration;
 use tauri::Manager;
 
 /// WebSocket 服务的默认主机地址
@@ -90,6 +94,69 @@ pub struct WebSocketConfig {
     /// 客户端超时时间（单位：秒）。
     /// 如果客户端在此时间内无任何活动（未发送消息，包括 Ping），则认为其超时并断开连接。
     pub client_timeout_seconds: u64,
+    /// 客户端链路被标记为"可疑"的不活动时间（单位：秒）。
+    /// 超过此时间仍未收到客户端消息时，服务端通知其组内伙伴链路已降级；未配置时取 `client_timeout_seconds` 的一半。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_suspect_seconds: Option<u64>,
+    /// 会话恢复宽限期（单位：秒）。
+    /// 已加入组的客户端断线后，其身份与组内槽位会保留这么长时间，期间可凭恢复令牌重连并补收错过的消息。
+    /// 为 0 时禁用会话恢复。
+    #[serde(default = "default_resume_grace_seconds")]
+    pub resume_grace_seconds: u64,
+    /// 按角色覆盖的心跳超时策略。未列出的角色使用上面的全局设置。
+    #[serde(default = "default_role_heartbeat_policies")]
+    pub role_heartbeat_policies: HashMap<ClientRole, RoleHeartbeatConfig>,
+}
+
+/// 单个角色的心跳超时策略配置。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RoleHeartbeatConfig {
+    /// 链路被标记为"可疑"的不活动时间（单位：秒）。
+    pub suspect_seconds: u64,
+    /// 判定为断线的不活动时间（单位：秒）。
+    pub timeout_seconds: u64,
+    /// 断线后保留该角色组内槽位的宽限期（单位：秒）。未配置时使用全局的 `resume_grace_seconds`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_grace_seconds: Option<u64>,
+}
+
+impl WebSocketConfig {
+    /// 由全局设置得到的默认心跳策略。未配置可疑阈值时取超时时间的一半。
+    pub fn heartbeat_policy(&self) -> HeartbeatPolicy {
+        let timeout = Duration::from_secs(self.client_timeout_seconds);
+        match self.client_suspect_seconds {
+            Some(suspect_seconds) => HeartbeatPolicy { suspect_after: Duration::from_secs(suspect_seconds), timeout },
+            None => HeartbeatPolicy::with_timeout(timeout),
+        }
+    }
+}
+
+impl RoleHeartbeatConfig {
+    /// 该角色的心跳策略。
+    pub fn heartbeat_policy(&self) -> HeartbeatPolicy {
+        HeartbeatPolicy {
+            suspect_after: Duration::from_secs(self.suspect_seconds),
+            timeout: Duration::from_secs(self.timeout_seconds),
+        }
+    }
+}
+
+/// `WebSocketConfig::resume_grace_seconds` 的默认值，用于兼容未包含该字段的旧配置文件。
+fn default_resume_grace_seconds() -> u64 {
+    crate::ws_server::connection_manager::DEFAULT_RESUME_GRACE_SECONDS
+}
+
+/// `WebSocketConfig::role_heartbeat_policies` 的默认值：
+/// 现场移动端常处于信号较差的环境，放宽其超时判定并延长断线后的槽位保留时间。
+fn default_role_heartbeat_policies() -> HashMap<ClientRole, RoleHeartbeatConfig> {
+    HashMap::from([(
+        ClientRole::OnSiteMobile,
+        RoleHeartbeatConfig {
+            suspect_seconds: 45,
+            timeout_seconds: 150,
+            resume_grace_seconds: Some(300),
+        },
+    )])
 }
 
 // 为 WebSocketConfig 实现 Default trait，提供一组合理的默认值。
@@ -101,6 +168,75 @@ impl Default for WebSocketConfig {
             port: 8088,                         // 默认监听 8088 端口
             heartbeat_check_interval_seconds: 15, // 默认每 15 秒检查一次心跳
             client_timeout_seconds: 60,         // 默认客户端 60 秒无响应则超时
+            client_suspect_seconds: None,       // 默认 30 秒 (超时时间的一半) 无响应则标记为链路可疑
+            resume_grace_seconds: default_resume_grace_seconds(), // 默认断线后保留会话 60 秒
+            role_heartbeat_policies: default_role_heartbeat_policies(), // 默认放宽现场移动端的心跳策略
+        }
+    }
+}
+
+/// HTTP REST API 服务的配置 (登录等接口)。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpApiConfig {
+    /// HTTP API 服务绑定的主机地址。
+    pub host: String,
+    /// HTTP API 服务监听的端口号。
+    pub port: u16,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(), // 与 WebSocket 服务保持一致
+            port: 8089,                   // 默认监听 8089 端口 (WebSocket 使用 8088)
+        }
+    }
+}
+
+/// 数据库配置。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DatabaseConfig {
+    /// SQLite 数据库文件路径 (相对路径以进程当前工作目录为基准)。
+    pub path: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: "sat_cloud_service.db".to_string(),
+        }
+    }
+}
+
+/// 用户认证与令牌签发的配置。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthConfig {
+    /// 是否接受未携带访问令牌的匿名 WebSocket 连接。默认不接受，这类握手以 401 拒绝。
+    /// 开启后匿名连接只能以 `Observer` (只读观察者) 角色注册，服务启动时会输出警告；
+    /// 携带了无效令牌的连接无论此项如何都会被拒绝。
+    #[serde(default)]
+    pub allow_anonymous: bool,
+    /// 用于签名访问令牌的密钥。为空时服务启动时会随机生成一个，
+    /// 这意味着服务重启后之前签发的令牌全部失效。生产环境应显式配置。
+    pub token_secret: String,
+    /// 访问令牌的有效期（单位：秒）。
+    pub token_ttl_seconds: u64,
+    /// 首次启动 (用户表为空) 时自动创建的管理员用户名。为 `None` 时不自动创建。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_admin_username: Option<String>,
+    /// 首次启动时自动创建的管理员密码。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap_admin_password: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            allow_anonymous: false,        // 默认只接受携带有效令牌的连接
+            token_secret: String::new(),   // 默认为空，启动时随机生成
+            token_ttl_seconds: 8 * 60 * 60, // 默认令牌有效期 8 小时 (一个工作班次)
+            bootstrap_admin_username: None,
+            bootstrap_admin_password: None,
         }
     }
 }
@@ -111,8 +247,16 @@ impl Default for WebSocketConfig {
 pub struct AppConfig {
     /// WebSocket 服务的相关配置。
     pub websocket: WebSocketConfig,
+    /// HTTP REST API 服务的相关配置。旧配置文件中缺少此项时使用默认值。
+    #[serde(default)]
+    pub http_api: HttpApiConfig,
+    /// 数据库的相关配置。
+    #[serde(default)]
+    pub database: DatabaseConfig,
+    /// 用户认证与令牌签发的相关配置。
+    #[serde(default)]
+    pub auth: AuthConfig,
     // 在此可以添加其他配置项，例如：
-    // pub database: DatabaseConfig,
     // pub message_queue: MessageQueueConfig,
 }
 
@@ -14,10 +14,10 @@
 //!
 //! 设计目标是保持数据库逻辑的独立性和可测试性。
 
-// 目前此模块为占位，具体实现将根据项目后续阶段的数据持久化需求进行添加。
-// 例如，可能会添加如下子模块：
-// pub mod schema; // 若使用 Diesel ORM，用于存放数据库表结构定义
-// pub mod models; // 数据库模型结构体 (可能部分与 common_models 重叠或关联)
-// pub mod task_repo; // 任务数据仓库，封装任务相关的数据库操作
+/// 用户账户数据仓库 (用户名、密码哈希、角色分配)，基于 SQLite。
+pub mod user_repo;
+
+pub use user_repo::{UserAccount, UserRepository};
 
-// 暂时为空，后续会添加如 task_repo.rs 等具体的数据仓库实现。 
\ No newline at end of file
+// 后续可能会添加如下子模块：
+// pub mod task_repo; // 任务数据仓库，封装任务相关的数据库操作
@@ -5,7 +5,149 @@
 //! 此模块旨在统一管理和定义在 `SatCloudService` 应用中可能出现的各种错误情况。
 //! 通过定义具体的错误枚举或结构体，可以提供更丰富的错误信息和上下文，
 //! 便于错误处理、日志记录和问题诊断。
-// 后续可以根据服务端各模块（如 WebSocket、API处理、数据库交互等）的具体需求，
-// 在此添加详细的错误类型定义，例如使用 `thiserror` crate 来创建派生了 `Error` trait 的枚举。
 
-// 暂时为空，后续会根据需要定义具体的错误枚举或结构。 
\ No newline at end of file
+use common_models::enums::{ClientRole, ErrorCode};
+use common_models::ws_payloads::{AckPayload, ErrorResponsePayload, FieldErrorDetail};
+use thiserror::Error;
+
+/// 应用的主要错误类型。
+///
+/// 这个枚举定义了应用中可能出现的各种错误类型。
+/// 每一种错误类型都包含了相关的错误信息，以便进行调试和错误处理。
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("WebSocket 服务错误: {0}")]
+    WebSocketService(String),
+
+    #[error("配置错误: {0}")]
+    ConfigError(String),
+
+    #[error("数据库错误: {0}")]
+    DatabaseError(String),
+
+    #[error("认证错误: {0}")]
+    AuthenticationError(String),
+
+    #[error("未知错误: {0}")]
+    Unknown(String),
+}
+
+/// 云端协议层面的错误类型。
+///
+/// 与 `AppError` (服务内部的基础设施错误) 不同，`CloudError` 描述的是需要回复给客户端的业务/协议错误。
+/// 每个变体都对应一个稳定的机器可读错误码 ([`ErrorCode`])，并可以直接转换为 `ErrorResponsePayload`
+/// 或被拒绝的 `AckPayload`，客户端据此决定后续处理，而无需解析错误描述文本。
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CloudError {
+    #[error("客户端未注册到有效调试组或角色未知，无法处理此业务请求。")]
+    NotRegistered,
+
+    #[error("{message_type} 请求的负载格式无效: {reason}")]
+    PayloadInvalid {
+        message_type: String,
+        reason: String,
+        details: Vec<FieldErrorDetail>,
+    },
+
+    #[error("提供的任务ID '{requested}' 与组 '{group_id}' 已关联的任务ID '{existing}' 不匹配。")]
+    TaskMismatch {
+        group_id: String,
+        requested: String,
+        existing: String,
+    },
+
+    #[error("组 '{group_id}' 的角色 {role} 已被其他活动客户端占用。")]
+    RoleSlotTaken { group_id: String, role: ClientRole },
+
+    #[error("版本冲突：客户端基于版本 {expected}，服务端当前版本为 {current}。")]
+    VersionConflict { expected: u64, current: u64 },
+
+    #[error("无权执行该操作: {0}")]
+    Forbidden(String),
+
+    #[error("组 '{0}' 没有对应的任务状态，操作未生效。")]
+    TaskNotFound(String),
+
+    #[error("不支持的消息类型 '{0}'，或该类型不被识别为有效的业务操作。")]
+    UnsupportedMessage(String),
+
+    #[error("处理请求时服务端发生内部错误: {0}")]
+    Internal(String),
+}
+
+impl CloudError {
+    /// 根据负载反序列化错误构造 `PayloadInvalid`，并尽量从错误信息中提取出错的字段。
+    pub fn payload_invalid(message_type: &str, error: &serde_json::Error) -> Self {
+        let reason = error.to_string();
+        // serde_json 对缺失/未知字段的描述形如 "missing field `item_id` at line 1 column 20"
+        let field = reason
+            .split('`')
+            .nth(1)
+            .filter(|_| reason.starts_with("missing field") || reason.starts_with("unknown field"))
+            .unwrap_or("$");
+        CloudError::PayloadInvalid {
+            message_type: message_type.to_string(),
+            details: vec![FieldErrorDetail::new(field, reason.clone())],
+            reason,
+        }
+    }
+
+    /// 返回该错误对应的机器可读错误码。
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CloudError::NotRegistered => ErrorCode::NotRegistered,
+            CloudError::PayloadInvalid { .. } => ErrorCode::PayloadInvalid,
+            CloudError::TaskMismatch { .. } => ErrorCode::TaskMismatch,
+            CloudError::RoleSlotTaken { .. } => ErrorCode::RoleSlotTaken,
+            CloudError::VersionConflict { .. } => ErrorCode::VersionConflict,
+            CloudError::Forbidden(_) => ErrorCode::Forbidden,
+            CloudError::TaskNotFound(_) => ErrorCode::TaskNotFound,
+            CloudError::UnsupportedMessage(_) => ErrorCode::UnsupportedMessage,
+            CloudError::Internal(_) => ErrorCode::InternalError,
+        }
+    }
+
+    /// 返回字段级错误详情 (目前仅 `PayloadInvalid` 携带)。
+    pub fn details(&self) -> Vec<FieldErrorDetail> {
+        match self {
+            CloudError::PayloadInvalid { details, .. } => details.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 转换为发送给客户端的 `ErrorResponsePayload`。
+    pub fn to_error_response(&self, original_message_type: Option<String>) -> ErrorResponsePayload {
+        ErrorResponsePayload::new(self.code(), original_message_type, self.to_string()).with_details(self.details())
+    }
+
+    /// 转换为对业务消息的"被拒绝"确认。
+    pub fn to_rejected_ack(&self, original_message_type: &str) -> AckPayload {
+        AckPayload::rejected(original_message_type, self.code(), self.to_string()).with_details(self.details())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::UpdatePreCheckItemPayload;
+
+    #[test]
+    fn test_cloud_error_maps_to_codes_and_payloads() {
+        let parse_error = serde_json::from_str::<UpdatePreCheckItemPayload>(r#"{"task_id":"t1"}"#).unwrap_err();
+        let error = CloudError::payload_invalid("UpdatePreCheckItem", &parse_error);
+        assert_eq!(error.code(), ErrorCode::PayloadInvalid);
+        assert_eq!(error.details()[0].field, "item_id");
+
+        let response = error.to_error_response(Some("UpdatePreCheckItem".to_string()));
+        assert_eq!(response.code, Some(ErrorCode::PayloadInvalid));
+        assert_eq!(response.message_key.as_deref(), Some("error.payload_invalid"));
+        assert_eq!(response.details.len(), 1);
+
+        let ack = CloudError::RoleSlotTaken { group_id: "g1".to_string(), role: ClientRole::ControlCenter }
+            .to_rejected_ack("Register");
+        assert_eq!(ack.error_code, Some(ErrorCode::RoleSlotTaken));
+        assert!(ack.details.is_empty());
+        assert_eq!(CloudError::VersionConflict { expected: 1, current: 2 }.code(), ErrorCode::VersionConflict);
+        assert_eq!(CloudError::Forbidden("x".to_string()).code(), ErrorCode::Forbidden);
+    }
+}
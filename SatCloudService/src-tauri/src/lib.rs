@@ -5,14 +5,16 @@
 //! 
 //! 主要模块包括：
 //! - `api`: 定义和处理外部 HTTP API 请求。
+//! - `auth`: 用户认证与授权 (访问令牌签发与校验、WebSocket 握手认证)。
 //! - `config`: 管理应用的配置信息加载与访问。
-//! - `db`: (规划中) 数据库交互逻辑。
+//! - `db`: 数据库交互逻辑 (目前包含基于 SQLite 的用户账户仓库)。
 //! - `error`: 定义应用特定的错误类型。
 //! - `mq`: (规划中) 消息队列相关功能。
 //! - `state`: 管理应用级别的共享状态。
 //! - `ws_server`: 实现 WebSocket 服务端，处理客户端连接、消息路由和实时通信。
 
 pub mod api;
+pub mod auth;
 pub mod config;
 pub mod db;
 pub mod error;
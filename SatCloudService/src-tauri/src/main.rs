@@ -2,11 +2,13 @@
 
 use tauri::Manager; // 新增：导入 Manager trait 以使用 app.manage()
 // use tauri::Manager; // 暂时注释掉未使用的导入，后续如需使用应用句柄可取消注释
-use log::{error, info, LevelFilter}; // 引入日志宏 (error, info) 和日志级别过滤器 (LevelFilter)
+use log::{error, info, warn, LevelFilter}; // 引入日志宏 (error, info, warn) 和日志级别过滤器 (LevelFilter)
 use sat_cloud_service::ws_server::service::WsService; // 引入 WebSocket 服务实现
 use sat_cloud_service::ws_server::connection_manager::ConnectionManager; // 引入 WebSocket 连接管理器
 use sat_cloud_service::ws_server::task_state_manager::TaskStateManager; // P3.1.2: 引入任务状态管理器，用于管理调试任务的共享状态
 use sat_cloud_service::ws_server::heartbeat_monitor::HeartbeatMonitor; // P3.2.1: 引入心跳监视器，用于检测和处理客户端超时
+use sat_cloud_service::api::{self, auth_handler::{auth_router, AuthApiState}}; // HTTP API：登录接口
+use sat_cloud_service::auth::{self, TokenHandshakeAuthenticator}; // 用户认证：令牌服务与握手认证器
 use std::sync::Arc; // 引入原子引用计数 Arc，用于在多线程环境安全地共享状态所有权
 use std::time::Duration; // P3.2.1: 引入时间间隔 Duration，用于定义超时和检查周期
 use serde_json::Value as JsonValue; // 添加 JsonValue 支持
@@ -92,6 +94,40 @@ async fn admin_broadcast_task_state_update_cmd(
     Ok(())
 }
 
+/// 设置 (或以空值清除) 指定组任务的自定义共享数据 JSON Schema。
+///
+/// Schema 设置后，客户端通过 `UpdateCustomSharedData` 等消息写入的共享数据必须符合它，否则以 `PAYLOAD_INVALID` 拒绝。
+/// 现有共享数据不符合新的 Schema 时设置失败。
+#[tauri::command]
+async fn admin_set_custom_shared_data_schema_cmd(
+    group_id: String,
+    schema_json: Option<String>,
+    task_state_manager: State<'_, Arc<TaskStateManager>>,
+    connection_manager: State<'_, Arc<ConnectionManager>>,
+) -> Result<u64, String> {
+    info!(
+        "Tauri command 'admin_set_custom_shared_data_schema_cmd' called with groupId: {}, schema_json: {:?}",
+        group_id, schema_json
+    );
+    let schema = match schema_json.filter(|json_str| !json_str.trim().is_empty()) {
+        Some(json_str) => Some(serde_json::from_str::<JsonValue>(&json_str).map_err(|e| {
+            let err_msg = format!("[Admin CMD] Invalid schema_json format: {}", e);
+            error!("{}", err_msg);
+            err_msg
+        })?),
+        None => None,
+    };
+    let updated_state = task_state_manager
+        .set_custom_shared_data_schema(&group_id, schema, &connection_manager)
+        .await
+        .map_err(|e| {
+            let err_msg = format!("[Admin CMD] Failed to set custom shared data schema for group_id '{}': {}", group_id, e);
+            error!("{}", err_msg);
+            err_msg
+        })?;
+    Ok(updated_state.version)
+}
+
 // 声明应用配置模块，该模块负责加载和管理应用的配置信息
 // mod config; // 已在 lib.rs 中声明，此处无需重复，但保留注释以作说明
 // 声明 WebSocket 服务端相关模块的父模块
@@ -127,7 +163,19 @@ fn main() {
             // P1.2.1 & P3.1.2: 创建连接管理器 (ConnectionManager) 的实例。
             // ConnectionManager 负责管理所有 WebSocket 客户端连接、会话、组等。
             // 将 task_state_manager 的 Arc 克隆并注入到 ConnectionManager 中，使其能够访问和修改任务状态。
-            let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+            // 会话恢复宽限期取自配置：断线客户端在此期间可凭恢复令牌取回其身份与组内槽位。
+            // 心跳策略中为某些角色单独配置的宽限期会覆盖全局设置。
+            // 心跳策略同样保存在连接管理器中：由心跳监视器执行，并在 RegisterResponse 中公布给客户端。
+            let mut connection_manager = ConnectionManager::new(task_state_manager.clone())
+                .with_resume_grace(Duration::from_secs(app_config.websocket.resume_grace_seconds))
+                .with_heartbeat_policy(app_config.websocket.heartbeat_policy());
+            for (role, policy) in &app_config.websocket.role_heartbeat_policies {
+                connection_manager = connection_manager.with_role_heartbeat_policy(*role, policy.heartbeat_policy());
+                if let Some(resume_grace_seconds) = policy.resume_grace_seconds {
+                    connection_manager = connection_manager.with_role_resume_grace(*role, Duration::from_secs(resume_grace_seconds));
+                }
+            }
+            let connection_manager = Arc::new(connection_manager);
             info!("[主程序::Setup钩子] WebSocket 连接管理器 (ConnectionManager) 已创建，并已注入任务状态管理器。");
 
             // P1.2.1: 将 ConnectionManager 的 Arc 引用放入 Tauri 的托管状态 (Managed State) 中。
@@ -141,14 +189,46 @@ fn main() {
             info!("[主程序::Setup钩子] 任务状态管理器 (TaskStateManager) 已成功添加到 Tauri 托管状态。");
 
 
+            // 初始化用户仓库 (SQLite) 与访问令牌服务。
+            // 若用户表为空且配置了引导管理员，会在此创建管理员账户。
+            let (user_repository, token_service) = auth::init_auth(&app_config.auth, &app_config.database)
+                .map_err(|e| {
+                    error!("[主程序::Setup钩子] 致命错误：初始化用户认证模块失败: {}", e);
+                    e
+                })?;
+            info!(
+                "[主程序::Setup钩子] 用户认证模块已初始化。数据库: {}, 接受匿名连接: {}",
+                app_config.database.path, app_config.auth.allow_anonymous
+            );
+            if app_config.auth.allow_anonymous {
+                warn!("[主程序::Setup钩子] 已开启 auth.allow_anonymous：未携带访问令牌的连接将被接受，但只能以 Observer (只读观察者) 角色注册。");
+            }
+
+            // 在后台启动 HTTP API 服务，对外提供登录 (令牌签发) 接口。
+            let api_addr = format!("{}:{}", app_config.http_api.host, app_config.http_api.port);
+            let api_router = auth_router(AuthApiState {
+                user_repository: user_repository.clone(),
+                token_service: token_service.clone(),
+            });
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = api::start_api_server(api_addr, api_router).await {
+                    error!("[主程序::Setup钩子] HTTP API 服务异常终止: {:#}", e);
+                }
+            });
+
             // 为 WebSocket 服务创建一个新的 WsService 实例。
             // 它需要应用的 WebSocket 配置 (从 app_config 中获取) 和对 ConnectionManager 的共享引用。
             // P3.3.2: 同时还需要传递对 TaskStateManager 的共享引用。
+            // 握手阶段使用令牌认证器校验客户端携带的访问令牌。
             let ws_service_instance = WsService::new(
                 app_config.websocket.clone(), 
                 connection_manager.clone(),
                 task_state_manager.clone(), // P3.3.2: 传递 TaskStateManager
-            );
+            )
+            .with_authenticator(Arc::new(TokenHandshakeAuthenticator::new(
+                token_service,
+                app_config.auth.allow_anonymous,
+            )));
             
             // 使用 Tauri 的异步运行时 (tauri::async_runtime::spawn) 在后台启动 WebSocket 服务。
             // 这是一个独立的异步任务，不会阻塞 setup 钩子或主线程。
@@ -164,15 +244,13 @@ fn main() {
             });
             info!("[主程序::Setup钩子] WebSocket 服务启动任务已成功派生到后台异步执行。");
 
-            // P3.2.1: 从已加载的应用配置中读取心跳检查间隔和客户端超时时间。
+            // P3.2.1: 从已加载的应用配置中读取心跳检查间隔。
             let heartbeat_check_interval = Duration::from_secs(app_config.websocket.heartbeat_check_interval_seconds);
-            let client_timeout = Duration::from_secs(app_config.websocket.client_timeout_seconds);
             
             // 创建心跳监视器 (HeartbeatMonitor) 的实例。
-            // 它需要对 ConnectionManager 的共享引用 (以便移除超时的客户端)，以及配置的超时参数。
+            // 它需要对 ConnectionManager 的共享引用 (以便取得各角色的心跳策略、标记可疑链路并移除超时的客户端)。
             let heartbeat_monitor = HeartbeatMonitor::new(
                 connection_manager.clone(), // 传递 ConnectionManager 的 Arc 引用
-                client_timeout,             // 设置客户端不活跃的超时时长
                 heartbeat_check_interval,   // 设置心跳监视器自身的检查频率
             );
             // 使用 Tauri 的异步运行时在后台启动心跳监视器。
@@ -188,7 +266,7 @@ fn main() {
             info!("[主程序::Setup钩子] Tauri 应用的所有初始化设置已全部完成。");
             Ok(()) // 表示 setup 钩子成功完成
         })
-        .invoke_handler(tauri::generate_handler![greet, admin_broadcast_task_state_update_cmd]) // 注册 Tauri 命令处理器
+        .invoke_handler(tauri::generate_handler![greet, admin_broadcast_task_state_update_cmd, admin_set_custom_shared_data_schema_cmd]) // 注册 Tauri 命令处理器
         .run(tauri::generate_context!()) // 运行 Tauri 应用
         .expect("启动 Tauri 应用程序时发生严重错误，请检查日志！"); // 处理启动错误
 } // 关闭 main 函数
\ No newline at end of file
@@ -6,28 +6,39 @@
 //! - **客户端会话管理**: 跟踪所有通过 WebSocket 连接到服务器的活动客户端，
 //!   每个客户端由一个 `ClientSession` 实例表示，存储在并发安全的哈希映射中。
 //! - **组的创建与管理**: 允许客户端创建或加入特定的"组"（`Group`）。一个组通常对应一个
-//!   正在进行的调试/测试任务，包含一个控制中心 (ControlCenter) 客户端和一个现场移动端 (OnSiteMobile) 客户端。
+//!   正在进行的调试/测试任务，包含一个控制中心 (ControlCenter) 客户端、一个或多个现场移动端 (OnSiteMobile) 客户端
+//!   (各自负责一部分设备)、可选的一个 QA 主管 (Supervisor) 客户端以及任意多个只读观察者 (Observer)。
 //!   组信息也存储在并发安全的哈希映射中。
 //! - **角色分配与限制**: 在客户端加入组时，根据其声明的角色 (`ClientRole`) 将其分配到组内的
-//!   特定槽位 (例如，一个组只能有一个 `ControlCenter` 和一个 `OnSiteMobile`)。
+//!   特定槽位 (例如，一个组只能有一个 `ControlCenter` 和一个 `Supervisor`；现场移动端与观察者不限数量，
+//!   但各现场移动端声明负责的设备与预检查类别不能重叠)。
 //! - **生命周期处理**: 处理客户端的连接 (`add_client`) 和断开 (`remove_client`) 事件。
-//!   当客户端断开时，会将其从其所在的组中移除。
-//! - **伙伴状态通知**: 当一个客户端加入或离开组时，会通知其在同一组内的伙伴客户端
+//!   当客户端断开时，会将其从其所在的组中移除；客户端也可以不断开连接地离开组 (`leave_group`)
+//!   或切换到另一个任务组 (`switch_task`)。
+//! - **伙伴状态通知**: 当一个客户端加入或离开组时，会通知同一组内的全部其他成员
 //!   关于其在线状态的变化 (通过 `PartnerStatusPayload` 消息)。
+//! - **配对码开组**: 控制中心可以请求云端为任务开组 (`open_group`) 并取得短时有效、一次性使用的配对码，
+//!   现场端凭配对码注册，无需与控制中心事先约定完全一致的 `group_id` 与 `task_id`。
 //! - **任务状态关联**: 与 `TaskStateManager` 模块协作，在组创建时初始化与该组关联的
 //!   任务状态 (`TaskDebugState`)，并在组解散时清理该状态。
 
 use crate::ws_server::client_session::ClientSession;
+use crate::ws_server::heartbeat_monitor::HeartbeatPolicy; // 按角色的两阶段心跳超时策略
 use crate::ws_server::task_state_manager::TaskStateManager; // 引入任务状态管理器
-use common_models::enums::ClientRole; // 引入客户端角色枚举
+use common_models::enums::{ClientRole, ErrorCode}; // 引入客户端角色枚举与协议错误码
 use common_models::ws_payloads::{ // 引入WebSocket消息负载定义
-    PartnerStatusPayload, RegisterPayload, 
-    PARTNER_STATUS_UPDATE_MESSAGE_TYPE,
+    GroupLeftPayload, GroupOpenedPayload, GroupRosterPayload, HandoverTokenPayload, OpenGroupPayload, PartnerStatusPayload, RegisterPayload, SiteAssignment,
+    SlotReleaseReason, SlotReleasedPayload, SlotTakeoverDecisionPayload, SlotTakeoverPromptPayload, SlotTakeoverRequestPayload,
+    RosterMember, GROUP_ROSTER_MESSAGE_TYPE, PARTNER_STATUS_UPDATE_MESSAGE_TYPE, REGISTER_RESPONSE_MESSAGE_TYPE, SLOT_RELEASED_MESSAGE_TYPE,
+    SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE, TASK_STATE_UPDATE_MESSAGE_TYPE,
     // REGISTER_RESPONSE_MESSAGE_TYPE, // P3.1.2: 确保 RegisterResponse 类型被引入 -- 这个常量确实未被使用
 };
 use common_models::RegisterResponsePayload; // 新增导入
+use crate::error::CloudError; // 接管答复与交接令牌请求被拒绝时的协议错误
 use rust_websocket_utils::message::WsMessage; // 引入基础 WebSocket 消息结构
+use rust_websocket_utils::server::auth::AuthenticatedPrincipal; // 握手阶段认证得到的主体
 
+use chrono::{DateTime, Utc}; // 断线时间戳，用于判断会话恢复宽限期是否已过
 use dashmap::DashMap; // 高性能并发哈希映射库
 use log::{debug, error, info, warn}; // 日志宏
 use std::sync::Arc; // 原子引用计数，用于共享所有权
@@ -36,11 +47,119 @@ use uuid::Uuid; // 用于生成和操作 UUID
 use std::net::SocketAddr; // 套接字地址类型
 use tokio::sync::mpsc; // Tokio 提供的多生产者单消费者异步通道
 use std::sync::atomic::{AtomicBool, Ordering}; // 原子布尔型及内存顺序控制
+use std::collections::{HashMap, VecDeque}; // 现场工程师的设备分配; 断线期间错过的消息缓冲
+use std::time::{Duration, Instant}; // 会话恢复宽限期; 断线时刻 (单调时钟)
+
+/// 断线后为客户端保留身份与组内槽位的默认宽限期 (秒)。
+pub const DEFAULT_RESUME_GRACE_SECONDS: u64 = 60;
+
+/// 客户端多久未发送任何消息即被判定为断线的默认时长 (秒)。
+pub const DEFAULT_CLIENT_TIMEOUT_SECONDS: u64 = 60;
+/// 宽限期内为单个断线客户端缓存的错过消息的最大条数，超出时丢弃最旧的消息
+/// (此时恢复后会额外补发一条最新的完整任务状态)。
+const MISSED_MESSAGE_BUFFER_CAPACITY: usize = 256;
+/// 恢复会话时等待占位会话的消息收集任务结束的最长时间。
+const MISSED_MESSAGE_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+/// 接管请求等待当前槽位持有者答复的默认时长 (秒)，超时未答复视为同意。
+pub const DEFAULT_TAKEOVER_GRACE_SECONDS: u64 = 30;
+/// 交接令牌的有效期 (秒)。
+pub const HANDOVER_TOKEN_VALID_SECONDS: u64 = 300;
+/// 配对码的默认有效期 (秒)。
+pub const DEFAULT_JOIN_CODE_VALID_SECONDS: u64 = 600;
+/// 配对码的字符集：大写字母与数字，去掉了容易混淆的 I、O、0、1 (口头或手工输入时不易出错)。
+const JOIN_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// 配对码长度。
+const JOIN_CODE_LENGTH: usize = 6;
+/// 成员报告的往返时延与上次推送名册时相比变化至少这么多 (毫秒) 时，才重新推送组成员名册。
+const ROUND_TRIP_CHANGE_REPORT_THRESHOLD_MS: u64 = 100;
+
+/// 断线客户端在宽限期内错过的消息。
+#[derive(Debug, Default)]
+struct MissedMessages {
+    /// 按接收顺序缓存的消息。
+    messages: VecDeque<WsMessage>,
+    /// 因缓冲已满而被丢弃的 (最旧的) 消息数量。
+    dropped: usize,
+}
+
+/// 断线客户端在会话恢复宽限期内的保留记录，以恢复令牌为键存放在 `ConnectionManager::detached_sessions` 中。
+///
+/// 客户端断线时，其在组内的角色槽位被替换为一个同 ID 的占位会话 (`placeholder`)。
+/// 发给该客户端的组内消息 (伙伴状态、任务状态更新等) 照常发送到占位会话，由后台收集任务
+/// 存入 `missed_messages`，待客户端凭恢复令牌重新注册后按原顺序补发。
+/// 占位会话未被标记为关闭，因此宽限期内其他客户端无法占用该槽位。
+struct DetachedSession {
+    /// 断线客户端原有的客户端ID，恢复后继续沿用。
+    client_id: Uuid,
+    /// 断线前在组内的角色。
+    role: ClientRole,
+    /// 断线前所属的组ID。
+    group_id: String,
+    /// 断线前会话的已认证用户 (如有)，恢复时要求新连接是同一用户。
+    principal_user_id: Option<String>,
+    /// 断线 (进入宽限期) 的单调时钟时刻。
+    detached_at: Instant,
+    /// 占据组内槽位的占位会话。
+    placeholder: Arc<ClientSession>,
+    /// 占位会话收到的消息。
+    missed_messages: Arc<std::sync::Mutex<MissedMessages>>,
+    /// 把占位会话收到的消息转存到 `missed_messages` 的后台任务。
+    collector: tokio::task::JoinHandle<()>,
+}
+
+/// 等待当前槽位持有者答复的接管请求，以接管请求ID为键存放在 `ConnectionManager::pending_takeovers` 中。
+struct PendingTakeover {
+    /// 发起接管的客户端会话。
+    requester: Arc<ClientSession>,
+    /// `SlotTakeoverRequest` 消息的 `message_id`，最终的注册响应以此为 `in_reply_to`。
+    request_message_id: String,
+    /// 接管成功后用于加入组的注册信息。
+    register: RegisterPayload,
+    /// 被征求同意的当前持有者的客户端ID。
+    holder_id: Uuid,
+    /// 发起请求的时间，超过 `takeover_grace` 未答复时接管自动生效。
+    requested_at: DateTime<Utc>,
+}
+
+/// 已签发但尚未使用的交接令牌，以令牌为键存放在 `ConnectionManager::handover_grants` 中。
+struct HandoverGrant {
+    /// 交接的组ID。
+    group_id: String,
+    /// 交接的角色。
+    role: ClientRole,
+    /// 签发令牌时的槽位持有者；只有该持有者仍占据槽位时令牌才有效。
+    holder_id: Uuid,
+    /// 签发时间。
+    issued_at: DateTime<Utc>,
+}
+
+/// 已签发、尚未使用的配对码，以配对码为键存放在 `ConnectionManager::join_codes` 中。
+struct JoinCodeGrant {
+    /// 配对码对应的组ID。
+    group_id: String,
+    /// 配对码对应的任务ID。
+    task_id: String,
+    /// 签发配对码的控制中心客户端ID (仅用于日志)。
+    issued_by: Uuid,
+    /// 签发时间，超过 `join_code_ttl` 后配对码失效。
+    issued_at: DateTime<Utc>,
+}
+
+/// `ConnectionManager::request_slot_takeover` 的处理结果。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotTakeoverOutcome {
+    /// 已得出结果，注册响应已发送给请求方。`success` 表示是否已加入组。
+    Resolved { success: bool },
+    /// 已向当前持有者发送 `SlotTakeoverPrompt`；结果待其答复或超时后发送给请求方。
+    AwaitingHolder { takeover_id: String },
+}
 
 /// 代表一个客户端组，通常关联到一个特定的调试或协作任务。
 /// 
-/// 一个组设计为包含一个控制中心 (`ControlCenter`) 客户端和一个现场移动端 (`OnSiteMobile`) 客户端，
-/// 它们共同参与完成由 `task_id` 标识的任务。
+/// 一个组设计为包含一个控制中心 (`ControlCenter`) 客户端和一个或多个现场移动端 (`OnSiteMobile`) 客户端，
+/// 它们共同参与完成由 `task_id` 标识的任务 (大型撬装设备可由多名现场工程师分工测试，各自负责一部分设备)；
+/// 此外还可以有一个 QA 主管 (`Supervisor`) 负责最终签核，
+/// 以及任意多个只读观察者 (`Observer`)。组内任一成员的上下线都会通知其余全部成员，状态广播也发往全部成员。
 /// `Group` 实例由 `ConnectionManager` 创建和管理，并通过 `RwLock` 进行并发访问保护。
 #[derive(Debug)] // 允许使用 {:?} 格式化打印 Group 以进行调试
 pub struct Group {
@@ -54,11 +173,14 @@ pub struct Group {
     /// 使用 `Option<Arc<ClientSession>>` 表示该角色的客户端可能当前在线 (Some) 或离线/未加入 (None)。
     /// `Arc` 允许多处共享对 `ClientSession` 的只读访问。
     pub control_center_client: Option<Arc<ClientSession>>,
-    /// 组内的现场移动端 (`ClientRole::OnSiteMobile`) 客户端会话的共享引用。
-    /// 结构与 `control_center_client` 类似。
-    pub on_site_mobile_client: Option<Arc<ClientSession>>,
-    // 注意：未来可以扩展此结构以支持更多类型的客户端或观察者模式，
-    // 例如 `additional_observers: Vec<Arc<ClientSession>>`。
+    /// 组内的现场移动端 (`ClientRole::OnSiteMobile`) 客户端会话，数量不限，按加入顺序排列。
+    pub on_site_mobile_clients: Vec<Arc<ClientSession>>,
+    /// 各现场移动端负责的设备与预检查类别 (客户端ID -> 分配)。未声明分配的现场移动端记为空分配。
+    pub site_assignments: HashMap<Uuid, SiteAssignment>,
+    /// 组内的 QA 主管 (`ClientRole::Supervisor`) 客户端会话的共享引用。结构与 `control_center_client` 类似。
+    pub supervisor_client: Option<Arc<ClientSession>>,
+    /// 组内的只读观察者 (`ClientRole::Observer`) 客户端会话，数量不限，按加入顺序排列。
+    pub observers: Vec<Arc<ClientSession>>,
 }
 
 impl Group {
@@ -72,16 +194,146 @@ impl Group {
     /// * `task_id`: `String` - 与此组关联的任务的唯一标识符。
     ///
     /// # 返回值
-    /// 返回一个初始化后的 `Group` 实例，其中 `control_center_client` 和 `on_site_mobile_client`
-    /// 字段初始值均为 `None`。
+    /// 返回一个初始化后的 `Group` 实例，其中不含任何成员。
     pub fn new(group_id: String, task_id: String) -> Self {
         info!("[连接管理器::组] 正在创建新的客户端组。组ID: '{}', 关联任务ID: '{}'", group_id, task_id);
         Self {
             group_id, // 设置组ID
             task_id,  // 设置关联的任务ID
             control_center_client: None, // 初始时无控制中心客户端
-            on_site_mobile_client: None, // 初始时无现场移动端客户端
+            on_site_mobile_clients: Vec::new(), // 初始时无现场移动端客户端
+            site_assignments: HashMap::new(),
+            supervisor_client: None,     // 初始时无 QA 主管客户端
+            observers: Vec::new(),       // 初始时无观察者
+        }
+    }
+
+    /// 独占角色 (`ClientRole::has_exclusive_slot`) 在组内对应的槽位；其他角色返回 `None`。
+    fn exclusive_slot(&self, role: ClientRole) -> Option<&Option<Arc<ClientSession>>> {
+        match role {
+            ClientRole::ControlCenter => Some(&self.control_center_client),
+            ClientRole::Supervisor => Some(&self.supervisor_client),
+            ClientRole::OnSiteMobile | ClientRole::Observer | ClientRole::Unknown => None,
+        }
+    }
+
+    /// `exclusive_slot` 的可变版本。
+    fn exclusive_slot_mut(&mut self, role: ClientRole) -> Option<&mut Option<Arc<ClientSession>>> {
+        match role {
+            ClientRole::ControlCenter => Some(&mut self.control_center_client),
+            ClientRole::Supervisor => Some(&mut self.supervisor_client),
+            ClientRole::OnSiteMobile | ClientRole::Observer | ClientRole::Unknown => None,
+        }
+    }
+
+    /// 不限数量的角色 (现场移动端、观察者) 在组内对应的成员列表；其他角色返回 `None`。
+    fn member_list(&self, role: ClientRole) -> Option<&Vec<Arc<ClientSession>>> {
+        match role {
+            ClientRole::OnSiteMobile => Some(&self.on_site_mobile_clients),
+            ClientRole::Observer => Some(&self.observers),
+            _ => None,
+        }
+    }
+
+    /// `member_list` 的可变版本。
+    fn member_list_mut(&mut self, role: ClientRole) -> Option<&mut Vec<Arc<ClientSession>>> {
+        match role {
+            ClientRole::OnSiteMobile => Some(&mut self.on_site_mobile_clients),
+            ClientRole::Observer => Some(&mut self.observers),
+            _ => None,
+        }
+    }
+
+    /// 组内全部成员 (含断线宽限期内的占位会话) 及其角色。
+    pub fn members(&self) -> Vec<(ClientRole, Arc<ClientSession>)> {
+        let control_center = self.control_center_client.iter().map(|session| (ClientRole::ControlCenter, Arc::clone(session)));
+        let on_site = self.on_site_mobile_clients.iter().map(|session| (ClientRole::OnSiteMobile, Arc::clone(session)));
+        let supervisor = self.supervisor_client.iter().map(|session| (ClientRole::Supervisor, Arc::clone(session)));
+        let observers = self.observers.iter().map(|session| (ClientRole::Observer, Arc::clone(session)));
+        control_center.chain(on_site).chain(supervisor).chain(observers).collect()
+    }
+
+    /// 除指定客户端外的全部成员 (即该客户端的伙伴) 及其角色。
+    pub fn partners_of(&self, client_id: Uuid) -> Vec<(ClientRole, Arc<ClientSession>)> {
+        self.members().into_iter().filter(|(_, session)| session.client_id != client_id).collect()
+    }
+
+    /// 组内是否已没有任何成员。
+    pub fn is_empty(&self) -> bool {
+        self.control_center_client.is_none()
+            && self.on_site_mobile_clients.is_empty()
+            && self.supervisor_client.is_none()
+            && self.observers.is_empty()
+    }
+
+    /// 指定客户端当前是否以 `role` 角色在组内。
+    fn holds(&self, role: ClientRole, client_id: Uuid) -> bool {
+        match self.exclusive_slot(role) {
+            Some(slot) => slot.as_ref().is_some_and(|session| session.client_id == client_id),
+            None => self.member_list(role).is_some_and(|list| list.iter().any(|session| session.client_id == client_id)),
+        }
+    }
+
+    /// 以 `role` 角色放入一个会话：独占角色直接占据 (或替换) 其槽位；不限数量的角色替换同 ID 的已有会话，否则追加。
+    fn put_member(&mut self, role: ClientRole, session: Arc<ClientSession>) {
+        if let Some(slot) = self.exclusive_slot_mut(role) {
+            *slot = Some(session);
+        } else if let Some(list) = self.member_list_mut(role) {
+            match list.iter_mut().find(|existing| existing.client_id == session.client_id) {
+                Some(existing) => *existing = session,
+                None => list.push(session),
+            }
+        }
+    }
+
+    /// 将以 `role` 角色在组内的指定客户端移除。
+    ///
+    /// # 返回值
+    /// 该客户端确实在组内并被移除时返回 `true`。
+    fn remove_member(&mut self, role: ClientRole, client_id: Uuid) -> bool {
+        if !self.holds(role, client_id) {
+            return false;
+        }
+        match self.exclusive_slot_mut(role) {
+            Some(slot) => *slot = None,
+            None => {
+                if let Some(list) = self.member_list_mut(role) {
+                    list.retain(|session| session.client_id != client_id);
+                }
+            }
+        }
+        self.site_assignments.remove(&client_id);
+        true
+    }
+
+    /// 返回 `assignment` 与组内其他现场工程师已负责的设备/预检查类别重叠的部分。
+    fn site_assignment_overlap(&self, client_id: Uuid, assignment: &SiteAssignment) -> Vec<String> {
+        self.site_assignments
+            .iter()
+            .filter(|(owner_id, _)| **owner_id != client_id)
+            .flat_map(|(_, owned)| assignment.overlap_with(owned))
+            .collect()
+    }
+
+    /// 负责指定设备的现场移动端会话。
+    ///
+    /// 优先返回在分配中声明了该设备的工程师；没有人认领该设备时，返回未声明任何设备的工程师 (通才)。
+    pub fn site_engineers_for_device(&self, device_id: &str) -> Vec<Arc<ClientSession>> {
+        let assignment_of = |session: &Arc<ClientSession>| self.site_assignments.get(&session.client_id);
+        let owners: Vec<Arc<ClientSession>> = self
+            .on_site_mobile_clients
+            .iter()
+            .filter(|session| assignment_of(session).is_some_and(|assignment| assignment.owns_device(device_id)))
+            .cloned()
+            .collect();
+        if !owners.is_empty() {
+            return owners;
         }
+        self.on_site_mobile_clients
+            .iter()
+            .filter(|session| assignment_of(session).map_or(true, |assignment| assignment.device_ids.is_empty()))
+            .cloned()
+            .collect()
     }
 }
 
@@ -108,6 +360,47 @@ pub struct ConnectionManager {
     /// `ConnectionManager` 使用它来在组创建时初始化与该组关联的任务的共享状态 (`TaskDebugState`)，
     /// 并在组解散（例如，最后一个成员离开组）时通知 `TaskStateManager` 清理相关状态。
     task_state_manager: Arc<TaskStateManager>,
+
+    /// 断线后为客户端保留身份与组内槽位的宽限期。为零时不签发恢复令牌，断线即从组中移除。
+    resume_grace: Duration,
+
+    /// 按角色覆盖的会话恢复宽限期 (例如网络条件较差的现场移动端保留更久)，未覆盖的角色使用 `resume_grace`。
+    role_resume_grace: HashMap<ClientRole, Duration>,
+
+    /// 未单独配置心跳策略的角色 (包括尚未注册、角色为 `Unknown` 的连接) 所使用的心跳策略。
+    /// 由 `HeartbeatMonitor` 执行，并在 `RegisterResponse` 中公布给客户端。
+    heartbeat_policy: HeartbeatPolicy,
+
+    /// 按角色覆盖的心跳策略，例如为网络条件较差的现场移动端设置更宽松的阈值。
+    role_heartbeat_policies: HashMap<ClientRole, HeartbeatPolicy>,
+
+    /// 已加入组的活动客户端当前持有的会话恢复令牌 (客户端ID -> 令牌)。
+    session_resume_tokens: Arc<DashMap<Uuid, String>>,
+
+    /// 处于宽限期内的断线客户端 (恢复令牌 -> 保留记录)。
+    detached_sessions: Arc<DashMap<String, DetachedSession>>,
+
+    /// 会话恢复后的连接别名 (新连接最初的临时客户端ID -> 恢复后沿用的原客户端ID)。
+    /// 新连接的收发循环仍持有最初创建的会话，收到消息与断开时据此找到恢复后的会话。
+    session_aliases: Arc<DashMap<Uuid, Uuid>>,
+
+    /// 会话恢复后待补发的错过消息 (原客户端ID -> 消息)，在 `RegisterResponse` 发出后由 `deliver_missed_messages` 发送。
+    pending_replays: Arc<DashMap<Uuid, Vec<WsMessage>>>,
+
+    /// 接管请求等待当前持有者答复的时长，超时未答复视为同意。
+    takeover_grace: Duration,
+
+    /// 等待持有者答复的接管请求 (接管请求ID -> 请求)。
+    pending_takeovers: Arc<DashMap<String, PendingTakeover>>,
+
+    /// 已签发、尚未使用的交接令牌 (令牌 -> 记录)。
+    handover_grants: Arc<DashMap<String, HandoverGrant>>,
+
+    /// 配对码的有效期。
+    join_code_ttl: Duration,
+
+    /// 已签发、尚未使用的配对码 (配对码 -> 记录)。
+    join_codes: Arc<DashMap<String, JoinCodeGrant>>,
 }
 
 impl ConnectionManager {
@@ -127,9 +420,68 @@ impl ConnectionManager {
             clients: Arc::new(DashMap::new()), // 初始化空的客户端会话映射
             groups: Arc::new(DashMap::new()),  // 初始化空的客户端组映射
             task_state_manager,              // 存储对任务状态管理器的引用
+            resume_grace: Duration::from_secs(DEFAULT_RESUME_GRACE_SECONDS),
+            role_resume_grace: HashMap::new(),
+            heartbeat_policy: HeartbeatPolicy::with_timeout(Duration::from_secs(DEFAULT_CLIENT_TIMEOUT_SECONDS)),
+            role_heartbeat_policies: HashMap::new(),
+            session_resume_tokens: Arc::new(DashMap::new()),
+            detached_sessions: Arc::new(DashMap::new()),
+            session_aliases: Arc::new(DashMap::new()),
+            pending_replays: Arc::new(DashMap::new()),
+            takeover_grace: Duration::from_secs(DEFAULT_TAKEOVER_GRACE_SECONDS),
+            pending_takeovers: Arc::new(DashMap::new()),
+            handover_grants: Arc::new(DashMap::new()),
+            join_code_ttl: Duration::from_secs(DEFAULT_JOIN_CODE_VALID_SECONDS),
+            join_codes: Arc::new(DashMap::new()),
         }
     }
 
+    /// 设置会话恢复的宽限期 (默认为 `DEFAULT_RESUME_GRACE_SECONDS` 秒)。传入零表示禁用会话恢复。
+    pub fn with_resume_grace(mut self, resume_grace: Duration) -> Self {
+        self.resume_grace = resume_grace;
+        self
+    }
+
+    /// 为指定角色单独设置会话恢复的宽限期，覆盖 `with_resume_grace` 的设置。传入零表示该角色不进行会话恢复。
+    pub fn with_role_resume_grace(mut self, role: ClientRole, resume_grace: Duration) -> Self {
+        self.role_resume_grace.insert(role, resume_grace);
+        self
+    }
+
+    /// 指定角色适用的会话恢复宽限期。
+    fn resume_grace_for(&self, role: ClientRole) -> Duration {
+        self.role_resume_grace.get(&role).copied().unwrap_or(self.resume_grace)
+    }
+
+    /// 设置默认心跳策略 (默认断线判定时长为 `DEFAULT_CLIENT_TIMEOUT_SECONDS` 秒，可疑阈值取其一半)。
+    pub fn with_heartbeat_policy(mut self, policy: HeartbeatPolicy) -> Self {
+        self.heartbeat_policy = policy;
+        self
+    }
+
+    /// 为指定角色单独设置心跳策略，覆盖 `with_heartbeat_policy` 的设置。
+    pub fn with_role_heartbeat_policy(mut self, role: ClientRole, policy: HeartbeatPolicy) -> Self {
+        self.role_heartbeat_policies.insert(role, policy);
+        self
+    }
+
+    /// 指定角色适用的心跳策略。
+    pub fn heartbeat_policy_for(&self, role: ClientRole) -> HeartbeatPolicy {
+        self.role_heartbeat_policies.get(&role).copied().unwrap_or(self.heartbeat_policy)
+    }
+
+    /// 设置接管请求等待当前持有者答复的时长 (默认为 `DEFAULT_TAKEOVER_GRACE_SECONDS` 秒)。
+    pub fn with_takeover_grace(mut self, takeover_grace: Duration) -> Self {
+        self.takeover_grace = takeover_grace;
+        self
+    }
+
+    /// 设置配对码的有效期 (默认为 `DEFAULT_JOIN_CODE_VALID_SECONDS` 秒)。
+    pub fn with_join_code_ttl(mut self, join_code_ttl: Duration) -> Self {
+        self.join_code_ttl = join_code_ttl;
+        self
+    }
+
     /// 将一个新的客户端会话添加到连接管理器中进行跟踪。
     /// 
     /// 此方法通常在 WebSocket 服务成功接受一个新的客户端连接后被调用 (例如，在 `WsService` 内部)。
@@ -142,6 +494,8 @@ impl ConnectionManager {
     /// * `connection_should_close`: `Arc<AtomicBool>` - 一个共享的原子布尔标志。
     ///   外部模块 (如 `HeartbeatMonitor` 或 `ConnectionManager` 自身在移除客户端时)
     ///   可以通过设置此标志为 `true` 来请求关闭与此会话关联的底层 WebSocket 连接。
+    /// * `principal`: `Option<AuthenticatedPrincipal>` - WebSocket 握手阶段认证得到的主体 (如有)，
+    ///   将被存储在 `ClientSession::principal` 中，供后续的注册和授权检查使用。
     ///
     /// # 返回值
     /// 返回对新创建并已添加的 `ClientSession` 实例的共享引用 (`Arc<ClientSession>`)。
@@ -150,12 +504,14 @@ impl ConnectionManager {
         addr: SocketAddr,
         sender: mpsc::Sender<WsMessage>,
         connection_should_close: Arc<AtomicBool>,
+        principal: Option<AuthenticatedPrincipal>,
     ) -> Arc<ClientSession> {
         // 创建一个新的 ClientSession 实例。ClientSession::new 内部会为其生成一个唯一的 client_id。
         let client_session = Arc::new(ClientSession::new(
             addr,                    // 客户端网络地址
             sender,                  // 用于向此客户端发送消息的通道
             connection_should_close, // 连接关闭标志
+            principal,               // 握手阶段认证得到的主体
         ));
         
         // 将新创建的客户端会话插入到全局的 `clients` 映射中。
@@ -166,8 +522,11 @@ impl ConnectionManager {
         // 获取客户端的初始角色以用于日志记录 (在 ClientSession::new 中默认为 Unknown)
         let initial_role = client_session.role.read().await.clone();
         info!(
-            "[连接管理器] 新客户端已成功连接并添加至管理器进行跟踪。ID: {}, 地址: {}, 初始角色: {:?}",
-            client_session.client_id, client_session.addr, initial_role
+            "[连接管理器] 新客户端已成功连接并添加至管理器进行跟踪。ID: {}, 地址: {}, 初始角色: {:?}, 认证用户: {}",
+            client_session.client_id,
+            client_session.addr,
+            initial_role,
+            client_session.principal.as_ref().map(|p| p.user_id.as_str()).unwrap_or("<匿名>")
         );
         debug!("[连接管理器] 新增客户端会话详细信息: {:?}", client_session);
         debug!("[连接管理器] 当前活动客户端总数: {}", self.clients.len());
@@ -190,6 +549,9 @@ impl ConnectionManager {
     ///    c. 如果移除此客户端后该组变为空，则从 `groups` 映射中移除该组，并通知
     ///       `TaskStateManager` 清理与该组关联的任务状态。
     ///
+    /// 若该客户端持有会话恢复令牌 (会话恢复未被禁用)，步骤 3 改为进入宽限期：其组内槽位由占位会话保留，
+    /// 发往它的消息被缓存，伙伴收到下线通知；宽限期内凭令牌重连即可恢复，超时后才真正释放槽位。
+    ///
     /// # 参数
     /// * `client_id`: `&Uuid` - 要移除的客户端的唯一ID。
     pub async fn remove_client(&self, client_id: &Uuid) {
@@ -239,158 +601,16 @@ impl ConnectionManager {
                         client_id, role_at_disconnect, group_id
                     );
                     
-                    // 尝试获取该组的锁以进行修改。
-                    // `DashMap::get` 返回的是对 `Arc<RwLock<Group>>` 的引用，如果组存在的话。
-                    if let Some(group_entry) = self.groups.get(&group_id) {
-                        let group_lock = group_entry.value(); // 获取 Arc<RwLock<Group>>
-                        let mut group = group_lock.write().await; // 获取组的异步写锁，准备修改组内成员
-                        info!(
-                            "[连接管理器::组处理] 正在为组 '{}' (任务ID: '{}') 处理客户端 {} (角色: {:?}) 的移除操作。",
-                            group.group_id, group.task_id, client_id, role_at_disconnect
-                        );
-
-                        let mut partner_session_to_notify: Option<Arc<ClientSession>> = None; // 用于存储可能需要被通知的伙伴会话
-
-                        // 根据被移除客户端的角色，将其从组内对应槽位移除，并确定其伙伴（如果存在）。
-                        match role_at_disconnect {
-                            ClientRole::ControlCenter => {
-                                // 检查被移除的是否确实是当前组内的控制中心客户端。
-                                if group.control_center_client.as_ref().map_or(false, |cs| cs.client_id == *client_id) {
-                                    group.control_center_client = None; // 从组中移除控制中心客户端的引用
-                                    partner_session_to_notify = group.on_site_mobile_client.as_ref().map(Arc::clone); // 伙伴是现场移动端
-                                    info!(
-                                        "[连接管理器::组处理] 客户端 {} (控制中心) 已从组 '{}' 中移除。",
-                                        client_id, group.group_id
-                                    );
-                                } else {
-                                    warn!(
-                                        "[连接管理器::组处理] 客户端 {} (声明为控制中心) 在尝试从组 '{}' 移除时，发现其并非该组记录的控制中心客户端。可能状态不一致或重复移除。",
-                                        client_id, group.group_id
-                                    );
-                                }
-                            }
-                            ClientRole::OnSiteMobile => {
-                                // 检查被移除的是否确实是当前组内的现场移动端客户端。
-                                if group.on_site_mobile_client.as_ref().map_or(false, |cs| cs.client_id == *client_id) {
-                                    group.on_site_mobile_client = None; // 从组中移除现场移动端客户端的引用
-                                    partner_session_to_notify = group.control_center_client.as_ref().map(Arc::clone); // 伙伴是控制中心
-                                    info!(
-                                        "[连接管理器::组处理] 客户端 {} (现场移动端) 已从组 '{}' 中移除。",
-                                        client_id, group.group_id
-                                    );
-                                } else {
-                                    warn!(
-                                        "[连接管理器::组处理] 客户端 {} (声明为现场移动端) 在尝试从组 '{}' 移除时，发现其并非该组记录的现场移动端客户端。可能状态不一致或重复移除。",
-                                        client_id, group.group_id
-                                    );
-                                }
-                            }
-                            ClientRole::Unknown => {
-                                // Unknown 角色理论上不应出现在这里，因为前面已过滤。但为完整性保留。
-                                warn!(
-                                    "[连接管理器::组处理] 客户端 {} (角色: 未知) 正在被从组 '{}' 中处理移除，此情况非预期。",
-                                    client_id, group.group_id
-                                );
-                            }
-                        }
-
-                        // 如果找到了伙伴，则向其发送关于当前客户端下线的通知。
-                        if let Some(partner_session) = partner_session_to_notify {
-                            let partner_status_payload = PartnerStatusPayload {
-                                partner_role: role_at_disconnect.clone(), // 下线的是刚被移除的客户端的角色
-                                partner_client_id: *client_id,            // 下线的是刚被移除的客户端的ID
-                                is_online: false,                         // 状态是下线
-                                group_id: group.group_id.clone(),         // 相关的组ID
-                            };
-                            match WsMessage::new(PARTNER_STATUS_UPDATE_MESSAGE_TYPE.to_string(), &partner_status_payload) {
-                                Ok(ws_message) => {
-                                    if let Err(e) = partner_session.sender.send(ws_message).await {
-                                        error!(
-                                            "[连接管理器::组处理] 向客户端 {} (伙伴 of {}) 发送关于客户端 {} (角色: {:?}) 下线的通知失败: {}。该伙伴可能也已断开。",
-                                            partner_session.client_id, client_id, client_id, role_at_disconnect, e
-                                        );
-                                    } else {
-                                        info!(
-                                            "[连接管理器::组处理] 已成功向客户端 {} (伙伴 of {}) 发送了关于客户端 {} (角色: {:?}) 下线的通知。",
-                                            partner_session.client_id, client_id, client_id, role_at_disconnect
-                                        );
-                                    }
-                                }
-                                Err(e) => {
-                                    error!(
-                                        "[连接管理器::组处理] 创建伙伴下线通知 WsMessage 失败: {}. Payload: {:?}",
-                                        e, partner_status_payload
-                                    );
-                                }
-                            }
-                        } else {
-                            info!(
-                                "[连接管理器::组处理] 客户端 {} (角色: {:?}) 从组 '{}' 移除后，该组内无其他伙伴需要通知。",
-                                client_id, role_at_disconnect, group.group_id
-                            );
+                    // 若该客户端持有会话恢复令牌，则进入宽限期：保留其身份与组内槽位，等待其凭令牌重连；
+                    // 否则立即将其从组中移除。
+                    let detached = match self.session_resume_tokens.remove(client_id) {
+                        Some((_, resume_token)) => {
+                            self.detach_session(&client_session, role_at_disconnect, group_id.clone(), resume_token).await
                         }
-                        
-                        // 检查移除此客户端后，组是否变为空。
-                        // 组变为空的条件是：控制中心客户端和现场移动端客户端均不存在 (None)。
-                        let is_group_now_empty = group.control_center_client.is_none() && group.on_site_mobile_client.is_none();
-                        
-                        // 克隆需要在 drop(group) 之后使用的值
-                        let group_id_for_cleanup = group.group_id.clone();
-                        let task_id_for_cleanup = group.task_id.clone(); // 保持克隆 task_id 以用于日志
-
-                        if is_group_now_empty {
-                            info!(
-                                "[连接管理器::组处理] 组 '{}' (任务ID: '{}') 在客户端 {} 移除后已变为空。即将清理其状态...",
-                                group_id_for_cleanup, task_id_for_cleanup, client_id
-                            );
-
-                            // 修正：确保使用 group_id_for_cleanup 调用 TaskStateManager
-                            if let Err(e) = self.task_state_manager.remove_task_state(&group_id_for_cleanup).await {
-                                error!(
-                                    "[连接管理器::组处理] 调用 TaskStateManager::remove_task_state 为组 '{}' (关联任务ID '{}') 清理任务状态时发生错误: {:?}",
-                                    group_id_for_cleanup, task_id_for_cleanup, e
-                                );
-                            } else {
-                                info!(
-                                    "[连接管理器::组处理] 已成功请求 TaskStateManager::remove_task_state 为组 '{}' (关联任务ID '{}') 清理任务状态。",
-                                    group_id_for_cleanup, task_id_for_cleanup
-                                );
-                            }
-
-                            // 在操作 self.groups (DashMap) 之前释放 group (RwLock) 的写锁，以策安全
-                            info!("[CM DEBUG] 在操作 self.groups 映射之前，为组ID '{}' 释放 Group 对象的写锁。", group_id_for_cleanup);
-                            drop(group); // 显式释放写锁
-
-                            // 从 ConnectionManager 内部移除空组
-                            info!("[CM DEBUG] 即将为组ID '{}' 调用 self.groups.remove()。", group_id_for_cleanup);
-                            let removal_result = self.groups.remove(&group_id_for_cleanup);
-                            info!("[CM DEBUG] self.groups.remove() 调用完成。结果是否 Some (即是否找到并移除): {}", removal_result.is_some());
-
-                            if removal_result.is_some() {
-                                info!("[连接管理器::组处理] 空组 '{}' 已成功从 ConnectionManager 的 groups 映射中移除。", group_id_for_cleanup);
-                            } else {
-                                warn!("[连接管理器::组处理] 尝试从 ConnectionManager 的 groups 映射中移除空组 '{}'，但未找到该组。这可能表示状态不一致或已被其他线程移除。", group_id_for_cleanup);
-                            }
-                        } else {
-                            // 组没有变空，不需要从 self.groups 中移除，但仍然需要释放写锁。
-                            info!("[CM DEBUG] 组 '{}' (任务ID '{}') 在客户端 {} 移除后并未变空。仅释放 Group 对象的写锁。", group.group_id, group.task_id, client_id);
-                            drop(group); // 显式释放写锁
-                        }
-
-                        // 注意：原先的 drop(group) 在 is_group_now_empty 块的末尾或对应的 else 块中。
-                        // 新的逻辑是，在 is_group_now_empty 为 true 时，在 self.groups.remove() 之前 drop。
-                        // 在 is_group_now_empty 为 false 时，也显式 drop。
-                        // 这样确保了锁在离开当前作用域前被释放，并且持有时间尽可能短。
-
-                    } else { // group_id 存在于 client_session 中，但在 self.groups 中未找到该组
-                        warn!(
-                            "[连接管理器] 客户端 {} (角色: {:?}) 声称属于组 '{}'，但在管理器中未找到该组。无法执行组内清理。",
-                            client_id, role_at_disconnect, group_id
-                        );
-                         // P3.3.1 (考虑): 即使组在 ConnectionManager 中找不到了，但 TaskStateManager 中可能仍有残留状态。
-                         // 是否需要根据 group_id尝试调用 task_state_manager.remove_task_state(&group_id).await;？
-                         // 当前：不调用，因为组的权威记录在 ConnectionManager。如果 CM 中没有组，TSM 中也不应有活跃状态。
-                         // （除非存在不一致的情况，这需要更深层次的错误恢复机制）
+                        None => false,
+                    };
+                    if !detached {
+                        self.release_group_slot(client_id, role_at_disconnect, group_id, true).await;
                     }
                 } // 结束 else (role_at_disconnect != ClientRole::Unknown)
             } else { // client_session.group_id 为 None
@@ -413,6 +633,474 @@ impl ConnectionManager {
         debug!("[连接管理器] 当前活动组总数: {}", self.groups.len());
     }
 
+    /// 将客户端从其所在组的角色槽位中移除，通知伙伴 (可选)，并在组变空时清理组及其任务状态。
+    ///
+    /// 由 `remove_client` (客户端断开且不进入会话恢复宽限期时) 以及 `expire_detached_sessions`
+    /// (宽限期结束仍未恢复时，移除占位会话) 调用。
+    ///
+    /// # 参数
+    /// * `client_id`: `&Uuid` - 要移除的客户端ID (占位会话与原会话使用相同的ID)。
+    /// * `role_at_disconnect`: `ClientRole` - 该客户端在组内的角色。
+    /// * `group_id`: `String` - 该客户端所属的组ID。
+    /// * `notify_partner`: `bool` - 是否向伙伴发送下线通知。
+    async fn release_group_slot(&self, client_id: &Uuid, role_at_disconnect: ClientRole, group_id: String, notify_partner: bool) {
+        // 尝试获取该组的锁以进行修改。
+        // 先克隆出 `Arc<RwLock<Group>>` 并立即释放 `DashMap` 的条目引用，
+        // 以免在组变空后调用 `self.groups.remove` 时与仍持有的条目引用互相等待。
+        let group_arc = self.groups.get(&group_id).map(|entry| Arc::clone(entry.value()));
+        if let Some(group_lock) = group_arc {
+            let mut group = group_lock.write().await; // 获取组的异步写锁，准备修改组内成员
+            info!(
+                "[连接管理器::组处理] 正在为组 '{}' (任务ID: '{}') 处理客户端 {} (角色: {:?}) 的移除操作。",
+                group.group_id, group.task_id, client_id, role_at_disconnect
+            );
+
+            // 将其从组内对应的槽位 (或观察者列表) 移除，其余成员即为需要通知的伙伴。
+            let partners_to_notify = if group.remove_member(role_at_disconnect, *client_id) {
+                info!(
+                    "[连接管理器::组处理] 客户端 {} ({:?}) 已从组 '{}' 中移除。",
+                    client_id, role_at_disconnect, group.group_id
+                );
+                group.partners_of(*client_id)
+            } else {
+                warn!(
+                    "[连接管理器::组处理] 客户端 {} (声明为 {:?}) 在尝试从组 '{}' 移除时，发现其并非该组记录的成员。可能状态不一致或重复移除。",
+                    client_id, role_at_disconnect, group.group_id
+                );
+                Vec::new()
+            };
+
+            // 向全部伙伴发送关于当前客户端下线的通知 (宽限期结束时伙伴已在断线时收到过通知，不再重复发送)。
+            if notify_partner && !partners_to_notify.is_empty() {
+                for (_, partner_session) in &partners_to_notify {
+                    notify_partner_status(partner_session, role_at_disconnect, *client_id, false, &group.group_id).await;
+                }
+                info!(
+                    "[连接管理器::组处理] 已向组 '{}' 的 {} 个伙伴发送了关于客户端 {} (角色: {:?}) 下线的通知。",
+                    group.group_id, partners_to_notify.len(), client_id, role_at_disconnect
+                );
+            } else {
+                info!(
+                    "[连接管理器::组处理] 客户端 {} (角色: {:?}) 从组 '{}' 移除后，该组内无其他伙伴需要通知。",
+                    client_id, role_at_disconnect, group.group_id
+                );
+            }
+            
+            // 检查移除此客户端后，组是否变为空 (所有槽位与观察者列表均为空)。
+            let is_group_now_empty = group.is_empty();
+            
+            // 克隆需要在 drop(group) 之后使用的值
+            let group_id_for_cleanup = group.group_id.clone();
+            let task_id_for_cleanup = group.task_id.clone(); // 保持克隆 task_id 以用于日志
+
+            if is_group_now_empty {
+                info!(
+                    "[连接管理器::组处理] 组 '{}' (任务ID: '{}') 在客户端 {} 移除后已变为空。即将清理其状态...",
+                    group_id_for_cleanup, task_id_for_cleanup, client_id
+                );
+
+                // 修正：确保使用 group_id_for_cleanup 调用 TaskStateManager
+                if let Err(e) = self.task_state_manager.remove_task_state(&group_id_for_cleanup).await {
+                    error!(
+                        "[连接管理器::组处理] 调用 TaskStateManager::remove_task_state 为组 '{}' (关联任务ID '{}') 清理任务状态时发生错误: {:?}",
+                        group_id_for_cleanup, task_id_for_cleanup, e
+                    );
+                } else {
+                    info!(
+                        "[连接管理器::组处理] 已成功请求 TaskStateManager::remove_task_state 为组 '{}' (关联任务ID '{}') 清理任务状态。",
+                        group_id_for_cleanup, task_id_for_cleanup
+                    );
+                }
+
+                // 在操作 self.groups (DashMap) 之前释放 group (RwLock) 的写锁，以策安全
+                info!("[CM DEBUG] 在操作 self.groups 映射之前，为组ID '{}' 释放 Group 对象的写锁。", group_id_for_cleanup);
+                drop(group); // 显式释放写锁
+
+                // 从 ConnectionManager 内部移除空组
+                info!("[CM DEBUG] 即将为组ID '{}' 调用 self.groups.remove()。", group_id_for_cleanup);
+                let removal_result = self.groups.remove(&group_id_for_cleanup);
+                info!("[CM DEBUG] self.groups.remove() 调用完成。结果是否 Some (即是否找到并移除): {}", removal_result.is_some());
+
+                if removal_result.is_some() {
+                    info!("[连接管理器::组处理] 空组 '{}' 已成功从 ConnectionManager 的 groups 映射中移除。", group_id_for_cleanup);
+                } else {
+                    warn!("[连接管理器::组处理] 尝试从 ConnectionManager 的 groups 映射中移除空组 '{}'，但未找到该组。这可能表示状态不一致或已被其他线程移除。", group_id_for_cleanup);
+                }
+            } else {
+                // 组没有变空，不需要从 self.groups 中移除，但仍然需要释放写锁。
+                info!("[CM DEBUG] 组 '{}' (任务ID '{}') 在客户端 {} 移除后并未变空。仅释放 Group 对象的写锁。", group.group_id, group.task_id, client_id);
+                drop(group); // 显式释放写锁
+                self.broadcast_roster(&group_id_for_cleanup).await;
+            }
+
+            // 注意：原先的 drop(group) 在 is_group_now_empty 块的末尾或对应的 else 块中。
+            // 新的逻辑是，在 is_group_now_empty 为 true 时，在 self.groups.remove() 之前 drop。
+            // 在 is_group_now_empty 为 false 时，也显式 drop。
+            // 这样确保了锁在离开当前作用域前被释放，并且持有时间尽可能短。
+
+        } else { // group_id 存在于 client_session 中，但在 self.groups 中未找到该组
+            warn!(
+                "[连接管理器] 客户端 {} (角色: {:?}) 声称属于组 '{}'，但在管理器中未找到该组。无法执行组内清理。",
+                client_id, role_at_disconnect, group_id
+            );
+             // P3.3.1 (考虑): 即使组在 ConnectionManager 中找不到了，但 TaskStateManager 中可能仍有残留状态。
+             // 是否需要根据 group_id尝试调用 task_state_manager.remove_task_state(&group_id).await;？
+             // 当前：不调用，因为组的权威记录在 ConnectionManager。如果 CM 中没有组，TSM 中也不应有活跃状态。
+             // （除非存在不一致的情况，这需要更深层次的错误恢复机制）
+        }
+    }
+
+    /// 为已加入组的客户端签发新的会话恢复令牌 (替换其之前持有的令牌)。
+    ///
+    /// # 返回值
+    /// 返回新令牌；若该角色的会话恢复被禁用 (宽限期为零) 则返回 `None`。
+    fn issue_resume_token(&self, client_id: Uuid, role: ClientRole) -> Option<String> {
+        if self.resume_grace_for(role).is_zero() {
+            return None;
+        }
+        let token = Uuid::new_v4().simple().to_string();
+        self.session_resume_tokens.insert(client_id, token.clone());
+        Some(token)
+    }
+
+    /// 指定角色的会话恢复宽限期 (秒)，用于填充 `RegisterResponsePayload::resume_grace_seconds`。
+    fn resume_grace_seconds(&self, role: ClientRole) -> Option<u64> {
+        let resume_grace = self.resume_grace_for(role);
+        (!resume_grace.is_zero()).then_some(resume_grace.as_secs())
+    }
+
+    /// 让断线客户端进入会话恢复宽限期。
+    ///
+    /// 用一个同 ID 的占位会话替换该客户端在组内的角色槽位，启动收集错过消息的后台任务，
+    /// 以恢复令牌为键记录保留信息，并通知伙伴该客户端已下线。
+    ///
+    /// # 返回值
+    /// 成功进入宽限期时返回 `true`；若组不存在或槽位已不属于该客户端则返回 `false`，
+    /// 调用方应按普通断开处理。
+    async fn detach_session(
+        &self,
+        client_session: &Arc<ClientSession>,
+        role: ClientRole,
+        group_id: String,
+        resume_token: String,
+    ) -> bool {
+        let client_id = client_session.client_id;
+        let Some(group_arc) = self.groups.get(&group_id).map(|entry| Arc::clone(entry.value())) else {
+            warn!("[连接管理器::会话恢复] 客户端 {} 所属的组 '{}' 不存在，无法保留其会话。", client_id, group_id);
+            return false;
+        };
+
+        // 占位会话沿用原客户端ID，其消息通道由收集任务读取；它不对应任何物理连接，因此不加入 `clients`。
+        let (placeholder_tx, placeholder_rx) = mpsc::channel(MISSED_MESSAGE_BUFFER_CAPACITY);
+        let mut placeholder = ClientSession::with_client_id(
+            client_id,
+            client_session.addr,
+            placeholder_tx,
+            Arc::new(AtomicBool::new(false)),
+            client_session.principal.clone(),
+        );
+        // 占位会话在名册中以离线状态展示原连接的信息
+        placeholder.creation_time = client_session.creation_time;
+        let placeholder = Arc::new(placeholder);
+        *placeholder.role.write().await = role;
+        *placeholder.group_id.write().await = Some(group_id.clone());
+        *placeholder.last_seen.write().await = *client_session.last_seen.read().await;
+        *placeholder.display_name.write().await = client_session.display_name.read().await.clone();
+        *placeholder.software_version.write().await = client_session.software_version.read().await.clone();
+        *placeholder.round_trip_ms.write().await = *client_session.round_trip_ms.read().await;
+
+        let mut group = group_arc.write().await;
+        if !group.holds(role, client_id) {
+            warn!(
+                "[连接管理器::会话恢复] 组 '{}' 的 {:?} 槽位已不属于客户端 {}，无法保留其会话。",
+                group_id, role, client_id
+            );
+            return false;
+        }
+        group.put_member(role, Arc::clone(&placeholder));
+        let partners = group.partners_of(client_id);
+        drop(group);
+
+        let missed_messages = Arc::new(std::sync::Mutex::new(MissedMessages::default()));
+        let collector = tokio::spawn(collect_missed_messages(placeholder_rx, Arc::clone(&missed_messages)));
+        self.detached_sessions.insert(
+            resume_token,
+            DetachedSession {
+                client_id,
+                role,
+                group_id: group_id.clone(),
+                principal_user_id: client_session.principal.as_ref().map(|p| p.user_id.clone()),
+                detached_at: Instant::now(),
+                placeholder,
+                missed_messages,
+                collector,
+            },
+        );
+        info!(
+            "[连接管理器::会话恢复] 客户端 {} (角色: {:?}) 已断线，其在组 '{}' 中的身份与槽位将保留 {:?}，等待凭恢复令牌重连。",
+            client_id, role, group_id, self.resume_grace_for(role)
+        );
+
+        for (_, partner) in &partners {
+            notify_partner_status(partner, role, client_id, false, &group_id).await;
+        }
+        self.broadcast_roster(&group_id).await;
+        true
+    }
+
+    /// 尝试凭 `RegisterPayload::resume_token` 恢复之前的会话。
+    ///
+    /// 令牌有效 (处于宽限期内、组与角色一致、已认证用户一致) 时：新连接沿用原客户端ID，
+    /// 取回组内槽位，断线期间错过的消息被放入待补发队列，伙伴收到上线通知。
+    /// 若令牌对应的旧连接尚未被判定断开 (半开连接)，会先将旧连接断开再恢复。
+    ///
+    /// # 返回值
+    /// 恢复成功时返回成功的注册响应；令牌缺失、无效、过期或不匹配时返回 `None`，调用方按普通注册处理。
+    async fn try_resume_session(
+        &self,
+        client_session: &Arc<ClientSession>,
+        payload: &RegisterPayload,
+    ) -> Option<RegisterResponsePayload> {
+        let token = payload.resume_token.as_deref()?;
+
+        // 令牌仍属于一个活动会话：旧连接可能已经失效但尚未被心跳检测发现，先断开它使其进入宽限期。
+        let active_holder = self
+            .session_resume_tokens
+            .iter()
+            .find(|entry| entry.value() == token)
+            .map(|entry| *entry.key());
+        if let Some(active_id) = active_holder {
+            if active_id == client_session.client_id {
+                return None; // 同一连接重复注册，按普通注册处理
+            }
+            info!(
+                "[连接管理器::会话恢复] 恢复令牌仍属于活动客户端 {}，先断开该旧连接再恢复会话。",
+                active_id
+            );
+            self.remove_client(&active_id).await;
+        }
+
+        let Some((token, record)) = self.detached_sessions.remove(token) else {
+            info!("[连接管理器::会话恢复] 客户端 {} 提供的恢复令牌无效或已过期，按普通注册处理。", client_session.client_id);
+            return None;
+        };
+
+        if record.detached_at.elapsed() > self.resume_grace_for(record.role) {
+            info!("[连接管理器::会话恢复] 客户端 {} 的恢复令牌已超过宽限期，按普通注册处理。", record.client_id);
+            self.release_detached_session(record).await;
+            return None;
+        }
+        let principal_user_id = client_session.principal.as_ref().map(|p| p.user_id.as_str());
+        let group_arc = self.groups.get(&record.group_id).map(|entry| Arc::clone(entry.value()));
+        let task_matches = match &group_arc {
+            Some(group_arc) => group_arc.read().await.task_id == payload.task_id,
+            None => false,
+        };
+        if record.group_id != payload.group_id
+            || record.role != payload.role
+            || record.principal_user_id.as_deref() != principal_user_id
+            || !task_matches
+        {
+            warn!(
+                "[连接管理器::会话恢复] 客户端 {} 的恢复请求 (组 '{}', 角色 {:?}) 与保留的会话 (组 '{}', 角色 {:?}) 不匹配，按普通注册处理。",
+                client_session.client_id, payload.group_id, payload.role, record.group_id, record.role
+            );
+            self.detached_sessions.insert(token, record); // 保留记录，原客户端仍可在宽限期内恢复
+            return None;
+        }
+        let group_arc = group_arc?;
+
+        // 新会话沿用原客户端ID，但使用新连接的消息通道与关闭标志。
+        let DetachedSession { client_id, role, group_id, placeholder, missed_messages, mut collector, .. } = record;
+        let resumed_session = Arc::new(ClientSession::with_client_id(
+            client_id,
+            client_session.addr,
+            client_session.sender.clone(),
+            Arc::clone(&client_session.connection_should_close),
+            client_session.principal.clone(),
+        ));
+        *resumed_session.role.write().await = role;
+        *resumed_session.group_id.write().await = Some(group_id.clone());
+        *resumed_session.display_name.write().await = payload.client_display_name.clone();
+        *resumed_session.software_version.write().await = payload.client_software_version.clone();
+
+        let mut group = group_arc.write().await;
+        group.put_member(role, Arc::clone(&resumed_session));
+        let partners = group.partners_of(client_id);
+        drop(group);
+
+        self.clients.remove(&client_session.client_id);
+        self.clients.insert(client_id, Arc::clone(&resumed_session));
+        self.session_aliases.insert(client_session.client_id, client_id);
+        let resume_token = self.issue_resume_token(client_id, role);
+
+        // 占位会话不再被引用后，收集任务会把通道中剩余的消息转存完毕并结束。
+        drop(placeholder);
+        if tokio::time::timeout(MISSED_MESSAGE_DRAIN_TIMEOUT, &mut collector).await.is_err() {
+            warn!("[连接管理器::会话恢复] 等待客户端 {} 的错过消息收集任务结束超时，仅补发已收集的消息。", client_id);
+            collector.abort();
+        }
+        let missed = std::mem::take(&mut *missed_messages.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        let replay: Vec<WsMessage> = missed.messages.into();
+        if missed.dropped > 0 {
+            // 注册成功后服务端总会推送一次完整任务状态，被丢弃的状态更新不会导致客户端最终状态错误
+            warn!(
+                "[连接管理器::会话恢复] 客户端 {} 断线期间有 {} 条消息因缓冲已满被丢弃，仅补发最近的 {} 条。",
+                client_id, missed.dropped, replay.len()
+            );
+        }
+        let replay_count = replay.len();
+        self.pending_replays.insert(client_id, replay);
+
+        for (_, partner) in &partners {
+            notify_partner_status(partner, role, client_id, true, &group_id).await;
+        }
+        self.broadcast_roster(&group_id).await;
+        info!(
+            "[连接管理器::会话恢复] 连接 {} 已恢复客户端 {} (角色: {:?}) 在组 '{}' 中的会话，待补发 {} 条错过的消息。",
+            client_session.client_id, client_id, role, group_id, replay_count
+        );
+        Some(RegisterResponsePayload {
+            success: true,
+            message: Some(format!("已恢复之前的会话，将补发 {} 条断线期间错过的消息。", replay_count)),
+            assigned_client_id: client_id,
+            effective_group_id: Some(group_id),
+            effective_role: Some(role),
+            error_code: None,
+            resume_token,
+            resume_grace_seconds: self.resume_grace_seconds(role),
+            resumed: true,
+            heartbeat: Some(self.heartbeat_policy_for(role).advertised_bounds()),
+        })
+    }
+
+    /// 向客户端发送其所在组当前完整的任务调试状态 (`TaskStateUpdate`)。
+    ///
+    /// 在注册成功后由 `MessageRouter` 调用，使新加入 (或重连) 的客户端立即获得权威状态，
+    /// 无需等待伙伴的下一次操作；也用于响应客户端的 `TaskStateSyncRequest`，此时 `in_reply_to` 指向该请求。
+    ///
+    /// # 返回值
+    /// 成功发送时返回 `true`；该组没有任务状态或发送失败时返回 `false`。
+    pub async fn send_task_state_snapshot(
+        &self,
+        client_session: &ClientSession,
+        group_id: &str,
+        in_reply_to: Option<&str>,
+    ) -> bool {
+        let Some(state_arc) = self.task_state_manager.get_task_state(group_id).await else {
+            warn!("[连接管理器::状态同步] 组 '{}' 没有任务状态，无法向客户端 {} 发送完整状态。", group_id, client_session.client_id);
+            return false;
+        };
+        let message = {
+            let state = state_arc.read().await;
+            match in_reply_to {
+                Some(request_id) => WsMessage::new_reply(TASK_STATE_UPDATE_MESSAGE_TYPE.to_string(), &*state, request_id),
+                None => WsMessage::new(TASK_STATE_UPDATE_MESSAGE_TYPE.to_string(), &*state),
+            }
+        };
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                error!("[连接管理器::状态同步] 为组 '{}' 构造任务状态消息失败: {}", group_id, e);
+                return false;
+            }
+        };
+        if let Err(e) = client_session.sender.send(message).await {
+            error!("[连接管理器::状态同步] 向客户端 {} 发送组 '{}' 的完整任务状态失败: {}", client_session.client_id, group_id, e);
+            return false;
+        }
+        info!("[连接管理器::状态同步] 已向客户端 {} 发送组 '{}' 的完整任务状态。", client_session.client_id, group_id);
+        true
+    }
+
+    /// 结束一条断线会话的保留：停止消息收集任务，并将占位会话从组中移除 (组变空时一并清理)。
+    async fn release_detached_session(&self, record: DetachedSession) {
+        let DetachedSession { client_id, role, group_id, collector, .. } = record;
+        collector.abort();
+        // 伙伴已在断线时收到下线通知，此处不再重复通知
+        self.release_group_slot(&client_id, role, group_id, false).await;
+    }
+
+    /// 清理所有已超过宽限期仍未恢复的断线会话。由 `HeartbeatMonitor` 在每个检查周期调用。
+    ///
+    /// # 返回值
+    /// 返回本次清理的断线会话数量。
+    pub async fn expire_detached_sessions(&self) -> usize {
+        let expired_tokens: Vec<String> = self
+            .detached_sessions
+            .iter()
+            .filter(|entry| entry.detached_at.elapsed() > self.resume_grace_for(entry.role))
+            .map(|entry| entry.key().clone())
+            .collect();
+        let mut expired_count = 0;
+        for token in expired_tokens {
+            if let Some((_, record)) = self.detached_sessions.remove(&token) {
+                info!(
+                    "[连接管理器::会话恢复] 客户端 {} (组 '{}') 的会话恢复宽限期已过，释放其组内槽位。",
+                    record.client_id, record.group_id
+                );
+                self.release_detached_session(record).await;
+                expired_count += 1;
+            }
+        }
+        expired_count
+    }
+
+    /// 返回某个连接当前对应的客户端会话。
+    ///
+    /// 连接凭恢复令牌恢复了之前的会话后，其收发循环仍持有最初创建的临时会话；
+    /// 此方法据连接别名返回恢复后沿用原客户端ID的会话，未恢复过的连接原样返回。
+    pub fn resolve_session(&self, connection_session: Arc<ClientSession>) -> Arc<ClientSession> {
+        let resumed = self
+            .session_aliases
+            .get(&connection_session.client_id)
+            .and_then(|alias| self.clients.get(alias.value()).map(|entry| Arc::clone(entry.value())));
+        resumed.unwrap_or(connection_session)
+    }
+
+    /// 在某个物理连接结束时移除其对应的客户端会话。
+    ///
+    /// 与 `remove_client` 不同，此方法会解析会话恢复产生的连接别名，并且只移除仍属于该连接的会话：
+    /// 若该客户端ID已被另一个新连接恢复 (例如旧的半开连接在恢复之后才结束)，则不会误删新连接的会话。
+    pub async fn remove_connection(&self, connection_session: &Arc<ClientSession>) {
+        let client_id = match self.session_aliases.remove(&connection_session.client_id) {
+            Some((_, resumed_client_id)) => resumed_client_id,
+            None => connection_session.client_id,
+        };
+        let owned_by_connection = self.clients.get(&client_id).is_some_and(|entry| {
+            Arc::ptr_eq(&entry.value().connection_should_close, &connection_session.connection_should_close)
+        });
+        if !owned_by_connection {
+            info!(
+                "[连接管理器] 连接 {} 结束时，客户端 {} 已被移除或已由其他连接恢复，无需再次移除。",
+                connection_session.client_id, client_id
+            );
+            connection_session.connection_should_close.store(true, Ordering::SeqCst);
+            return;
+        }
+        self.remove_client(&client_id).await;
+    }
+
+    /// 向刚恢复会话的客户端补发断线期间错过的消息 (应在发送 `RegisterResponse` 之后调用)。
+    ///
+    /// # 返回值
+    /// 返回补发的消息数量；没有待补发消息时返回 0。
+    pub async fn deliver_missed_messages(&self, client_session: &Arc<ClientSession>) -> usize {
+        let client_session = self.resolve_session(Arc::clone(client_session));
+        let Some((_, replay)) = self.pending_replays.remove(&client_session.client_id) else {
+            return 0;
+        };
+        let replay_count = replay.len();
+        for message in replay {
+            if let Err(e) = client_session.sender.send(message).await {
+                error!("[连接管理器::会话恢复] 向客户端 {} 补发错过的消息失败: {}", client_session.client_id, e);
+                return 0;
+            }
+        }
+        info!("[连接管理器::会话恢复] 已向客户端 {} 补发 {} 条断线期间错过的消息。", client_session.client_id, replay_count);
+        replay_count
+    }
+
     /// 处理客户端加入组的请求。
     ///
     /// 此方法由 `MessageRouter` 在收到类型为 "Register" 的 WebSocket 消息后调用。
@@ -440,8 +1128,26 @@ impl ConnectionManager {
         &self,
         client_session: Arc<ClientSession>,
         payload: RegisterPayload,
+    ) -> Result<RegisterResponsePayload, RegisterResponsePayload> {
+        self.join_group_in_scope(client_session, payload, AccessScope::Group).await
+    }
+
+    /// 与 `join_group` 相同，但按 `access_scope` 做项目授权检查 (凭配对码注册时总是按任务检查)。
+    ///
+    /// 凭配对码注册时，配对码在客户端被放入槽位的同时才被消耗 (在组的写锁内)；
+    /// 被拒绝的注册 (无权加入、槽位已被占用等) 不会使其失效。凭恢复令牌恢复会话时不消耗配对码。
+    async fn join_group_in_scope(
+        &self,
+        client_session: Arc<ClientSession>,
+        mut payload: RegisterPayload,
+        mut access_scope: AccessScope,
     ) -> Result<RegisterResponsePayload, RegisterResponsePayload> {
         let client_id = client_session.client_id;
+        // 凭配对码注册时，由配对码确定要加入的组与任务
+        let join_code = self.resolve_join_code(client_id, &mut payload).map_err(|denied| denied.into_response(client_id))?;
+        if join_code.is_some() {
+            access_scope = AccessScope::Task;
+        }
         let requested_role = payload.role.clone();
         let group_id = payload.group_id.clone();
         let task_id = payload.task_id.clone();
@@ -451,15 +1157,12 @@ impl ConnectionManager {
             client_id, group_id, task_id, requested_role
         );
 
-        if task_id.is_empty() {
-            warn!("[CM::join_group EARLY_EXIT_A] task_id is empty for client {}, group '{}'. Registration rejected.", client_id, group_id);
-            return Err(RegisterResponsePayload {
-                success: false,
-                message: Some("注册失败：必须提供有效的 task_id。".to_string()),
-                assigned_client_id: client_id,
-                effective_group_id: None,
-                effective_role: None,
-            });
+        // 基本校验与授权检查 (接管请求在征求持有者同意前也会执行同样的检查)
+        check_registration_allowed(&client_session, &payload, access_scope).map_err(|denied| denied.into_response(client_id))?;
+
+        // 会话恢复：携带有效恢复令牌的重连客户端直接取回其在宽限期内保留的身份与组内槽位。
+        if let Some(resumed_response) = self.try_resume_session(&client_session, &payload).await {
+            return Ok(resumed_response);
         }
 
         info!("[CM::join_group PRE_CONTAINS_KEY_CHECK] Group '{}', Task '{}'. About to check self.groups (len: {}).", group_id, task_id, self.groups.len());
@@ -508,6 +1211,11 @@ impl ConnectionManager {
                             assigned_client_id: client_id,
                             effective_group_id: None,
                             effective_role: None,
+                            error_code: Some(ErrorCode::TaskMismatch),
+                            resume_token: None,
+                            resume_grace_seconds: None,
+                            resumed: false,
+                            heartbeat: None,
                         });
                     }
                     info!(
@@ -543,6 +1251,11 @@ impl ConnectionManager {
                         assigned_client_id: client_id,
                         effective_group_id: None,
                         effective_role: None,
+                        error_code: Some(ErrorCode::InternalError),
+                        resume_token: None,
+                        resume_grace_seconds: None,
+                        resumed: false,
+                        heartbeat: None,
                     });
                 }
             }
@@ -562,6 +1275,11 @@ impl ConnectionManager {
                     assigned_client_id: client_id,
                     effective_group_id: None,
                     effective_role: None,
+                    error_code: Some(ErrorCode::TaskMismatch),
+                    resume_token: None,
+                    resume_grace_seconds: None,
+                    resumed: false,
+                    heartbeat: None,
                 });
             }
             info!(
@@ -577,79 +1295,82 @@ impl ConnectionManager {
 
         info!("[CM::join_group PRE_WRITE_LOCK] Group '{}' (Task '{}'). Client {}. About to acquire write lock.", group_id, task_id, client_id);
         let mut group = group_arc.write().await;
-        info!("[CM::join_group POST_WRITE_LOCK] Group '{}' (Task '{}'). Client {}. Acquired write lock. Group cc: {:?}, os: {:?}", 
-            group_id, task_id, client_id, 
-            group.control_center_client.as_ref().map(|c| c.client_id),
-            group.on_site_mobile_client.as_ref().map(|c| c.client_id)
+        info!("[CM::join_group POST_WRITE_LOCK] Group '{}' (Task '{}'). Client {}. Acquired write lock. Members: {:?}",
+            group_id, task_id, client_id,
+            group.members().iter().map(|(role, c)| (*role, c.client_id)).collect::<Vec<_>>()
         );
 
         // 根据请求的角色检查组内是否已有同角色的客户端。
-        // 同时处理将当前客户端分配到组内对应角色的槽位。
-        let role_conflict_message: Option<String> = match requested_role {
-            ClientRole::ControlCenter => {
-                // Check if the slot is occupied
-                if let Some(existing_session) = group.control_center_client.as_ref() {
-                    // Slot is occupied. Check if it's the same session trying to re-register (unlikely but safe)
-                    if existing_session.client_id == client_id {
-                        // Same session, allow overwriting/refreshing
-                        group.control_center_client = Some(Arc::clone(&client_session));
-                        None // No conflict
-                    } else {
-                        // Different session in slot. Check if it's marked for closure.
-                        if existing_session.connection_should_close.load(Ordering::SeqCst) {
-                            // Existing session is closing, allow replacement
-                            info!(
-                                "[连接管理器::注册] 组 '{}' 的 {:?} 槽位被标记为关闭的会话 {} 占用。新会话 {} 将替换它。",
-                                group_id, requested_role, existing_session.client_id, client_id
-                            );
-                            group.control_center_client = Some(Arc::clone(&client_session));
-                            None // No conflict, replaced closing session
-                        } else {
-                            // Slot occupied by a different, active session. Conflict.
-                            Some(format!(
-                                "组 '{}' 已有一个活动的控制中心客户端 ({}).",
-                                group_id, existing_session.client_id // Optionally log the existing client ID
-                            ))
-                        }
-                    }
-                } else {
-                    // Slot is empty, place the new session
-                    group.control_center_client = Some(Arc::clone(&client_session));
-                    None // 无冲突
+        // 同时处理将当前客户端分配到组内对应角色的槽位；观察者不占独占槽位，直接加入观察者列表。
+        let was_member = group.holds(requested_role, client_id);
+        let role_conflict_message: Option<(ErrorCode, String)> = match group.exclusive_slot(requested_role) {
+            // 槽位被另一个仍然活动的会话占用 (同一会话重复注册或旧会话已被标记为关闭时允许替换)
+            Some(Some(existing_session))
+                if existing_session.client_id != client_id
+                    && !existing_session.connection_should_close.load(Ordering::SeqCst) =>
+            {
+                Some((ErrorCode::RoleSlotTaken, format!(
+                    "组 '{}' 已有一个活动的 {} 客户端 ({}).",
+                    group_id, requested_role, existing_session.client_id
+                )))
+            }
+            Some(existing_slot) => {
+                if let Some(existing_session) = existing_slot.as_ref().filter(|existing| existing.client_id != client_id) {
+                    info!(
+                        "[连接管理器::注册] 组 '{}' 的 {:?} 槽位被标记为关闭的会话 {} 占用。新会话 {} 将替换它。",
+                        group_id, requested_role, existing_session.client_id, client_id
+                    );
                 }
+                group.put_member(requested_role, Arc::clone(&client_session));
+                None // 无冲突
             }
-            ClientRole::OnSiteMobile => {
-                // Similar logic as above, checking the on_site_mobile_client slot
-                if let Some(existing_session) = group.on_site_mobile_client.as_ref() {
-                    if existing_session.client_id == client_id {
-                        group.on_site_mobile_client = Some(Arc::clone(&client_session));
-                        None
-                    } else {
-                        if existing_session.connection_should_close.load(Ordering::SeqCst) {
-                            info!(
-                                "[连接管理器::注册] 组 '{}' 的 {:?} 槽位被标记为关闭的会话 {} 占用。新会话 {} 将替换它。",
-                                group_id, requested_role, existing_session.client_id, client_id
-                            );
-                            group.on_site_mobile_client = Some(Arc::clone(&client_session));
-                            None
-                        } else {
-                            Some(format!(
-                                "组 '{}' 已有一个活动的现场移动端客户端 ({}).",
-                                group_id, existing_session.client_id
-                            ))
-                        }
-                    }
+            None if requested_role == ClientRole::OnSiteMobile => {
+                // 正在关闭的旧连接不再参与设备分配 (例如同一工程师未凭恢复令牌重新注册)
+                let closing_ids: Vec<Uuid> = group
+                    .on_site_mobile_clients
+                    .iter()
+                    .filter(|existing| existing.client_id != client_id && existing.connection_should_close.load(Ordering::SeqCst))
+                    .map(|existing| existing.client_id)
+                    .collect();
+                for closing_id in closing_ids {
+                    info!("[连接管理器::注册] 组 '{}' 中正在关闭的现场移动端会话 {} 被移出组。", group_id, closing_id);
+                    group.remove_member(ClientRole::OnSiteMobile, closing_id);
+                }
+                let assignment = payload.site_assignment.clone().unwrap_or_default();
+                let overlap = group.site_assignment_overlap(client_id, &assignment);
+                if overlap.is_empty() {
+                    group.put_member(requested_role, Arc::clone(&client_session));
+                    group.site_assignments.insert(client_id, assignment);
+                    None // 现场移动端数量不限，只要负责的设备与类别不重叠
                 } else {
-                    group.on_site_mobile_client = Some(Arc::clone(&client_session));
-                    None // 无冲突
+                    Some((ErrorCode::RoleSlotTaken, format!(
+                        "组 '{}' 中以下设备或预检查类别已由其他现场工程师负责: {}.",
+                        group_id, overlap.join(", ")
+                    )))
                 }
             }
-            ClientRole::Unknown => { // 不允许以 Unknown 角色注册到特定槽位
-                Some("不允许以 'Unknown' 角色注册。请提供有效的客户端角色。".to_string())
+            None if requested_role == ClientRole::Observer => {
+                group.put_member(requested_role, Arc::clone(&client_session));
+                None // 观察者数量不限
+            }
+            None => { // 不允许以 Unknown 角色注册到特定槽位
+                Some((ErrorCode::PayloadInvalid, "不允许以 'Unknown' 角色注册。请提供有效的客户端角色。".to_string()))
             }
         };
 
-        if let Some(conflict_msg) = role_conflict_message {
+        // 配对码在放入槽位的同时被消耗：同一配对码的并发注册只有一个能成功，被拒绝的注册不会使其失效
+        let role_conflict_message = role_conflict_message.or_else(|| {
+            let join_code = join_code.as_deref()?;
+            if self.consume_join_code(client_id, join_code, &payload) {
+                return None;
+            }
+            if !was_member {
+                group.remove_member(requested_role, client_id);
+            }
+            Some((ErrorCode::JoinCodeInvalid, "配对码无效或已过期，请向控制中心重新获取。".to_string()))
+        });
+
+        if let Some((conflict_code, conflict_msg)) = role_conflict_message {
             warn!(
                 "[连接管理器::注册] 客户端 {} 注册到组 '{}' 失败，角色冲突: {}",
                 client_id, group_id, conflict_msg
@@ -664,6 +1385,11 @@ impl ConnectionManager {
                 assigned_client_id: client_id,
                 effective_group_id: None, // 注册失败，没有有效组ID
                 effective_role: None,     // 注册失败，没有有效角色
+                error_code: Some(conflict_code),
+                resume_token: None,
+                resume_grace_seconds: None,
+                resumed: false,
+                heartbeat: None,
             });
         }
 
@@ -671,45 +1397,30 @@ impl ConnectionManager {
         // 获取客户端会话内部状态的写锁以更新其角色和组ID。
         *client_session.role.write().await = requested_role.clone();
         *client_session.group_id.write().await = Some(group_id.clone());
+        *client_session.display_name.write().await = payload.client_display_name.clone();
+        *client_session.software_version.write().await = payload.client_software_version.clone();
 
         info!(
             "[连接管理器::注册] 客户端 {} (角色: {:?}) 已成功加入/更新到组 '{}' (任务ID: '{}')。",
             client_id, requested_role, group_id, group.task_id // 使用 group.task_id 以确保一致性
         );
         debug!(
-            "[连接管理器::注册] 组 '{}' 当前状态: 控制中心: {:?}, 现场移动端: {:?}.",
+            "[连接管理器::注册] 组 '{}' 当前成员: {:?}.",
             group_id,
-            group.control_center_client.as_ref().map(|cs| cs.client_id),
-            group.on_site_mobile_client.as_ref().map(|cs| cs.client_id)
+            group.members().iter().map(|(role, cs)| (*role, cs.client_id)).collect::<Vec<_>>()
         );
 
         // --- 步骤 4: 通知组内伙伴关于当前客户端的上线状态 ---
         // (注意：此处的逻辑需要仔细处理，避免向自己发送通知，并正确识别伙伴)
-        let mut partner_sessions_to_notify: Vec<Arc<ClientSession>> = Vec::new();
         info!(
             "[CM::join_group DBG_STEP_4_PRE_PARTNER_NOTIFY] Client {}: Starting partner notification logic.",
             client_id
         );
         
-        // 根据当前客户端的角色，找到其伙伴。
-        match requested_role {
-            ClientRole::ControlCenter => {
-                if let Some(partner) = &group.on_site_mobile_client {
-                    // 确保伙伴不是自己 (理论上不太可能，因为角色不同)
-                    if partner.client_id != client_id {
-                        partner_sessions_to_notify.push(Arc::clone(partner));
-                    }
-                }
-            }
-            ClientRole::OnSiteMobile => {
-                if let Some(partner) = &group.control_center_client {
-                    if partner.client_id != client_id {
-                        partner_sessions_to_notify.push(Arc::clone(partner));
-                    }
-                }
-            }
-            ClientRole::Unknown => { /* Unknown 角色不应有伙伴通知 */ }
-        }
+        // 组内除当前客户端外的全部成员 (包括 QA 主管与观察者) 都是需要通知的伙伴。
+        let existing_partners = group.partners_of(client_id);
+        let partner_sessions_to_notify: Vec<Arc<ClientSession>> =
+            existing_partners.iter().map(|(_, session)| Arc::clone(session)).collect();
 
         // 向识别出的伙伴发送上线通知。
         info!(
@@ -726,6 +1437,7 @@ impl ConnectionManager {
                 partner_client_id: client_id,         // 上线的是当前客户端的ID
                 is_online: true,                      // 状态是在线
                 group_id: group_id.clone(),           // 相关的组ID
+                link_suspect: false,
             };
             match WsMessage::new(PARTNER_STATUS_UPDATE_MESSAGE_TYPE.to_string(), &partner_status_payload) {
                 Ok(ws_message) => {
@@ -760,35 +1472,12 @@ impl ConnectionManager {
         
         // --- 步骤 5: 通知当前客户端其伙伴（如果已存在）的在线状态 ---
         // (在释放组的写锁前完成，以保证伙伴信息的一致性)
-        let mut existing_partners_for_current_client: Vec<(ClientRole, Uuid)> = Vec::new();
         info!(
             "[CM::join_group DBG_STEP_5_PRE_SELF_NOTIFY] Client {}: Starting self-notification logic about existing partners.",
             client_id
         );
-        match requested_role {
-            ClientRole::ControlCenter => { // 当前客户端是控制中心，其伙伴是现场端
-                if let Some(on_site_client) = &group.on_site_mobile_client {
-                    // 确保不是自己 (虽然不太可能，因为角色不同)
-                    if on_site_client.client_id != client_id {
-                         existing_partners_for_current_client.push((
-                            ClientRole::OnSiteMobile, // 伙伴的角色
-                            on_site_client.client_id // 伙伴的ID
-                        ));
-                    }
-                }
-            }
-            ClientRole::OnSiteMobile => { // 当前客户端是现场端，其伙伴是控制中心
-                if let Some(control_client) = &group.control_center_client {
-                    if control_client.client_id != client_id {
-                        existing_partners_for_current_client.push((
-                            ClientRole::ControlCenter, // 伙伴的角色
-                            control_client.client_id   // 伙伴的ID
-                        ));
-                    }
-                }
-            }
-            ClientRole::Unknown => { /* Unknown 角色不查找伙伴 */ }
-        }
+        let existing_partners_for_current_client: Vec<(ClientRole, Uuid)> =
+            existing_partners.iter().map(|(role, session)| (*role, session.client_id)).collect();
 
         // 为了简化，这里先收集信息，待会儿在锁外发送。
         debug!(
@@ -816,6 +1505,7 @@ impl ConnectionManager {
                 partner_client_id: *partner_client_id, // 这是已存在伙伴的ID
                 is_online: true, // 因为伙伴仍在组内，所以是在线
                 group_id: group_id.clone(),
+                link_suspect: false,
             };
             match WsMessage::new(PARTNER_STATUS_UPDATE_MESSAGE_TYPE.to_string(), &partner_status_payload_for_self) {
                 Ok(ws_message_for_self) => {
@@ -847,6 +1537,8 @@ impl ConnectionManager {
             "[CM::join_group DBG_STEP_5_COMPLETE] Client {}: Finished notifying self about all existing partners.",
             client_id
         );
+        // 向全部在线成员 (包括当前客户端) 推送更新后的组成员名册
+        self.broadcast_roster(&group_id).await;
 
         // --- 步骤 6: 返回成功的注册响应 ---
         info!(
@@ -863,9 +1555,702 @@ impl ConnectionManager {
             assigned_client_id: client_id,
             effective_group_id: Some(group_id),
             effective_role: Some(requested_role),
+            error_code: None,
+            resume_token: self.issue_resume_token(client_id, requested_role),
+            resume_grace_seconds: self.resume_grace_seconds(requested_role),
+            resumed: false,
+            heartbeat: Some(self.heartbeat_policy_for(requested_role).advertised_bounds()),
         })
     }
 
+    /// 处理接管组内已被占用的独占角色槽位的请求 (`SlotTakeoverRequest`)。
+    ///
+    /// 按以下顺序决定结果：
+    /// 1. 槽位空闲、持有者已被标记为关闭或任务不匹配：直接按普通注册处理 (由 `join_group` 给出结果)。
+    /// 2. 携带交接令牌：令牌有效则持有者的槽位立即交给请求方 (`HandedOver`)，否则拒绝。
+    /// 3. `force`：请求方用户被允许强制接管时立即接管 (`TakenOver`)，否则拒绝。
+    /// 4. 其余情况：向持有者发送 `SlotTakeoverPrompt` 征求同意，等待 `answer_slot_takeover`
+    ///    或超过 `takeover_grace` 后由 `expire_slot_takeovers` 自动完成。
+    ///    持有者处于断线宽限期时同样如此：提示由占位会话缓存，持有者在期限内恢复会话后仍可答复。
+    ///
+    /// 结果以 `in_reply_to` 指向 `request_message_id` 的 `RegisterResponse` 发送给请求方；
+    /// 接管成功时随后推送一次完整任务状态，组与任务状态在整个过程中保持不变。
+    pub async fn request_slot_takeover(
+        &self,
+        requester: Arc<ClientSession>,
+        request_message_id: &str,
+        request: SlotTakeoverRequestPayload,
+    ) -> SlotTakeoverOutcome {
+        let client_id = requester.client_id;
+        let SlotTakeoverRequestPayload { register, force, handover_token } = request;
+        let role = register.role;
+        info!(
+            "[连接管理器::槽位接管] 客户端 {} 请求接管组 '{}' 的 {:?} 槽位 (强制: {}, 交接令牌: {})。",
+            client_id, register.group_id, role, force, handover_token.is_some()
+        );
+
+        if !role.has_exclusive_slot() {
+            let failure = register_failure(client_id, ErrorCode::PayloadInvalid, format!("角色 {} 不占用独占槽位，请直接注册。", role));
+            return self.send_takeover_result(&requester, request_message_id, Err(failure)).await;
+        }
+        if let Err(denied) = check_registration_allowed(&requester, &register, AccessScope::Group) {
+            return self.send_takeover_result(&requester, request_message_id, Err(denied.into_response(client_id))).await;
+        }
+
+        let holder = match self.groups.get(&register.group_id).map(|entry| Arc::clone(entry.value())) {
+            Some(group_arc) => {
+                let group = group_arc.read().await;
+                if group.task_id == register.task_id { group.exclusive_slot(role).cloned().flatten() } else { None }
+            }
+            None => None,
+        };
+        let Some(holder) = holder.filter(|holder| {
+            holder.client_id != client_id && !holder.connection_should_close.load(Ordering::SeqCst)
+        }) else {
+            let result = self.join_group(Arc::clone(&requester), register).await;
+            return self.send_takeover_result(&requester, request_message_id, result).await;
+        };
+
+        if let Some(handover_token) = handover_token {
+            // 先校验再消耗 (在同一分片锁内完成)：与本次请求不匹配的令牌保持不变，持有者签发给他人的令牌不会因此失效
+            let grant_is_valid = self
+                .handover_grants
+                .remove_if(&handover_token, |_, grant| {
+                    grant.group_id == register.group_id
+                        && grant.role == role
+                        && grant.holder_id == holder.client_id
+                        && elapsed_since(grant.issued_at) <= Duration::from_secs(HANDOVER_TOKEN_VALID_SECONDS)
+                })
+                .is_some();
+            if !grant_is_valid {
+                let failure = register_failure(client_id, ErrorCode::Forbidden, "交接令牌无效、已使用或已过期。".to_string());
+                return self.send_takeover_result(&requester, request_message_id, Err(failure)).await;
+            }
+            return self
+                .complete_takeover(&requester, request_message_id, register, holder.client_id, SlotReleaseReason::HandedOver)
+                .await;
+        }
+
+        if force {
+            let may_force = requester.principal.as_ref().is_some_and(|principal| principal.may_force_slot_takeover);
+            if !may_force {
+                let failure = register_failure(client_id, ErrorCode::Forbidden, "当前用户无权强制接管角色槽位。".to_string());
+                return self.send_takeover_result(&requester, request_message_id, Err(failure)).await;
+            }
+            return self
+                .complete_takeover(&requester, request_message_id, register, holder.client_id, SlotReleaseReason::TakenOver)
+                .await;
+        }
+
+        if self.pending_takeovers.iter().any(|pending| pending.holder_id == holder.client_id) {
+            let failure = register_failure(
+                client_id,
+                ErrorCode::RoleSlotTaken,
+                format!("组 '{}' 的 {} 槽位已有一个接管请求在等待持有者答复。", register.group_id, role),
+            );
+            return self.send_takeover_result(&requester, request_message_id, Err(failure)).await;
+        }
+
+        let takeover_id = Uuid::new_v4().simple().to_string();
+        let prompt = SlotTakeoverPromptPayload {
+            takeover_id: takeover_id.clone(),
+            role,
+            requester_client_id: client_id,
+            requester_display_name: register.client_display_name.clone(),
+            requester_user_id: requester.principal.as_ref().map(|principal| principal.user_id.clone()),
+            auto_approve_after_seconds: self.takeover_grace.as_secs(),
+        };
+        self.pending_takeovers.insert(
+            takeover_id.clone(),
+            PendingTakeover {
+                requester: Arc::clone(&requester),
+                request_message_id: request_message_id.to_string(),
+                register,
+                holder_id: holder.client_id,
+                requested_at: Utc::now(),
+            },
+        );
+        match WsMessage::new(SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE.to_string(), &prompt) {
+            Ok(message) => {
+                if let Err(e) = holder.sender.send(message).await {
+                    // 持有者的连接已失效：下一次 `expire_slot_takeovers` 会发现并完成接管
+                    warn!("[连接管理器::槽位接管] 向持有者 {} 发送接管确认请求失败: {}", holder.client_id, e);
+                }
+            }
+            Err(e) => error!("[连接管理器::槽位接管] 创建接管确认请求消息失败: {}. Payload: {:?}", e, prompt),
+        }
+        info!(
+            "[连接管理器::槽位接管] 已向持有者 {} 征求接管同意 (接管请求ID: {})，{:?} 内未答复将自动接管。",
+            holder.client_id, takeover_id, self.takeover_grace
+        );
+        SlotTakeoverOutcome::AwaitingHolder { takeover_id }
+    }
+
+    /// 处理当前槽位持有者对接管请求的答复 (`SlotTakeoverDecision`)。
+    ///
+    /// 同意时槽位交给请求方 (`HandedOver`，持有者连接保持)；拒绝时请求方收到失败的注册响应。
+    ///
+    /// # 错误
+    /// 接管请求不存在 (已被处理或已超时自动完成) 时返回 `PayloadInvalid`；答复者不是被征求同意的持有者时返回 `Forbidden`。
+    pub async fn answer_slot_takeover(
+        &self,
+        holder: &Arc<ClientSession>,
+        decision: SlotTakeoverDecisionPayload,
+    ) -> Result<SlotTakeoverOutcome, CloudError> {
+        let Some((takeover_id, pending)) = self.pending_takeovers.remove(&decision.takeover_id) else {
+            return Err(CloudError::PayloadInvalid {
+                message_type: common_models::ws_payloads::SLOT_TAKEOVER_DECISION_MESSAGE_TYPE.to_string(),
+                reason: format!("接管请求 '{}' 不存在或已处理。", decision.takeover_id),
+                details: Vec::new(),
+            });
+        };
+        if pending.holder_id != holder.client_id {
+            self.pending_takeovers.insert(takeover_id, pending);
+            return Err(CloudError::Forbidden("只有被征求同意的槽位持有者可以答复接管请求。".to_string()));
+        }
+        let PendingTakeover { requester, request_message_id, register, holder_id, .. } = pending;
+        if decision.approve {
+            info!("[连接管理器::槽位接管] 持有者 {} 同意了接管请求 {}。", holder_id, takeover_id);
+            Ok(self.complete_takeover(&requester, &request_message_id, register, holder_id, SlotReleaseReason::HandedOver).await)
+        } else {
+            info!("[连接管理器::槽位接管] 持有者 {} 拒绝了接管请求 {}。", holder_id, takeover_id);
+            let failure = register_failure(
+                requester.client_id,
+                ErrorCode::RoleSlotTaken,
+                format!("组 '{}' 的 {} 槽位持有者拒绝了接管请求。", register.group_id, register.role),
+            );
+            Ok(self.send_takeover_result(&requester, &request_message_id, Err(failure)).await)
+        }
+    }
+
+    /// 完成所有已超过答复期限、或持有者已彻底离开的接管请求。由 `HeartbeatMonitor` 在每个检查周期调用。
+    ///
+    /// 处于断线宽限期的持有者仍可能恢复会话并答复，因此其接管请求同样要等到 `takeover_grace` 过后才完成。
+    ///
+    /// # 返回值
+    /// 返回本次完成的接管请求数量。
+    pub async fn expire_slot_takeovers(&self) -> usize {
+        let due: Vec<String> = self
+            .pending_takeovers
+            .iter()
+            .filter(|pending| {
+                let holder_connected = self
+                    .clients
+                    .get(&pending.holder_id)
+                    .is_some_and(|holder| !holder.connection_should_close.load(Ordering::SeqCst));
+                let holder_detached = self.detached_sessions.iter().any(|record| record.client_id == pending.holder_id);
+                !(holder_connected || holder_detached) || elapsed_since(pending.requested_at) > self.takeover_grace
+            })
+            .map(|pending| pending.key().clone())
+            .collect();
+        let mut completed = 0;
+        for takeover_id in due {
+            if let Some((_, pending)) = self.pending_takeovers.remove(&takeover_id) {
+                info!(
+                    "[连接管理器::槽位接管] 持有者 {} 未在期限内答复接管请求 {} (或已断开)，接管自动生效。",
+                    pending.holder_id, takeover_id
+                );
+                let PendingTakeover { requester, request_message_id, register, holder_id, .. } = pending;
+                self.complete_takeover(&requester, &request_message_id, register, holder_id, SlotReleaseReason::TakenOver).await;
+                completed += 1;
+            }
+        }
+        completed
+    }
+
+    /// 为当前持有独占角色槽位的客户端签发一次性交接令牌 (计划内换班)。
+    ///
+    /// 接班的操作员在 `SlotTakeoverRequest` 中携带该令牌即可立即取得槽位，无需再次确认；
+    /// 持有者离开槽位 (断线释放、被接管等) 后令牌随之失效。
+    ///
+    /// # 错误
+    /// 客户端未注册时返回 `NotRegistered`；其角色不占用独占槽位时返回 `Forbidden`。
+    pub async fn prepare_handover(&self, holder: &Arc<ClientSession>) -> Result<HandoverTokenPayload, CloudError> {
+        let role = *holder.role.read().await;
+        let Some(group_id) = holder.group_id.read().await.clone() else {
+            return Err(CloudError::NotRegistered);
+        };
+        if role == ClientRole::Unknown {
+            return Err(CloudError::NotRegistered);
+        }
+        if !role.has_exclusive_slot() {
+            return Err(CloudError::Forbidden(format!("角色 {} 不占用独占槽位，无需交接。", role)));
+        }
+        self.handover_grants.retain(|_, grant| grant.holder_id != holder.client_id); // 每个持有者只保留最新的令牌
+        let handover_token = Uuid::new_v4().simple().to_string();
+        self.handover_grants.insert(
+            handover_token.clone(),
+            HandoverGrant { group_id: group_id.clone(), role, holder_id: holder.client_id, issued_at: Utc::now() },
+        );
+        info!("[连接管理器::槽位交接] 已为客户端 {} (组 '{}', 角色 {:?}) 签发交接令牌。", holder.client_id, group_id, role);
+        Ok(HandoverTokenPayload { handover_token, group_id, role, expires_in_seconds: HANDOVER_TOKEN_VALID_SECONDS })
+    }
+
+    /// 客户端不断开连接地离开其当前任务组。
+    ///
+    /// 组内成员的处理与断开连接 (不进入会话恢复宽限期) 相同：客户端从其槽位移除，伙伴收到下线通知，
+    /// 组变空时清理组及其任务状态。客户端的会话恢复令牌与交接令牌随之失效，会话变为未注册状态，
+    /// 之后可以通过 `Register` 加入其他组。
+    ///
+    /// 若有接管请求正在等待该客户端答复，离开即视为同意：槽位直接交给请求方 (组与任务状态得以保留)。
+    ///
+    /// # 错误
+    /// 客户端尚未加入任何组时返回 `NotRegistered`。
+    pub async fn leave_group(&self, client_session: &Arc<ClientSession>) -> Result<GroupLeftPayload, CloudError> {
+        let client_id = client_session.client_id;
+        let role = *client_session.role.read().await;
+        let Some(group_id) = client_session.group_id.read().await.clone() else {
+            return Err(CloudError::NotRegistered);
+        };
+        if role == ClientRole::Unknown {
+            return Err(CloudError::NotRegistered);
+        }
+        info!("[连接管理器::离开组] 客户端 {} (角色: {:?}) 请求离开组 '{}'。", client_id, role, group_id);
+
+        let awaiting_answer: Vec<String> = self
+            .pending_takeovers
+            .iter()
+            .filter(|pending| pending.holder_id == client_id)
+            .map(|pending| pending.key().clone())
+            .collect();
+        for takeover_id in awaiting_answer {
+            if let Some((_, pending)) = self.pending_takeovers.remove(&takeover_id) {
+                info!("[连接管理器::离开组] 客户端 {} 离开组时接管请求 {} 仍在等待其答复，槽位直接交给请求方。", client_id, takeover_id);
+                let PendingTakeover { requester, request_message_id, register, holder_id, .. } = pending;
+                self.complete_takeover(&requester, &request_message_id, register, holder_id, SlotReleaseReason::HandedOver).await;
+            }
+        }
+        // 槽位已交给接管请求方时，客户端已在移交过程中被移出组
+        if client_session.group_id.read().await.is_none() {
+            return Ok(GroupLeftPayload { group_id, role });
+        }
+
+        self.session_resume_tokens.remove(&client_id);
+        self.handover_grants.retain(|_, grant| grant.holder_id != client_id);
+        *client_session.role.write().await = ClientRole::Unknown;
+        *client_session.group_id.write().await = None;
+        self.release_group_slot(&client_id, role, group_id.clone(), true).await;
+        info!("[连接管理器::离开组] 客户端 {} 已离开组 '{}'，连接保持。", client_id, group_id);
+        Ok(GroupLeftPayload { group_id, role })
+    }
+
+    /// 客户端离开当前任务组 (若已加入) 并以 `payload` 注册到另一个任务组，连接保持不变。
+    ///
+    /// 先对目标组执行与 `join_group` 相同的基本校验与授权检查，通过后才离开当前组，
+    /// 因此无权加入目标组的请求不会使客户端失去原组的成员身份。目标与当前所在的组相同时请求被拒绝。
+    ///
+    /// # 返回值
+    /// 与 `join_group` 相同：`Ok` 为加入目标组成功的注册响应，`Err` 为失败的注册响应
+    /// (若失败发生在离开原组之后，客户端此时不属于任何组)。
+    pub async fn switch_task(
+        &self,
+        client_session: Arc<ClientSession>,
+        payload: RegisterPayload,
+    ) -> Result<RegisterResponsePayload, RegisterResponsePayload> {
+        self.switch_task_in_scope(client_session, payload, AccessScope::Group).await
+    }
+
+    /// 与 `switch_task` 相同，但按 `access_scope` 做项目授权检查 (凭配对码注册时总是按任务检查)。
+    async fn switch_task_in_scope(
+        &self,
+        client_session: Arc<ClientSession>,
+        mut payload: RegisterPayload,
+        mut access_scope: AccessScope,
+    ) -> Result<RegisterResponsePayload, RegisterResponsePayload> {
+        let client_id = client_session.client_id;
+        // 只解析配对码以确定目标组；配对码在 `join_group_in_scope` 中注册成功后才被消耗
+        if self.resolve_join_code(client_id, &mut payload).map_err(|denied| denied.into_response(client_id))?.is_some() {
+            access_scope = AccessScope::Task;
+        }
+        check_registration_allowed(&client_session, &payload, access_scope).map_err(|denied| denied.into_response(client_id))?;
+        let current_group_id = client_session.group_id.read().await.clone();
+        if current_group_id.as_deref() == Some(payload.group_id.as_str()) {
+            return Err(register_failure(
+                client_id,
+                ErrorCode::PayloadInvalid,
+                format!("客户端已在任务组 '{}' 中，无需切换。", payload.group_id),
+            ));
+        }
+        if current_group_id.is_some() {
+            if let Err(e) = self.leave_group(&client_session).await {
+                debug!("[连接管理器::切换任务] 客户端 {} 离开原组时无需处理: {}", client_id, e);
+            }
+        }
+        info!(
+            "[连接管理器::切换任务] 客户端 {} 从组 {:?} 切换到组 '{}' (任务 '{}', 角色 {:?})。",
+            client_id, current_group_id, payload.group_id, payload.task_id, payload.role
+        );
+        self.join_group_in_scope(client_session, payload, access_scope).await
+    }
+
+    /// 控制中心请求为任务开组并取得配对码。
+    ///
+    /// * 客户端已作为控制中心加入某个组、且未指定其他组时，仅为该组签发新的配对码
+    ///   (请求的任务与组关联的任务不一致时以 `TASK_MISMATCH` 失败)；
+    /// * 否则与 `switch_task` 相同：离开当前组 (若有)，以 `ControlCenter` 角色加入指定的组
+    ///   (未指定时生成新的组ID)，加入成功后签发配对码。
+    ///
+    /// 每个配对码只能使用一次，可以为同一组签发多个 (例如多名现场工程师各用一个)。
+    /// 组ID由服务端生成时，按请求的任务做项目授权检查 (用户无从事先获得对新组ID的授权)。
+    pub async fn open_group(&self, client_session: Arc<ClientSession>, request: OpenGroupPayload) -> GroupOpenedPayload {
+        let client_id = client_session.client_id;
+        let current_role = *client_session.role.read().await;
+        let current_group_id = client_session.group_id.read().await.clone();
+        let reissue_for = current_group_id
+            .filter(|current| current_role == ClientRole::ControlCenter && request.group_id.as_ref().map_or(true, |requested| requested == current));
+
+        let registration = match reissue_for {
+            Some(group_id) => {
+                let group_task_id = match self.get_group(&group_id).await {
+                    Some(group) => group.task_id.clone(),
+                    None => String::new(),
+                };
+                if group_task_id == request.task_id {
+                    Ok(RegisterResponsePayload {
+                        success: true,
+                        message: Some("控制中心已在任务组中，已签发新的配对码。".to_string()),
+                        assigned_client_id: client_id,
+                        effective_group_id: Some(group_id),
+                        effective_role: Some(ClientRole::ControlCenter),
+                        error_code: None,
+                        resume_token: None,
+                        resume_grace_seconds: None,
+                        resumed: false,
+                        heartbeat: Some(self.heartbeat_policy_for(ClientRole::ControlCenter).advertised_bounds()),
+                    })
+                } else {
+                    Err(register_failure(
+                        client_id,
+                        ErrorCode::TaskMismatch,
+                        format!("任务组 '{}' 关联的任务为 '{}'，与请求的任务 '{}' 不一致。", group_id, group_task_id, request.task_id),
+                    ))
+                }
+            }
+            None => {
+                let (group_id, access_scope) = match request.group_id.clone() {
+                    Some(group_id) => (group_id, AccessScope::Group),
+                    None => (format!("grp-{}", &Uuid::new_v4().simple().to_string()[..12]), AccessScope::Task),
+                };
+                let register = RegisterPayload {
+                    group_id,
+                    role: ClientRole::ControlCenter,
+                    task_id: request.task_id.clone(),
+                    client_software_version: request.client_software_version.clone(),
+                    client_display_name: request.client_display_name.clone(),
+                    resume_token: None,
+                    site_assignment: None,
+                    join_code: None,
+                };
+                self.switch_task_in_scope(client_session, register, access_scope).await
+            }
+        };
+
+        match registration {
+            Ok(registration) => {
+                let group_id = registration.effective_group_id.clone().unwrap_or_default();
+                let join_code = self.issue_join_code(&group_id, &request.task_id, client_id);
+                info!("[连接管理器::配对码] 已为组 '{}' (任务 '{}') 签发配对码，签发者: {}。", group_id, request.task_id, client_id);
+                GroupOpenedPayload {
+                    registration,
+                    task_id: request.task_id,
+                    join_code: Some(join_code),
+                    join_code_expires_in_seconds: Some(self.join_code_ttl.as_secs()),
+                }
+            }
+            Err(registration) => {
+                warn!("[连接管理器::配对码] 客户端 {} 开组失败: {:?}", client_id, registration.message);
+                GroupOpenedPayload { registration, task_id: request.task_id, join_code: None, join_code_expires_in_seconds: None }
+            }
+        }
+    }
+
+    /// 丢弃已过期而未被使用的配对码。由 `HeartbeatMonitor` 在每个检查周期调用。
+    ///
+    /// # 返回值
+    /// 返回本次丢弃的配对码数量。
+    pub fn expire_join_codes(&self) -> usize {
+        let before = self.join_codes.len();
+        self.join_codes.retain(|_, grant| elapsed_since(grant.issued_at) <= self.join_code_ttl);
+        before.saturating_sub(self.join_codes.len())
+    }
+
+    /// 为组签发一个新的配对码 (与尚未使用的配对码不重复)。
+    fn issue_join_code(&self, group_id: &str, task_id: &str, issued_by: Uuid) -> String {
+        loop {
+            let join_code = generate_join_code();
+            if let dashmap::mapref::entry::Entry::Vacant(entry) = self.join_codes.entry(join_code.clone()) {
+                entry.insert(JoinCodeGrant {
+                    group_id: group_id.to_string(),
+                    task_id: task_id.to_string(),
+                    issued_by,
+                    issued_at: Utc::now(),
+                });
+                return join_code;
+            }
+        }
+    }
+
+    /// 若注册信息携带配对码，则以其对应的组与任务替换注册信息中的 `group_id`/`task_id`。
+    ///
+    /// 此处只查找配对码而不消耗它，注册成功后由 `consume_join_code` 消耗；
+    /// 不存在、已过期或其组已解散时以 `JOIN_CODE_INVALID` 拒绝。
+    ///
+    /// # 返回值
+    /// `Ok(Some(code))` 表示已凭配对码 (规范化后的 `code`) 确定组与任务，`Ok(None)` 表示注册信息未携带配对码。
+    fn resolve_join_code(&self, client_id: Uuid, payload: &mut RegisterPayload) -> Result<Option<String>, RegistrationDenied> {
+        let Some(join_code) = payload.join_code.as_deref() else {
+            return Ok(None);
+        };
+        let normalized = join_code.trim().to_ascii_uppercase();
+        let grant = self
+            .join_codes
+            .get(&normalized)
+            .filter(|grant| self.join_code_is_usable(grant))
+            .map(|grant| (grant.group_id.clone(), grant.task_id.clone()));
+        let Some((group_id, task_id)) = grant else {
+            warn!("[连接管理器::配对码] 客户端 {} 提交的配对码 '{}' 无效或已过期。", client_id, normalized);
+            return Err(RegistrationDenied::new(ErrorCode::JoinCodeInvalid, "配对码无效或已过期，请向控制中心重新获取。".to_string()));
+        };
+        payload.group_id = group_id;
+        payload.task_id = task_id;
+        Ok(Some(normalized))
+    }
+
+    /// 在凭配对码注册成功后消耗该配对码。
+    ///
+    /// 与 `resolve_join_code` 使用相同的有效性判断，并要求配对码仍指向 `payload` 的组与任务；
+    /// 判断与移除在同一分片锁内完成，因此同一配对码只有一次注册能成功消耗它。
+    ///
+    /// # 返回值
+    /// 成功消耗时返回 `true`；配对码已被他人使用或已失效时返回 `false`。
+    fn consume_join_code(&self, client_id: Uuid, join_code: &str, payload: &RegisterPayload) -> bool {
+        let consumed = self.join_codes.remove_if(join_code, |_, grant| {
+            self.join_code_is_usable(grant) && grant.group_id == payload.group_id && grant.task_id == payload.task_id
+        });
+        let Some((_, grant)) = consumed else {
+            return false;
+        };
+        info!(
+            "[连接管理器::配对码] 客户端 {} 使用配对码加入组 '{}' (任务 '{}')，配对码由 {} 签发，现已失效。",
+            client_id, grant.group_id, grant.task_id, grant.issued_by
+        );
+        true
+    }
+
+    /// 配对码未过期且其组仍存在。
+    fn join_code_is_usable(&self, grant: &JoinCodeGrant) -> bool {
+        elapsed_since(grant.issued_at) <= self.join_code_ttl && self.groups.contains_key(&grant.group_id)
+    }
+
+    /// 把持有者移出槽位后，让请求方以 `register` 加入组，并发送结果。
+    async fn complete_takeover(
+        &self,
+        requester: &Arc<ClientSession>,
+        request_message_id: &str,
+        register: RegisterPayload,
+        holder_id: Uuid,
+        reason: SlotReleaseReason,
+    ) -> SlotTakeoverOutcome {
+        let requester_connected = self.clients.contains_key(&requester.client_id)
+            && !requester.connection_should_close.load(Ordering::SeqCst);
+        if !requester_connected {
+            info!("[连接管理器::槽位接管] 请求方 {} 已断开，放弃接管，持有者保留槽位。", requester.client_id);
+            return SlotTakeoverOutcome::Resolved { success: false };
+        }
+        self.evict_slot_holder(&register.group_id, register.role, holder_id, reason, requester.client_id).await;
+        let result = self.join_group(Arc::clone(requester), register).await;
+        self.send_takeover_result(requester, request_message_id, result).await
+    }
+
+    /// 将持有者移出其独占槽位，但保留组及其任务状态 (即使组暂时为空)，以便接替者无缝加入。
+    ///
+    /// 持有者收到 `SlotReleased` 通知并变为未注册状态，其会话恢复令牌与交接令牌一并失效；
+    /// 处于断线宽限期的持有者的保留记录被丢弃。`TakenOver` 时持有者的连接随后被关闭。
+    /// 其余成员收到该持有者的下线通知。
+    async fn evict_slot_holder(&self, group_id: &str, role: ClientRole, holder_id: Uuid, reason: SlotReleaseReason, successor_id: Uuid) {
+        let Some(group_arc) = self.groups.get(group_id).map(|entry| Arc::clone(entry.value())) else {
+            return;
+        };
+        let mut group = group_arc.write().await;
+        let Some(holder) = group.exclusive_slot(role).cloned().flatten().filter(|holder| holder.client_id == holder_id) else {
+            info!("[连接管理器::槽位接管] 组 '{}' 的 {:?} 槽位已不属于客户端 {}，无需移出。", group_id, role, holder_id);
+            return;
+        };
+        group.remove_member(role, holder_id);
+        let partners = group.partners_of(holder_id);
+        drop(group);
+
+        let detached_tokens: Vec<String> = self
+            .detached_sessions
+            .iter()
+            .filter(|record| record.client_id == holder_id)
+            .map(|record| record.key().clone())
+            .collect();
+        for token in detached_tokens {
+            if let Some((_, record)) = self.detached_sessions.remove(&token) {
+                record.collector.abort();
+            }
+        }
+        self.session_resume_tokens.remove(&holder_id);
+        self.handover_grants.retain(|_, grant| grant.holder_id != holder_id);
+        *holder.role.write().await = ClientRole::Unknown;
+        *holder.group_id.write().await = None;
+
+        let released = SlotReleasedPayload { group_id: group_id.to_string(), role, reason, successor_client_id: successor_id };
+        match WsMessage::new(SLOT_RELEASED_MESSAGE_TYPE.to_string(), &released) {
+            Ok(message) => {
+                if let Err(e) = holder.sender.send(message).await {
+                    debug!("[连接管理器::槽位接管] 向原持有者 {} 发送槽位释放通知失败 (连接可能已失效): {}", holder_id, e);
+                }
+            }
+            Err(e) => error!("[连接管理器::槽位接管] 创建槽位释放通知消息失败: {}. Payload: {:?}", e, released),
+        }
+        if reason == SlotReleaseReason::TakenOver {
+            holder.connection_should_close.store(true, Ordering::SeqCst);
+        }
+        for (_, partner) in &partners {
+            notify_partner_status(partner, role, holder_id, false, group_id).await;
+        }
+        self.broadcast_roster(group_id).await;
+        info!(
+            "[连接管理器::槽位接管] 客户端 {} 已被移出组 '{}' 的 {:?} 槽位 (原因: {:?})，由客户端 {} 接替。",
+            holder_id, group_id, role, reason, successor_id
+        );
+    }
+
+    /// 向接管请求方发送最终的注册响应；加入成功时随后推送一次完整任务状态。
+    async fn send_takeover_result(
+        &self,
+        requester: &Arc<ClientSession>,
+        request_message_id: &str,
+        result: Result<RegisterResponsePayload, RegisterResponsePayload>,
+    ) -> SlotTakeoverOutcome {
+        let success = result.is_ok();
+        let response = result.unwrap_or_else(|failure| failure);
+        match WsMessage::new_reply(REGISTER_RESPONSE_MESSAGE_TYPE.to_string(), &response, request_message_id) {
+            Ok(message) => {
+                if let Err(e) = requester.sender.send(message).await {
+                    error!("[连接管理器::槽位接管] 向客户端 {} 发送接管结果失败: {}", requester.client_id, e);
+                }
+            }
+            Err(e) => error!("[连接管理器::槽位接管] 创建接管结果消息失败: {}. Payload: {:?}", e, response),
+        }
+        if let Some(group_id) = response.effective_group_id.as_deref().filter(|_| success) {
+            self.send_task_state_snapshot(requester, group_id, None).await;
+        }
+        SlotTakeoverOutcome::Resolved { success }
+    }
+
+    /// 记录客户端在 Ping 中报告的往返时延。
+    ///
+    /// 这是该成员的第一次测量，或与上次记录的值相差至少 `ROUND_TRIP_CHANGE_REPORT_THRESHOLD_MS` 时，
+    /// 向组内推送更新后的成员名册；细小的抖动只更新记录，不推送。
+    pub async fn record_round_trip(&self, client_session: &ClientSession, round_trip_ms: u64) {
+        let previous = client_session.round_trip_ms.write().await.replace(round_trip_ms);
+        let changed = previous.map_or(true, |previous| previous.abs_diff(round_trip_ms) >= ROUND_TRIP_CHANGE_REPORT_THRESHOLD_MS);
+        if !changed {
+            return;
+        }
+        debug!(
+            "[连接管理器::名册] 客户端 {} 的往返时延由 {:?} ms 变为 {} ms。",
+            client_session.client_id, previous, round_trip_ms
+        );
+        let group_id = client_session.group_id.read().await.clone();
+        if let Some(group_id) = group_id {
+            self.broadcast_roster(&group_id).await;
+        }
+    }
+
+    /// 设置客户端链路的"可疑"状态。
+    ///
+    /// 由 `HeartbeatMonitor` 在客户端的不活动时长超过其角色的可疑阈值时置为 `true`，
+    /// 由 `MessageRouter` 在收到可疑客户端的消息时清除。状态确实发生变化且客户端已加入组时，
+    /// 向组内伙伴发送 `link_suspect` 相应变化的伙伴状态通知，并推送更新后的成员名册。
+    pub async fn set_link_suspect(&self, client_session: &ClientSession, suspect: bool) {
+        if client_session.link_suspect.swap(suspect, Ordering::SeqCst) == suspect {
+            return;
+        }
+        let role = *client_session.role.read().await;
+        let group_id = client_session.group_id.read().await.clone();
+        let Some(group_id) = group_id.filter(|_| role != ClientRole::Unknown) else {
+            return;
+        };
+        if suspect {
+            warn!("[连接管理器::心跳] 客户端 {} (角色: {:?}, 组 '{}') 链路可疑，已通知伙伴。", client_session.client_id, role, group_id);
+        } else {
+            info!("[连接管理器::心跳] 客户端 {} (角色: {:?}, 组 '{}') 链路已恢复。", client_session.client_id, role, group_id);
+        }
+        let payload = PartnerStatusPayload {
+            partner_role: role,
+            partner_client_id: client_session.client_id,
+            is_online: true,
+            group_id: group_id.clone(),
+            link_suspect: suspect,
+        };
+        match WsMessage::new(PARTNER_STATUS_UPDATE_MESSAGE_TYPE.to_string(), &payload) {
+            Ok(message) => {
+                for partner in self.get_group_members_for_broadcast(&group_id, Some(&client_session.client_id)).await {
+                    if let Err(e) = partner.sender.send(message.clone()).await {
+                        debug!("[连接管理器::心跳] 向客户端 {} 发送链路状态通知失败 (连接可能已失效): {}", partner.client_id, e);
+                    }
+                }
+            }
+            Err(e) => error!("[连接管理器::心跳] 创建链路状态通知消息失败: {}. Payload: {:?}", e, payload),
+        }
+        self.broadcast_roster(&group_id).await;
+    }
+
+    /// 生成指定组当前的成员名册。组不存在时返回 `None`。
+    ///
+    /// 处于断线宽限期的成员 (占位会话) 与已被标记为关闭的连接以 `is_online: false` 列出。
+    pub async fn group_roster(&self, group_id: &str) -> Option<GroupRosterPayload> {
+        let group_arc = self.groups.get(group_id).map(|entry| Arc::clone(entry.value()))?;
+        let (task_id, sessions) = {
+            let group = group_arc.read().await;
+            (group.task_id.clone(), group.members())
+        };
+        let mut members = Vec::with_capacity(sessions.len());
+        for (role, session) in sessions {
+            let is_online = self.clients.get(&session.client_id).is_some_and(|entry| Arc::ptr_eq(entry.value(), &session))
+                && !session.connection_should_close.load(Ordering::SeqCst);
+            members.push(RosterMember {
+                client_id: session.client_id,
+                role,
+                display_name: session.display_name.read().await.clone(),
+                software_version: session.software_version.read().await.clone(),
+                connected_at: session.creation_time,
+                last_seen: *session.last_seen.read().await,
+                round_trip_ms: *session.round_trip_ms.read().await,
+                is_online,
+                link_suspect: is_online && session.is_link_suspect(),
+            });
+        }
+        Some(GroupRosterPayload { group_id: group_id.to_string(), task_id, members })
+    }
+
+    /// 向组内全部在线成员推送当前的成员名册 (`GroupRoster`)。组不存在 (例如已解散) 时不做任何事。
+    pub async fn broadcast_roster(&self, group_id: &str) {
+        let Some(roster) = self.group_roster(group_id).await else {
+            return;
+        };
+        let message = match WsMessage::new(GROUP_ROSTER_MESSAGE_TYPE.to_string(), &roster) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("[连接管理器::名册] 为组 '{}' 构造成员名册消息失败: {}", group_id, e);
+                return;
+            }
+        };
+        let online_ids: Vec<Uuid> = roster.members.iter().filter(|member| member.is_online).map(|member| member.client_id).collect();
+        for client_id in online_ids {
+            let Some(session) = self.clients.get(&client_id).map(|entry| Arc::clone(entry.value())) else {
+                continue;
+            };
+            if let Err(e) = session.sender.send(message.clone()).await {
+                debug!("[连接管理器::名册] 向客户端 {} 推送组 '{}' 的成员名册失败 (连接可能已失效): {}", client_id, group_id, e);
+            }
+        }
+    }
+
     /// 获取当前所有活动客户端会话的一个快照 (克隆的 `Arc<ClientSession>` 列表)。
     /// 此方法主要用于内部监控，例如由 `HeartbeatMonitor` 定期调用以检查客户端活跃状态。
     ///
@@ -886,9 +2271,7 @@ impl ConnectionManager {
         self.clients.len() // 返回 DashMap 的长度
     }
     
-    /// 根据组ID获取对组信息的只读访问权。 (目前未使用，但保留用于测试或未来扩展)
-    /// `#[allow(dead_code)]` 属性用于抑制编译器关于未使用代码的警告。
-    #[allow(dead_code)] // 抑制未使用代码警告
+    /// 根据组ID获取对组信息的只读访问权 (例如 `open_group` 读取组关联的任务ID；测试中用于检查组内成员)。
     pub async fn get_group(&self, group_id: &str) -> Option<tokio::sync::OwnedRwLockReadGuard<Group>> {
         if let Some(group_entry) = self.groups.get(group_id) { // 尝试从 groups DashMap 中获取组条目
             let group_arc_rwlock = group_entry.value().clone();    // 克隆 Arc<RwLock<Group>>
@@ -898,6 +2281,16 @@ impl ConnectionManager {
         }
     }
 
+    /// 获取指定组内负责某台设备的现场移动端会话 (规则见 `Group::site_engineers_for_device`)。
+    /// 组不存在时返回空列表。
+    pub async fn site_engineers_for_device(&self, group_id: &str, device_id: &str) -> Vec<Arc<ClientSession>> {
+        let Some(group_arc) = self.groups.get(group_id).map(|entry| Arc::clone(entry.value())) else {
+            return Vec::new();
+        };
+        let group = group_arc.read().await;
+        group.site_engineers_for_device(device_id)
+    }
+
     /// 获取指定组ID中所有活动客户端会话的列表，可以选择排除一个特定的客户端ID。
     /// 此方法主要用于向组内伙伴广播消息的场景。
     ///
@@ -918,22 +2311,14 @@ impl ConnectionManager {
             let group_lock = group_entry.value();
             let group = group_lock.read().await; // 获取组的读锁
 
-            // 检查控制中心客户端
-            if let Some(cc_client) = &group.control_center_client {
-                if exclude_client_id.map_or(true, |id| id != &cc_client.client_id) {
-                    members.push(cc_client.clone());
-                }
-            }
-
-            // 检查现场移动端客户端
-            if let Some(os_client) = &group.on_site_mobile_client {
-                if exclude_client_id.map_or(true, |id| id != &os_client.client_id) {
-                    members.push(os_client.clone());
-                }
-            }
-            
-            // 未来如果 Group 结构支持更多类型的客户端或观察者 (e.g., additional_observers)，
-            // 也需要在此处添加逻辑来遍历它们并根据 exclude_client_id 进行过滤。
+            // 组内全部成员 (控制中心、现场移动端、QA 主管以及所有观察者)
+            members.extend(
+                group
+                    .members()
+                    .into_iter()
+                    .map(|(_, session)| session)
+                    .filter(|session| exclude_client_id.map_or(true, |id| id != &session.client_id)),
+            );
 
             debug!(
                 "[连接管理器] 为组 '{}' 获取广播成员列表。排除客户端ID: {:?}. 最终成员数: {}.",
@@ -949,6 +2334,152 @@ impl ConnectionManager {
     }
 }
 
+/// 把断线客户端占位会话收到的消息转存到缓冲中，直到占位会话被丢弃 (通道关闭)。
+async fn collect_missed_messages(mut receiver: mpsc::Receiver<WsMessage>, buffer: Arc<std::sync::Mutex<MissedMessages>>) {
+    while let Some(message) = receiver.recv().await {
+        let mut missed = buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if missed.messages.len() >= MISSED_MESSAGE_BUFFER_CAPACITY {
+            missed.messages.pop_front();
+            missed.dropped += 1;
+        }
+        missed.messages.push_back(message);
+    }
+}
+
+/// 注册时做项目授权检查的对象。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AccessScope {
+    /// 请求的任务组ID (客户端自行指定组ID时)。
+    Group,
+    /// 组关联的任务ID (组ID由服务端生成或由配对码确定时)，有权访问该组ID本身也可以。
+    Task,
+}
+
+/// 注册 (或接管) 请求在加入组之前被拒绝的原因，由调用处转换为失败的注册响应。
+#[derive(Debug)]
+struct RegistrationDenied {
+    error_code: ErrorCode,
+    reason: String,
+}
+
+impl RegistrationDenied {
+    fn new(error_code: ErrorCode, reason: String) -> Self {
+        Self { error_code, reason }
+    }
+
+    fn into_response(self, client_id: Uuid) -> RegisterResponsePayload {
+        register_failure(client_id, self.error_code, self.reason)
+    }
+}
+
+/// 注册 (或接管) 请求的基本校验与授权检查。
+///
+/// 要求提供有效的 `task_id`；若连接在握手阶段通过了认证，还要求该用户被允许以请求的角色
+/// 访问 `access_scope` 指定的项目 (组ID或任务ID)。
+/// 未携带主体的连接 (服务开启 `auth.allow_anonymous` 时的匿名连接) 只能以 `Observer` 角色注册。
+fn check_registration_allowed(
+    client_session: &ClientSession,
+    payload: &RegisterPayload,
+    access_scope: AccessScope,
+) -> Result<(), RegistrationDenied> {
+    let client_id = client_session.client_id;
+    if payload.task_id.is_empty() {
+        warn!("[CM::join_group EARLY_EXIT_A] task_id is empty for client {}, group '{}'. Registration rejected.", client_id, payload.group_id);
+        return Err(RegistrationDenied::new(ErrorCode::PayloadInvalid, "必须提供有效的 task_id。".to_string()));
+    }
+    let Some(principal) = &client_session.principal else {
+        if payload.role != ClientRole::Observer {
+            warn!(
+                "[CM::join_group AUTHZ_REJECT] Anonymous client {} requested role {:?}; only Observer is allowed. Registration rejected.",
+                client_id, payload.role
+            );
+            return Err(RegistrationDenied::new(
+                ErrorCode::Forbidden,
+                format!("匿名连接只能以观察者角色加入任务组，以角色 {} 加入前请先登录。", payload.role),
+            ));
+        }
+        return Ok(());
+    };
+    if !principal.may_take_role(payload.role) {
+        warn!(
+            "[CM::join_group AUTHZ_REJECT] User '{}' (client {}) is not allowed to take role {:?}. Registration rejected.",
+            principal.user_id, client_id, payload.role
+        );
+        return Err(RegistrationDenied::new(
+            ErrorCode::Forbidden,
+            format!("用户 '{}' 无权以角色 {} 加入任务组。", principal.user_id, payload.role),
+        ));
+    }
+    let may_access = match access_scope {
+        AccessScope::Group => principal.may_access_project(&payload.group_id),
+        AccessScope::Task => principal.may_access_project(&payload.task_id) || principal.may_access_project(&payload.group_id),
+    };
+    if !may_access {
+        warn!(
+            "[CM::join_group AUTHZ_REJECT] User '{}' (client {}) is not allowed to access group '{}' (task '{}', scope {:?}). Registration rejected.",
+            principal.user_id, client_id, payload.group_id, payload.task_id, access_scope
+        );
+        return Err(RegistrationDenied::new(
+            ErrorCode::Forbidden,
+            format!("用户 '{}' 无权访问任务组 '{}'。", principal.user_id, payload.group_id),
+        ));
+    }
+    Ok(())
+}
+
+/// 构造一个失败的注册响应，`reason` 前会加上 "注册失败：" 前缀。
+fn register_failure(client_id: Uuid, error_code: ErrorCode, reason: String) -> RegisterResponsePayload {
+    RegisterResponsePayload {
+        success: false,
+        message: Some(format!("注册失败：{}", reason)),
+        assigned_client_id: client_id,
+        effective_group_id: None,
+        effective_role: None,
+        error_code: Some(error_code),
+        resume_token: None,
+        resume_grace_seconds: None,
+        resumed: false,
+        heartbeat: None,
+    }
+}
+
+/// 生成一个随机配对码 (`JOIN_CODE_LENGTH` 个取自 `JOIN_CODE_ALPHABET` 的字符)。
+fn generate_join_code() -> String {
+    Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(JOIN_CODE_LENGTH)
+        .map(|byte| JOIN_CODE_ALPHABET[*byte as usize % JOIN_CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// 自某一时刻起经过的时长 (时钟回拨时视为零)。
+fn elapsed_since(instant: DateTime<Utc>) -> Duration {
+    Utc::now().signed_duration_since(instant).to_std().unwrap_or_default()
+}
+
+/// 向伙伴发送某个客户端的上线/下线通知 (`PartnerStatusUpdate`)。
+async fn notify_partner_status(partner: &ClientSession, role: ClientRole, client_id: Uuid, is_online: bool, group_id: &str) {
+    let partner_status_payload = PartnerStatusPayload {
+        partner_role: role,
+        partner_client_id: client_id,
+        is_online,
+        group_id: group_id.to_string(),
+        link_suspect: false,
+    };
+    match WsMessage::new(PARTNER_STATUS_UPDATE_MESSAGE_TYPE.to_string(), &partner_status_payload) {
+        Ok(ws_message) => {
+            if let Err(e) = partner.sender.send(ws_message).await {
+                error!(
+                    "[连接管理器] 向客户端 {} 发送关于客户端 {} 的伙伴状态通知 (在线: {}) 失败: {}",
+                    partner.client_id, client_id, is_online, e
+                );
+            }
+        }
+        Err(e) => error!("[连接管理器] 创建伙伴状态通知 WsMessage 失败: {}. Payload: {:?}", e, partner_status_payload),
+    }
+}
+
 // 为 ConnectionManager 实现 Default trait。
 // 这允许在没有明确提供 TaskStateManager 时（例如在某些测试场景或默认初始化中）创建一个实例。
 impl Default for ConnectionManager {
@@ -957,4 +2488,597 @@ impl Default for ConnectionManager {
         // 创建一个新的 TaskStateManager (使用其 default 实现) 并传递给 ConnectionManager::new
         Self::new(Arc::new(TaskStateManager::default()))
     }
-} 
\ No newline at end of file
+} 
+/// 测试用的已认证主体：可以以任意角色访问任意项目，但不能强制接管槽位。
+#[cfg(test)]
+pub(crate) fn test_principal() -> AuthenticatedPrincipal {
+    AuthenticatedPrincipal {
+        user_id: "tester".to_string(),
+        allowed_roles: vec![ClientRole::ControlCenter, ClientRole::OnSiteMobile, ClientRole::Supervisor, ClientRole::Observer],
+        allowed_projects: vec![rust_websocket_utils::server::auth::ANY_PROJECT.to_string()],
+        may_force_slot_takeover: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_websocket_utils::server::auth::AuthenticatedPrincipal;
+
+    /// 创建一个携带指定主体的测试客户端会话，返回会话及其消息接收端 (需保持存活以免通道关闭)。
+    async fn add_test_client(
+        manager: &ConnectionManager,
+        principal: Option<AuthenticatedPrincipal>,
+    ) -> (Arc<ClientSession>, mpsc::Receiver<WsMessage>) {
+        let (tx, rx) = mpsc::channel(32);
+        let session = manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), principal)
+            .await;
+        (session, rx)
+    }
+
+    fn register_payload(group_id: &str, role: ClientRole) -> RegisterPayload {
+        RegisterPayload {
+            group_id: group_id.to_string(),
+            role,
+            task_id: "task-1".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_join_group_enforces_principal_role_and_project() {
+        let manager = ConnectionManager::default();
+        let operator = AuthenticatedPrincipal {
+            user_id: "operator".to_string(),
+            allowed_roles: vec![ClientRole::ControlCenter],
+            allowed_projects: vec!["group-1".to_string()],
+            may_force_slot_takeover: false,
+        };
+
+        // 角色不允许
+        let (session, _rx1) = add_test_client(&manager, Some(operator.clone())).await;
+        let result = manager.join_group(session, register_payload("group-1", ClientRole::OnSiteMobile)).await;
+        assert!(result.is_err(), "操作员不应能以现场端角色加入");
+
+        // 项目不允许
+        let (session, _rx2) = add_test_client(&manager, Some(operator.clone())).await;
+        let result = manager.join_group(session, register_payload("group-2", ClientRole::ControlCenter)).await;
+        assert!(result.is_err(), "操作员不应能加入未授权的任务组");
+
+        // 角色与项目均允许
+        let (session, _rx3) = add_test_client(&manager, Some(operator)).await;
+        let result = manager.join_group(session, register_payload("group-1", ClientRole::ControlCenter)).await;
+        assert!(result.is_ok(), "操作员应能以控制中心角色加入授权的任务组");
+
+        // 匿名连接 (服务开启 allow_anonymous 时) 只能以观察者角色加入
+        let (session, _rx4) = add_test_client(&manager, None).await;
+        let result = manager.join_group(session, register_payload("group-1", ClientRole::OnSiteMobile)).await;
+        assert_eq!(result.unwrap_err().error_code, Some(ErrorCode::Forbidden), "匿名连接不应能以现场端角色加入");
+        let (session, _rx5) = add_test_client(&manager, None).await;
+        let result = manager.join_group(session, register_payload("group-1", ClientRole::Observer)).await;
+        assert!(result.is_ok(), "匿名连接应能以观察者角色加入");
+    }
+
+    /// 通过组内槽位向指定角色的客户端发送一条消息 (模拟伙伴或服务端的转发)。
+    async fn send_to_slot(manager: &ConnectionManager, group_id: &str, role: ClientRole, message: WsMessage) {
+        let group_arc = manager.groups.get(group_id).map(|entry| Arc::clone(entry.value())).unwrap();
+        let slot = match role {
+            ClientRole::ControlCenter => group_arc.read().await.control_center_client.clone(),
+            _ => group_arc.read().await.on_site_mobile_clients.first().cloned(),
+        };
+        slot.expect("槽位应被占用").sender.send(message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_client_resumes_within_grace_and_receives_missed_messages() {
+        let manager = ConnectionManager::default().with_resume_grace(Duration::from_secs(60));
+        let (control, mut control_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(control, register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+        let (mobile, _mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut mobile_payload = register_payload("group-1", ClientRole::OnSiteMobile);
+        mobile_payload.site_assignment = Some(SiteAssignment { device_ids: vec!["pump-1".to_string()], ..Default::default() });
+        let joined = manager.join_group(mobile.clone(), mobile_payload.clone()).await.unwrap();
+        let token = joined.resume_token.expect("加入成功后应签发恢复令牌");
+        assert_eq!(joined.resume_grace_seconds, Some(60));
+        while control_rx.try_recv().is_ok() {}
+
+        // 断线：伙伴收到下线通知，槽位仍被保留
+        manager.remove_connection(&mobile).await;
+        let offline: PartnerStatusPayload = control_rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert!(!offline.is_online);
+        assert_eq!(offline.partner_client_id, mobile.client_id);
+        let (intruder, _intruder_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let result = manager.join_group(intruder, mobile_payload.clone()).await;
+        assert!(result.is_err(), "宽限期内断线客户端负责的设备应仍为其保留");
+
+        // 断线期间发往该客户端的消息被缓存
+        let missed = WsMessage::new("Echo".to_string(), &serde_json::json!({"n": 1})).unwrap();
+        send_to_slot(&manager, "group-1", ClientRole::OnSiteMobile, missed.clone()).await;
+
+        // 凭令牌重连：沿用原客户端ID，补发错过的消息，伙伴收到上线通知
+        let (reconnected, mut reconnected_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut payload = register_payload("group-1", ClientRole::OnSiteMobile);
+        payload.resume_token = Some(token.clone());
+        let resumed = manager.join_group(reconnected.clone(), payload).await.unwrap();
+        assert!(resumed.resumed);
+        assert_eq!(resumed.assigned_client_id, mobile.client_id);
+        assert!(resumed.resume_token.is_some_and(|new_token| new_token != token), "恢复后应签发新令牌");
+        assert_eq!(manager.resolve_session(reconnected.clone()).client_id, mobile.client_id);
+
+        assert_eq!(manager.deliver_missed_messages(&reconnected).await, 1);
+        assert_eq!(recv_of_type(&mut reconnected_rx, "Echo").await.message_id, missed.message_id);
+        let online: PartnerStatusPayload =
+            recv_of_type(&mut control_rx, PARTNER_STATUS_UPDATE_MESSAGE_TYPE).await.deserialize_payload().unwrap();
+        assert!(online.is_online);
+
+        // 旧令牌只能使用一次 (按普通注册处理，其设备仍由恢复后的客户端负责)
+        let (another, _another_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut payload = mobile_payload;
+        payload.resume_token = Some(token);
+        assert!(manager.join_group(another, payload).await.is_err());
+    }
+
+    /// 取出接收端中已到达的全部伙伴状态通知。
+    fn drain_partner_updates(rx: &mut mpsc::Receiver<WsMessage>) -> Vec<PartnerStatusPayload> {
+        let mut updates = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if message.message_type == PARTNER_STATUS_UPDATE_MESSAGE_TYPE {
+                updates.push(message.deserialize_payload().unwrap());
+            }
+        }
+        updates
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_and_any_number_of_observers_share_partner_notifications() {
+        let manager = ConnectionManager::default().with_resume_grace(Duration::ZERO);
+        let (control, mut control_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(control.clone(), register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+        let (supervisor, mut supervisor_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(supervisor.clone(), register_payload("group-1", ClientRole::Supervisor)).await.unwrap();
+        let (observer_a, mut observer_a_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(observer_a.clone(), register_payload("group-1", ClientRole::Observer)).await.unwrap();
+        let (observer_b, mut observer_b_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(observer_b.clone(), register_payload("group-1", ClientRole::Observer)).await.unwrap();
+
+        // QA 主管槽位独占，观察者不限数量
+        let (second_supervisor, _second_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let rejected = manager.join_group(second_supervisor, register_payload("group-1", ClientRole::Supervisor)).await.unwrap_err();
+        assert_eq!(rejected.error_code, Some(ErrorCode::RoleSlotTaken));
+
+        // 新成员收到全部已有成员的在线状态，已有成员收到新成员上线通知
+        let seen_by_b: Vec<(ClientRole, Uuid)> = drain_partner_updates(&mut observer_b_rx).iter().map(|u| (u.partner_role, u.partner_client_id)).collect();
+        assert_eq!(
+            seen_by_b,
+            vec![(ClientRole::ControlCenter, control.client_id), (ClientRole::Supervisor, supervisor.client_id), (ClientRole::Observer, observer_a.client_id)]
+        );
+        assert_eq!(drain_partner_updates(&mut control_rx).len(), 3);
+        assert_eq!(drain_partner_updates(&mut supervisor_rx).len(), 3); // 控制中心 (已在组内) 与两个新加入的观察者
+        assert_eq!(drain_partner_updates(&mut observer_a_rx).len(), 3); // 控制中心、QA 主管 (已在组内) 与观察者 B (新加入)
+        assert_eq!(manager.get_group_members_for_broadcast("group-1", None).await.len(), 4);
+        assert_eq!(manager.get_group_members_for_broadcast("group-1", Some(&observer_a.client_id)).await.len(), 3);
+
+        // 观察者离开：其余全部成员收到下线通知，组仍保留
+        manager.remove_client(&observer_a.client_id).await;
+        for rx in [&mut control_rx, &mut supervisor_rx, &mut observer_b_rx] {
+            let updates = drain_partner_updates(rx);
+            assert_eq!(updates.len(), 1);
+            assert!(!updates[0].is_online);
+            assert_eq!(updates[0].partner_client_id, observer_a.client_id);
+        }
+        assert_eq!(manager.get_group_members_for_broadcast("group-1", None).await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_site_engineers_with_disjoint_device_assignments() {
+        let manager = ConnectionManager::default().with_resume_grace(Duration::from_millis(20));
+        let assigned = |devices: &[&str]| {
+            let mut payload = register_payload("group-1", ClientRole::OnSiteMobile);
+            payload.site_assignment = Some(SiteAssignment {
+                device_ids: devices.iter().map(|device| device.to_string()).collect(),
+                pre_check_categories: Vec::new(),
+            });
+            payload
+        };
+        let (pumps, _pumps_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(pumps.clone(), assigned(&["pump-1", "pump-2"])).await.unwrap();
+        let (valves, _valves_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(valves.clone(), assigned(&["valve-1"])).await.unwrap();
+        let (generalist, _generalist_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(generalist.clone(), register_payload("group-1", ClientRole::OnSiteMobile)).await.unwrap();
+
+        // 重复认领设备被拒绝
+        let (greedy, _greedy_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let rejected = manager.join_group(greedy, assigned(&["pump-2"])).await.unwrap_err();
+        assert_eq!(rejected.error_code, Some(ErrorCode::RoleSlotTaken));
+
+        let ids = |sessions: Vec<Arc<ClientSession>>| sessions.iter().map(|s| s.client_id).collect::<Vec<_>>();
+        assert_eq!(ids(manager.site_engineers_for_device("group-1", "pump-2").await), vec![pumps.client_id]);
+        assert_eq!(ids(manager.site_engineers_for_device("group-1", "valve-1").await), vec![valves.client_id]);
+        assert_eq!(ids(manager.site_engineers_for_device("group-1", "fan-9").await), vec![generalist.client_id], "无人认领的设备交给通才");
+        assert_eq!(manager.get_group_members_for_broadcast("group-1", None).await.len(), 3);
+
+        // 离开 (宽限期过后) 其设备可被重新认领
+        manager.remove_connection(&pumps).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.expire_detached_sessions().await, 1);
+        let (replacement, _replacement_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(replacement.clone(), assigned(&["pump-2"])).await.unwrap();
+        assert_eq!(ids(manager.site_engineers_for_device("group-1", "pump-2").await), vec![replacement.client_id]);
+    }
+
+    /// 发起一次接管请求 (消息ID 为 `request_id`)。
+    async fn request_takeover(
+        manager: &ConnectionManager,
+        requester: &Arc<ClientSession>,
+        request_id: &str,
+        force: bool,
+        handover_token: Option<String>,
+    ) -> SlotTakeoverOutcome {
+        let request = SlotTakeoverRequestPayload {
+            register: register_payload("group-1", ClientRole::ControlCenter),
+            force,
+            handover_token,
+        };
+        manager.request_slot_takeover(Arc::clone(requester), request_id, request).await
+    }
+
+    /// 接收下一条指定类型的消息，跳过其他消息 (伙伴通知、任务状态等)。
+    async fn recv_of_type(rx: &mut mpsc::Receiver<WsMessage>, message_type: &str) -> WsMessage {
+        loop {
+            let message = rx.recv().await.expect("通道不应关闭");
+            if message.message_type == message_type {
+                return message;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slot_takeover_asks_holder_and_completes_after_grace() {
+        let manager = ConnectionManager::default().with_takeover_grace(Duration::from_millis(20));
+        let (holder, mut holder_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(holder.clone(), register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+        let (mobile, mut mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(mobile.clone(), register_payload("group-1", ClientRole::OnSiteMobile)).await.unwrap();
+        let (requester, mut requester_rx) = add_test_client(&manager, Some(test_principal())).await;
+
+        // 持有者拒绝
+        let outcome = request_takeover(&manager, &requester, "req-1", false, None).await;
+        let SlotTakeoverOutcome::AwaitingHolder { takeover_id } = outcome else { panic!("应等待持有者答复: {:?}", outcome) };
+        let prompt: SlotTakeoverPromptPayload = recv_of_type(&mut holder_rx, SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE).await.deserialize_payload().unwrap();
+        assert_eq!((prompt.takeover_id.as_str(), prompt.requester_client_id), (takeover_id.as_str(), requester.client_id));
+        let (other, _other_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let decision = SlotTakeoverDecisionPayload { takeover_id: takeover_id.clone(), approve: true };
+        assert!(matches!(manager.answer_slot_takeover(&other, decision).await, Err(CloudError::Forbidden(_))), "只有持有者可以答复");
+        let decision = SlotTakeoverDecisionPayload { takeover_id, approve: false };
+        assert_eq!(manager.answer_slot_takeover(&holder, decision).await.unwrap(), SlotTakeoverOutcome::Resolved { success: false });
+        let denied = recv_of_type(&mut requester_rx, REGISTER_RESPONSE_MESSAGE_TYPE).await;
+        assert_eq!(denied.in_reply_to.as_deref(), Some("req-1"));
+        let denied: RegisterResponsePayload = denied.deserialize_payload().unwrap();
+        assert_eq!(denied.error_code, Some(ErrorCode::RoleSlotTaken));
+
+        // 持有者未答复 (例如电脑已宕机)：宽限期过后自动接管，组与任务状态保留
+        let version_before = manager.task_state_manager.current_version("group-1").await;
+        assert!(matches!(request_takeover(&manager, &requester, "req-2", false, None).await, SlotTakeoverOutcome::AwaitingHolder { .. }));
+        assert_eq!(manager.expire_slot_takeovers().await, 0, "期限未到且持有者在线时不应接管");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.expire_slot_takeovers().await, 1);
+
+        let accepted = recv_of_type(&mut requester_rx, REGISTER_RESPONSE_MESSAGE_TYPE).await;
+        assert_eq!(accepted.in_reply_to.as_deref(), Some("req-2"));
+        assert!(accepted.deserialize_payload::<RegisterResponsePayload>().unwrap().success);
+        recv_of_type(&mut requester_rx, TASK_STATE_UPDATE_MESSAGE_TYPE).await; // 接管后推送完整任务状态
+        let released: SlotReleasedPayload = recv_of_type(&mut holder_rx, SLOT_RELEASED_MESSAGE_TYPE).await.deserialize_payload().unwrap();
+        assert_eq!((released.reason, released.successor_client_id), (SlotReleaseReason::TakenOver, requester.client_id));
+        assert!(holder.connection_should_close.load(Ordering::SeqCst), "被接管的旧连接应被关闭");
+        assert_eq!(*holder.role.read().await, ClientRole::Unknown);
+        assert_eq!(manager.task_state_manager.current_version("group-1").await, version_before);
+        let group = manager.get_group("group-1").await.unwrap();
+        assert_eq!(group.control_center_client.as_ref().map(|cc| cc.client_id), Some(requester.client_id));
+        drop(group);
+
+        // 伙伴看到旧持有者下线、新持有者上线
+        let partner_updates = drain_partner_updates(&mut mobile_rx);
+        assert!(partner_updates.iter().any(|update| update.partner_client_id == holder.client_id && !update.is_online));
+        assert!(partner_updates.iter().any(|update| update.partner_client_id == requester.client_id && update.is_online));
+    }
+
+    #[tokio::test]
+    async fn test_slot_takeover_waits_for_grace_when_holder_is_detached() {
+        let manager = ConnectionManager::default().with_takeover_grace(Duration::from_millis(20));
+        let (holder, _holder_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(holder.clone(), register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+        manager.remove_connection(&holder).await; // 持有者断线，进入会话恢复宽限期
+        let (requester, mut requester_rx) = add_test_client(&manager, Some(test_principal())).await;
+
+        // 断线的持有者无法立即答复，但普通请求方不能因此立即取得槽位
+        let outcome = request_takeover(&manager, &requester, "req-1", false, None).await;
+        assert!(matches!(outcome, SlotTakeoverOutcome::AwaitingHolder { .. }), "应等待持有者答复: {:?}", outcome);
+        assert_eq!(manager.expire_slot_takeovers().await, 0, "持有者仍在宽限期内，答复期限未到时不应接管");
+        let group = manager.get_group("group-1").await.unwrap();
+        assert_eq!(group.control_center_client.as_ref().map(|cc| cc.client_id), Some(holder.client_id));
+        drop(group);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.expire_slot_takeovers().await, 1);
+        let accepted = recv_of_type(&mut requester_rx, REGISTER_RESPONSE_MESSAGE_TYPE).await;
+        assert_eq!(accepted.in_reply_to.as_deref(), Some("req-1"));
+        assert!(accepted.deserialize_payload::<RegisterResponsePayload>().unwrap().success);
+        let group = manager.get_group("group-1").await.unwrap();
+        assert_eq!(group.control_center_client.as_ref().map(|cc| cc.client_id), Some(requester.client_id));
+    }
+
+    #[tokio::test]
+    async fn test_handover_token_and_forced_takeover() {
+        let manager = ConnectionManager::default();
+        let (day_shift, mut day_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(day_shift.clone(), register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+
+        // 计划内交接：持有者签发令牌，接班者凭令牌立即取得槽位，旧连接保留但离开组
+        let handover = manager.prepare_handover(&day_shift).await.unwrap();
+        assert_eq!((handover.group_id.as_str(), handover.role), ("group-1", ClientRole::ControlCenter));
+
+        // 令牌用于其他组的槽位时被拒绝，但不会因此失效
+        let (other_holder, _other_holder_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(other_holder, register_payload("group-2", ClientRole::ControlCenter)).await.unwrap();
+        let (misdirected, _misdirected_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let request = SlotTakeoverRequestPayload {
+            register: register_payload("group-2", ClientRole::ControlCenter),
+            force: false,
+            handover_token: Some(handover.handover_token.clone()),
+        };
+        let outcome = manager.request_slot_takeover(misdirected, "req-0", request).await;
+        assert_eq!(outcome, SlotTakeoverOutcome::Resolved { success: false });
+
+        let (night_shift, mut night_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let outcome = request_takeover(&manager, &night_shift, "req-1", false, Some(handover.handover_token.clone())).await;
+        assert_eq!(outcome, SlotTakeoverOutcome::Resolved { success: true });
+        let accepted = recv_of_type(&mut night_rx, REGISTER_RESPONSE_MESSAGE_TYPE).await;
+        assert!(accepted.deserialize_payload::<RegisterResponsePayload>().unwrap().success);
+        let released: SlotReleasedPayload = recv_of_type(&mut day_rx, SLOT_RELEASED_MESSAGE_TYPE).await.deserialize_payload().unwrap();
+        assert_eq!(released.reason, SlotReleaseReason::HandedOver);
+        assert!(!day_shift.connection_should_close.load(Ordering::SeqCst), "计划内交接不关闭旧连接");
+        assert!(day_shift.group_id.read().await.is_none());
+        assert!(matches!(manager.prepare_handover(&day_shift).await, Err(CloudError::NotRegistered)));
+
+        // 令牌只能使用一次
+        let (late, _late_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let outcome = request_takeover(&manager, &late, "req-2", false, Some(handover.handover_token)).await;
+        assert_eq!(outcome, SlotTakeoverOutcome::Resolved { success: false });
+
+        // 强制接管只对被授权的用户有效
+        let principal = |user_id: &str, may_force_slot_takeover: bool| AuthenticatedPrincipal {
+            user_id: user_id.to_string(),
+            allowed_roles: vec![ClientRole::ControlCenter],
+            allowed_projects: vec!["group-1".to_string()],
+            may_force_slot_takeover,
+        };
+        let (operator, _operator_rx) = add_test_client(&manager, Some(principal("operator", false))).await;
+        assert_eq!(request_takeover(&manager, &operator, "req-3", true, None).await, SlotTakeoverOutcome::Resolved { success: false });
+        let (admin, _admin_rx) = add_test_client(&manager, Some(principal("admin", true))).await;
+        assert_eq!(request_takeover(&manager, &admin, "req-4", true, None).await, SlotTakeoverOutcome::Resolved { success: true });
+        assert!(night_shift.connection_should_close.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_leave_group_and_switch_task_keep_the_connection() {
+        let manager = ConnectionManager::default();
+        let (center, mut center_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let (mobile, _mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        manager.join_group(center.clone(), register_payload("group-1", ClientRole::ControlCenter)).await.unwrap();
+        let joined = manager.join_group(mobile.clone(), register_payload("group-1", ClientRole::OnSiteMobile)).await.unwrap();
+        assert!(joined.resume_token.is_some());
+        drain_partner_updates(&mut center_rx);
+
+        // 切换到当前所在的组被拒绝，成员身份不变
+        let same = manager.switch_task(mobile.clone(), register_payload("group-1", ClientRole::OnSiteMobile)).await.unwrap_err();
+        assert_eq!(same.error_code, Some(ErrorCode::PayloadInvalid));
+        assert_eq!(mobile.group_id.read().await.as_deref(), Some("group-1"));
+
+        // 切换任务：离开 group-1 (伙伴收到下线通知、恢复令牌失效) 并加入 group-2
+        let switched = manager.switch_task(mobile.clone(), register_payload("group-2", ClientRole::OnSiteMobile)).await.unwrap();
+        assert_eq!(switched.effective_group_id.as_deref(), Some("group-2"));
+        assert_eq!(mobile.group_id.read().await.as_deref(), Some("group-2"));
+        assert!(!mobile.connection_should_close.load(Ordering::SeqCst));
+        let updates = drain_partner_updates(&mut center_rx);
+        assert!(updates.iter().any(|u| u.partner_client_id == mobile.client_id && !u.is_online));
+        assert!(manager.get_group("group-1").await.unwrap().on_site_mobile_clients.is_empty());
+
+        // 最后一名成员离开后组及其任务状态被清理
+        let left = manager.leave_group(&center).await.unwrap();
+        assert_eq!(left, GroupLeftPayload { group_id: "group-1".to_string(), role: ClientRole::ControlCenter });
+        assert!(!manager.groups.contains_key("group-1"));
+        assert!(manager.task_state_manager.get_task_state("group-1").await.is_none());
+        assert_eq!(*center.role.read().await, ClientRole::Unknown);
+        assert!(matches!(manager.leave_group(&center).await, Err(CloudError::NotRegistered)));
+
+        // 同一连接可以重新注册到其他组
+        assert!(manager.join_group(center.clone(), register_payload("group-2", ClientRole::ControlCenter)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_open_group_issues_single_use_join_codes() {
+        let manager = ConnectionManager::default().with_join_code_ttl(Duration::from_millis(200));
+        let (center, _center_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let open = |group_id: Option<&str>| OpenGroupPayload {
+            task_id: "task-1".to_string(),
+            group_id: group_id.map(str::to_string),
+            client_software_version: None,
+            client_display_name: None,
+        };
+        let opened = manager.open_group(center.clone(), open(None)).await;
+        assert!(opened.registration.success);
+        let group_id = opened.registration.effective_group_id.clone().unwrap();
+        assert!(group_id.starts_with("grp-"), "未指定组ID时由服务端生成");
+        let code = opened.join_code.unwrap();
+        assert_eq!(code.len(), JOIN_CODE_LENGTH);
+
+        // 现场端仅凭配对码 (大小写不敏感) 注册，不需要知道组ID与任务ID
+        let (mobile, _mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut by_code = register_payload("", ClientRole::OnSiteMobile);
+        by_code.task_id.clear();
+        by_code.join_code = Some(code.to_lowercase());
+        let joined = manager.join_group(mobile.clone(), by_code.clone()).await.unwrap();
+        assert_eq!(joined.effective_group_id.as_deref(), Some(group_id.as_str()));
+
+        // 配对码只能使用一次
+        let (second, _second_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut reused = by_code.clone();
+        reused.site_assignment = Some(SiteAssignment { device_ids: vec!["pump-1".to_string()], pre_check_categories: vec![] });
+        let rejected = manager.join_group(second.clone(), reused.clone()).await.unwrap_err();
+        assert_eq!(rejected.error_code, Some(ErrorCode::JoinCodeInvalid));
+
+        // 已在组内的控制中心再次开组时只签发新的配对码；任务不一致时被拒绝
+        let reissued = manager.open_group(center.clone(), open(Some(&group_id))).await;
+        assert_eq!(reissued.registration.effective_group_id.as_deref(), Some(group_id.as_str()));
+        let new_code = reissued.join_code.unwrap();
+        assert_ne!(new_code, code);
+        let mut other_task = open(None);
+        other_task.task_id = "task-2".to_string();
+        let mismatch = manager.open_group(center.clone(), other_task).await;
+        assert_eq!(mismatch.registration.error_code, Some(ErrorCode::TaskMismatch));
+        assert!(mismatch.join_code.is_none());
+
+        // 过期的配对码失效
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        reused.join_code = Some(new_code);
+        assert_eq!(manager.join_group(second, reused).await.unwrap_err().error_code, Some(ErrorCode::JoinCodeInvalid));
+        manager.open_group(center, open(None)).await.join_code.unwrap();
+        assert_eq!(manager.expire_join_codes(), 1, "只丢弃已过期的配对码，刚签发的配对码仍有效");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(manager.expire_join_codes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_open_group_and_join_code_authorize_against_task() {
+        let manager = ConnectionManager::default();
+        let principal = |user_id: &str, role: ClientRole, project: &str| AuthenticatedPrincipal {
+            user_id: user_id.to_string(),
+            allowed_roles: vec![role],
+            allowed_projects: vec![project.to_string()],
+            may_force_slot_takeover: false,
+        };
+        let open = OpenGroupPayload {
+            task_id: "task-1".to_string(),
+            group_id: None,
+            client_software_version: None,
+            client_display_name: None,
+        };
+
+        // 只被授权访问任务的用户可以为该任务开组 (组ID由服务端生成)
+        let (center, _center_rx) = add_test_client(&manager, Some(principal("operator", ClientRole::ControlCenter, "task-1"))).await;
+        let opened = manager.open_group(center, open.clone()).await;
+        assert!(opened.registration.success, "{:?}", opened.registration.message);
+        let code = opened.join_code.unwrap();
+
+        // 现场工程师凭配对码加入，按配对码的任务授权
+        let (mobile, _mobile_rx) = add_test_client(&manager, Some(principal("engineer", ClientRole::OnSiteMobile, "task-1"))).await;
+        let mut by_code = register_payload("", ClientRole::OnSiteMobile);
+        by_code.join_code = Some(code);
+        assert!(manager.join_group(mobile, by_code.clone()).await.is_ok());
+
+        // 无权访问该任务的用户不能开组，也不能凭配对码加入
+        let (stranger, _stranger_rx) = add_test_client(&manager, Some(principal("stranger", ClientRole::ControlCenter, "task-2"))).await;
+        let denied = manager.open_group(stranger, open.clone()).await;
+        assert_eq!(denied.registration.error_code, Some(ErrorCode::Forbidden));
+        let (center, _center_rx2) = add_test_client(&manager, Some(principal("operator-2", ClientRole::ControlCenter, "task-1"))).await;
+        by_code.join_code = manager.open_group(center.clone(), open.clone()).await.join_code;
+        let (outsider, _outsider_rx) = add_test_client(&manager, Some(principal("outsider", ClientRole::OnSiteMobile, "task-2"))).await;
+        let rejected = manager.join_group(outsider, by_code.clone()).await.unwrap_err();
+        assert_eq!(rejected.error_code, Some(ErrorCode::Forbidden));
+
+        // 被拒绝的注册不消耗配对码：设备已被他人负责时同样如此，授权用户随后仍可凭该配对码加入
+        let (overlapping, _overlapping_rx) = add_test_client(&manager, Some(principal("overlapping", ClientRole::OnSiteMobile, "task-1"))).await;
+        let mut overlapping_payload = by_code.clone();
+        overlapping_payload.site_assignment = Some(SiteAssignment { device_ids: vec!["pump-1".to_string()], pre_check_categories: vec![] });
+        let (owner, _owner_rx) = add_test_client(&manager, Some(principal("owner", ClientRole::OnSiteMobile, "task-1"))).await;
+        let mut owner_payload = overlapping_payload.clone();
+        owner_payload.join_code = manager.open_group(center, open).await.join_code;
+        assert!(manager.join_group(owner, owner_payload).await.is_ok());
+        let rejected = manager.join_group(overlapping, overlapping_payload).await.unwrap_err();
+        assert_eq!(rejected.error_code, Some(ErrorCode::RoleSlotTaken));
+        let (engineer, _engineer_rx) = add_test_client(&manager, Some(principal("engineer-2", ClientRole::OnSiteMobile, "task-1"))).await;
+        assert!(manager.join_group(engineer, by_code.clone()).await.is_ok(), "被拒绝的注册不应使配对码失效");
+        let (late, _late_rx) = add_test_client(&manager, Some(principal("late", ClientRole::OnSiteMobile, "task-1"))).await;
+        assert_eq!(manager.join_group(late, by_code).await.unwrap_err().error_code, Some(ErrorCode::JoinCodeInvalid));
+    }
+
+    /// 取出接收端中已到达的全部组成员名册。
+    fn drain_rosters(rx: &mut mpsc::Receiver<WsMessage>) -> Vec<GroupRosterPayload> {
+        let mut rosters = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if message.message_type == GROUP_ROSTER_MESSAGE_TYPE {
+                rosters.push(message.deserialize_payload().unwrap());
+            }
+        }
+        rosters
+    }
+
+    #[tokio::test]
+    async fn test_group_roster_tracks_members_presence_and_round_trip() {
+        let manager = ConnectionManager::default();
+        let (center, mut center_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let (mobile, mut mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut center_register = register_payload("group-1", ClientRole::ControlCenter);
+        center_register.client_display_name = Some("中控-张工".to_string());
+        center_register.client_software_version = Some("1.4.0".to_string());
+        manager.join_group(center.clone(), center_register).await.unwrap();
+        let mut mobile_register = register_payload("group-1", ClientRole::OnSiteMobile);
+        mobile_register.client_display_name = Some("现场-李工".to_string());
+        manager.join_group(mobile.clone(), mobile_register).await.unwrap();
+
+        // 每次成员变化都向全部在线成员 (含新成员自身) 推送完整名册
+        let roster = drain_rosters(&mut center_rx).pop().expect("控制中心应收到名册");
+        assert_eq!(drain_rosters(&mut mobile_rx).pop(), Some(roster.clone()));
+        assert_eq!(roster.task_id, "task-1");
+        assert_eq!(roster.members.len(), 2);
+        let center_entry = roster.members.iter().find(|m| m.client_id == center.client_id).unwrap();
+        assert_eq!(center_entry.display_name.as_deref(), Some("中控-张工"));
+        assert_eq!(center_entry.software_version.as_deref(), Some("1.4.0"));
+        assert!(roster.members.iter().all(|m| m.is_online && m.round_trip_ms.is_none()));
+
+        // 第一次测量与明显变化推送名册，细小抖动不推送
+        manager.record_round_trip(&mobile, 80).await;
+        let roster = drain_rosters(&mut center_rx).pop().expect("第一次测量应推送名册");
+        assert_eq!(roster.members.iter().find(|m| m.client_id == mobile.client_id).unwrap().round_trip_ms, Some(80));
+        manager.record_round_trip(&mobile, 120).await;
+        assert!(drain_rosters(&mut center_rx).is_empty());
+        manager.record_round_trip(&mobile, 400).await;
+        assert_eq!(drain_rosters(&mut center_rx).len(), 1);
+
+        // 断线进入宽限期的成员仍在名册中，但标记为离线并保留其信息
+        manager.remove_connection(&mobile).await;
+        let roster = drain_rosters(&mut center_rx).pop().expect("断线应推送名册");
+        let mobile_entry = roster.members.iter().find(|m| m.client_id == mobile.client_id).unwrap();
+        assert!(!mobile_entry.is_online);
+        assert_eq!(mobile_entry.display_name.as_deref(), Some("现场-李工"));
+        assert_eq!(mobile_entry.connected_at, mobile.creation_time);
+    }
+
+    #[tokio::test]
+    async fn test_detached_session_is_released_after_grace() {
+        let manager = ConnectionManager::default().with_resume_grace(Duration::from_millis(20));
+        let (mobile, _mobile_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let joined = manager.join_group(mobile.clone(), register_payload("group-1", ClientRole::OnSiteMobile)).await.unwrap();
+        let token = joined.resume_token.unwrap();
+
+        manager.remove_connection(&mobile).await;
+        assert!(manager.groups.contains_key("group-1"), "宽限期内组应被保留");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.expire_detached_sessions().await, 1);
+        assert!(!manager.groups.contains_key("group-1"), "宽限期过后空组应被清理");
+
+        // 过期令牌按普通注册处理
+        let (reconnected, _reconnected_rx) = add_test_client(&manager, Some(test_principal())).await;
+        let mut payload = register_payload("group-1", ClientRole::OnSiteMobile);
+        payload.resume_token = Some(token);
+        let response = manager.join_group(reconnected.clone(), payload).await.unwrap();
+        assert!(!response.resumed);
+        assert_eq!(response.assigned_client_id, reconnected.client_id);
+    }
+}
@@ -273,6 +273,9 @@ pub async fn register_client_with_task(
         task_id: task_id.clone(), // 克隆以所有权传递
         client_software_version: client_sw_version, // 新增字段
         client_display_name: client_display_name,   // 新增字段
+        resume_token: None,
+        site_assignment: None,
+        join_code: None,
     };
 
     // 4. 构建 WsMessage
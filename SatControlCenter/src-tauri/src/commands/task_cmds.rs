@@ -74,6 +74,8 @@ pub async fn update_task_debug_note_cmd(
         group_id: group_id.clone(),
         new_note,
         custom_shared_data: custom_data_value,
+        // 基于本地缓存的任务状态版本提交，若期间另一端已修改则由云端以版本冲突拒绝
+        expected_version: ws_client_service.get_cached_task_state().await.map(|state| state.version),
     };
 
     match WsMessage::new(
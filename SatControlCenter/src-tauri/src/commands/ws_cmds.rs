@@ -6,9 +6,15 @@ use std::sync::Arc;
 use log::{info, error};
 
 use crate::ws_client::service::WebSocketClientService;
+use crate::ws_client::version_conflict::ConflictResolution;
 // 导入 GeneralResponse 并移除不再需要的 CommonGeneralResponse 别名
-use common_models::ws_payloads::{RegisterPayload, EchoPayload, GeneralResponse};
+use common_models::ws_payloads::{
+    RegisterPayload, EchoPayload, GeneralResponse, GroupLeftPayload, GroupOpenedPayload, HandoverTokenPayload, OpenGroupPayload,
+    SlotTakeoverDecisionPayload, SlotTakeoverRequestPayload,
+};
+use common_models::RegisterResponsePayload;
 use common_models::enums::ClientRole;
+use common_models::TaskDebugState;
 
 #[tauri::command]
 pub async fn connect_to_ws_server_cmd(
@@ -67,6 +73,9 @@ pub async fn send_register_message_cmd(
         role: ClientRole::ControlCenter, 
         client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()), // 与 general_cmds 保持一致
         client_display_name: Some("ControlCenterViaWsCmds".to_string()), // 提供一个默认的或考虑是否需要从参数传入
+        resume_token: None,
+        site_assignment: None,
+        join_code: None,
     };
 
     match ws_client_service.send_specific_message(common_models::ws_payloads::REGISTER_MESSAGE_TYPE, &register_payload).await {
@@ -83,4 +92,141 @@ pub async fn send_register_message_cmd(
 //     pub success: bool,
 //     pub message: String,
 // }
-// 使用上面已有的 GeneralResponse 即可。 
\ No newline at end of file
+// 使用上面已有的 GeneralResponse 即可。 
+
+/// 请求云端重新推送当前完整的任务调试状态，返回同步后的状态 (本地缓存与前端事件同时更新)。
+#[tauri::command]
+pub async fn resync_task_state_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<TaskDebugState, String> {
+    info!("[中心端CMD] resync_task_state_cmd called.");
+    ws_client_service.resync_task_state().await.map_err(|e| {
+        error!("[中心端CMD] Failed to resync task state: {}", e);
+        e
+    })
+}
+
+/// 请求接管任务组中已被占用的控制中心槽位 (例如原操作员的电脑宕机)。
+///
+/// 携带 `handover_token` (由原操作员通过 `prepare_handover_cmd` 取得) 时立即完成交接；
+/// `force` 仅对被授权的用户 (管理员) 有效；否则云端先征求原操作员同意，超时未答复时自动接管。
+/// 最终结果通过 `ws_registration_status_event` 通知前端。
+#[tauri::command]
+pub async fn request_slot_takeover_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    group_id: String,
+    task_id: String,
+    force: Option<bool>,
+    handover_token: Option<String>,
+) -> Result<GeneralResponse, String> {
+    info!("[中心端CMD] request_slot_takeover_cmd for group: {}, task: {}, force: {:?}", group_id, task_id, force);
+    let request = SlotTakeoverRequestPayload {
+        register: RegisterPayload {
+            group_id,
+            task_id,
+            role: ClientRole::ControlCenter,
+            client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            client_display_name: Some("ControlCenterViaWsCmds".to_string()),
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        },
+        force: force.unwrap_or(false),
+        handover_token,
+    };
+    match ws_client_service.send_specific_message(common_models::ws_payloads::SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE, &request).await {
+        Ok(_) => Ok(GeneralResponse { success: true, message: "Slot takeover request sent.".to_string() }),
+        Err(e) => Err(format!("Failed to send slot takeover request: {:?}", e)),
+    }
+}
+
+/// 答复云端转来的接管请求 (见 `ws_slot_takeover_prompt_event`)：同意时本端的槽位交给请求方。
+#[tauri::command]
+pub async fn answer_slot_takeover_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    takeover_id: String,
+    approve: bool,
+) -> Result<GeneralResponse, String> {
+    info!("[中心端CMD] answer_slot_takeover_cmd called. takeover_id: {}, approve: {}", takeover_id, approve);
+    let decision = SlotTakeoverDecisionPayload { takeover_id, approve };
+    match ws_client_service.send_specific_message(common_models::ws_payloads::SLOT_TAKEOVER_DECISION_MESSAGE_TYPE, &decision).await {
+        Ok(_) => Ok(GeneralResponse { success: true, message: "Slot takeover decision sent.".to_string() }),
+        Err(e) => Err(format!("Failed to send slot takeover decision: {:?}", e)),
+    }
+}
+
+/// 换班前为本端占用的槽位申请一次性交接令牌，交给接班的操作员用于 `request_slot_takeover_cmd`。
+#[tauri::command]
+pub async fn prepare_handover_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<HandoverTokenPayload, String> {
+    info!("[中心端CMD] prepare_handover_cmd called.");
+    ws_client_service.prepare_handover().await.map_err(|e| {
+        error!("[中心端CMD] Failed to prepare handover: {}", e);
+        e
+    })
+}
+
+/// 请求云端为任务开组 (本端以控制中心角色加入) 并签发配对码，现场端凭配对码即可加入，无需手工输入组ID与任务ID。
+/// `group_id` 省略时由云端生成；已在组内时再次调用只签发新的配对码 (每个配对码只能使用一次)。
+#[tauri::command]
+pub async fn open_group_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: String,
+    group_id: Option<String>,
+) -> Result<GroupOpenedPayload, String> {
+    info!("[中心端CMD] open_group_cmd for task: {}, group: {:?}", task_id, group_id);
+    let request = OpenGroupPayload {
+        task_id,
+        group_id,
+        client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        client_display_name: Some("ControlCenterViaWsCmds".to_string()),
+    };
+    ws_client_service.open_group(request).await
+}
+
+/// 不断开连接地离开当前任务组，返回已离开的组与角色 (同时发送 `ws_group_left_event`)。
+#[tauri::command]
+pub async fn leave_group_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<GroupLeftPayload, String> {
+    info!("[中心端CMD] leave_group_cmd called.");
+    ws_client_service.leave_group().await
+}
+
+/// 离开当前任务组并以控制中心角色注册到另一个任务组，无需断开 WebSocket 连接。
+/// 返回云端的注册响应 (同时发送 `ws_registration_status_event`)。
+#[tauri::command]
+pub async fn switch_task_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    group_id: String,
+    task_id: String,
+) -> Result<RegisterResponsePayload, String> {
+    info!("[中心端CMD] switch_task_cmd for group: {}, task: {}", group_id, task_id);
+    let register = RegisterPayload {
+        group_id,
+        task_id,
+        role: ClientRole::ControlCenter,
+        client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        client_display_name: Some("ControlCenterViaWsCmds".to_string()),
+        resume_token: None,
+        site_assignment: None,
+        join_code: None,
+    };
+    ws_client_service.switch_task(register).await
+}
+
+/// 处理一次业务消息的版本冲突 (见 `ws_version_conflict_event`)：变基重新提交、提交合并后的负载或放弃本端修改。
+/// 重新提交时返回新业务消息的 `message_id`，放弃时返回 `None`。
+#[tauri::command]
+pub async fn resolve_version_conflict_cmd(
+    request_message_id: String,
+    resolution: ConflictResolution,
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<Option<String>, String> {
+    info!("[中心端CMD] resolve_version_conflict_cmd called. request_message_id: {}, resolution: {:?}", request_message_id, resolution);
+    ws_client_service.resolve_version_conflict(&request_message_id, resolution).await.map_err(|e| {
+        error!("[中心端CMD] Failed to resolve version conflict: {}", e);
+        e
+    })
+}
@@ -9,6 +9,13 @@
 use serde::{Serialize, Deserialize};
 // use common_models::{self, TaskDebugState, enums::ClientRole}; // ClientRole is unused if partner_role is String
 use common_models::{self, TaskDebugState}; // Ensure TaskDebugState is correctly imported
+use common_models::enums::ErrorCode;
+use common_models::ws_payloads::{
+    AckPayload, FieldErrorDetail, GroupLeftPayload, GroupOpenedPayload, GroupRosterPayload, SlotReleasedPayload, SlotTakeoverPromptPayload,
+};
+use crate::ws_client::error_handling::ServerErrorHandling;
+use rust_websocket_utils::client::heartbeat::LinkQuality;
+use rust_websocket_utils::client::reconnect::ConnectionState;
 // use uuid::Uuid; // Uuid is unused if client_id fields are String
 
 /// WebSocket 连接状态变更事件的统一名称常量。
@@ -36,6 +43,34 @@ pub struct WsConnectionStatusEvent {
     pub error_message: Option<String>,
 }
 
+/// WebSocket 自动重连状态事件的名称常量。
+///
+/// 与只区分"已连接/未连接"的 `WS_CONNECTION_STATUS_EVENT` 不同，此事件携带重连客户端的详细状态
+/// (正在连接、已连接/已重连、等待重连及剩余等待时间、已放弃重连、已关闭)，前端可据此展示重连进度。
+pub const WS_CONNECTION_STATE_EVENT: &str = "ws_connection_state_event";
+
+/// `WS_CONNECTION_STATE_EVENT` 事件的负载结构体。
+#[derive(Clone, Serialize, Debug)]
+pub struct WsConnectionStateEventPayload {
+    /// 重连客户端的当前状态，以 `state` 字段区分 (例如 `{"state":"Reconnecting","attempt":2,...}`)。
+    #[serde(flatten)]
+    pub state: ConnectionState,
+}
+
+/// 链路质量事件的名称常量。
+///
+/// 心跳任务每次评估一次 Ping 的结果 (收到 Pong 或等待超时) 后发出，携带实测往返时延、抖动、
+/// 质量等级以及当前采用的心跳间隔，前端可据此展示信号强弱。
+pub const WS_LINK_QUALITY_EVENT: &str = "ws_link_quality_event";
+
+/// `WS_LINK_QUALITY_EVENT` 事件的负载结构体。
+#[derive(Clone, Serialize, Debug)]
+pub struct WsLinkQualityEventPayload {
+    /// 链路质量评估结果 (例如 `{"level":"Fair","smoothed_rtt_ms":420,"jitter_ms":130,...}`)。
+    #[serde(flatten)]
+    pub quality: LinkQuality,
+}
+
 // --- Echo (回声测试) 相关事件 --- 
 
 /// Echo (回声) 响应消息事件的名称常量。
@@ -87,6 +122,15 @@ pub struct WsRegistrationStatusEventPayload {
     /// 如果注册成功且操作涉及特定角色，此字段可能包含客户端的角色。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
+    /// 如果注册失败，云端返回的机器可读错误码 (例如 `ROLE_SLOT_TAKEN`)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// 如果注册失败，根据错误码确定的建议处理方式。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handling: Option<ServerErrorHandling>,
+    /// 本次注册是否凭会话恢复令牌恢复了断线前的会话 (云端随后会补发断线期间错过的消息)。
+    #[serde(default)]
+    pub resumed: bool,
 }
 
 /// WebSocket 伙伴客户端状态更新事件的名称常量。
@@ -109,6 +153,10 @@ pub struct WsPartnerStatusEventPayload {
     /// - `true`: 表示伙伴客户端当前在线并已加入任务组。
     /// - `false`: 表示伙伴客户端已离线或已离开任务组。
     pub is_online: bool,
+    /// 伙伴在线但链路可疑 (服务端已有一段时间未收到其消息，尚未判定为断线)。
+    /// 前端可据此提示"伙伴网络不稳定"，而不是直接显示离线。
+    #[serde(default)]
+    pub link_suspect: bool,
     /// 发生状态变化的伙伴客户端的唯一标识符 (UUID 字符串格式)。
     /// 用于在前端精确识别是哪个伙伴的状态发生了变化。
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -145,6 +193,128 @@ pub struct WsServerErrorEventPayload {
     pub error_message: String, // 来自 ErrorResponsePayload.error
     #[serde(skip_serializing_if = "Option::is_none")]
     pub original_message_type: Option<String>, // 来自 ErrorResponsePayload.original_message_type
+    /// 机器可读的错误码 (旧版本云端不提供)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// 本地化消息键，前端据此查找面向用户的提示文本。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    /// 字段级错误详情。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldErrorDetail>,
+    /// 根据错误码确定的建议处理方式。
+    pub handling: ServerErrorHandling,
+}
+
+// --- 业务确认事件 ---
+/// 云端对本端发出的业务消息给出统一确认 (`Ack`) 时发送给前端的事件名称常量。
+///
+/// 前端可据此确认一次操作 (例如点击更新预检项) 是否真正到达云端、是否生效，以及生效后的任务状态版本。
+pub const WS_BUSINESS_ACK_EVENT: &str = "ws_business_ack_event";
+
+/// `WS_BUSINESS_ACK_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsBusinessAckEventPayload {
+    /// 被确认的业务消息的 `message_id` (即 Ack 消息的 `in_reply_to`)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_message_id: Option<String>,
+    /// 云端返回的确认内容 (处理结果、版本号、错误码等)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub ack: AckPayload,
+    /// 被拒绝时根据错误码确定的建议处理方式。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handling: Option<ServerErrorHandling>,
+}
+
+// --- 版本冲突事件 ---
+/// 本端业务消息因基于过期的任务状态版本被云端拒绝 (`VERSION_CONFLICT`) 时发送给前端的事件名称常量。
+///
+/// 收到该事件时本地缓存已刷新为云端当前状态 (同时会收到 `LocalTaskStateUpdatedEvent`)。前端应向用户展示
+/// 本端的修改与当前状态，并调用 `resolve_version_conflict_cmd` 选择变基、合并或放弃。
+pub const WS_VERSION_CONFLICT_EVENT: &str = "ws_version_conflict_event";
+
+/// `WS_VERSION_CONFLICT_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsVersionConflictEventPayload {
+    /// 被拒绝的业务消息的 `message_id`，处理冲突时据此指定要处理的消息。
+    pub request_message_id: String,
+    /// 被拒绝的业务消息类型，例如 "UpdatePreCheckItem"。
+    pub original_message_type: String,
+    /// 本端被拒绝的修改内容 (原业务消息负载)，可作为合并的起点。
+    pub local_payload: serde_json::Value,
+    /// 云端当前完整的任务状态。
+    pub current_state: TaskDebugState,
+}
+
+// --- 角色槽位接管事件 ---
+/// 其他客户端请求接管本端占用的角色槽位、云端征求本端同意时发送给前端的事件名称常量。
+///
+/// 前端应提示操作员，并调用 `answer_slot_takeover_cmd` 同意或拒绝；
+/// 在 `auto_approve_after_seconds` 秒内未答复时云端视为同意 (原操作员的电脑可能已宕机)。
+pub const WS_SLOT_TAKEOVER_PROMPT_EVENT: &str = "ws_slot_takeover_prompt_event";
+
+/// `WS_SLOT_TAKEOVER_PROMPT_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsSlotTakeoverPromptEventPayload {
+    /// 云端发来的接管请求内容 (请求ID、请求方、自动同意期限等)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub prompt: SlotTakeoverPromptPayload,
+}
+
+/// 本端的角色槽位已被接管或已交接给他人时发送给前端的事件名称常量。
+///
+/// 收到后本端不再属于该任务组：`HandedOver` 时连接保持，可重新注册为其他角色；`TakenOver` 时连接随后被云端关闭。
+pub const WS_SLOT_RELEASED_EVENT: &str = "ws_slot_released_event";
+
+/// `WS_SLOT_RELEASED_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsSlotReleasedEventPayload {
+    /// 云端发来的槽位释放通知 (组、角色、原因、接替者)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub released: SlotReleasedPayload,
+}
+
+// --- 配对码开组事件 ---
+/// 云端回复 `OpenGroup` (开组并签发配对码) 时发送给前端的事件名称常量。
+///
+/// 成功时前端应展示配对码 (及其剩余有效期)，供现场端输入或扫码；失败原因见 `registration`。
+pub const WS_GROUP_OPENED_EVENT: &str = "ws_group_opened_event";
+
+/// `WS_GROUP_OPENED_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsGroupOpenedEventPayload {
+    /// 云端的开组结果 (注册响应、任务ID与配对码)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub opened: GroupOpenedPayload,
+}
+
+// --- 离开任务组事件 ---
+/// 本端已离开任务组 (主动 `LeaveGroup`，或 `SwitchTask` 离开原组后) 时发送给前端的事件名称常量。
+///
+/// 收到后本地任务状态缓存已清空，连接保持，可以重新注册到其他任务组。
+pub const WS_GROUP_LEFT_EVENT: &str = "ws_group_left_event";
+
+/// `WS_GROUP_LEFT_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsGroupLeftEventPayload {
+    /// 云端的离开确认 (组ID与本端在组内的角色)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub left: GroupLeftPayload,
+}
+
+// --- 组成员名册事件 ---
+/// 云端推送组成员名册 (`GroupRoster`) 时发送给前端的事件名称常量。
+///
+/// 组内成员加入、离开、断线、恢复或链路延迟明显变化时推送，负载为组内全部成员的完整列表，
+/// 前端应整体替换本地名册 (显示名称、软件版本、连接时间、最后活跃时间、往返时延与在线状态)。
+pub const WS_GROUP_ROSTER_EVENT: &str = "ws_group_roster_event";
+
+/// `WS_GROUP_ROSTER_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsGroupRosterEventPayload {
+    /// 云端推送的完整名册，平铺到事件负载中。
+    #[serde(flatten)]
+    pub roster: GroupRosterPayload,
 }
 
 // WebSocket Server (Cloud) Connection Events
@@ -69,6 +69,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             commands::ws_cmds::disconnect_from_ws_server_cmd,
             commands::ws_cmds::send_echo_message_cmd,
             commands::ws_cmds::send_register_message_cmd,
+            commands::ws_cmds::resync_task_state_cmd,
+            commands::ws_cmds::resolve_version_conflict_cmd,
+            commands::ws_cmds::request_slot_takeover_cmd,
+            commands::ws_cmds::answer_slot_takeover_cmd,
+            commands::ws_cmds::prepare_handover_cmd,
+            commands::ws_cmds::open_group_cmd,
+            commands::ws_cmds::leave_group_cmd,
+            commands::ws_cmds::switch_task_cmd,
+            commands::test_cmds::start_single_test_step_cmd,   // 向现场端发起单体测试步骤
+            commands::test_cmds::confirm_single_test_step_cmd, // 确认或驳回现场端反馈的步骤结果
+            commands::test_cmds::confirm_pre_check_item_cmd,   // 确认或驳回现场端上报的预检查项
             commands::task_cmds::update_task_debug_note_cmd // 中心端 task_commands 中的对应命令
         ])
         .build(tauri::generate_context!()) 
@@ -8,6 +8,8 @@
 //! ## 核心组件：
 //! - `service.rs` (`pub mod service`): 包含核心的 `WebSocketClientService` 结构体及其实现，
 //!   该服务是 WebSocket 通信的主要协调者。
+//! - `error_handling` / `version_conflict`: 错误码处理方式映射与版本冲突的变基/合并流程，
+//!   与连接、心跳、任务状态缓存一样由共享的业务客户端 (`rust_websocket_utils::client`) 提供，此处重新导出。
 //!
 //! ## 使用方式：
 //! 通过 `pub use service::WebSocketClientService;` 将 `WebSocketClientService` 导出，
@@ -19,6 +21,9 @@
 /// `service.rs` 文件包含了该服务的具体实现。
 pub mod service;
 
+/// 云端错误码到前端处理方式的映射 (`ServerErrorHandling`)，以及业务消息版本冲突 (`VERSION_CONFLICT`) 的变基/合并处理。
+pub use rust_websocket_utils::client::{error_handling, version_conflict};
+
 // --- 公开导出 (Re-export) --- 
 
 // pub use service::WebSocketClientService; // Removing unused import
@@ -1,5 +1,103 @@
 // SatOnSiteMobile/src-tauri/src/commands/data_cmds.rs
 
 //! 处理与数据获取相关的 Tauri 命令。
+//!
+//! 现场经常没有网络信号，因此任务开始前应把离线工作所需的数据保存到本地任务数据缓存：
+//! - `cache_task_templates_cmd`: 保存任务所用的调试模板 (预检查、单体测试、联锁测试)。
+//! - `cache_point_table_cmd`: 保存任务所属项目的点表。
+//! - `get_offline_task_bundle_cmd`: 读取一个任务在本地保存的全部数据 (任务状态、模板与点表)，无需网络。
+//!
+//! 任务状态不需要前端保存：每次从云端同步到的状态都会自动写入本地缓存。
 
-// 暂时为空，后续会根据 P5.2.1, P8.1.3 等步骤添加具体命令实现。 
\ No newline at end of file
+use std::sync::Arc;
+
+use log::{error, info};
+use tauri::State;
+
+use common_models::project_details::ProjectPointTable;
+use common_models::templates::TaskTemplateSet;
+
+use crate::db::OfflineTaskBundle;
+use crate::ws_client::service::WebSocketClientService;
+
+/// 保存任务所用的调试模板，替换该任务之前保存的模板，返回保存的模板数量。
+///
+/// # 参数
+/// * `task_id`: 模板所属的任务ID。
+/// * `templates`: 任务所用的全部模板，每个模板的 `template_id` 不能为空且不能重复。
+#[tauri::command]
+pub async fn cache_task_templates_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: String,
+    templates: TaskTemplateSet,
+) -> Result<usize, String> {
+    info!("[现场端数据命令] 保存任务 '{}' 的模板: {} 个", task_id, templates.metadata().len());
+    require_non_empty("任务ID", &task_id)?;
+    if templates.metadata().iter().any(|m| m.template_id.trim().is_empty()) {
+        return Err("模板ID不能为空。".to_string());
+    }
+    ws_client_service.task_cache().save_templates(&task_id, &templates).map_err(|e| {
+        error!("[现场端数据命令] 保存任务 '{}' 的模板失败: {}", task_id, e);
+        e
+    })
+}
+
+/// 保存任务所属项目的点表，覆盖该任务之前保存的点表。
+///
+/// # 参数
+/// * `task_id`: 点表所属的任务ID。
+/// * `point_table`: 项目点表，`version` 不能为空。
+#[tauri::command]
+pub async fn cache_point_table_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: String,
+    point_table: ProjectPointTable,
+) -> Result<(), String> {
+    info!(
+        "[现场端数据命令] 保存任务 '{}' 的点表: 项目={}, 版本={}, 点位 {} 个",
+        task_id, point_table.project_id, point_table.version, point_table.points.len()
+    );
+    require_non_empty("任务ID", &task_id)?;
+    require_non_empty("点表版本", &point_table.version)?;
+    ws_client_service.task_cache().save_point_table(&task_id, &point_table).map_err(|e| {
+        error!("[现场端数据命令] 保存任务 '{}' 的点表失败: {}", task_id, e);
+        e
+    })
+}
+
+/// 读取一个任务在本地保存的全部数据，无需网络。
+///
+/// # 参数
+/// * `task_id`: 任务ID。未指定时使用当前任务 (最近一次保存了任务状态、且此后未离开任务组的任务)。
+///
+/// # 返回
+/// * `Ok(Some(OfflineTaskBundle))`: 本地保存的数据，其中各部分可能缺失 (例如尚未保存点表)。
+/// * `Ok(None)`: 本地没有该任务的任何数据。
+#[tauri::command]
+pub async fn get_offline_task_bundle_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: Option<String>,
+) -> Result<Option<OfflineTaskBundle>, String> {
+    let task_cache = ws_client_service.task_cache();
+    let task_id = match task_id.filter(|id| !id.trim().is_empty()) {
+        Some(task_id) => task_id,
+        None => match task_cache.latest_task_state()? {
+            Some(cached) => cached.state.task_id,
+            None => return Ok(None),
+        },
+    };
+    info!("[现场端数据命令] 读取任务 '{}' 的本地数据", task_id);
+    let bundle = task_cache.load_bundle(&task_id).map_err(|e| {
+        error!("[现场端数据命令] 读取任务 '{}' 的本地数据失败: {}", task_id, e);
+        e
+    })?;
+    Ok((!bundle.is_empty()).then_some(bundle))
+}
+
+/// 校验必填字段不为空白。
+fn require_non_empty(field: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{}不能为空。", field));
+    }
+    Ok(())
+}
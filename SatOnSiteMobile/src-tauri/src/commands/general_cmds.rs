@@ -18,7 +18,7 @@ use rust_websocket_utils::message::WsMessage; // WebSocket 消息的通用结构
 use common_models; // 项目共享的数据模型和常量
 // use chrono::Utc; // 移除了未使用的导入
 // use uuid::Uuid; // 移除了未使用的导入
-use common_models::ws_payloads::{RegisterPayload, REGISTER_MESSAGE_TYPE, EchoPayload, ECHO_MESSAGE_TYPE}; // WebSocket 消息负载定义和类型常量
+use common_models::ws_payloads::{RegisterPayload, SiteAssignment, REGISTER_MESSAGE_TYPE, EchoPayload, ECHO_MESSAGE_TYPE}; // WebSocket 消息负载定义和类型常量
 use common_models::enums::ClientRole; // 客户端角色枚举
 use tauri::Manager; // 引入 Manager trait 以便在 AppHandle 上使用 state() 等方法
 
@@ -237,10 +237,14 @@ pub async fn send_ws_echo(
 /// * `group_id`: 字符串，客户端希望加入或创建的调试组的唯一标识符。
 /// * `task_id`: 字符串，与此调试会话关联的具体任务的唯一标识符。
 /// * `client_display_name_param`: 可选字符串，客户端显示名称。
+/// * `device_ids`: 可选，本工程师负责的设备ID列表。同一组内可有多名现场工程师，各自负责的设备不能重叠；
+///   云端只会把这些设备的 `StartSingleTestStep` 指令转发给本客户端。省略时表示不限设备 (接收无人认领设备的指令)。
+/// * `pre_check_categories`: 可选，本工程师负责的预检查类别列表，同样不能与组内其他工程师重叠。
 ///
 /// # 返回
 /// * `Result<(), String>`: 
 ///     - `Ok(())`: 如果注册消息已成功排队等待发送。实际的注册成功与否将通过后续的 "RegisterResponse" (`WsRegistrationStatusEvent`) 事件进行通知。
+///       若声明的设备已由组内其他工程师负责，注册响应会以 `ROLE_SLOT_TAKEN` 失败。
 ///     - `Err(String)`: 如果在构建或发送注册消息过程中发生错误，则返回包含中文错误描述的字符串。
 #[tauri::command(rename_all = "snake_case")]
 pub async fn register_client_with_task(
@@ -248,18 +252,29 @@ pub async fn register_client_with_task(
     group_id: String,
     task_id: String,
     client_display_name_param: Option<String>, // 从前端接收 display_name，重命名以区分
+    device_ids: Option<Vec<String>>,
+    pre_check_categories: Option<Vec<String>>,
 ) -> Result<(), String> {
     info!(
-        "[现场端通用命令] 'register_client_with_task' 被调用, 组ID: {}, 任务ID: {}, 显示名称: {:?}",
-        group_id, task_id, client_display_name_param // 使用重命名后的参数
+        "[现场端通用命令] 'register_client_with_task' 被调用, 组ID: {}, 任务ID: {}, 显示名称: {:?}, 负责设备: {:?}, 负责预检查类别: {:?}",
+        group_id, task_id, client_display_name_param, device_ids, pre_check_categories // 使用重命名后的参数
     );
 
+    // 仅在声明了负责范围时携带设备分配
+    let site_assignment = (device_ids.is_some() || pre_check_categories.is_some()).then(|| SiteAssignment {
+        device_ids: device_ids.unwrap_or_default(),
+        pre_check_categories: pre_check_categories.unwrap_or_default(),
+    });
+
     // 1. 构建 RegisterPayload
     let register_payload = RegisterPayload {
         group_id: group_id.clone(),
         role: ClientRole::OnSiteMobile, // 现场端固定角色
         task_id: task_id.clone(),
         client_display_name: client_display_name_param.or_else(|| Some("OnSiteMobileClient".to_string())), // client_display_name_param 在此被消耗
+        resume_token: None,
+        site_assignment,
+        join_code: None,
         client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()), // 使用 crate 版本
     };
 
@@ -301,6 +316,69 @@ pub async fn register_client_with_task(
     }
 }
 
+/// Tauri 命令：凭控制中心开组时取得的配对码注册到任务组，无需输入组ID与任务ID。
+///
+/// 云端根据配对码确定要加入的组与任务，配对码随即失效 (即使注册因其他原因失败，也需向控制中心重新获取)。
+///
+/// # 参数
+/// * `app_handle`: Tauri 应用句柄，用于获取共享的 `WebSocketClientService` 状态。
+/// * `join_code`: 控制中心提供的配对码 (不区分大小写)。
+/// * `client_display_name_param`、`device_ids`、`pre_check_categories`: 与 `register_client_with_task` 相同。
+///
+/// # 返回
+/// * `Ok(())`: 注册消息已成功排队等待发送。结果通过 `WsRegistrationStatusEvent` 事件通知，
+///   配对码无效或已过期时以 `JOIN_CODE_INVALID` 失败。
+/// * `Err(String)`: 构建或发送注册消息失败。
+#[tauri::command(rename_all = "snake_case")]
+pub async fn register_with_join_code(
+    app_handle: tauri::AppHandle,
+    join_code: String,
+    client_display_name_param: Option<String>,
+    device_ids: Option<Vec<String>>,
+    pre_check_categories: Option<Vec<String>>,
+) -> Result<(), String> {
+    info!(
+        "[现场端通用命令] 'register_with_join_code' 被调用, 显示名称: {:?}, 负责设备: {:?}, 负责预检查类别: {:?}",
+        client_display_name_param, device_ids, pre_check_categories
+    );
+
+    let site_assignment = (device_ids.is_some() || pre_check_categories.is_some()).then(|| SiteAssignment {
+        device_ids: device_ids.unwrap_or_default(),
+        pre_check_categories: pre_check_categories.unwrap_or_default(),
+    });
+    // 组ID与任务ID由云端根据配对码确定
+    let register_payload = RegisterPayload {
+        group_id: String::new(),
+        role: ClientRole::OnSiteMobile,
+        task_id: String::new(),
+        client_display_name: client_display_name_param.or_else(|| Some("OnSiteMobileClient".to_string())),
+        resume_token: None,
+        site_assignment,
+        join_code: Some(join_code),
+        client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+    };
+    let ws_message = WsMessage::new(REGISTER_MESSAGE_TYPE.to_string(), &register_payload).map_err(|e| {
+        let err_msg = format!("创建注册请求类型的 WsMessage 失败: {}", e);
+        error!("[现场端通用命令] {}", err_msg);
+        err_msg
+    })?;
+
+    let ws_service = match app_handle.try_state::<Arc<WebSocketClientService>>() {
+        Some(state_val) => state_val.inner().clone(),
+        None => {
+            error!("[现场端通用命令] 无法从 Tauri AppHandle 获取 WebSocketClientService 状态。服务可能未正确初始化或注册。");
+            return Err("内部服务器错误: WebSocket 服务状态不可用".to_string());
+        }
+    };
+    ws_service.send_ws_message(ws_message).await.map_err(|e| {
+        let err_msg = format!("通过 WsService 发送凭配对码的 'Register' 消息失败: {}", e);
+        error!("[现场端通用命令] {}", err_msg);
+        err_msg
+    })?;
+    info!("[现场端通用命令] 已通过 WsService 发送凭配对码的 'Register' 消息。");
+    Ok(())
+}
+
 // 提示：后续根据项目开发步骤 (例如 P4.2.1 - 客户端数据同步功能) 的需要，
 // 可能会在此文件或新创建的、按功能划分的 `*_cmds.rs` 文件中添加更多业务相关的 Tauri 命令。
 // 例如: `send_pre_check_item_update_command`, `send_single_test_step_feedback_command` 等。
@@ -4,8 +4,8 @@ use log::{info, error};
 // use uuid::Uuid; // WsMessage::new 通常会处理 message_id
 // use chrono::Utc; // WsMessage::new 通常会处理 timestamp
 
-use common_models::ws_payloads::{UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE};
-use rust_websocket_utils::message::WsMessage; // 假设路径和定义
+use common_models::ws_payloads::{AckStatus, UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE};
+use crate::ws_client::outbox::SubmitOutcome;
 use crate::ws_client::service::WebSocketClientService; // 更正模块路径
 use serde_json;
 
@@ -27,12 +27,6 @@ pub async fn send_debug_note_from_site_cmd(
         group_id, new_note, custom_shared_data_json_string
     );
 
-    if !ws_client_service.is_connected().await {
-        let err_msg = "WebSocket client is not connected. Cannot send debug note.";
-        error!("[SiteCMD::send_debug_note] {}", err_msg);
-        return Err(err_msg.to_string());
-    }
-
     let custom_data_value: Option<serde_json::Value> = match custom_shared_data_json_string {
         Some(json_str) if !json_str.trim().is_empty() => {
             match serde_json::from_str(&json_str) {
@@ -51,31 +45,33 @@ pub async fn send_debug_note_from_site_cmd(
         group_id: group_id.clone(),
         new_note,
         custom_shared_data: custom_data_value,
+        // 基于本地缓存的任务状态版本提交，若期间另一端已修改则由云端以版本冲突拒绝
+        expected_version: ws_client_service.get_cached_task_state().await.map(|state| state.version),
     };
 
-    match WsMessage::new(
-        UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string(),
-        &payload, // WsMessage::new 应该能处理序列化
-    ) {
-        Ok(ws_message) => {
-            match ws_client_service.send_ws_message(ws_message).await {
-                Ok(_) => {
-                    let success_msg = format!("Debug note message sent successfully to server for group '{}'.", group_id);
-                    info!("[SiteCMD::send_debug_note] {}", success_msg);
-                    Ok(GenericResponse {
-                        success: true,
-                        message: success_msg,
-                    })
-                }
-                Err(e) => {
-                    let err_msg = format!("Failed to send debug note message to server for group '{}': {:?}", group_id, e);
-                    error!("[SiteCMD::send_debug_note] {}", err_msg);
-                    Err(err_msg)
-                }
-            }
+    // 经由离线待发队列提交：离线时保存在本地，重连后按顺序重放
+    match ws_client_service.outbox().submit(UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, &payload).await {
+        Ok(SubmitOutcome::Acknowledged { ack }) => {
+            let success_msg = format!("Debug note for group '{}' acknowledged by server: {:?}.", group_id, ack.status);
+            info!("[SiteCMD::send_debug_note] {}", success_msg);
+            Ok(GenericResponse {
+                success: ack.status != AckStatus::Rejected,
+                message: ack.message.unwrap_or(success_msg),
+            })
+        }
+        Ok(SubmitOutcome::Queued { message_id, pending_count }) => {
+            let queued_msg = format!(
+                "Debug note for group '{}' queued offline (message ID: {}, {} pending); it will be sent after reconnecting.",
+                group_id, message_id, pending_count
+            );
+            info!("[SiteCMD::send_debug_note] {}", queued_msg);
+            Ok(GenericResponse {
+                success: true,
+                message: queued_msg,
+            })
         }
         Err(e) => {
-            let err_msg = format!("Failed to create WsMessage for debug note for group '{}': {:?}", group_id, e);
+            let err_msg = format!("Failed to submit debug note for group '{}': {}", group_id, e);
             error!("[SiteCMD::send_debug_note] {}", err_msg);
             Err(err_msg)
         }
@@ -2,55 +2,159 @@
 
 //! `SatOnSiteMobile` (现场端移动应用) 的测试流程相关 Tauri 命令模块。
 //!
-//! 本模块旨在包含所有与具体测试执行、测试步骤反馈、预检查项确认等相关的 Tauri 命令。
-//! 这些命令通常会将前端用户的操作（例如，点击"开始测试步骤"、"确认预检项完成并填写备注"、
-//! "上报单体测试步骤结果"）封装成相应的业务 Payload，并通过 WebSocket 服务发送到云端，
-//! 或者直接与本地连接的被测设备进行交互（如果适用）。
+//! 本模块包含现场端角色可以执行的业务操作：
+//! - `update_pre_check_item_cmd`: 上报预检查项的现场检查结果 (`UpdatePreCheckItem`)。
+//! - `feedback_single_test_step_cmd`: 上报单体测试步骤的执行结果 (`FeedbackSingleTestStep`)。
 //!
-//! **当前状态**: 此模块为占位符，等待根据项目开发计划（例如 P4.2.1, P8.2.3, P9.2.3, P10.3.1）的具体需求填充命令。
-//!
-//! ## 未来可能的命令示例:
-//! ```rust
-//! // use common_models::ws_payloads::{UpdatePreCheckItemPayload, SingleTestStepFeedbackPayload};
-//! // use common_models::task_state::{PreCheckStatus, TestStepStatus};
-//! // use crate::ws_client::service::WebSocketClientService; // 假设服务已在 Tauri 状态中管理
-//! // use std::sync::Arc;
-//! // use tauri::State;
-//!
-//! // #[tauri::command]
-//! // async fn send_pre_check_item_update(
-//! //     app_handle: tauri::AppHandle, 
-//! //     item_id: String, 
-//! //     status: String, // 前端可能传来字符串，需要解析为 PreCheckStatus 枚举
-//! //     notes: Option<String>
-//! // ) -> Result<(), String> {
-//! //     log::info!("[现场端测试命令] 更新预检项: ID={}, 状态={}, 备注={:?}", item_id, status, notes);
-//! //     // 1. 解析 status 字符串为 PreCheckStatus 枚举 (此处省略具体实现)
-//! //     let parsed_status = PreCheckStatus::Pending; // 示例
-//! //     // 2. 构建 UpdatePreCheckItemPayload
-//! //     let payload = UpdatePreCheckItemPayload { /* ... */ task_id: "current_task_id".to_string(), item_id, status: parsed_status, notes };
-//! //     // 3. 获取 WebSocket 服务并发送消息 (此处省略错误处理和 WsMessage 构建细节)
-//! //     // let ws_service = app_handle.state::<Arc<WebSocketClientService>>().inner().clone();
-//! //     // ws_service.send_ws_message(...).await.map_err(|e| e.to_string())?;
-//! //     Ok(())
-//! // }
-//! 
-//! // #[tauri::command]
-//! // async fn send_single_test_step_feedback(
-//! //     app_handle: tauri::AppHandle,
-//! //     device_id: String,
-//! //     step_id: String,
-//! //     status: String, // 前端可能传来字符串，需要解析为 TestStepStatus 枚举
-//! //     result_data: Option<serde_json::Value>,
-//! //     feedback_message: Option<String>
-//! // ) -> Result<(), String> {
-//! //     log::info!("[现场端测试命令] 发送单体测试步骤反馈: 设备={}, 步骤={}, 状态={}", device_id, step_id, status);
-//! //     // 实现类似 send_pre_check_item_update 的逻辑：解析、构建Payload、发送消息
-//! //     Ok(())
-//! // }
-//! ```
-
-// 初始占位：此模块当前为空。
-// 后续将根据项目开发计划（例如 P4.2.1, P8.2.3, P9.2.3, P10.3.1 等）中定义的需求，
-// 在此处添加与测试流程控制和数据反馈相关的具体 Tauri 命令函数。
-// 请确保所有新增命令都遵循标准的错误处理、日志记录和数据模型规范。 
\ No newline at end of file
+//! 每个命令先在本地校验输入 (必填字段、状态取值、是否为当前任务)，再以本地缓存的任务状态版本作为
+//! `expected_version` 经由离线待发队列提交：已连接时返回云端的确认 (`SubmitOutcome::Acknowledged`)，
+//! 离线时操作保存在本地队列中 (`SubmitOutcome::Queued`)，重连后按顺序重放。被云端拒绝的操作同样以 `Ok` 返回，
+//! 由前端根据 `ack.status` 与 `ack.error_code` 提示用户；版本冲突另外通过 `ws_version_conflict_event` 通知。
+
+use std::sync::Arc;
+
+use log::{error, info};
+use tauri::State;
+
+use common_models::task_models::{FeedbackSingleTestStepPayload, UpdatePreCheckItemPayload};
+use common_models::ws_payloads::{FEEDBACK_SINGLE_TEST_STEP_TYPE, UPDATE_PRE_CHECK_ITEM_TYPE};
+use common_models::TaskDebugState;
+
+use crate::ws_client::outbox::SubmitOutcome;
+use crate::ws_client::service::WebSocketClientService;
+
+/// 现场端可以上报的预检查项状态。
+const SITE_PRE_CHECK_STATUSES: &[&str] = &["Pending", "Site_Completed", "Site_Failed"];
+
+/// 现场端可以上报的单体测试步骤执行状态。
+const STEP_EXECUTION_STATUSES: &[&str] = &["Running", "Completed", "Failed"];
+
+/// 上报预检查项的现场检查结果，返回云端的确认或已离线排队。
+///
+/// # 参数
+/// * `task_id`: 当前任务ID，必须与本地缓存的任务状态一致。
+/// * `item_id`: 预检查项ID。
+/// * `status`: 检查结果，取值见 `SITE_PRE_CHECK_STATUSES`。
+/// * `notes`: 可选的备注，空白备注视为未填写。
+#[tauri::command]
+pub async fn update_pre_check_item_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: String,
+    item_id: String,
+    status: String,
+    notes: Option<String>,
+) -> Result<SubmitOutcome, String> {
+    info!("[现场端测试命令] 更新预检项: 任务={}, ID={}, 状态={}, 备注={:?}", task_id, item_id, status, notes);
+
+    let state = current_task_state(&ws_client_service, &task_id).await?;
+    require_non_empty("预检查项ID", &item_id)?;
+    require_one_of("预检查项状态", &status, SITE_PRE_CHECK_STATUSES)?;
+
+    let payload = UpdatePreCheckItemPayload {
+        task_id,
+        item_id,
+        status,
+        notes: non_blank(notes),
+        expected_version: Some(state.version),
+    };
+    ws_client_service.outbox().submit(UPDATE_PRE_CHECK_ITEM_TYPE, &payload).await.map_err(|e| {
+        error!("[现场端测试命令] 更新预检项失败: {}", e);
+        e
+    })
+}
+
+/// 上报单体测试步骤的执行结果，返回云端的确认或已离线排队。
+///
+/// 只能反馈控制中心已发起的步骤 (本地缓存的任务状态中该步骤已有指令)。
+///
+/// # 参数
+/// * `task_id`: 当前任务ID，必须与本地缓存的任务状态一致。
+/// * `device_id` / `step_id`: 被测设备与测试步骤。
+/// * `execution_status`: 执行状态，取值见 `STEP_EXECUTION_STATUSES`。
+/// * `result_data`: 可选的测试结果数据 (任意 JSON)。
+/// * `feedback_notes`: 可选的备注，空白备注视为未填写。
+#[tauri::command]
+pub async fn feedback_single_test_step_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+    task_id: String,
+    device_id: String,
+    step_id: String,
+    execution_status: String,
+    result_data: Option<serde_json::Value>,
+    feedback_notes: Option<String>,
+) -> Result<SubmitOutcome, String> {
+    info!(
+        "[现场端测试命令] 发送单体测试步骤反馈: 任务={}, 设备={}, 步骤={}, 状态={}",
+        task_id, device_id, step_id, execution_status
+    );
+
+    let state = current_task_state(&ws_client_service, &task_id).await?;
+    require_non_empty("设备ID", &device_id)?;
+    require_non_empty("测试步骤ID", &step_id)?;
+    require_one_of("执行状态", &execution_status, STEP_EXECUTION_STATUSES)?;
+    let started = state
+        .single_test_steps
+        .get(&step_id)
+        .is_some_and(|step| step.command_from_control.is_some());
+    if !started {
+        let err_msg = format!("测试步骤 '{}' 尚未由控制中心发起，无法反馈执行结果。", step_id);
+        error!("[现场端测试命令] {}", err_msg);
+        return Err(err_msg);
+    }
+
+    let payload = FeedbackSingleTestStepPayload {
+        task_id,
+        device_id,
+        step_id,
+        execution_status,
+        result_data,
+        feedback_notes: non_blank(feedback_notes),
+        expected_version: Some(state.version),
+    };
+    ws_client_service.outbox().submit(FEEDBACK_SINGLE_TEST_STEP_TYPE, &payload).await.map_err(|e| {
+        error!("[现场端测试命令] 发送单体测试步骤反馈失败: {}", e);
+        e
+    })
+}
+
+/// 取得本地缓存的任务状态，并确认它就是 `task_id` 所指的任务。
+///
+/// 业务操作以缓存状态的版本作为 `expected_version` 提交，因此尚未同步到任务状态时不允许操作；
+/// 离线时只要本地仍有该任务的状态即可继续操作。
+async fn current_task_state(ws_client_service: &WebSocketClientService, task_id: &str) -> Result<TaskDebugState, String> {
+    require_non_empty("任务ID", task_id)?;
+    match ws_client_service.get_cached_task_state().await {
+        Some(state) if state.task_id == task_id => Ok(state),
+        Some(state) => {
+            let err_msg = format!("任务 '{}' 不是当前任务 (当前任务为 '{}')。", task_id, state.task_id);
+            error!("[现场端测试命令] {}", err_msg);
+            Err(err_msg)
+        }
+        None => {
+            let err_msg = "尚未从云端同步到任务状态，请先注册到任务组或重新同步。".to_string();
+            error!("[现场端测试命令] {}", err_msg);
+            Err(err_msg)
+        }
+    }
+}
+
+/// 校验必填字段不为空白。
+fn require_non_empty(field: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{}不能为空。", field));
+    }
+    Ok(())
+}
+
+/// 校验字段取值属于允许的集合。
+fn require_one_of(field: &str, value: &str, allowed: &[&str]) -> Result<(), String> {
+    if !allowed.contains(&value) {
+        return Err(format!("无效的{} '{}'，允许的取值: {}。", field, value, allowed.join(", ")));
+    }
+    Ok(())
+}
+
+/// 把空白的可选文本视为未填写。
+fn non_blank(text: Option<String>) -> Option<String> {
+    text.filter(|t| !t.trim().is_empty())
+}
@@ -3,7 +3,13 @@
 use tauri::State;
 use std::sync::Arc;
 use crate::ws_client::service::WebSocketClientService; // 假设 ws_client_service 在此路径
-use common_models::ws_payloads::GeneralResponse; // 假设一个通用响应结构
+use common_models::ws_payloads::{GeneralResponse, GroupLeftPayload, RegisterPayload, SiteAssignment}; // 假设一个通用响应结构
+use common_models::enums::ClientRole;
+use common_models::RegisterResponsePayload;
+use common_models::TaskDebugState;
+use crate::ws_client::version_conflict::ConflictResolution;
+use crate::ws_client::outbox::ReplayReport;
+use crate::db::OutboxEntry;
 
 #[tauri::command]
 pub async fn connect_to_ws_server_cmd(
@@ -56,4 +62,102 @@ pub async fn send_register_message_cmd(
     // let ws_message = common_models::message::WsMessage::new(...);
     // match ws_client_service.send_ws_message(ws_message).await { ... }
     Ok(GeneralResponse { success: true, message: "Registration message sent (mock).".to_string() })
-} 
\ No newline at end of file
+} 
+
+/// 请求云端重新推送当前完整的任务调试状态，返回同步后的状态 (本地缓存与前端事件同时更新)。
+#[tauri::command]
+pub async fn resync_task_state_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<TaskDebugState, String> {
+    log::info!("[WsCMD::resync_task_state] Requesting full task state from cloud.");
+    ws_client_service.resync_task_state().await
+}
+
+/// 不断开连接地离开当前任务组，返回已离开的组与角色 (同时发送 `ws_group_left_event`)。
+#[tauri::command]
+pub async fn leave_group_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<GroupLeftPayload, String> {
+    log::info!("[WsCMD::leave_group] Leaving current task group.");
+    ws_client_service.leave_group().await
+}
+
+/// 离开当前任务组并注册到另一个任务组 (完成一个任务后转到下一个)，无需断开 WebSocket 连接。
+/// 参数与 `register_client_with_task` 相同；返回云端的注册响应 (同时发送 `ws_registration_status_event`)。
+#[tauri::command(rename_all = "snake_case")]
+pub async fn switch_task_cmd(
+    group_id: String,
+    task_id: String,
+    client_display_name_param: Option<String>,
+    device_ids: Option<Vec<String>>,
+    pre_check_categories: Option<Vec<String>>,
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<RegisterResponsePayload, String> {
+    log::info!(
+        "[WsCMD::switch_task] Switching to group '{}', task '{}', devices: {:?}, pre-check categories: {:?}",
+        group_id, task_id, device_ids, pre_check_categories
+    );
+    let site_assignment = (device_ids.is_some() || pre_check_categories.is_some()).then(|| SiteAssignment {
+        device_ids: device_ids.unwrap_or_default(),
+        pre_check_categories: pre_check_categories.unwrap_or_default(),
+    });
+    let register = RegisterPayload {
+        group_id,
+        role: ClientRole::OnSiteMobile,
+        task_id,
+        client_display_name: client_display_name_param.or_else(|| Some("OnSiteMobileClient".to_string())),
+        resume_token: None,
+        site_assignment,
+        join_code: None,
+        client_software_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+    };
+    ws_client_service.switch_task(register).await
+}
+
+/// 处理一次业务消息的版本冲突 (见 `ws_version_conflict_event`)：变基重新提交、提交合并后的负载或放弃本端修改。
+/// 重新提交时返回新业务消息的 `message_id`，放弃时返回 `None`。
+#[tauri::command]
+pub async fn resolve_version_conflict_cmd(
+    request_message_id: String,
+    resolution: ConflictResolution,
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<Option<String>, String> {
+    log::info!("[WsCMD::resolve_version_conflict] request_message_id: {}, resolution: {:?}", request_message_id, resolution);
+    let resubmitted = ws_client_service.resolve_version_conflict(&request_message_id, resolution).await?;
+    // 冲突来自离线重放时，队列中保留的冲突记录已处理完毕
+    if let Err(e) = ws_client_service.outbox().dismiss(&request_message_id) {
+        log::warn!("[WsCMD::resolve_version_conflict] Failed to dismiss outbox entry '{}': {}", request_message_id, e);
+    }
+    Ok(resubmitted)
+}
+
+/// 按写入顺序列出离线待发队列中的全部记录：尚未送达的操作，以及被云端拒绝或发生版本冲突、等待用户处理的操作。
+#[tauri::command]
+pub async fn list_outbox_entries_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<Vec<OutboxEntry>, String> {
+    log::info!("[WsCMD::list_outbox_entries] Listing offline outbox entries.");
+    ws_client_service.outbox().entries()
+}
+
+/// 立即按顺序发送离线待发队列中尚未送达的操作 (重新注册成功后也会自动进行)，返回本次的结果汇总。
+#[tauri::command]
+pub async fn replay_outbox_cmd(
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<ReplayReport, String> {
+    log::info!("[WsCMD::replay_outbox] Replaying offline outbox.");
+    if ws_client_service.client().client_id().await.is_none() {
+        return Err("Not registered with a task group; the outbox will be replayed automatically after registering.".to_string());
+    }
+    ws_client_service.outbox().flush().await
+}
+
+/// 清除一条被拒绝或发生版本冲突的待发记录 (用户已查看)。尚未送达的记录不能清除，此时返回 `false`。
+#[tauri::command]
+pub async fn dismiss_outbox_entry_cmd(
+    message_id: String,
+    ws_client_service: State<'_, Arc<WebSocketClientService>>,
+) -> Result<bool, String> {
+    log::info!("[WsCMD::dismiss_outbox_entry] message_id: {}", message_id);
+    ws_client_service.outbox().dismiss(&message_id)
+}
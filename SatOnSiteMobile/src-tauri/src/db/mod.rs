@@ -0,0 +1,14 @@
+// SatOnSiteMobile/src-tauri/src/db/mod.rs
+
+//! `SatOnSiteMobile` (现场端移动应用) 的本地数据库模块。
+//!
+//! 现场经常没有网络信号 (例如地下室)，因此需要在设备本地持久化的数据都放在这里，基于 SQLite。
+
+/// 离线待发队列 (outbox) 数据仓库：无网络时记录的业务操作，重连后按顺序重放。
+pub mod outbox_repo;
+
+/// 任务数据本地缓存数据仓库：最近的任务状态、任务模板与项目点表，供离线时继续工作。
+pub mod task_cache_repo;
+
+pub use outbox_repo::{OutboxEntry, OutboxRepository, OutboxStatus};
+pub use task_cache_repo::{CachedTaskState, OfflineTaskBundle, TaskCacheRepository};
@@ -0,0 +1,317 @@
+// SatOnSiteMobile/src-tauri/src/db/outbox_repo.rs
+
+//! 离线待发队列 (outbox) 数据仓库。
+//!
+//! 现场工程师的每个业务操作 (预检查项、测试步骤反馈、调试备注等) 在发送前先写入 SQLite 数据库的
+//! `outbox` 表，收到云端的确认后才删除。因此无网络时记录的操作、以及发出后未等到确认就断线的操作
+//! 都不会丢失，重连后按写入顺序 (`seq`) 重放。
+//!
+//! 每条记录保存完整的 `WsMessage`，重放时沿用原 `message_id` 作为幂等键，云端据此识别重复投递。
+//! 被云端拒绝或发生版本冲突的记录不会自动删除，而是保留其确认 (`AckPayload`) 供用户查看和处理。
+//!
+//! `rusqlite::Connection` 不是 `Sync` 的，因此仓库内部使用 `std::sync::Mutex` 保护连接。
+//! 所有方法都是同步的，单次操作只涉及少量行，可以直接在异步上下文中调用。
+
+use chrono::{DateTime, Utc};
+use common_models::ws_payloads::AckPayload;
+use log::info;
+use rusqlite::{params, Connection};
+use rust_websocket_utils::message::WsMessage;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 待发队列中一条记录的状态。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// 尚未收到云端确认，重连后会被重放。
+    Pending,
+    /// 云端拒绝了该操作 (例如权限不足或负载无效)，等待用户查看后清除。
+    Rejected,
+    /// 云端因版本冲突拒绝了该操作，等待用户变基、合并或放弃后清除。
+    Conflicted,
+}
+
+impl OutboxStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Rejected => "rejected",
+            OutboxStatus::Conflicted => "conflicted",
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "pending" => Ok(OutboxStatus::Pending),
+            "rejected" => Ok(OutboxStatus::Rejected),
+            "conflicted" => Ok(OutboxStatus::Conflicted),
+            other => Err(format!("未知的待发记录状态 '{}'", other)),
+        }
+    }
+}
+
+/// 待发队列中的一条记录。
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEntry {
+    /// 写入顺序，重放按此升序进行。
+    pub seq: i64,
+    /// 待发送的业务消息，其 `message_id` 即幂等键。
+    pub message: WsMessage,
+    /// 记录状态。
+    pub status: OutboxStatus,
+    /// 云端的确认。仅在 `Rejected` / `Conflicted` 时存在。
+    pub ack: Option<AckPayload>,
+    /// 已尝试发送的次数。
+    pub attempts: u32,
+    /// 最近一次发送失败 (未送达或未等到确认) 的原因。
+    pub last_error: Option<String>,
+    /// 写入队列的时间。
+    pub queued_at: DateTime<Utc>,
+}
+
+/// 离线待发队列数据仓库，封装对 `outbox` 表的所有访问。
+#[derive(Debug)]
+pub struct OutboxRepository {
+    /// 受互斥锁保护的 SQLite 连接。
+    conn: Mutex<Connection>,
+}
+
+impl OutboxRepository {
+    /// 打开 (或创建) 指定路径的 SQLite 数据库文件，并确保 `outbox` 表存在。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).map_err(|e| format!("打开数据库文件 {:?} 失败: {}", path, e))?;
+        info!("[待发队列] 已打开数据库文件 {:?}", path);
+        Self::with_connection(conn)
+    }
+
+    /// 创建一个基于内存数据库的仓库 (应用重启后内容丢失)，用于数据库文件无法打开时的降级和测试。
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("创建内存数据库失败: {}", e))?;
+        Self::with_connection(conn)
+    }
+
+    /// 使用已有连接初始化仓库并执行建表语句。
+    fn with_connection(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                seq          INTEGER PRIMARY KEY AUTOINCREMENT,
+                message_id   TEXT NOT NULL UNIQUE,
+                message      TEXT NOT NULL,
+                status       TEXT NOT NULL DEFAULT 'pending',
+                ack          TEXT,
+                attempts     INTEGER NOT NULL DEFAULT 0,
+                last_error   TEXT,
+                queued_at    TEXT NOT NULL
+            );",
+        )
+        .map_err(|e| format!("初始化 outbox 表失败: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// 把一条业务消息写入队列末尾。
+    pub fn enqueue(&self, message: &WsMessage) -> Result<OutboxEntry, String> {
+        let queued_at = Utc::now();
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO outbox (message_id, message, status, queued_at) VALUES (?1, ?2, 'pending', ?3)",
+            params![message.message_id, to_json(message)?, queued_at.to_rfc3339()],
+        )
+        .map_err(|e| format!("写入待发消息 '{}' 失败: {}", message.message_id, e))?;
+        Ok(OutboxEntry {
+            seq: conn.last_insert_rowid(),
+            message: message.clone(),
+            status: OutboxStatus::Pending,
+            ack: None,
+            attempts: 0,
+            last_error: None,
+            queued_at,
+        })
+    }
+
+    /// 按写入顺序返回所有尚未收到确认的记录。
+    pub fn pending(&self) -> Result<Vec<OutboxEntry>, String> {
+        self.query("WHERE status = 'pending'")
+    }
+
+    /// 按写入顺序返回全部记录 (含被拒绝和冲突的记录)。
+    pub fn entries(&self) -> Result<Vec<OutboxEntry>, String> {
+        self.query("")
+    }
+
+    /// 尚未收到确认的记录数。
+    pub fn pending_count(&self) -> Result<usize, String> {
+        let conn = self.lock_conn()?;
+        conn.query_row("SELECT COUNT(*) FROM outbox WHERE status = 'pending'", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as usize)
+            .map_err(|e| format!("统计待发消息数量失败: {}", e))
+    }
+
+    /// 记录一次未成功的发送尝试 (消息仍留在队列中)。
+    pub fn record_failed_attempt(&self, message_id: &str, error: &str) -> Result<(), String> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE outbox SET attempts = attempts + 1, last_error = ?1 WHERE message_id = ?2",
+            params![error, message_id],
+        )
+        .map_err(|e| format!("更新待发消息 '{}' 失败: {}", message_id, e))?;
+        Ok(())
+    }
+
+    /// 云端拒绝了该消息：保留记录及其确认，等待用户处理。
+    pub fn mark_settled(&self, message_id: &str, status: OutboxStatus, ack: &AckPayload) -> Result<(), String> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "UPDATE outbox SET status = ?1, ack = ?2, attempts = attempts + 1, last_error = NULL WHERE message_id = ?3",
+            params![status.as_str(), to_json(ack)?, message_id],
+        )
+        .map_err(|e| format!("更新待发消息 '{}' 失败: {}", message_id, e))?;
+        Ok(())
+    }
+
+    /// 云端已接受该消息：从队列中删除，并在同一事务中保存因此改写的后续待发消息 (见 `OfflineOutbox::flush` 的版本链)。
+    ///
+    /// `rebased` 中的消息按 `message_id` 替换仍在等待确认的记录，其余记录不受影响。
+    pub fn acknowledge(&self, message_id: &str, rebased: &[WsMessage]) -> Result<(), String> {
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction().map_err(|e| format!("开始事务失败: {}", e))?;
+        tx.execute("DELETE FROM outbox WHERE message_id = ?1", params![message_id])
+            .map_err(|e| format!("删除待发消息 '{}' 失败: {}", message_id, e))?;
+        for message in rebased {
+            tx.execute(
+                "UPDATE outbox SET message = ?1 WHERE message_id = ?2 AND status = 'pending'",
+                params![to_json(message)?, message.message_id],
+            )
+            .map_err(|e| format!("更新待发消息 '{}' 失败: {}", message.message_id, e))?;
+        }
+        tx.commit().map_err(|e| format!("提交事务失败: {}", e))
+    }
+
+    /// 用户已处理被拒绝或冲突的记录后将其清除。尚未确认的记录不能清除，返回 `false`。
+    pub fn dismiss(&self, message_id: &str) -> Result<bool, String> {
+        let conn = self.lock_conn()?;
+        conn.execute("DELETE FROM outbox WHERE message_id = ?1 AND status != 'pending'", params![message_id])
+            .map(|deleted| deleted > 0)
+            .map_err(|e| format!("清除待发消息 '{}' 失败: {}", message_id, e))
+    }
+
+    /// 按 `filter` 条件查询记录，按写入顺序排列。
+    fn query(&self, filter: &str) -> Result<Vec<OutboxEntry>, String> {
+        let conn = self.lock_conn()?;
+        let sql = format!(
+            "SELECT seq, message, status, ack, attempts, last_error, queued_at FROM outbox {} ORDER BY seq",
+            filter
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| format!("查询待发消息失败: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })
+            .map_err(|e| format!("查询待发消息失败: {}", e))?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (seq, message, status, ack, attempts, last_error, queued_at) =
+                row.map_err(|e| format!("读取待发消息失败: {}", e))?;
+            entries.push(OutboxEntry {
+                seq,
+                message: from_json(&message)?,
+                status: OutboxStatus::parse(&status)?,
+                ack: ack.as_deref().map(from_json).transpose()?,
+                attempts: attempts as u32,
+                last_error,
+                queued_at: DateTime::parse_from_rfc3339(&queued_at)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .map_err(|e| format!("解析待发消息写入时间失败: {}", e))?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// 获取数据库连接的互斥锁。
+    fn lock_conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|_| "数据库连接锁已中毒".to_string())
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("序列化字段失败: {}", e))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, String> {
+    serde_json::from_str(text).map_err(|e| format!("反序列化字段失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::enums::ErrorCode;
+    use common_models::ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE;
+
+    fn business_message(item_id: &str) -> WsMessage {
+        WsMessage::new(
+            UPDATE_PRE_CHECK_ITEM_TYPE.to_string(),
+            &serde_json::json!({ "task_id": "t1", "item_id": item_id, "status": "Site_Completed", "expected_version": 2 }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_entries_are_kept_in_order_until_settled() {
+        let repo = OutboxRepository::open_in_memory().expect("创建内存仓库失败");
+        let first = repo.enqueue(&business_message("i1")).unwrap();
+        let second = repo.enqueue(&business_message("i2")).unwrap();
+        assert!(repo.enqueue(&first.message).is_err(), "同一 message_id 不应重复入队");
+
+        let pending = repo.pending().unwrap();
+        assert_eq!(
+            pending.iter().map(|e| e.message.message_id.clone()).collect::<Vec<_>>(),
+            vec![first.message.message_id.clone(), second.message.message_id.clone()]
+        );
+        assert_eq!(pending[0].message.payload, first.message.payload);
+
+        repo.record_failed_attempt(&first.message.message_id, "等待确认超时").unwrap();
+        let conflict = AckPayload::rejected(UPDATE_PRE_CHECK_ITEM_TYPE, ErrorCode::VersionConflict, "版本冲突".to_string());
+        repo.mark_settled(&first.message.message_id, OutboxStatus::Conflicted, &conflict).unwrap();
+        repo.acknowledge(&second.message.message_id, &[]).unwrap();
+        assert_eq!(repo.pending_count().unwrap(), 0);
+
+        let entries = repo.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].status, OutboxStatus::Conflicted);
+        assert_eq!(entries[0].ack.as_ref(), Some(&conflict));
+        assert_eq!(entries[0].attempts, 2);
+        assert_eq!(entries[0].last_error, None);
+
+        assert!(repo.dismiss(&first.message.message_id).unwrap());
+        assert!(repo.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_acknowledge_persists_rebased_messages() {
+        let repo = OutboxRepository::open_in_memory().expect("创建内存仓库失败");
+        let first = repo.enqueue(&business_message("i1")).unwrap();
+        let second = repo.enqueue(&business_message("i2")).unwrap();
+
+        let mut rebased = second.message.clone();
+        rebased.payload = rebased.payload.replace("\"expected_version\":2", "\"expected_version\":3");
+        repo.acknowledge(&first.message.message_id, &[rebased.clone()]).unwrap();
+
+        // 重新读取 (相当于断线或重启后的下一次发送) 时得到改写后的消息，message_id 不变
+        let pending = repo.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].message.message_id, second.message.message_id);
+        assert_eq!(pending[0].message.payload, rebased.payload);
+        assert!(pending[0].message.payload.contains("\"expected_version\":3"));
+    }
+}
@@ -0,0 +1,385 @@
+// SatOnSiteMobile/src-tauri/src/db/task_cache_repo.rs
+
+//! 任务数据本地缓存数据仓库。
+//!
+//! 业务客户端的任务状态缓存只在内存中，应用重启后即丢失。为了让现场工程师在没有网络时重新打开应用
+//! 也能继续按检查清单和测试步骤工作，这里把以下数据按任务持久化到 SQLite 数据库：
+//! - `task_state_cache`: 最近一次同步到的 `TaskDebugState`，版本戳为状态的 `version`。
+//!   离开任务组或槽位被接替后，状态仍保留供按任务ID读取，但不再被视为当前任务 (`active` 列)。
+//! - `template_cache`: 任务所用的调试模板，版本戳为每个模板的 `template_version`。
+//! - `point_table_cache`: 项目点表，版本戳为点表的 `version`。
+//!
+//! 每条记录另外保存写入时间 (`cached_at`)，供界面提示数据的新旧程度。
+//! 与 [`OutboxRepository`](super::OutboxRepository) 相同，仓库内部使用 `std::sync::Mutex` 保护连接，所有方法都是同步的。
+
+use chrono::{DateTime, Utc};
+use common_models::project_details::ProjectPointTable;
+use common_models::templates::TaskTemplateSet;
+use common_models::TaskDebugState;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// 模板在 `template_cache.template_type` 列中的取值。
+const PRE_CHECK: &str = "pre_check";
+const SINGLE_DEVICE_TEST: &str = "single_device_test";
+const INTERLOCK_TEST: &str = "interlock_test";
+
+/// 本地保存的一份任务状态。
+#[derive(Debug, Clone, Serialize)]
+pub struct CachedTaskState {
+    /// 任务状态，版本戳即 `state.version`。
+    pub state: TaskDebugState,
+    /// 写入本地缓存的时间。
+    pub cached_at: DateTime<Utc>,
+}
+
+/// 离线工作所需的一个任务的全部本地数据。
+#[derive(Debug, Clone, Serialize)]
+pub struct OfflineTaskBundle {
+    /// 任务ID。
+    pub task_id: String,
+    /// 最近一次同步到的任务状态。
+    pub task_state: Option<CachedTaskState>,
+    /// 任务所用的调试模板 (未缓存时为空)。
+    pub templates: TaskTemplateSet,
+    /// 模板的写入时间，未缓存模板时为 `None`。
+    pub templates_cached_at: Option<DateTime<Utc>>,
+    /// 项目点表。
+    pub point_table: Option<ProjectPointTable>,
+    /// 点表的写入时间，未缓存点表时为 `None`。
+    pub point_table_cached_at: Option<DateTime<Utc>>,
+}
+
+impl OfflineTaskBundle {
+    /// 本地是否没有该任务的任何数据。
+    pub fn is_empty(&self) -> bool {
+        self.task_state.is_none() && self.templates.is_empty() && self.point_table.is_none()
+    }
+}
+
+/// 任务数据本地缓存数据仓库。
+#[derive(Debug)]
+pub struct TaskCacheRepository {
+    /// 受互斥锁保护的 SQLite 连接。
+    conn: Mutex<Connection>,
+}
+
+impl TaskCacheRepository {
+    /// 打开 (或创建) 指定路径的 SQLite 数据库文件，并确保缓存表存在。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let conn = Connection::open(path).map_err(|e| format!("打开数据库文件 {:?} 失败: {}", path, e))?;
+        info!("[任务数据缓存] 已打开数据库文件 {:?}", path);
+        Self::with_connection(conn)
+    }
+
+    /// 创建一个基于内存数据库的仓库 (应用重启后内容丢失)，用于数据库文件无法打开时的降级和测试。
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| format!("创建内存数据库失败: {}", e))?;
+        Self::with_connection(conn)
+    }
+
+    /// 使用已有连接初始化仓库并执行建表语句。
+    fn with_connection(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS task_state_cache (
+                task_id      TEXT PRIMARY KEY,
+                version      INTEGER NOT NULL,
+                state        TEXT NOT NULL,
+                cached_at    TEXT NOT NULL,
+                active       INTEGER NOT NULL DEFAULT 1
+            );
+            CREATE TABLE IF NOT EXISTS template_cache (
+                task_id          TEXT NOT NULL,
+                template_id      TEXT NOT NULL,
+                template_type    TEXT NOT NULL,
+                template_version TEXT NOT NULL,
+                template         TEXT NOT NULL,
+                cached_at        TEXT NOT NULL,
+                PRIMARY KEY (task_id, template_id)
+            );
+            CREATE TABLE IF NOT EXISTS point_table_cache (
+                task_id      TEXT PRIMARY KEY,
+                project_id   TEXT NOT NULL,
+                version      TEXT NOT NULL,
+                point_table  TEXT NOT NULL,
+                cached_at    TEXT NOT NULL
+            );",
+        )
+        .map_err(|e| format!("初始化任务数据缓存表失败: {}", e))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// 保存任务状态，覆盖该任务之前保存的状态。
+    ///
+    /// 调用方只应传入从云端同步到的状态 (由业务客户端的 `TaskStateUpdated` 事件给出)，因此总是覆盖，
+    /// 而不比较版本：云端重启后任务状态的版本会从头开始，此时仍应以云端为准。保存的任务成为当前任务。
+    pub fn save_task_state(&self, state: &TaskDebugState) -> Result<(), String> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO task_state_cache (task_id, version, state, cached_at, active) VALUES (?1, ?2, ?3, ?4, 1)
+             ON CONFLICT(task_id) DO UPDATE SET version = excluded.version, state = excluded.state, cached_at = excluded.cached_at, active = 1",
+            params![state.task_id, state.version as i64, to_json(state)?, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| format!("保存任务 '{}' 的状态失败: {}", state.task_id, e))?;
+        Ok(())
+    }
+
+    /// 读取指定任务保存的状态。
+    pub fn load_task_state(&self, task_id: &str) -> Result<Option<CachedTaskState>, String> {
+        self.query_task_state("WHERE task_id = ?1", params![task_id])
+    }
+
+    /// 读取当前任务最近一次保存的状态 (应用重启时恢复的当前任务)。离开任务组后没有当前任务，返回 `None`。
+    pub fn latest_task_state(&self) -> Result<Option<CachedTaskState>, String> {
+        self.query_task_state("WHERE active = 1 ORDER BY cached_at DESC LIMIT 1", params![])
+    }
+
+    /// 本端已离开任务组 (主动离开或槽位被接替)：保存的任务状态都不再是当前任务，但仍可按任务ID读取。
+    ///
+    /// # 返回
+    /// 受影响的任务数。
+    pub fn deactivate_task_states(&self) -> Result<usize, String> {
+        let conn = self.lock_conn()?;
+        conn.execute("UPDATE task_state_cache SET active = 0 WHERE active = 1", [])
+            .map_err(|e| format!("更新任务状态缓存失败: {}", e))
+    }
+
+    /// 保存任务所用的调试模板，替换该任务之前保存的全部模板。
+    ///
+    /// # 返回
+    /// 保存的模板数量。同一集合中出现重复的 `template_id` 时返回错误，之前保存的模板保持不变。
+    pub fn save_templates(&self, task_id: &str, templates: &TaskTemplateSet) -> Result<usize, String> {
+        let cached_at = Utc::now().to_rfc3339();
+        let mut conn = self.lock_conn()?;
+        let tx = conn.transaction().map_err(|e| format!("开始保存模板的事务失败: {}", e))?;
+        tx.execute("DELETE FROM template_cache WHERE task_id = ?1", params![task_id])
+            .map_err(|e| format!("清除任务 '{}' 的旧模板失败: {}", task_id, e))?;
+
+        let rows = templates
+            .pre_check
+            .iter()
+            .map(|t| (PRE_CHECK, &t.metadata, to_json(t)))
+            .chain(templates.single_device_tests.iter().map(|t| (SINGLE_DEVICE_TEST, &t.metadata, to_json(t))))
+            .chain(templates.interlock_tests.iter().map(|t| (INTERLOCK_TEST, &t.metadata, to_json(t))));
+        let mut saved = 0;
+        for (template_type, metadata, json) in rows {
+            tx.execute(
+                "INSERT INTO template_cache (task_id, template_id, template_type, template_version, template, cached_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![task_id, metadata.template_id, template_type, metadata.template_version, json?, cached_at],
+            )
+            .map_err(|e| format!("保存模板 '{}' 失败: {}", metadata.template_id, e))?;
+            saved += 1;
+        }
+        tx.commit().map_err(|e| format!("提交任务 '{}' 的模板失败: {}", task_id, e))?;
+        Ok(saved)
+    }
+
+    /// 读取任务保存的调试模板 (按保存时的顺序)，以及保存时间；未保存过模板时返回 `None`。
+    pub fn load_templates(&self, task_id: &str) -> Result<Option<(TaskTemplateSet, DateTime<Utc>)>, String> {
+        let conn = self.lock_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT template_type, template, cached_at FROM template_cache WHERE task_id = ?1 ORDER BY rowid")
+            .map_err(|e| format!("查询任务 '{}' 的模板失败: {}", task_id, e))?;
+        let rows = stmt
+            .query_map(params![task_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })
+            .map_err(|e| format!("查询任务 '{}' 的模板失败: {}", task_id, e))?;
+
+        let mut templates = TaskTemplateSet::default();
+        let mut cached_at = None;
+        for row in rows {
+            let (template_type, json, saved_at) = row.map_err(|e| format!("读取模板失败: {}", e))?;
+            match template_type.as_str() {
+                PRE_CHECK => templates.pre_check.push(from_json(&json)?),
+                SINGLE_DEVICE_TEST => templates.single_device_tests.push(from_json(&json)?),
+                INTERLOCK_TEST => templates.interlock_tests.push(from_json(&json)?),
+                other => return Err(format!("未知的模板类型 '{}'", other)),
+            }
+            cached_at = Some(parse_time(&saved_at)?);
+        }
+        Ok(cached_at.map(|cached_at| (templates, cached_at)))
+    }
+
+    /// 保存任务所属项目的点表，覆盖该任务之前保存的点表。
+    pub fn save_point_table(&self, task_id: &str, point_table: &ProjectPointTable) -> Result<(), String> {
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO point_table_cache (task_id, project_id, version, point_table, cached_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(task_id) DO UPDATE SET project_id = excluded.project_id, version = excluded.version,
+                 point_table = excluded.point_table, cached_at = excluded.cached_at",
+            params![task_id, point_table.project_id, point_table.version, to_json(point_table)?, Utc::now().to_rfc3339()],
+        )
+        .map_err(|e| format!("保存任务 '{}' 的点表失败: {}", task_id, e))?;
+        Ok(())
+    }
+
+    /// 读取任务保存的点表及其保存时间。
+    pub fn load_point_table(&self, task_id: &str) -> Result<Option<(ProjectPointTable, DateTime<Utc>)>, String> {
+        let conn = self.lock_conn()?;
+        let row = conn
+            .query_row(
+                "SELECT point_table, cached_at FROM point_table_cache WHERE task_id = ?1",
+                params![task_id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()
+            .map_err(|e| format!("查询任务 '{}' 的点表失败: {}", task_id, e))?;
+        row.map(|(json, cached_at)| Ok((from_json(&json)?, parse_time(&cached_at)?))).transpose()
+    }
+
+    /// 读取离线工作所需的一个任务的全部本地数据。
+    pub fn load_bundle(&self, task_id: &str) -> Result<OfflineTaskBundle, String> {
+        let (templates, templates_cached_at) = match self.load_templates(task_id)? {
+            Some((templates, cached_at)) => (templates, Some(cached_at)),
+            None => (TaskTemplateSet::default(), None),
+        };
+        let (point_table, point_table_cached_at) = match self.load_point_table(task_id)? {
+            Some((point_table, cached_at)) => (Some(point_table), Some(cached_at)),
+            None => (None, None),
+        };
+        Ok(OfflineTaskBundle {
+            task_id: task_id.to_string(),
+            task_state: self.load_task_state(task_id)?,
+            templates,
+            templates_cached_at,
+            point_table,
+            point_table_cached_at,
+        })
+    }
+
+    /// 按 `clause` (筛选与排序) 查询一条任务状态记录。
+    fn query_task_state(&self, clause: &str, params: &[&dyn rusqlite::ToSql]) -> Result<Option<CachedTaskState>, String> {
+        let conn = self.lock_conn()?;
+        let sql = format!("SELECT state, cached_at FROM task_state_cache {}", clause);
+        let row = conn
+            .query_row(&sql, params, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .optional()
+            .map_err(|e| format!("查询任务状态缓存失败: {}", e))?;
+        row.map(|(state, cached_at)| Ok(CachedTaskState { state: from_json(&state)?, cached_at: parse_time(&cached_at)? }))
+            .transpose()
+    }
+
+    /// 获取数据库连接的互斥锁。
+    fn lock_conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        self.conn.lock().map_err(|_| "数据库连接锁已中毒".to_string())
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|e| format!("序列化字段失败: {}", e))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, String> {
+    serde_json::from_str(text).map_err(|e| format!("反序列化字段失败: {}", e))
+}
+
+fn parse_time(text: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| format!("解析缓存写入时间失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::project_details::PointDefinition;
+    use common_models::templates::{FieldInputType, PreCheckItemDefinition, PreCheckTemplate, TemplateMetadata, TemplateType};
+
+    fn pre_check_template(template_id: &str, template_version: &str) -> PreCheckTemplate {
+        PreCheckTemplate {
+            metadata: TemplateMetadata {
+                template_id: template_id.to_string(),
+                template_name: "泵类预检查".to_string(),
+                template_version: template_version.to_string(),
+                template_type: TemplateType::PreCheck,
+                description: None,
+                applicable_scope: None,
+                created_by: None,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            items: vec![PreCheckItemDefinition {
+                item_id: "i1".to_string(),
+                item_order: 1,
+                category: "电气".to_string(),
+                description: "电机接线检查".to_string(),
+                standard_or_expected_value: "接线牢固".to_string(),
+                check_method_hint: None,
+                input_type: FieldInputType::Boolean,
+                is_critical: true,
+                default_status_on_load: "PENDING_CHECK".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_task_data_survives_reopen_with_version_stamps() {
+        let path = std::env::temp_dir().join(format!("task_cache_{}.db", uuid::Uuid::new_v4()));
+        {
+            let repo = TaskCacheRepository::open(&path).expect("打开数据库失败");
+            let mut state = TaskDebugState::new("t1".to_string());
+            state.version = 3;
+            repo.save_task_state(&state).unwrap();
+            state.version = 5;
+            repo.save_task_state(&state).unwrap();
+
+            let templates = TaskTemplateSet { pre_check: vec![pre_check_template("pc1", "1.0.0")], ..Default::default() };
+            assert_eq!(repo.save_templates("t1", &templates).unwrap(), 1);
+            let newer = TaskTemplateSet { pre_check: vec![pre_check_template("pc1", "1.1.0")], ..Default::default() };
+            assert_eq!(repo.save_templates("t1", &newer).unwrap(), 1);
+            let duplicated = TaskTemplateSet {
+                pre_check: vec![pre_check_template("pc2", "1.0.0"), pre_check_template("pc2", "1.0.0")],
+                ..Default::default()
+            };
+            assert!(repo.save_templates("t1", &duplicated).is_err(), "重复的 template_id 不应被保存");
+
+            let point_table = ProjectPointTable {
+                project_id: "p1".to_string(),
+                version: "7".to_string(),
+                points: vec![PointDefinition {
+                    point_name: "PUMP_01_RUN".to_string(),
+                    description: None,
+                    device_id: Some("d1".to_string()),
+                    data_type: "bool".to_string(),
+                    address: Some("DB10.DBX0.1".to_string()),
+                    unit: None,
+                }],
+                updated_at: Utc::now(),
+            };
+            repo.save_point_table("t1", &point_table).unwrap();
+        }
+
+        // 重新打开 (相当于应用重启) 后，数据仍在
+        let repo = TaskCacheRepository::open(&path).expect("重新打开数据库失败");
+        let latest = repo.latest_task_state().unwrap().expect("应有最近保存的任务状态");
+        assert_eq!((latest.state.task_id.as_str(), latest.state.version), ("t1", 5));
+
+        let bundle = repo.load_bundle("t1").unwrap();
+        assert!(!bundle.is_empty());
+        let versions: Vec<_> = bundle.templates.metadata().iter().map(|m| m.template_version.clone()).collect();
+        assert_eq!(versions, vec!["1.1.0".to_string()]);
+        assert!(bundle.templates_cached_at.is_some());
+        let point_table = bundle.point_table.expect("应有点表");
+        assert_eq!(point_table.version, "7");
+        assert!(point_table.point("PUMP_01_RUN").is_some());
+
+        assert!(repo.load_bundle("t2").unwrap().is_empty());
+
+        // 离开任务组后没有当前任务，但该任务的数据仍可按任务ID读取；再次同步到状态时恢复为当前任务
+        assert_eq!(repo.deactivate_task_states().unwrap(), 1);
+        assert!(repo.latest_task_state().unwrap().is_none());
+        assert_eq!(repo.load_bundle("t1").unwrap().task_state.map(|cached| cached.state.version), Some(5));
+        let mut state = TaskDebugState::new("t1".to_string());
+        state.version = 6;
+        repo.save_task_state(&state).unwrap();
+        assert_eq!(repo.latest_task_state().unwrap().map(|cached| cached.state.version), Some(6));
+        drop(repo);
+        let _ = std::fs::remove_file(&path);
+    }
+}
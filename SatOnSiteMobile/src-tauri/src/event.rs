@@ -8,6 +8,13 @@
 
 use serde::{Serialize, Deserialize};
 use common_models::TaskDebugState; // 确保导入 TaskDebugState
+use common_models::enums::ErrorCode;
+use common_models::ws_payloads::{AckPayload, FieldErrorDetail, GroupLeftPayload, GroupRosterPayload};
+use common_models::task_models::StartSingleTestStepPayload;
+use crate::db::OutboxEntry;
+use crate::ws_client::error_handling::ServerErrorHandling;
+use rust_websocket_utils::client::heartbeat::LinkQuality;
+use rust_websocket_utils::client::reconnect::ConnectionState;
 // 尝试从 common_models 引入 ClientRole，如果事件负载中确实需要强类型角色。
 // use common_models::enums::ClientRole;
 
@@ -46,6 +53,34 @@ pub struct WsConnectionStatusEvent {
     pub error_message: Option<String>,
 }
 
+/// WebSocket 自动重连状态事件的名称常量。
+///
+/// 与只区分"已连接/未连接"的 `WS_CONNECTION_STATUS_EVENT` 不同，此事件携带重连客户端的详细状态
+/// (正在连接、已连接/已重连、等待重连及剩余等待时间、已放弃重连、已关闭)，前端可据此展示重连进度。
+pub const WS_CONNECTION_STATE_EVENT: &str = "ws_connection_state_event";
+
+/// `WS_CONNECTION_STATE_EVENT` 事件的负载结构体。
+#[derive(Clone, Serialize, Debug)]
+pub struct WsConnectionStateEventPayload {
+    /// 重连客户端的当前状态，以 `state` 字段区分 (例如 `{"state":"Reconnecting","attempt":2,...}`)。
+    #[serde(flatten)]
+    pub state: ConnectionState,
+}
+
+/// 链路质量事件的名称常量。
+///
+/// 心跳任务每次评估一次 Ping 的结果 (收到 Pong 或等待超时) 后发出，携带实测往返时延、抖动、
+/// 质量等级以及当前采用的心跳间隔，前端可据此展示信号强弱。
+pub const WS_LINK_QUALITY_EVENT: &str = "ws_link_quality_event";
+
+/// `WS_LINK_QUALITY_EVENT` 事件的负载结构体。
+#[derive(Clone, Serialize, Debug)]
+pub struct WsLinkQualityEventPayload {
+    /// 链路质量评估结果 (例如 `{"level":"Fair","smoothed_rtt_ms":420,"jitter_ms":130,...}`)。
+    #[serde(flatten)]
+    pub quality: LinkQuality,
+}
+
 // --- Echo (回声测试) 相关事件 --- 
 
 /// Echo (回声) 响应消息事件的名称常量。
@@ -97,6 +132,15 @@ pub struct WsRegistrationStatusEventPayload {
     /// 新增 role 字段
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
+    /// 如果注册失败，云端返回的机器可读错误码 (例如 `ROLE_SLOT_TAKEN`)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// 如果注册失败，根据错误码确定的建议处理方式。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handling: Option<ServerErrorHandling>,
+    /// 本次注册是否凭会话恢复令牌恢复了断线前的会话 (云端随后会补发断线期间错过的消息)。
+    #[serde(default)]
+    pub resumed: bool,
 }
 
 /// WebSocket 伙伴客户端状态更新事件的名称常量。
@@ -119,6 +163,10 @@ pub struct WsPartnerStatusEventPayload {
     /// - `true`: 表示伙伴客户端当前在线并已加入任务组。
     /// - `false`: 表示伙伴客户端已离线或已离开任务组。
     pub is_online: bool,
+    /// 伙伴在线但链路可疑 (服务端已有一段时间未收到其消息，尚未判定为断线)。
+    /// 前端可据此提示"伙伴网络不稳定"，而不是直接显示离线。
+    #[serde(default)]
+    pub link_suspect: bool,
     /// 发生状态变化的伙伴客户端的唯一标识符 (UUID 字符串格式)。
     /// 用于在前端精确识别是哪个伙伴的状态发生了变化。
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -157,4 +205,121 @@ pub struct WsServerErrorEventPayload {
     pub error_message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub original_message_type: Option<String>,
-} 
\ No newline at end of file
+    /// 机器可读的错误码 (旧版本云端不提供)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// 本地化消息键，前端据此查找面向用户的提示文本。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    /// 字段级错误详情。
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldErrorDetail>,
+    /// 根据错误码确定的建议处理方式。
+    pub handling: ServerErrorHandling,
+}
+
+// --- 业务确认事件 ---
+/// 云端对本端发出的业务消息给出统一确认 (`Ack`) 时发送给前端的事件名称常量。
+///
+/// 前端可据此确认一次操作 (例如点击更新预检项) 是否真正到达云端、是否生效，以及生效后的任务状态版本。
+pub const WS_BUSINESS_ACK_EVENT: &str = "ws_business_ack_event";
+
+/// `WS_BUSINESS_ACK_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsBusinessAckEventPayload {
+    /// 被确认的业务消息的 `message_id` (即 Ack 消息的 `in_reply_to`)。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_message_id: Option<String>,
+    /// 云端返回的确认内容 (处理结果、版本号、错误码等)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub ack: AckPayload,
+    /// 被拒绝时根据错误码确定的建议处理方式。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handling: Option<ServerErrorHandling>,
+}
+
+// --- 版本冲突事件 ---
+/// 本端业务消息因基于过期的任务状态版本被云端拒绝 (`VERSION_CONFLICT`) 时发送给前端的事件名称常量。
+///
+/// 收到该事件时本地缓存已刷新为云端当前状态 (同时会收到 `LocalTaskStateUpdatedEvent`)。前端应向用户展示
+/// 本端的修改与当前状态，并调用 `resolve_version_conflict_cmd` 选择变基、合并或放弃。
+pub const WS_VERSION_CONFLICT_EVENT: &str = "ws_version_conflict_event";
+
+/// `WS_VERSION_CONFLICT_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsVersionConflictEventPayload {
+    /// 被拒绝的业务消息的 `message_id`，处理冲突时据此指定要处理的消息。
+    pub request_message_id: String,
+    /// 被拒绝的业务消息类型，例如 "UpdatePreCheckItem"。
+    pub original_message_type: String,
+    /// 本端被拒绝的修改内容 (原业务消息负载)，可作为合并的起点。
+    pub local_payload: serde_json::Value,
+    /// 云端当前完整的任务状态。
+    pub current_state: TaskDebugState,
+}
+
+// --- 离开任务组事件 ---
+/// 本端已离开任务组 (主动 `LeaveGroup`，或 `SwitchTask` 离开原组后) 时发送给前端的事件名称常量。
+///
+/// 收到后本地任务状态缓存已清空，连接保持，可以重新注册到其他任务组。
+pub const WS_GROUP_LEFT_EVENT: &str = "ws_group_left_event";
+
+/// `WS_GROUP_LEFT_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsGroupLeftEventPayload {
+    /// 云端的离开确认 (组ID与本端在组内的角色)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub left: GroupLeftPayload,
+}
+
+// --- 组成员名册事件 ---
+/// 云端推送组成员名册 (`GroupRoster`) 时发送给前端的事件名称常量。
+///
+/// 组内成员加入、离开、断线、恢复或链路延迟明显变化时推送，负载为组内全部成员的完整列表，
+/// 前端应整体替换本地名册 (显示名称、软件版本、连接时间、最后活跃时间、往返时延与在线状态)。
+pub const WS_GROUP_ROSTER_EVENT: &str = "ws_group_roster_event";
+
+/// `WS_GROUP_ROSTER_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsGroupRosterEventPayload {
+    /// 云端推送的完整名册，平铺到事件负载中。
+    #[serde(flatten)]
+    pub roster: GroupRosterPayload,
+}
+
+// --- 单体测试指令事件 ---
+/// 云端转发 `StartSingleTestStep` 指令给本端时发送给前端的事件名称常量。
+///
+/// 同一组内可有多名现场工程师，云端只会把本端注册时声明负责的设备 (或无人认领的设备) 的指令转发过来，
+/// 前端收到即表示需要由本机操作员执行该测试步骤。
+pub const WS_START_SINGLE_TEST_STEP_EVENT: &str = "ws_start_single_test_step_event";
+
+/// `WS_START_SINGLE_TEST_STEP_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsStartSingleTestStepEventPayload {
+    /// 指令消息的 `message_id`。
+    pub command_message_id: String,
+    /// 指令内容 (任务、设备、步骤与具体指令)，平铺到事件负载中。
+    #[serde(flatten)]
+    pub step: StartSingleTestStepPayload,
+}
+
+// --- 离线待发队列重放事件 ---
+/// 离线待发队列中的业务操作被发送 (通常是重连并重新注册后自动重放) 后发送给前端的事件名称常量。
+///
+/// 被接受的操作已从队列删除，只给出数量；被拒绝或发生版本冲突的操作仍保留在队列中，
+/// 前端应提示用户查看，冲突可通过 `resolve_version_conflict_cmd` 以原 `message_id` 处理。
+pub const WS_OUTBOX_REPLAY_EVENT: &str = "ws_outbox_replay_event";
+
+/// `WS_OUTBOX_REPLAY_EVENT` 事件的负载结构体。
+#[derive(Debug, Clone, Serialize)]
+pub struct WsOutboxReplayEventPayload {
+    /// 被云端接受的操作数。
+    pub acknowledged: usize,
+    /// 被云端拒绝的操作 (含确认中的错误码与说明)。
+    pub rejected: Vec<OutboxEntry>,
+    /// 因版本冲突被拒绝的操作。
+    pub conflicted: Vec<OutboxEntry>,
+    /// 仍未送达、留待下次重连的操作数。
+    pub remaining: usize,
+}
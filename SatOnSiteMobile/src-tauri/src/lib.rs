@@ -9,6 +9,7 @@
 pub mod api_client;      // 与外部 API（非 WebSocket）交互的客户端逻辑（如果需要）。
 pub mod commands;        // 定义所有可由前端通过 Tauri `invoke` 调用的 Rust 函数。
 pub mod config;          // 应用配置加载与管理。
+pub mod db;              // 本地 SQLite 数据库（离线待发队列、任务数据缓存）。
 pub mod device_comms;    // 与具体硬件设备通信的逻辑（例如通过串口、蓝牙等）。
 pub mod error;           // 定义应用级别的错误类型和处理机制。
 pub mod event;           // 定义后端与前端之间通过 Tauri 事件系统传递的事件及其负载结构。
@@ -7,13 +7,15 @@ mod config;     // 应用配置模块 (P2.1.1 - 现场端)
 mod event;      // Tauri 事件定义模块 (P2.1.1 - 现场端)
 mod ws_client;  // WebSocket 客户端服务模块 (P2.1.1 - 现场端)
 mod commands;   // Tauri 命令定义模块 (P2.1.1 - 现场端)
+mod db;         // 本地 SQLite 数据库 (离线待发队列、任务数据缓存)
 // mod error; // 如果有全局错误处理模块，请取消注释
 // mod state; // 如果有全局状态管理模块（除了Tauri管理的），请取消注释
 
 // --- 依赖引入 --- 
 use std::sync::Arc;
 use crate::ws_client::service::WebSocketClientService; // WebSocket 客户端服务
-use log::{info, LevelFilter}; // 日志记录宏和级别过滤器
+use crate::db::{OutboxRepository, TaskCacheRepository}; // 离线待发队列与任务数据缓存数据仓库
+use log::{error, info, LevelFilter}; // 日志记录宏和级别过滤器
 use tauri::Manager; // 引入 tauri::Manager trait 以便使用 app.manage() 方法管理状态
 use env_logger; // 基于环境变量的日志记录器
 
@@ -45,7 +47,12 @@ fn main() {
 
             // --- WebSocket 客户端服务初始化与管理 ---
             // 创建 WebSocketClientService 的实例，使用 Arc 进行原子引用计数，以便安全共享
-            let ws_service_instance = Arc::new(WebSocketClientService::new(app_handle.clone()));
+            // 业务操作经由持久化在应用数据目录中的离线待发队列发送；任务状态、模板与点表同样持久化在本地
+            let ws_service_instance = Arc::new(WebSocketClientService::new(
+                app_handle.clone(),
+                open_outbox_repository(app),
+                open_task_cache_repository(app),
+            ));
             
             // 将 WebSocketClientService 实例注册到 Tauri 的状态管理器中，
             // 这样就可以在 Tauri 命令处理函数中通过 AppHandle::state() 来访问它。
@@ -63,6 +70,7 @@ fn main() {
             commands::general_cmds::check_ws_connection_status, // 检查当前 WebSocket 连接状态
             commands::general_cmds::send_ws_echo,             // 发送 Echo 消息到云端
             commands::general_cmds::register_client_with_task, // 注册客户端到任务组
+            commands::general_cmds::register_with_join_code, // 凭控制中心提供的配对码注册到任务组
             commands::dev_tools_cmds::open_dev_tools,
             commands::app_lifecycle_cmds::on_window_ready,
             commands::network_cmds::check_server_connectivity_cmd,
@@ -70,6 +78,18 @@ fn main() {
             commands::ws_cmds::disconnect_from_ws_server_cmd,
             commands::ws_cmds::send_echo_message_cmd,
             commands::ws_cmds::send_register_message_cmd,
+            commands::ws_cmds::resync_task_state_cmd,          // 请求云端重新同步完整任务状态
+            commands::ws_cmds::resolve_version_conflict_cmd,   // 处理业务消息的版本冲突 (变基/合并/放弃)
+            commands::ws_cmds::leave_group_cmd,                // 不断开连接地离开当前任务组
+            commands::ws_cmds::switch_task_cmd,                // 离开当前任务组并注册到另一个任务组
+            commands::ws_cmds::list_outbox_entries_cmd,        // 列出离线待发队列中的记录
+            commands::ws_cmds::replay_outbox_cmd,              // 立即重放离线待发队列
+            commands::ws_cmds::dismiss_outbox_entry_cmd,       // 清除被拒绝或冲突的待发记录
+            commands::data_cmds::cache_task_templates_cmd,     // 保存任务模板供离线使用
+            commands::data_cmds::cache_point_table_cmd,        // 保存项目点表供离线使用
+            commands::data_cmds::get_offline_task_bundle_cmd,  // 读取本地保存的任务状态、模板与点表
+            commands::test_cmds::update_pre_check_item_cmd,     // 上报预检查项的现场检查结果
+            commands::test_cmds::feedback_single_test_step_cmd, // 上报单体测试步骤的执行结果
             commands::send_debug_note_from_site_cmd
         ])
         .build(tauri::generate_context!()) // 根据 tauri.conf.json 和 Cargo.toml 生成上下文
@@ -101,3 +121,36 @@ fn main() {
     // 因此，这条日志通常在应用关闭后才可能（在某些情况下）被记录，或者根本不会执行到。
     info!("Tauri 应用的 run 方法已结束调用，应用已关闭 (SatOnSiteMobile)。");
 }
+
+/// 打开应用数据目录下的离线待发队列数据库 (`outbox.db`)。
+///
+/// 无法打开时退回到内存数据库：应用仍可正常使用，但未送达的业务操作在应用重启后会丢失。
+fn open_outbox_repository(app: &tauri::App) -> OutboxRepository {
+    match local_database_path(app, "outbox.db").and_then(OutboxRepository::open) {
+        Ok(repo) => repo,
+        Err(e) => {
+            error!("打开离线待发队列数据库失败，退回到内存数据库 (应用重启后未送达的操作将丢失): {}", e);
+            OutboxRepository::open_in_memory().expect("创建内存中的离线待发队列数据库失败 (SatOnSiteMobile)")
+        }
+    }
+}
+
+/// 打开应用数据目录下的任务数据缓存数据库 (`task_cache.db`)。
+///
+/// 无法打开时退回到内存数据库：联网时应用仍可正常使用，但应用重启后无法离线恢复任务数据。
+fn open_task_cache_repository(app: &tauri::App) -> TaskCacheRepository {
+    match local_database_path(app, "task_cache.db").and_then(TaskCacheRepository::open) {
+        Ok(repo) => repo,
+        Err(e) => {
+            error!("打开任务数据缓存数据库失败，退回到内存数据库 (应用重启后无法离线恢复任务数据): {}", e);
+            TaskCacheRepository::open_in_memory().expect("创建内存中的任务数据缓存数据库失败 (SatOnSiteMobile)")
+        }
+    }
+}
+
+/// 应用数据目录下指定数据库文件的路径，必要时创建该目录。
+fn local_database_path(app: &tauri::App, file_name: &str) -> Result<std::path::PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| format!("无法确定应用数据目录: {}", e))?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建应用数据目录 {:?} 失败: {}", dir, e))?;
+    Ok(dir.join(file_name))
+}
@@ -0,0 +1,225 @@
+// SatOnSiteMobile/src-tauri/src/ws_client/outbox.rs
+
+//! 离线待发队列服务：让现场端在没有网络时也能继续记录业务操作。
+//!
+//! 每个业务操作先写入本地 SQLite 待发队列 ([`OutboxRepository`])，再在连接可用时按写入顺序发送；
+//! 收到云端确认后才从队列中删除。断线期间记录的操作在重新注册成功后自动重放。
+//!
+//! - **幂等**: 重放沿用入队时生成的 `message_id`，发出后未等到确认就断线的操作再次发送时，云端据此识别重复投递。
+//! - **版本链**: 离线期间排队的操作都基于同一个本地缓存版本。前一个操作被接受后，队列中后续基于同一版本的操作
+//!   立即改为基于该确认返回的新版本并写回队列，避免本端自己的操作之间互相产生版本冲突；
+//!   改写随确认一并持久化，因此在此之后断线或重启，下一次重放仍基于新版本提交。
+//! - **结果上报**: 被拒绝或发生版本冲突的操作保留在队列中 (状态为 `Rejected` / `Conflicted`)，
+//!   通过 `ws_outbox_replay_event` 通知前端；冲突可通过 `resolve_version_conflict_cmd` 以原 `message_id` 处理。
+
+use std::sync::Arc;
+
+use log::{error, info, warn};
+use rust_websocket_utils::client::sat_client::SatClient;
+use rust_websocket_utils::message::WsMessage;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Mutex as TokioMutex;
+
+use common_models::enums::ErrorCode;
+use common_models::ws_payloads::{AckPayload, AckStatus};
+
+use crate::db::{OutboxEntry, OutboxRepository, OutboxStatus};
+use crate::event::{WS_OUTBOX_REPLAY_EVENT, WsOutboxReplayEventPayload};
+
+/// 提交一个业务操作的结果。
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum SubmitOutcome {
+    /// 已送达云端并收到确认。被拒绝的确认同样属于此类，由前端查看 `ack.status`。
+    Acknowledged { ack: AckPayload },
+    /// 暂未送达 (离线或未等到确认)，已保存在本地待发队列中，重连后按顺序重放。
+    Queued { message_id: String, pending_count: usize },
+}
+
+/// 一次队列发送 (重放) 的结果汇总。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReplayReport {
+    /// 被云端接受 (`Applied` / `NoChange`) 的操作的确认，按发送顺序排列，键为 `message_id`。
+    pub acknowledged: Vec<(String, AckPayload)>,
+    /// 被云端拒绝的操作。
+    pub rejected: Vec<OutboxEntry>,
+    /// 因版本冲突被拒绝的操作。
+    pub conflicted: Vec<OutboxEntry>,
+    /// 本次结束后仍未送达的操作数 (连接中断时剩余的操作留待下次重连)。
+    pub remaining: usize,
+}
+
+impl ReplayReport {
+    /// 本次是否处理了任何操作。
+    fn is_empty(&self) -> bool {
+        self.acknowledged.is_empty() && self.rejected.is_empty() && self.conflicted.is_empty()
+    }
+
+    /// 指定消息在本次发送中得到的确认 (无论被接受还是被拒绝)。
+    fn ack_for(&self, message_id: &str) -> Option<AckPayload> {
+        self.acknowledged
+            .iter()
+            .find(|(id, _)| id == message_id)
+            .map(|(_, ack)| ack.clone())
+            .or_else(|| {
+                self.rejected
+                    .iter()
+                    .chain(self.conflicted.iter())
+                    .find(|entry| entry.message.message_id == message_id)
+                    .and_then(|entry| entry.ack.clone())
+            })
+    }
+}
+
+/// 离线待发队列服务。
+#[derive(Debug)]
+pub struct OfflineOutbox {
+    /// 持久化的待发队列。
+    repo: OutboxRepository,
+    /// 共享的业务客户端，用于发送队列中的消息。
+    client: Arc<SatClient>,
+    /// Tauri 应用句柄，用于向前端发送重放结果事件。
+    app_handle: AppHandle,
+    /// 保证同一时刻只有一个发送过程在按顺序处理队列。
+    flush_lock: TokioMutex<()>,
+}
+
+impl OfflineOutbox {
+    /// 创建待发队列服务。
+    pub fn new(repo: OutboxRepository, client: Arc<SatClient>, app_handle: AppHandle) -> Self {
+        Self { repo, client, app_handle, flush_lock: TokioMutex::new(()) }
+    }
+
+    /// 提交一个业务操作：先写入待发队列，连接可用时立即按顺序发送。
+    ///
+    /// 队列中还有更早的操作时，它们会先于本操作发送。
+    ///
+    /// # 返回
+    /// * `Ok(SubmitOutcome::Acknowledged)`: 已收到云端对本操作的确认。
+    /// * `Ok(SubmitOutcome::Queued)`: 暂未送达，已保存在队列中。
+    /// * `Err(String)`: 无法构造消息或写入队列失败，操作未被记录。
+    pub async fn submit<T: Serialize>(&self, message_type: &str, payload: &T) -> Result<SubmitOutcome, String> {
+        let message = WsMessage::new(message_type.to_string(), payload)
+            .map_err(|e| format!("创建类型为 '{}' 的 WsMessage 失败: {}", message_type, e))?;
+        let message_id = message.message_id.clone();
+        self.repo.enqueue(&message)?;
+        info!("[离线待发队列] 业务操作 '{}' (ID: {}) 已写入待发队列。", message_type, message_id);
+
+        // 只在已注册到任务组时发送；重连后尚未重新注册时由注册成功后的重放负责发送
+        if self.client.client_id().await.is_some() {
+            let report = self.flush().await?;
+            if let Some(ack) = report.ack_for(&message_id) {
+                return Ok(SubmitOutcome::Acknowledged { ack });
+            }
+        }
+        Ok(SubmitOutcome::Queued { message_id, pending_count: self.repo.pending_count()? })
+    }
+
+    /// 按写入顺序发送队列中所有尚未确认的操作，处理了任何操作时通过 `ws_outbox_replay_event` 通知前端。
+    ///
+    /// 连接中断或未等到确认时停止，剩余操作留待下次重连。
+    pub async fn flush(&self) -> Result<ReplayReport, String> {
+        let _guard = self.flush_lock.lock().await;
+        let mut pending = self.repo.pending()?;
+        let mut report = ReplayReport::default();
+
+        for index in 0..pending.len() {
+            let entry = pending[index].clone();
+            let message_id = entry.message.message_id.clone();
+
+            let ack = match self.client.send_business_message(entry.message.clone()).await {
+                Ok(ack) => ack,
+                Err(e) => {
+                    warn!("[离线待发队列] 发送待发操作 (ID: {}) 失败，停止本次发送: {}", message_id, e);
+                    self.repo.record_failed_attempt(&message_id, &e)?;
+                    report.remaining = pending.len() - index;
+                    break;
+                }
+            };
+
+            match ack.status {
+                AckStatus::Applied | AckStatus::NoChange => {
+                    let rebased = match (expected_version(&entry.message), ack.version) {
+                        (Some(base), Some(version)) if version != base => rebase_entries(&mut pending[index + 1..], base, version)?,
+                        _ => Vec::new(),
+                    };
+                    self.repo.acknowledge(&message_id, &rebased)?;
+                    if !rebased.is_empty() {
+                        info!("[离线待发队列] 待发操作 (ID: {}) 已被接受，{} 个后续操作改为基于版本 {}。", message_id, rebased.len(), ack.version.unwrap_or_default());
+                    }
+                    report.acknowledged.push((message_id, ack));
+                }
+                AckStatus::Rejected => {
+                    let conflicted = ack.error_code == Some(ErrorCode::VersionConflict);
+                    let status = if conflicted { OutboxStatus::Conflicted } else { OutboxStatus::Rejected };
+                    warn!("[离线待发队列] 待发操作 (ID: {}) 被云端拒绝 ({:?}): {:?}", message_id, ack.error_code, ack.message);
+                    self.repo.mark_settled(&message_id, status, &ack)?;
+                    let settled = OutboxEntry { status, ack: Some(ack), attempts: entry.attempts + 1, last_error: None, ..entry.clone() };
+                    if conflicted {
+                        report.conflicted.push(settled);
+                    } else {
+                        report.rejected.push(settled);
+                    }
+                }
+            }
+        }
+
+        if !report.is_empty() {
+            info!(
+                "[离线待发队列] 本次发送完成: 接受 {} 个, 拒绝 {} 个, 冲突 {} 个, 剩余 {} 个。",
+                report.acknowledged.len(), report.rejected.len(), report.conflicted.len(), report.remaining
+            );
+            let payload = WsOutboxReplayEventPayload {
+                acknowledged: report.acknowledged.len(),
+                rejected: report.rejected.clone(),
+                conflicted: report.conflicted.clone(),
+                remaining: report.remaining,
+            };
+            if let Err(e) = self.app_handle.emit(WS_OUTBOX_REPLAY_EVENT, &payload) {
+                error!("[离线待发队列] 发送事件 ({}) 给前端失败: {}", WS_OUTBOX_REPLAY_EVENT, e);
+            }
+        }
+        Ok(report)
+    }
+
+    /// 按写入顺序返回队列中的全部记录 (含被拒绝和冲突的记录)。
+    pub fn entries(&self) -> Result<Vec<OutboxEntry>, String> {
+        self.repo.entries()
+    }
+
+    /// 清除一条被拒绝或冲突的记录 (用户已查看或已处理冲突)。
+    pub fn dismiss(&self, message_id: &str) -> Result<bool, String> {
+        self.repo.dismiss(message_id)
+    }
+}
+
+/// 业务消息负载中的 `expected_version`。
+fn expected_version(message: &WsMessage) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(&message.payload)
+        .ok()
+        .and_then(|payload| payload.get("expected_version").and_then(|v| v.as_u64()))
+}
+
+/// 把 `entries` 中基于 `base` 版本的待发消息改为基于 `version`，返回被改写的消息。
+fn rebase_entries(entries: &mut [OutboxEntry], base: u64, version: u64) -> Result<Vec<WsMessage>, String> {
+    let mut rebased = Vec::new();
+    for entry in entries.iter_mut().filter(|entry| expected_version(&entry.message) == Some(base)) {
+        entry.message = with_expected_version(&entry.message, version)?;
+        rebased.push(entry.message.clone());
+    }
+    Ok(rebased)
+}
+
+/// 把业务消息的 `expected_version` 改为 `version`，`message_id` 保持不变。
+fn with_expected_version(message: &WsMessage, version: u64) -> Result<WsMessage, String> {
+    let mut payload = serde_json::from_str::<serde_json::Value>(&message.payload)
+        .map_err(|e| format!("解析待发消息 (ID: {}) 的负载失败: {}", message.message_id, e))?;
+    let Some(fields) = payload.as_object_mut() else {
+        return Err(format!("待发消息 (ID: {}) 的负载不是 JSON 对象", message.message_id));
+    };
+    fields.insert("expected_version".to_string(), serde_json::Value::from(version));
+    let mut rebased = message.clone();
+    rebased.payload = payload.to_string();
+    Ok(rebased)
+}
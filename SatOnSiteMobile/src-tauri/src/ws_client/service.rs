@@ -2,968 +2,398 @@
 
 //! `SatOnSiteMobile` (现场端) 应用的 WebSocket 客户端服务模块。
 //!
-//! 该模块负责管理与云端 `SatCloudService` 的 WebSocket 连接，
-//! 包括连接建立、断开、消息收发、状态同步以及心跳维持。
+//! 连接管理、心跳、接收分发、任务状态缓存与版本冲突处理均由共享的业务客户端
+//! (`rust_websocket_utils::client::sat_client::SatClient`) 实现；本模块只是它在 Tauri 中的适配层：
+//! 把业务客户端的事件转换为现场端前端约定的 Tauri 事件，并为 `commands` 提供原有的服务接口。
+//! 业务操作经由离线待发队列 ([`OfflineOutbox`]) 发送，每次注册成功后自动重放断线期间排队的操作。
+//! 同步到的任务状态同时写入本地任务数据缓存 ([`TaskCacheRepository`])，应用重启后无需网络即可恢复。
 
-use anyhow::Result;
 use log::{debug, error, info, warn};
-use rust_websocket_utils::client::transport;
-use rust_websocket_utils::client::transport::ClientWsStream;
+use rust_websocket_utils::client::sat_client::{SatClient, SatClientConfig, SatClientEvent};
+use rust_websocket_utils::client::version_conflict::ConflictResolution;
 use rust_websocket_utils::message::WsMessage;
-use rust_websocket_utils::error::WsError;
-use tauri::{AppHandle, Emitter};
-use tokio::sync::{RwLock};
-use tokio::sync::Mutex as TokioMutex;
+use serde::Serialize;
 use std::sync::Arc;
-use futures_util::stream::{SplitSink};
-use tokio_tungstenite::tungstenite::protocol::Message as TungsteniteMessage;
-use futures_util::SinkExt;
-use chrono::{Utc, DateTime};
 use std::time::Duration;
-use uuid::Uuid;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
 
+use crate::db::{OutboxRepository, TaskCacheRepository};
+use crate::ws_client::outbox::OfflineOutbox;
 use crate::event::{
-    WS_CONNECTION_STATUS_EVENT, WsConnectionStatusEvent, ECHO_RESPONSE_EVENT, EchoResponseEventPayload,
+    WS_CONNECTION_STATUS_EVENT, WsConnectionStatusEvent, WS_CONNECTION_STATE_EVENT, WsConnectionStateEventPayload, WS_LINK_QUALITY_EVENT, WsLinkQualityEventPayload,
+    ECHO_RESPONSE_EVENT, EchoResponseEventPayload,
     WS_REGISTRATION_STATUS_EVENT, WsRegistrationStatusEventPayload,
     WS_PARTNER_STATUS_EVENT, WsPartnerStatusEventPayload,
     LOCAL_TASK_STATE_UPDATED_EVENT, LocalTaskStateUpdatedEventPayload,
-    // WS_SERVER_ERROR_EVENT, WsServerErrorEventPayload,
+    WS_SERVER_ERROR_EVENT, WsServerErrorEventPayload,
+    WS_BUSINESS_ACK_EVENT, WsBusinessAckEventPayload,
+    WS_VERSION_CONFLICT_EVENT, WsVersionConflictEventPayload,
+    WS_START_SINGLE_TEST_STEP_EVENT, WsStartSingleTestStepEventPayload,
+    WS_GROUP_LEFT_EVENT, WsGroupLeftEventPayload,
+    WS_GROUP_ROSTER_EVENT, WsGroupRosterEventPayload,
 };
 use common_models::{
-    self,
-    ws_payloads::{
-        PING_MESSAGE_TYPE, PONG_MESSAGE_TYPE, PingPayload,
-        REGISTER_RESPONSE_MESSAGE_TYPE, RegisterResponsePayload,
-        PARTNER_STATUS_UPDATE_MESSAGE_TYPE, PartnerStatusPayload,
-        TASK_STATE_UPDATE_MESSAGE_TYPE,
-        ERROR_RESPONSE_MESSAGE_TYPE, ErrorResponsePayload,
-        ECHO_MESSAGE_TYPE, EchoPayload,
-    },
+    ws_payloads::{EchoPayload, GroupLeftPayload, RegisterPayload, RegisterResponsePayload},
     TaskDebugState,
 };
 
-// 心跳机制相关常量
-/// 心跳发送间隔，单位：秒。
-const HEARTBEAT_INTERVAL_SECONDS: u64 = 30;
-/// 等待 Pong 回复的超时时间，单位：秒。
-/// 注意：此值应小于 `HEARTBEAT_INTERVAL_SECONDS`，以确保在下一个心跳周期开始前能检测到超时。
-const PONG_TIMEOUT_SECONDS: u64 = 10;
-
 /// WebSocket 客户端服务。
 ///
-/// 封装了与云端 WebSocket 服务交互的所有逻辑，包括连接管理、
-/// 消息发送与接收、心跳维持以及状态事件的发射。
+/// 持有共享的业务客户端 (`SatClient`)、离线待发队列与任务数据缓存，并在后台任务中把业务客户端的事件转发给前端。
 #[derive(Debug)]
 pub struct WebSocketClientService {
-    /// WebSocket 消息发送通道 (Sink 部分)。
-    ///
-    /// `SplitSink` 用于向 WebSocket 连接异步发送消息。
-    /// 使用 `Arc<TokioMutex<Option<...>>>` 以允许在异步任务间共享和修改。
-    /// `Option` 表示连接可能尚未建立或已断开。
-    ws_send_channel: Arc<TokioMutex<Option<SplitSink<ClientWsStream, TungsteniteMessage>>>>,
-    /// 云端分配给此客户端的唯一标识符 (UUID)。
-    ///
-    /// 在成功连接并注册后，由云端分配 (通过 RegisterResponse 确认)。
-    /// 使用 `Arc<RwLock<Option<Uuid>>>` 以便在异步回调中安全地更新和读取。
-    cloud_assigned_client_id: Arc<RwLock<Option<Uuid>>>,
-    /// Tauri 应用句柄。
-    ///
-    /// 用于向前端发送事件和管理应用状态。
+    /// 与界面框架无关的业务客户端，负责连接、心跳、消息收发与任务状态缓存。
+    client: Arc<SatClient>,
+    /// 离线待发队列，业务操作经由它发送。
+    outbox: Arc<OfflineOutbox>,
+    /// 任务数据本地缓存 (任务状态、模板与点表)。
+    task_cache: Arc<TaskCacheRepository>,
+    /// Tauri 应用句柄，用于向前端发送事件。
     app_handle: AppHandle,
-    /// 当前 WebSocket 连接状态。
-    ///
-    /// `true` 表示已连接，`false` 表示未连接或已断开。
-    /// 使用 `Arc<RwLock<...>>` 以便在异步回调中安全地更新和读取。
-    is_connected_status: Arc<RwLock<bool>>,
-    /// WebSocket 连接处理任务的句柄。
-    ///
-    /// `tokio::task::JoinHandle` 用于管理 `connect_client` 返回的连接处理任务的生命周期。
-    /// `Option` 表示任务可能尚未启动或已结束。
-    connection_task_handle: Arc<TokioMutex<Option<tokio::task::JoinHandle<()>>>>,
-    /// 心跳任务的句柄。
-    ///
-    /// 用于管理定期发送 Ping 消息的后台任务。
-    heartbeat_task_handle: Arc<TokioMutex<Option<tokio::task::JoinHandle<()>>>>,
-    /// 最后一次收到 Pong 消息的时间戳。
-    ///
-    /// 用于心跳机制中检测连接是否仍然活跃。
-    last_pong_received_at: Arc<RwLock<Option<DateTime<Utc>>>>,
-    /// 本地缓存的、从云端同步过来的权威任务状态。
-    ///
-    /// 当收到云端的 "TaskStateUpdate" 消息时，此状态会被更新。
-    /// 前端可以通过监听 `LocalTaskStateUpdatedEvent` 事件来获取最新的状态。
-    local_task_state_cache: Arc<RwLock<Option<TaskDebugState>>>,
 }
 
 impl WebSocketClientService {
-    /// 创建 `WebSocketClientService` 的新实例。
-    ///
-    /// # 参数
-    ///
-    /// * `app_handle` - Tauri 应用的句柄，用于事件发射和应用管理。
+    /// 创建 `WebSocketClientService` 的新实例，并启动把业务客户端事件转发给前端的后台任务。
     ///
-    /// # 返回
+    /// 本地任务数据缓存中有最近保存的任务状态时，将其恢复到业务客户端的缓存中，使应用离线重启后仍可继续操作。
     ///
-    /// 返回一个新的 `WebSocketClientService` 实例。
-    pub fn new(app_handle: AppHandle) -> Self {
+    /// # 参数
+    /// * `app_handle`: Tauri 应用句柄，用于后续向前端发送事件。
+    /// * `outbox_repo`: 持久化离线待发队列的本地数据库。
+    /// * `task_cache`: 持久化任务状态、模板与点表的本地数据库。
+    pub fn new(app_handle: AppHandle, outbox_repo: OutboxRepository, task_cache: TaskCacheRepository) -> Self {
         info!("[SatOnSiteMobile] WebSocketClientService: 正在初始化...");
-        Self {
-            ws_send_channel: Arc::new(TokioMutex::new(None)),
-            cloud_assigned_client_id: Arc::new(RwLock::new(None)),
-            app_handle,
-            is_connected_status: Arc::new(RwLock::new(false)),
-            connection_task_handle: Arc::new(TokioMutex::new(None)),
-            heartbeat_task_handle: Arc::new(TokioMutex::new(None)),
-            last_pong_received_at: Arc::new(RwLock::new(None)),
-            local_task_state_cache: Arc::new(RwLock::new(None)),
-        }
+        let (client, events) = SatClient::new(SatClientConfig::default());
+        let client = Arc::new(client);
+        let outbox = Arc::new(OfflineOutbox::new(outbox_repo, client.clone(), app_handle.clone()));
+        let task_cache = Arc::new(task_cache);
+        tauri::async_runtime::spawn(Self::forward_events(app_handle.clone(), events, outbox.clone(), task_cache.clone()));
+        tauri::async_runtime::spawn(Self::restore_task_state(client.clone(), task_cache.clone()));
+        Self { client, outbox, task_cache, app_handle }
     }
 
-    /// 尝试连接到指定的 WebSocket 服务器 URL。
-    ///
-    /// 此方法会启动一个后台异步任务来处理 WebSocket 连接的整个生命周期，
-    /// 包括建立连接、接收消息、处理心跳、以及处理断开或错误。
-    /// 状态变更会通过 Tauri 事件通知前端。
-    ///
-    /// # 参数
-    /// * `url_str`: 要连接的 WebSocket 服务器 URL 字符串。
-    ///
-    /// # 返回
-    /// * `Result<(), String>`: 如果连接启动过程成功（即后台任务已安排），则返回 `Ok(())`。
-    ///   如果 URL 无效或在启动连接任务时发生即时错误，则返回 `Err(String)`。
-    ///   注意：实际的连接成功或失败将通过 `WS_CONNECTION_STATUS_EVENT` 事件异步通知。
-    pub async fn connect(&self, url_str: &str) -> Result<(), String> {
-        info!("[SatOnSiteMobile] WebSocketClientService::connect 调用，目标 URL: {}", url_str);
-
-        // 克隆需要在新任务中使用的 Arc 引用
-        let app_handle_clone = self.app_handle.clone();
-        let ws_send_channel_clone = self.ws_send_channel.clone();
-        let cloud_assigned_client_id_clone = self.cloud_assigned_client_id.clone();
-        let is_connected_status_clone = self.is_connected_status.clone();
-        let connection_task_handle_clone = self.connection_task_handle.clone();
-        let heartbeat_task_handle_clone = self.heartbeat_task_handle.clone();
-        let last_pong_received_at_clone = self.last_pong_received_at.clone();
-        let local_task_state_cache_clone = self.local_task_state_cache.clone();
-        let url_string = url_str.to_string();
-
-        // 如果当前已有连接任务在运行，先尝试取消它
-        let mut conn_task_guard = connection_task_handle_clone.lock().await;
-        if let Some(handle) = conn_task_guard.take() {
-            info!("[SatOnSiteMobile] 检测到之前的连接任务正在运行，正在尝试取消...");
-            handle.abort(); // 请求取消任务
-            match handle.await { // 等待任务结束（即使是被取消的）
-                Ok(_) => info!("[SatOnSiteMobile] 之前的连接任务已成功取消或完成。"),
-                Err(e) if e.is_cancelled() => info!("[SatOnSiteMobile] 之前的连接任务已被取消。"),
-                Err(e) => warn!("[SatOnSiteMobile] 等待之前的连接任务结束时发生错误: {:?}", e),
-            }
-            info!("[SatOnSiteMobile] 已清理之前的连接任务句柄。");
-            // 清理相关状态
-            *is_connected_status_clone.write().await = false;
-            *ws_send_channel_clone.lock().await = None;
-            *cloud_assigned_client_id_clone.write().await = None;
-            // 心跳任务句柄也应停止
-            let mut hb_task_guard = heartbeat_task_handle_clone.lock().await;
-            if let Some(hb_handle) = hb_task_guard.take() {
-                 hb_handle.abort();
-                 info!("[SatOnSiteMobile] 已请求取消相关的心跳任务。");
+    /// 把本地任务数据缓存中最近保存的任务状态恢复到业务客户端的缓存中。
+    async fn restore_task_state(client: Arc<SatClient>, task_cache: Arc<TaskCacheRepository>) {
+        match task_cache.latest_task_state() {
+            Ok(Some(cached)) => {
+                info!(
+                    "[现场端移动服务] 恢复本地保存的任务状态: 任务 {}, 版本 {}, 保存于 {}",
+                    cached.state.task_id, cached.state.version, cached.cached_at
+                );
+                client.restore_task_state(cached.state).await;
             }
-            *last_pong_received_at_clone.write().await = None;
+            Ok(None) => debug!("[现场端移动服务] 本地没有保存的任务状态，无需恢复。"),
+            Err(e) => error!("[现场端移动服务] 读取本地保存的任务状态失败: {}", e),
         }
-        drop(conn_task_guard); // 释放锁
+    }
 
-        // 启动新的异步任务来处理连接生命周期
-        let connection_task = tokio::spawn(async move {
-            let mut connection_attempt_successful = false;
-            let mut final_error_message: Option<String> = None;
-            let mut heartbeat_join_handle: Option<tokio::task::JoinHandle<()>> = None;
+    /// 共享的业务客户端，供需要类型化业务方法 (例如 `update_pre_check_item`、`feedback_step`) 的命令直接使用。
+    pub fn client(&self) -> &Arc<SatClient> {
+        &self.client
+    }
 
-            // --- 连接阶段 ---
-            info!("[SatOnSiteMobile] (连接任务) 开始尝试连接到: {}", url_string);
-            match transport::connect_client(url_string.clone()).await {
-                Ok(mut client_connection) => {
-                    info!("[SatOnSiteMobile] (连接任务) 连接成功建立。");
-                    connection_attempt_successful = true;
+    /// 离线待发队列，供提交业务操作和查看被拒绝或冲突的操作的命令使用。
+    pub fn outbox(&self) -> &Arc<OfflineOutbox> {
+        &self.outbox
+    }
 
-                    // --- 连接成功后的处理 (原 handle_on_open 逻辑) ---
-                    *is_connected_status_clone.write().await = true;
-                    *last_pong_received_at_clone.write().await = Some(Utc::now());
+    /// 任务数据本地缓存，供保存与读取模板、点表等离线数据的命令使用。
+    pub fn task_cache(&self) -> &Arc<TaskCacheRepository> {
+        &self.task_cache
+    }
 
-                    // 发送连接成功事件
-                    let event_payload = WsConnectionStatusEvent {
-                        connected: true,
-                        error_message: Some("成功连接到云端WebSocket服务".to_string()),
-                        client_id: None, 
+    /// 事件转发循环：把业务客户端的每个事件转换为对应的 Tauri 事件发给前端，直到业务客户端被丢弃。
+    async fn forward_events(
+        app_handle: AppHandle,
+        mut events: mpsc::UnboundedReceiver<SatClientEvent>,
+        outbox: Arc<OfflineOutbox>,
+        task_cache: Arc<TaskCacheRepository>,
+    ) {
+        // 最近一次注册成功时云端分配的客户端ID，随云端错误报告一并发给前端
+        let mut assigned_client_id: Option<String> = None;
+        while let Some(event) = events.recv().await {
+            match event {
+                SatClientEvent::ConnectionState(state) => {
+                    emit(&app_handle, WS_CONNECTION_STATE_EVENT, &WsConnectionStateEventPayload { state });
+                }
+                SatClientEvent::Connected { reconnected } => {
+                    let message = if reconnected {
+                        "已重新连接到云端WebSocket服务，正在恢复注册"
+                    } else {
+                        "成功连接到云端WebSocket服务"
                     };
-                    if let Err(e) = app_handle_clone.emit(WS_CONNECTION_STATUS_EVENT, &event_payload) {
-                        error!(
-                            "[SatOnSiteMobile] (连接任务) 发送 \"已连接\" 事件 ({}) 失败: {}", 
-                            WS_CONNECTION_STATUS_EVENT, e
-                        );
-                    }
-
-                    // 存储发送通道
-                    *ws_send_channel_clone.lock().await = Some(client_connection.ws_sender);
-
-                    // 启动心跳任务
-                    let hb_app_handle = app_handle_clone.clone();
-                    let hb_ws_send = ws_send_channel_clone.clone();
-                    let hb_last_pong = last_pong_received_at_clone.clone();
-                    let hb_is_connected = is_connected_status_clone.clone();
-                    let hb_cloud_id = cloud_assigned_client_id_clone.clone();
-                    let hb_task = tokio::spawn(async move {
-                        Self::run_heartbeat_loop(
-                            hb_app_handle,
-                            hb_ws_send,
-                            hb_last_pong,
-                            hb_is_connected,
-                            hb_cloud_id,
-                        ).await;
+                    emit(&app_handle, WS_CONNECTION_STATUS_EVENT, &WsConnectionStatusEvent {
+                        connected: true,
+                        error_message: Some(message.to_string()),
+                        client_id: None,
                     });
-                    heartbeat_join_handle = Some(hb_task);
-
-                    // --- 消息接收循环 ---
-                    loop {
-                        //Tokio 异步运行时库提供的一个非常强大的宏。它的核心功能是并发地等待多个不同的异步操作，并在其中任何一个操作完成时立即执行相应的代码块，而不会等待其他未完成的操作。
-                        //可以把它想象成一个岔路口，程序同时关注几条路上的信号。哪条路上的信号先来了（即哪个异步操作先完成了），程序就走哪条路，处理那个信号。其余信号不处理。
-                        tokio::select! {
-                            // 接收消息，当receive_message异步程序的future执行完成后将执行=>内的代码
-                            maybe_msg_result = transport::receive_message(&mut client_connection.ws_receiver) => {
-                                match maybe_msg_result {
-                                    Some(Ok(ws_msg)) => {
-                                        // --- 处理收到的消息 (原 handle_on_message 逻辑) ---
-                                         Self::process_received_message(
-                                             &app_handle_clone,
-                                             ws_msg,
-                                             &cloud_assigned_client_id_clone,
-                                             &last_pong_received_at_clone,
-                                             &local_task_state_cache_clone,
-                                         ).await;
-                                    }
-                                    Some(Err(e)) => {
-                                        // 更新为中心端更详细的错误处理
-                                        error!("[SatOnSiteMobile] (连接任务) 消息接收或解析时发生错误: {:?}", e);
-                                        match e {
-                                            rust_websocket_utils::error::WsError::WebSocketProtocolError(ws_err) => {
-                                                match ws_err {
-                                                    tokio_tungstenite::tungstenite::Error::ConnectionClosed => {
-                                                        warn!("[SatOnSiteMobile] (连接任务) WebSocket 连接已由对方关闭 (via Tungstenite Error)。");
-                                                        final_error_message = Some("WebSocket 连接已由对方关闭".to_string());
-                                                        break;
-                                                    }
-                                                    tokio_tungstenite::tungstenite::Error::Protocol(protocol_err) => {
-                                                        warn!("[SatOnSiteMobile] (连接任务) WebSocket 协议错误 (例如收到Close帧): {:?}", protocol_err);
-                                                        final_error_message = Some(format!("WebSocket 协议错误: {:?}", protocol_err));
-                                                        break;
-                                                    }
-                                                    other_tungstenite_err => {
-                                                        error!("[SatOnSiteMobile] (连接任务) 接收消息时发生 Tungstenite 错误: {:?}", other_tungstenite_err);
-                                                        final_error_message = Some(format!("接收消息时发生 Tungstenite 错误: {:?}", other_tungstenite_err));
-                                                        break;
-                                                    }
-                                                }
-                                            }
-                                            rust_websocket_utils::error::WsError::DeserializationError(de_err) => {
-                                                // 通常在 process_received_message 内部处理更具体的反序列化错误
-                                                // 但如果错误发生在 transport::receive_message 本身（例如，它尝试解析外层 WsMessage 结构失败）
-                                                error!("[SatOnSiteMobile] (连接任务) WsMessage级别 JSON (反)序列化错误: {:?}", de_err);
-                                                // 对于这种基本结构错误，可能也需要断开
-                                                final_error_message = Some(format!("核心消息结构解析错误: {:?}", de_err));
-                                                break; 
-                                            }
-                                            rust_websocket_utils::error::WsError::IoError(io_err) => {
-                                                error!("[SatOnSiteMobile] (连接任务) IO 错误: {:?}", io_err);
-                                                final_error_message = Some(format!("IO 错误: {:?}", io_err));
-                                                break;
-                                            }
-                                            other_ws_util_err => { 
-                                                error!("[SatOnSiteMobile] (连接任务) 接收消息时发生未处理的 WsUtilError: {:?}", other_ws_util_err);
-                                                final_error_message = Some(format!("接收消息时发生未处理的库错误: {:?}", other_ws_util_err));
-                                                break; 
-                                            }
-                                        }
-                                    }
-                                    None => {
-                                        info!("[SatOnSiteMobile] (连接任务) WebSocket 连接已由对端关闭。");
-                                        final_error_message = Some("连接已由对端关闭".to_string());
-                                        break; 
-                                    }
-                                }
-                            }
-                            // 检查外部断开信号
-                             _ = async { // 异步块，用于检测 is_connected_status_clone 状态的变化
-                                 loop {
-                                     tokio::time::sleep(Duration::from_millis(500)).await; // 每500毫秒检查一次
-                                     // 如果外部状态 (is_connected_status_clone) 变为 false (例如用户调用了 disconnect)
-                                     if !*is_connected_status_clone.read().await {
-                                         break; // 退出循环，从而使 select! 宏的这个分支被触发
-                                     }
-                                 }
-                             } => {
-                                 info!("[现场端移动服务] (连接任务) 检测到连接状态标志变为 false (可能由外部断开调用触发)，主动退出连接任务。");
-                                 final_error_message = final_error_message.or_else(|| Some("连接被主动断开".to_string()));
-                                 break; // 跳出主 select! 循环，进入清理阶段
-                            }
-                        }
-                    }
-                }
-                Err(e) => { // WebSocket 连接尝试本身失败 (例如，URL无效，服务器未响应)
-                    error!("[现场端移动服务] (连接任务) 尝试连接到 WebSocket 服务器 '{}' 失败: {}", url_string, e);
-                    final_error_message = Some(format!("连接到 WebSocket 服务器失败: {}", e));
-                    // 不需要 break，因为已经处于循环之外的错误处理路径，将直接进入清理阶段
                 }
-            }
-
-            // --- 清理阶段 ---
-            // 无论连接是正常关闭、发生错误还是被外部中断，都会执行此清理代码块。
-            info!("[现场端移动服务] (连接任务) 进入资源清理阶段...");
-
-            // 停止心跳任务
-            if let Some(hb_handle) = heartbeat_join_handle {
-                info!("[现场端移动服务] (连接任务) 正在中止并等待心跳任务 (heartbeat_task) 结束...");
-                hb_handle.abort(); // 请求心跳任务中止
-                // 这里可以添加 hb_handle.await 来等待任务实际结束，但 abort() 通常已足够使其停止
-                // 清理全局的心跳任务句柄引用
-                 let mut hb_guard = heartbeat_task_handle_clone.lock().await;
-                 if hb_guard.is_some() { 
-                    *hb_guard = None; 
-                    info!("[现场端移动服务] (连接任务) 心跳任务句柄已从全局状态中移除。");
-                 } 
-            }
-
-            // 更新最终的连接状态标记和相关数据
-            let was_previously_connected = *is_connected_status_clone.read().await; // 记录断开前的状态
-            *is_connected_status_clone.write().await = false; // 明确设置连接状态为 false
-            *cloud_assigned_client_id_clone.write().await = None; // 清除已分配的客户端ID
-            *ws_send_channel_clone.lock().await = None; // 清除 WebSocket 发送通道
-            *last_pong_received_at_clone.write().await = None; // 清除最后接收 Pong 的时间
-
-            // 发送最终的连接状态事件 (WsConnectionStatusEvent) 给前端
-            // 仅当连接尝试不成功 (从未连接上) 或之前是连接状态 (现在断开了) 时才发送事件，避免冗余。
-            if !connection_attempt_successful || was_previously_connected {
-                let event_payload = WsConnectionStatusEvent { 
-                    connected: false, // 明确为 false
-                    // 提供一个默认的错误/关闭消息，如果 final_error_message 中没有更具体的信息
-                    error_message: final_error_message.or_else(|| Some("连接已关闭或意外断开".to_string())), 
-                    client_id: None, // 断开后 client_id 无效
-                };
-                if let Err(e) = app_handle_clone.emit(WS_CONNECTION_STATUS_EVENT, &event_payload) {
-                    error!(
-                        "[现场端移动服务] (连接任务-清理) 发送连接已关闭或失败事件 ({}) 给前端失败: {}",
-                        WS_CONNECTION_STATUS_EVENT, e
-                    );
-                } else {
-                    info!("[现场端移动服务] (连接任务-清理) 已成功发送连接已关闭或失败事件 ({}) 给前端。", WS_CONNECTION_STATUS_EVENT);
+                SatClientEvent::Disconnected { reason } => {
+                    assigned_client_id = None;
+                    emit(&app_handle, WS_CONNECTION_STATUS_EVENT, &WsConnectionStatusEvent {
+                        connected: false,
+                        error_message: Some(reason),
+                        client_id: None,
+                    });
                 }
-            }
-
-            // 从主服务中移除此连接任务的句柄 (如果它仍然是当前的活动任务句柄)
-            // 这一步是为了确保如果快速连续调用 connect, 旧的、已结束的连接任务句柄不会被错误地持有。
-            // 注意：这里使用了原始的 self.connection_task_handle (通过 connection_task_handle_clone 访问)
-            // 而不是当前 tokio::spawn 任务自身的 JoinHandle。
-            // 目的是检查全局存储的句柄是否就是刚刚完成的这个任务的句柄。
-            let mut task_guard = connection_task_handle_clone.lock().await; // 获取主服务中存储的任务句柄的锁
-             if let Some(_current_handle_in_service) = task_guard.as_ref() {
-                 // 比较句柄是否指向同一个任务 (JoinHandle 不直接支持 PartialEq, 但可以通过 id 或其他方式间接比较，
-                 // 或者更简单地，如果此任务结束时全局句柄仍然是 Some(_)，则通常意味着它是当前句柄)
-                 // 为简化，这里假设如果存在句柄，就清除它。更严谨的做法可能需要比较任务ID。
-                 info!("[现场端移动服务] (连接任务) 正在从服务状态中移除已完成的连接任务句柄。");
-                 *task_guard = None;
-             } else {
-                 info!("[现场端移动服务] (连接任务) 服务状态中的连接任务句柄已为 None，无需移除 (可能已被新的连接任务覆盖或从未设置)。");
-             }
-             // MutexGuard 在此作用域结束时自动释放
-
-            info!("[现场端移动服务] (连接任务) 连接处理和清理流程已全部完成。");
-        }); // tokio::spawn 的连接任务结束
-
-        // 存储新启动的连接任务的句柄到 self.connection_task_handle
-        let mut task_guard = self.connection_task_handle.lock().await;
-        *task_guard = Some(connection_task); // 将新创建的后台任务的 JoinHandle 存储起来
-        info!("[现场端移动服务] WebSocket 后台连接任务已成功启动，并已存储其句柄。");
-
-        Ok(()) // connect 方法返回 Ok，表示启动连接过程的请求已接受并处理
-    }
-
-    /// 辅助函数：处理接收到的单个 WebSocket 消息。
-    async fn process_received_message(
-        app_handle: &AppHandle,
-        ws_msg: WsMessage,
-        cloud_assigned_client_id_state: &Arc<RwLock<Option<Uuid>>>,
-        last_pong_received_at_clone: &Arc<RwLock<Option<DateTime<Utc>>>>,
-        local_task_state_cache_clone: &Arc<RwLock<Option<TaskDebugState>>>,
-    ) {
-        info!(
-            "[现场端移动服务] (处理消息) 收到消息: 类型='{}', 消息ID='{}', 时间戳='{}', Payload摘要 (前80字符)='{}...'",
-            ws_msg.message_type,
-            ws_msg.message_id,
-            ws_msg.timestamp,
-            ws_msg.payload.chars().take(80).collect::<String>() // 获取 Payload 的前80个字符作为摘要
-        );
-
-        // --- 根据消息类型分发处理 ---
-
-        // 处理 Pong 消息
-        if ws_msg.message_type == PONG_MESSAGE_TYPE {
-            info!("[现场端移动服务] (处理消息) 收到来自云端的 Pong 消息。");
-            *last_pong_received_at_clone.write().await = Some(Utc::now());
-        }
-        // 处理 RegisterResponse 消息
-        else if ws_msg.message_type == REGISTER_RESPONSE_MESSAGE_TYPE {
-            match serde_json::from_str::<RegisterResponsePayload>(&ws_msg.payload) {
-                Ok(payload) => {
-                    info!(
-                        "[现场端移动服务] (处理消息) 成功解析类型为 '{}' 的响应: success={}, msg='{:?}', client_id='{:?}', group_id='{:?}', role='{:?}'",
-                        REGISTER_RESPONSE_MESSAGE_TYPE,
-                        payload.success, 
-                        payload.message, 
-                        payload.assigned_client_id, // 这是 Uuid 类型
-                        payload.effective_group_id, 
-                        payload.effective_role
-                    );
-                    if payload.success {
-                        let client_id_uuid = payload.assigned_client_id;
-
-                        {
-                            let mut id_guard = cloud_assigned_client_id_state.write().await;
-                            *id_guard = Some(client_id_uuid);
-                            info!("[现场端移动服务] (处理消息) 已成功存储云端分配的客户端ID: {}", client_id_uuid);
-                        }
-
-                        let conn_status_payload = WsConnectionStatusEvent {
+                SatClientEvent::Registration { response, handling } => {
+                    let reg_status_payload = if response.success {
+                        assigned_client_id = Some(response.assigned_client_id.to_string());
+                        emit(&app_handle, WS_CONNECTION_STATUS_EVENT, &WsConnectionStatusEvent {
                             connected: true,
-                            client_id: Some(client_id_uuid.to_string()), // 转换为 String 给前端
+                            client_id: assigned_client_id.clone(),
                             error_message: Some("客户端注册成功，已从云端获取并分配客户端ID。".to_string()),
-                        };
-                        if let Err(e) = app_handle.emit(WS_CONNECTION_STATUS_EVENT, conn_status_payload.clone()) {
-                            error!("[现场端移动服务] (处理消息) 发送注册成功后连接状态更新事件 ({}) 给前端失败: {}", WS_CONNECTION_STATUS_EVENT, e);
-                        } else {
-                            info!("[现场端移动服务] (处理消息) 已成功发送注册成功后连接状态更新事件 ({}) 给前端。", WS_CONNECTION_STATUS_EVENT);
-                        }
-
-                        let reg_status_payload = WsRegistrationStatusEventPayload {
+                        });
+                        WsRegistrationStatusEventPayload {
                             success: true,
-                            message: payload.message.clone(), // 使用云端返回的成功消息
-                            assigned_client_id: Some(client_id_uuid.to_string()), 
-                            group_id: payload.effective_group_id.clone(),
-                            role: payload.effective_role.as_ref().map(|r| r.to_string()), // 从 payload.effective_role 获取
-                            // TODO: 从 RegisterResponsePayload 中获取 task_id 并填充。
-                            // 目前 RegisterResponsePayload (common_models) 定义中可能不直接包含 task_id，
-                            // 需要确认云端是否会返回，以及前端是否需要通过此事件获取 task_id。
-                            // 如果云端在 RegisterResponsePayload 中返回了 task_id (例如，如果注册时允许 task_id 不同于请求的)
-                            // 则应在这里填充: payload.task_id.clone() (假设字段名为 task_id)
-                            task_id: None, 
-                        };
-                        if let Err(e) = app_handle.emit(WS_REGISTRATION_STATUS_EVENT, reg_status_payload.clone()) {
-                             error!("[现场端移动服务] (处理消息) 发送WebSocket客户端注册成功事件 ({}) 给前端失败: {}", WS_REGISTRATION_STATUS_EVENT, e);
-                         } else {
-                            info!("[现场端移动服务] (处理消息) 已成功发送WebSocket客户端注册成功事件 ({}) 给前端。", WS_REGISTRATION_STATUS_EVENT);
-                         }
-
+                            message: response.message,
+                            assigned_client_id: assigned_client_id.clone(),
+                            group_id: response.effective_group_id,
+                            role: response.effective_role.map(|r| r.to_string()),
+                            task_id: None,
+                            error_code: None,
+                            handling: None,
+                            resumed: response.resumed,
+                        }
                     } else {
-                        // 注册失败
-                        warn!(
-                            "[现场端移动服务] (处理消息) 收到来自云端的客户端注册失败响应: 原因='{:?}'",
-                            payload.message
-                        );
-                        // 发送注册失败事件给前端
-                        let reg_status_payload = WsRegistrationStatusEventPayload {
+                        // 注册失败时附带错误码与建议处理方式 (例如角色已被占用时提示用户稍后再试)
+                        WsRegistrationStatusEventPayload {
                             success: false,
-                            message: payload.message.clone(), // 使用云端返回的失败消息
+                            message: response.message,
                             assigned_client_id: None,
                             group_id: None,
-                            role: None, // 注册失败时 role 也为 None
+                            role: None,
                             task_id: None,
-                        };
-                         if let Err(e) = app_handle.emit(WS_REGISTRATION_STATUS_EVENT, reg_status_payload.clone()) {
-                             error!("[现场端移动服务] (处理消息) 发送WebSocket客户端注册失败事件 ({}) 给前端失败: {}", WS_REGISTRATION_STATUS_EVENT, e);
-                         } else {
-                            info!("[现场端移动服务] (处理消息) 已成功发送WebSocket客户端注册失败事件 ({}) 给前端。", WS_REGISTRATION_STATUS_EVENT);
-                         }
-                    }
-                }
-                Err(e) => { // 反序列化 RegisterResponsePayload 失败
-                    error!(
-                        "[现场端移动服务] (处理消息) 反序列化来自云端的 '{}' 类型的响应 Payload 失败: {}. 原始Payload: '{}'",
-                        REGISTER_RESPONSE_MESSAGE_TYPE, e, ws_msg.payload
-                    );
-                    // 尝试发送一个通用的连接错误事件，提示处理消息时发生内部错误
-                    let current_id_str = (*cloud_assigned_client_id_state.read().await).as_ref().map_or_else(|| "无".to_string(), |id_ref| id_ref.to_string());
-                    let error_payload = WsConnectionStatusEvent {
-                        connected: cloud_assigned_client_id_state.read().await.is_some(), // 保持当前已知的连接状态
-                        client_id: if current_id_str == "无" { None } else { Some(current_id_str) }, // 修正client_id的逻辑
-                        error_message: Some(format!("处理来自云端的注册响应消息时发生内部错误: {}", e)),
+                            error_code: response.error_code,
+                            handling,
+                            resumed: false,
+                        }
                     };
-                    if let Err(emit_e) = app_handle.emit(WS_CONNECTION_STATUS_EVENT, error_payload.clone()) {
-                        error!("[现场端移动服务] (处理消息) 发送处理注册响应失败的连接状态事件 ({}) 给前端失败: {}", WS_CONNECTION_STATUS_EVENT, emit_e);
+                    let registered = reg_status_payload.success;
+                    emit(&app_handle, WS_REGISTRATION_STATUS_EVENT, &reg_status_payload);
+                    if registered {
+                        // 重放断线期间排队的业务操作。在独立任务中进行：重放要逐个等待云端确认，
+                        // 在此处等待会使重放期间的确认、状态更新等事件直到重放结束才转发给前端。
+                        let outbox = outbox.clone();
+                        tauri::async_runtime::spawn(async move {
+                            if let Err(e) = outbox.flush().await {
+                                error!("[现场端移动服务] 重放离线待发队列失败: {}", e);
+                            }
+                        });
                     }
                 }
-            }
-        }
-        // 处理 PartnerStatusUpdate 消息
-        else if ws_msg.message_type == PARTNER_STATUS_UPDATE_MESSAGE_TYPE {
-             match serde_json::from_str::<PartnerStatusPayload>(&ws_msg.payload) {
-                 Ok(payload) => {
-                     info!(
-                         "[现场端移动服务] (处理消息) 成功解析类型为 '{}' 的伙伴状态更新: 伙伴角色='{}', 是否在线={}, 伙伴客户端ID='{}', 组ID='{}'",
-                         PARTNER_STATUS_UPDATE_MESSAGE_TYPE,
-                         payload.partner_role, // ClientRole 枚举，会通过 Debug trait 打印
-                         payload.is_online,
-                         payload.partner_client_id, // Uuid 类型
-                         payload.group_id
-                     );
-                     // 构造发送给前端的 WsPartnerStatusEventPayload 事件负载
-                     // 注意：WsPartnerStatusEventPayload (在 event.rs 定义) 字段需要与此处匹配
-                     let event_payload = WsPartnerStatusEventPayload {
-                         partner_role: payload.partner_role.to_string(), // ClientRole 枚举转换为字符串
-                         is_online: payload.is_online,
-                         partner_client_id: Some(payload.partner_client_id.to_string()), // Uuid 转换为字符串
-                         group_id: Some(payload.group_id.clone()), // 组ID
-                     };
-                     if let Err(e) = app_handle.emit(WS_PARTNER_STATUS_EVENT, event_payload.clone()) { 
-                          error!("[现场端移动服务] (处理消息) 发送伙伴客户端状态更新事件 ({}) 给前端失败: {}", WS_PARTNER_STATUS_EVENT, e);
-                      } else {
-                        info!("[现场端移动服务] (处理消息) 已成功发送伙伴客户端状态更新事件 ({}) 给前端。", WS_PARTNER_STATUS_EVENT);
-                      }
-                 }
-                 Err(e) => { // 反序列化 PartnerStatusPayload 失败
-                     error!(
-                         "[现场端移动服务] (处理消息) 反序列化来自云端的 '{}' 类型的伙伴状态更新 Payload 失败: {}. 原始Payload: '{}'",
-                         PARTNER_STATUS_UPDATE_MESSAGE_TYPE, e, ws_msg.payload
-                     );
-                 }
-             }
-        }
-        // 处理 TaskStateUpdate 消息
-        else if ws_msg.message_type == TASK_STATE_UPDATE_MESSAGE_TYPE {
-            match serde_json::from_str::<TaskDebugState>(&ws_msg.payload) { // TaskDebugState 来自 common_models
-                 Ok(new_state) => {
-                     info!(
-                         "[现场端移动服务] (处理消息) 成功解析类型为 '{}' 的任务状态更新: 任务ID='{}', 最后更新者='{:?}', 时间戳={}",
-                         TASK_STATE_UPDATE_MESSAGE_TYPE,
-                         new_state.task_id,
-                         new_state.last_updated_by_role,
-                         new_state.last_update_timestamp
-                     );
-                     info!("[现场端移动服务] (处理消息) 收到的完整 TaskDebugState: {:?}", new_state);
-
-                     // 更新本地缓存中的任务状态
-                     *local_task_state_cache_clone.write().await = Some(new_state.clone()); // 克隆 new_state 以存入缓存
-                     info!("[现场端移动服务] (处理消息) 本地任务状态缓存已更新为来自云端的最新状态 (任务ID: {}).", new_state.task_id);
-                     
-                     // 构造发送给前端的 LocalTaskStateUpdatedEventPayload 事件负载
-                     // event.rs 中的 LocalTaskStateUpdatedEventPayload.new_state 现在直接是 TaskDebugState 类型
-                     let event_payload = LocalTaskStateUpdatedEventPayload { new_state: new_state.clone() }; // 直接使用克隆的 new_state
-                     if let Err(e) = app_handle.emit(LOCAL_TASK_STATE_UPDATED_EVENT, event_payload.clone()) { 
-                         error!("[现场端移动服务] (处理消息) 发送本地任务状态已更新事件 ({}) 给前端失败: {}", LOCAL_TASK_STATE_UPDATED_EVENT, e);
-                     } else {
-                        info!("[现场端移动服务] (处理消息) 已成功发送本地任务状态已更新事件 ({}) 给前端。", LOCAL_TASK_STATE_UPDATED_EVENT);
-                     }
-                 }
-                 Err(e) => { // 反序列化 TaskDebugState 失败
-                     error!(
-                         "[现场端移动服务] (处理消息) 反序列化来自云端的 '{}' 类型的任务状态更新 Payload 失败: {}. 原始Payload: '{}'",
-                         TASK_STATE_UPDATE_MESSAGE_TYPE, e, ws_msg.payload
-                     );
-                 }
-             }
-        }
-        // 处理 Echo 响应消息
-        else if ws_msg.message_type == ECHO_MESSAGE_TYPE { // 注意：通常 Echo 是客户端发往服务端，服务端响应。这里假设是处理服务端的 Echo 响应。
-            match serde_json::from_str::<EchoPayload>(&ws_msg.payload) { // EchoPayload 来自 common_models
-                Ok(payload) => {
-                    info!(
-                        "[现场端移动服务] (处理消息) 成功解析类型为 '{}' 的 Echo 响应: 内容='{}'",
-                        ECHO_MESSAGE_TYPE, payload.content
-                    );
-                    // 构造 EchoResponseEventPayload 事件负载并发送给前端
-                    let event_payload = EchoResponseEventPayload { content: payload.content }; // content 字段来自 EchoPayload
-                    if let Err(e) = app_handle.emit(ECHO_RESPONSE_EVENT, event_payload.clone()) { 
-                        error!("[现场端移动服务] (处理消息) 发送 Echo 响应事件给前端失败: {}", e);
-                    } else {
-                        info!("[现场端移动服务] (处理消息) 已成功发送 Echo 响应事件给前端。");
+                SatClientEvent::PartnerStatus(partner) => {
+                    emit(&app_handle, WS_PARTNER_STATUS_EVENT, &WsPartnerStatusEventPayload {
+                        partner_role: partner.partner_role.to_string(),
+                        is_online: partner.is_online,
+                        link_suspect: partner.link_suspect,
+                        partner_client_id: Some(partner.partner_client_id.to_string()),
+                        group_id: Some(partner.group_id),
+                    });
+                }
+                SatClientEvent::TaskStateUpdated(new_state) => {
+                    // 持久化最新状态，应用离线重启后据此恢复
+                    if let Err(e) = task_cache.save_task_state(&new_state) {
+                        error!("[现场端移动服务] 保存任务状态到本地缓存失败: {}", e);
                     }
+                    emit(&app_handle, LOCAL_TASK_STATE_UPDATED_EVENT, &LocalTaskStateUpdatedEventPayload { new_state });
                 }
-                Err(e) => { // 反序列化 EchoPayload 失败
-                    error!(
-                        "[现场端移动服务] (处理消息) 反序列化来自云端的 '{}' 类型的 Echo 响应 Payload 失败: {}. 原始Payload: '{}'",
-                        ECHO_MESSAGE_TYPE, e, ws_msg.payload
+                SatClientEvent::StartSingleTestStep { command_message_id, step } => {
+                    emit(&app_handle, WS_START_SINGLE_TEST_STEP_EVENT, &WsStartSingleTestStepEventPayload { command_message_id, step });
+                }
+                SatClientEvent::ServerError { error, handling } => {
+                    // 将错误信息随连接状态一并告知前端
+                    emit(&app_handle, WS_CONNECTION_STATUS_EVENT, &WsConnectionStatusEvent {
+                        connected: true,
+                        client_id: assigned_client_id.clone(),
+                        error_message: Some(format!("收到来自云端服务的错误报告: {}", error.error)),
+                    });
+                    // 同时发送结构化的云端错误事件 (与中心端对齐)，前端根据错误码和建议处理方式决定如何提示用户
+                    emit(&app_handle, WS_SERVER_ERROR_EVENT, &WsServerErrorEventPayload {
+                        error_message: error.error,
+                        original_message_type: error.original_message_type,
+                        code: error.code,
+                        message_key: error.message_key,
+                        details: error.details,
+                        handling,
+                    });
+                }
+                SatClientEvent::BusinessAck { request_message_id, ack, handling } => {
+                    // 使界面可以明确显示操作是否已被云端接受
+                    emit(&app_handle, WS_BUSINESS_ACK_EVENT, &WsBusinessAckEventPayload { request_message_id, ack, handling });
+                }
+                SatClientEvent::VersionConflict(conflict) => {
+                    emit(&app_handle, WS_VERSION_CONFLICT_EVENT, &WsVersionConflictEventPayload {
+                        request_message_id: conflict.request_message_id,
+                        original_message_type: conflict.original_message_type,
+                        local_payload: conflict.local_payload,
+                        current_state: conflict.current_state,
+                    });
+                }
+                SatClientEvent::GroupRoster(roster) => {
+                    emit(&app_handle, WS_GROUP_ROSTER_EVENT, &WsGroupRosterEventPayload { roster });
+                }
+                SatClientEvent::GroupLeft(left) => {
+                    Self::deactivate_cached_task(&task_cache);
+                    emit(&app_handle, WS_GROUP_LEFT_EVENT, &WsGroupLeftEventPayload { left });
+                }
+                SatClientEvent::Echo(echo) => {
+                    emit(&app_handle, ECHO_RESPONSE_EVENT, &EchoResponseEventPayload { content: echo.content });
+                }
+                SatClientEvent::LinkQuality(quality) => {
+                    emit(&app_handle, WS_LINK_QUALITY_EVENT, &WsLinkQualityEventPayload { quality });
+                }
+                SatClientEvent::SlotReleased(released) => {
+                    // 槽位已由其他客户端接替，本端不再属于该任务组
+                    warn!("[现场端移动服务] 本端在组 '{}' 的槽位已被接替 (原因: {:?})。", released.group_id, released.reason);
+                    Self::deactivate_cached_task(&task_cache);
+                }
+                SatClientEvent::SlotTakeoverPrompt(_) | SatClientEvent::GroupOpened(_) => {
+                    // 槽位接管请求与开组只涉及控制中心角色
+                    debug!("[现场端移动服务] 忽略仅适用于控制中心的事件: {:?}", event);
+                }
+                SatClientEvent::Unhandled(ws_msg) => {
+                    warn!(
+                        "[现场端移动服务] 收到未知的 WebSocket 消息类型: '{}'。消息ID: {}, 完整消息: {:?}",
+                        ws_msg.message_type, ws_msg.message_id, ws_msg
                     );
                 }
             }
         }
-        // 处理来自云端的错误响应消息
-        else if ws_msg.message_type == ERROR_RESPONSE_MESSAGE_TYPE {
-             match serde_json::from_str::<ErrorResponsePayload>(&ws_msg.payload) { // ErrorResponsePayload 来自 common_models
-                 Ok(payload) => {
-                     error!(
-                         "[现场端移动服务] (处理消息) 收到来自云端的错误响应消息: 错误内容='{}', 原始消息类型可能为='{:?}'",
-                         payload.error, // 错误描述信息
-                         payload.original_message_type // 触发错误的原始消息类型 (可选)
-                     );
-                     // 更新连接状态事件，将错误信息传递给前端
-                     let current_client_id_str = (*cloud_assigned_client_id_state.read().await).as_ref().map_or_else(|| String::new(), |id_ref| id_ref.to_string());
-                     let error_event_payload = WsConnectionStatusEvent {
-                        connected: cloud_assigned_client_id_state.read().await.is_some(), // 保持当前已知的连接状态
-                        client_id: if current_client_id_str.is_empty() { None } else { Some(current_client_id_str) },
-                        error_message: Some(format!("收到来自云端服务的错误报告: {}", payload.error)),
-                     };
-                     if let Err(e) = app_handle.emit(WS_CONNECTION_STATUS_EVENT, error_event_payload.clone()) {
-                        error!("[现场端移动服务] (处理消息) 发送云端错误报告的连接状态事件 ({}) 给前端失败: {}", WS_CONNECTION_STATUS_EVENT, e);
-                     }
-                 }
-                 Err(e) => { // 反序列化 ErrorResponsePayload 失败
-                     error!(
-                         "[现场端移动服务] (处理消息) 反序列化来自云端的 '{}' 类型的错误响应 Payload 失败: {}. 原始Payload: '{}'",
-                         ERROR_RESPONSE_MESSAGE_TYPE, e, ws_msg.payload
-                     );
-                 }
-             }
-        }
-        // 处理未知的消息类型
-        else {
-            warn!(
-                "[现场端移动服务] (处理消息) 收到未知的 WebSocket 消息类型: '{}'。消息ID: {}, 完整消息: {:?}",
-                ws_msg.message_type, ws_msg.message_id, ws_msg
-            );
+        info!("[现场端移动服务] 业务客户端的事件流已结束，事件转发任务退出。");
+    }
+
+    /// 本端已离开任务组：本地缓存的任务状态不再代表当前任务 (数据保留，仍可按任务ID读取)。
+    fn deactivate_cached_task(task_cache: &TaskCacheRepository) {
+        if let Err(e) = task_cache.deactivate_task_states() {
+            error!("[现场端移动服务] 更新本地任务状态缓存失败: {}", e);
         }
     }
 
-    /// 辅助异步函数：在独立的 Tokio 任务中运行心跳循环。
-    ///
-    /// 此函数由 `connect_client` 在成功建立 WebSocket 连接后启动。
+    /// 尝试连接到指定的 WebSocket 服务器 URL。
     ///
-    /// # 主要职责:
-    /// 1. 定期 (每 `HEARTBEAT_INTERVAL_SECONDS` 秒) 执行以下操作：
-    ///    a. 检查 WebSocket 连接是否仍然有效 (`is_connected_status_clone`)。
-    ///    b. 检查自上次收到 Pong 消息以来是否已超时 (`PONG_TIMEOUT_SECONDS`)。
-    ///       - 如果超时，认为连接已死，设置 `is_connected_status_clone` 为 `false` 以触发主连接任务的清理和退出，并发送连接错误事件给前端。
-    ///    c. 如果连接有效且未超时，则构建一个 `PingPayload`，封装在 `WsMessage` 中，并通过 `ws_send_channel_clone` 发送给云端服务器。
-    ///       - 如果发送 Ping 失败，也认为连接已断开，设置 `is_connected_status_clone` 为 `false`。
-    /// 2. 当检测到连接断开或发送 Ping 失败时，心跳循环会自行终止。
+    /// 连接失败或断开后按指数退避 (带抖动) 自动重试，重连成功后自动重新发送最后一次的 `Register`。
     ///
-    /// # 参数:
-    /// * `app_handle`: Tauri `AppHandle` 的克隆，用于向前端发送事件 (例如 Pong 超时事件)。
-    /// * `ws_send_channel_clone`: 对共享的 WebSocket 发送通道 (`SplitSink`) 的 `Arc<TokioMutex<...>>` 引用。
-    /// * `last_pong_received_at_clone`: 对共享状态 `last_pong_received_at` (上次收到 Pong 的时间) 的 `Arc<RwLock<...>>` 引用。
-    /// * `is_connected_status_clone`: 对共享的连接状态标志 (`bool`) 的 `Arc<RwLock<...>>` 引用。
-    /// * `_cloud_assigned_client_id_clone`: 对云端分配的客户端 ID 的 `Arc<RwLock<...>>` 引用 (当前在此函数中未使用，但保留以备将来扩展)。
-    async fn run_heartbeat_loop(
-        app_handle: AppHandle,
-        ws_send_channel_clone: Arc<TokioMutex<Option<SplitSink<ClientWsStream, TungsteniteMessage>>>>,
-        last_pong_received_at_clone: Arc<RwLock<Option<DateTime<Utc>>>>,
-        is_connected_status_clone: Arc<RwLock<bool>>,
-        _cloud_assigned_client_id_clone: Arc<RwLock<Option<Uuid>>>, // 参数保留，但当前未使用，加下划线
-    ) {
-        info!("[现场端移动服务] (心跳任务) 心跳维持任务已启动。心跳间隔: {}秒，Pong超时: {}秒。", HEARTBEAT_INTERVAL_SECONDS, PONG_TIMEOUT_SECONDS);
-        // 创建一个固定周期的计时器
-        let mut interval = tokio::time::interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECONDS));
-            loop {
-            // 等待下一个心跳间隔点到达
-            interval.tick().await;
-
-            // 1. 检查外部连接状态是否仍然指示为"已连接"
-            if !*is_connected_status_clone.read().await {
-                info!("[现场端移动服务] (心跳任务) 检测到主连接状态已变为未连接，心跳任务将自动终止。");
-                    break; // 退出心跳循环
-                }
-
-            // 2. 检查 Pong 响应是否超时
-            let now = Utc::now();
-            let last_pong_time = *last_pong_received_at_clone.read().await;
-            if let Some(last_pong) = last_pong_time { // 如果之前收到过 Pong
-                // 计算从上次收到 Pong 到现在的时间差，与 Pong 超时阈值比较
-                if now.signed_duration_since(last_pong) > chrono::Duration::seconds((HEARTBEAT_INTERVAL_SECONDS + PONG_TIMEOUT_SECONDS) as i64) {
-                    warn!(
-                        "[现场端移动服务] (心跳任务) Pong 响应超时！最后一次收到 Pong 是在: {:?} (UTC), 当前时间: {:?} (UTC)。将视作连接断开。",
-                        last_pong, now
-                    );
-                    // 发送连接错误事件给前端，通知 Pong 超时
-                    let event_payload = WsConnectionStatusEvent { 
-                        connected: false,
-                        error_message: Some("与云端服务的心跳响应超时，连接可能已丢失。".to_string()),
-                        client_id: None, // 超时后，之前的 client_id 可能已失效
-                    };
-                    if let Err(e) = app_handle.emit(WS_CONNECTION_STATUS_EVENT, &event_payload) {
-                        error!("[现场端移动服务] (心跳任务) 发送Pong响应超时导致连接断开事件 ({}) 给前端失败: {}", WS_CONNECTION_STATUS_EVENT, e);
-                    }
-                    // 将主连接状态设置为 false，这将使得主连接任务 (connect_client) 中的读取循环和 select! 终止，进而进入清理阶段。
-                    *is_connected_status_clone.write().await = false;
-                    info!("[现场端移动服务] (心跳任务) 因 Pong 响应超时，已将主连接状态设置为 false，心跳任务即将终止。");
-                    break; // 退出心跳循环
-                }
-            } else {
-                // 初始连接后，可能尚未收到第一个 Pong
-                debug!("[现场端移动服务] (心跳任务) 当前尚未收到任何 Pong 响应 (可能处于连接初期或上一个 Pong 间隔内)。");
-            }
-            
-            // 3. 如果连接仍然被认为是活动的，则发送 Ping 消息
-            let ping_payload = PingPayload {}; // PingPayload 是一个空结构体
-            match WsMessage::new(PING_MESSAGE_TYPE.to_string(), &ping_payload) {
-                Ok(ws_message) => {
-                    info!(
-                        "[现场端移动服务] (心跳任务) 准备向云端发送 Ping 消息 (ID: {}, 类型: {}) ...",
-                        ws_message.message_id, ws_message.message_type
-                    );
-                    // 获取对 WebSocket 发送通道的独占访问权限
-                    if let Some(sender) = ws_send_channel_clone.lock().await.as_mut() {
-                         match serde_json::to_string(&ws_message) { // 将 WsMessage 序列化为 JSON 字符串
-                             Ok(msg_json) => {
-                                // 发送文本类型的 WebSocket 消息 (Ping)
-                                match sender.send(TungsteniteMessage::Text(msg_json)).await { 
-                                Ok(_) => {
-                                        info!("[现场端移动服务] (心跳任务) Ping 消息 (ID: {}) 已成功发送至云端。", ws_message.message_id);
-                                }
-                                Err(e) => { // 发送 Ping 失败
-                                        error!("[现场端移动服务] (心跳任务) 发送 Ping 消息 (ID: {}) 失败: {}。将视作连接断开。", ws_message.message_id, e);
-                                        // 发送失败也应视为连接问题，设置主连接状态为 false
-                                        *is_connected_status_clone.write().await = false;
-                                        info!("[现场端移动服务] (心跳任务) 因 Ping 消息发送失败，已将主连接状态设置为 false，心跳任务即将终止。");
-                                        break; // 退出心跳循环
-                                    }
-                                }
-                            }
-                            Err(e) => { // 序列化 WsMessage (用于Ping) 失败
-                                error!("[现场端移动服务] (心跳任务) 序列化 Ping 类型的 WsMessage (ID: {}) 失败: {}。将跳过此次 Ping 发送。", ws_message.message_id, e);
-                                // 这种内部错误不直接断开连接，但需要记录
-                            }
-                        }
-                    } else {
-                        // 如果 ws_send_channel 是 None，说明连接已断开或正在断开
-                        warn!("[现场端移动服务] (心跳任务) WebSocket 发送通道当前不可用 (可能连接已关闭)，无法发送 Ping。心跳任务将终止。");
-                        break; // 退出心跳循环
-                    }
-                }
-            Err(e) => { // 创建 Ping 消息的 WsMessage 结构体失败
-                    error!("[现场端移动服务] (心跳任务) 构建 Ping 类型的 WsMessage 失败: {}。将跳过此次 Ping 发送。", e);
-                    // 这种内部错误不直接断开连接，但需要记录
-                }
-            }
-        } // loop 结束
-        info!("[现场端移动服务] (心跳任务) 心跳维持任务已结束。");
+    /// # 返回
+    /// * `Result<(), String>`: 连接过程已在后台启动时返回 `Ok(())`。
+    ///   实际的连接成功或失败将通过 `WS_CONNECTION_STATUS_EVENT` 与 `WS_CONNECTION_STATE_EVENT` 事件异步通知。
+    pub async fn connect(&self, url_str: &str) -> Result<(), String> {
+        info!("[SatOnSiteMobile] WebSocketClientService::connect 调用，目标 URL: {}", url_str);
+        self.client.connect(url_str).await;
+        Ok(())
     }
 
-    /// 客户端主动请求断开当前的 WebSocket 连接。
+    /// 客户端主动请求断开当前的 WebSocket 连接 (不再自动重连)。
     ///
-    /// # 主要流程:
-    /// 1. 检查当前是否已连接 (`is_connected_status`)。
-    ///    - 如果未连接，则记录日志并直接返回 `Ok(())`。
-    /// 2. 如果已连接：
-    ///    a. 将 `is_connected_status` 设置为 `false`。这将作为信号，使得 `connect_client` 中的主读取循环和心跳任务 (`run_heartbeat_loop`) 检测到并开始退出流程。
-    ///    b. 尝试向服务器发送一个 WebSocket Close 帧，以优雅地关闭连接。
-    ///    c. 中止 (abort) 当前正在运行的连接任务 (`connection_task_handle`)，以确保其彻底停止并执行清理逻辑。
-    ///    d. 向前端发送一个 `WsConnectionStatusEvent` 事件，通知连接已主动断开。
-    ///
-    /// # 返回
-    /// * `Result<(), String>`: 通常返回 `Ok(())`。如果发送事件失败，会记录错误但不会使此方法失败。
+    /// 若之前处于连接状态，向前端发送一个 `WsConnectionStatusEvent` 事件，通知连接已主动断开。
     pub async fn disconnect(&self) -> Result<(), String> {
         info!("[现场端移动服务] WebSocketClientService::disconnect (主动断开连接) 方法被调用。");
-        // 获取写锁以修改连接状态
-        let mut is_connected_guard = self.is_connected_status.write().await;
-        if *is_connected_guard { // 检查是否真的处于连接状态
-            info!("[现场端移动服务] (主动断开) 当前 WebSocket 处于连接状态，将执行断开流程...");
-            // 1. 设置共享的连接状态标志为 false。这是给 connect_client 任务和心跳任务的主要信号。
-            *is_connected_guard = false; 
-            drop(is_connected_guard); // 及时释放写锁，允许其他任务读取更新后的状态
-
-            // 2. 尝试向 WebSocket 服务器发送一个 Close 帧，进行优雅关闭。
-            let mut sender_guard = self.ws_send_channel.lock().await;
-            if let Some(sender) = sender_guard.as_mut() {
-                info!("[现场端移动服务] (主动断开) 尝试向云端 WebSocket 服务器发送 Close 帧...");
-                match sender.close().await {
-                   Ok(_) => info!("[现场端移动服务] (主动断开) WebSocket Close 帧已成功发送或请求已发出。"),
-                   Err(e) => warn!("[现场端移动服务] (主动断开) 尝试发送 WebSocket Close 帧失败 (可能连接此时已从远端断开): {}", e),
-                }
-            }
-            drop(sender_guard); // 释放发送通道的锁
+        let was_connected = self.client.is_connected();
+        self.client.disconnect().await;
 
-            // 3. 请求中止 (abort) 后台的连接处理任务 (connect_client)。
-            // abort() 会使得任务在下一个 .await 点被取消。
-             let task_guard = self.connection_task_handle.lock().await;
-             if let Some(handle) = task_guard.as_ref() {
-                 info!("[现场端移动服务] (主动断开) 请求中止正在运行的 WebSocket 连接任务...");
-                 handle.abort();
-             }
-             drop(task_guard); // 释放任务句柄的锁
-
-            // 4. 向前端发送一个明确的"主动断开"状态事件。
-            let event_payload = WsConnectionStatusEvent {
+        if was_connected {
+            emit(&self.app_handle, WS_CONNECTION_STATUS_EVENT, &WsConnectionStatusEvent {
                 connected: false,
                 error_message: Some("客户端已主动发起断开 WebSocket 连接的操作。".to_string()),
                 client_id: None, // 断开后，之前分配的 client_id 通常不再有效
-            };
-            if let Err(e) = self.app_handle.emit(WS_CONNECTION_STATUS_EVENT, &event_payload) {
-                error!(
-                    "[现场端移动服务] (主动断开) 发送主动断开连接事件 ({}) 给前端失败: {}",
-                    WS_CONNECTION_STATUS_EVENT, e
-                );
-            } else {
-                info!("[现场端移动服务] (主动断开) 已成功发送主动断开连接事件 ({}) 给前端。", WS_CONNECTION_STATUS_EVENT);
-            }
-             Ok(())
+            });
         } else {
-             drop(is_connected_guard); // 确保锁被释放
-             info!("[现场端移动服务] (主动断开) WebSocket 当前本就未连接，无需执行断开操作。");
-            Ok(())
+            info!("[现场端移动服务] (主动断开) WebSocket 当前本就未连接，已停止自动重连。");
         }
+        Ok(())
     }
 
     /// 检查当前 WebSocket 是否已连接。
-    ///
-    /// # 返回
-    /// * `bool`: 如果 `is_connected_status` 状态为 `true`，则返回 `true`；否则返回 `false`。
     pub async fn is_connected(&self) -> bool {
-        *self.is_connected_status.read().await // 读取共享的连接状态标志
+        self.client.is_connected()
     }
 
     /// 向已连接的 WebSocket 服务器异步发送一个 `WsMessage`。
     ///
-    /// # 主要流程:
-    /// 1. 检查当前是否已连接 (`is_connected()`)。
-    ///    - 如果未连接，记录错误并返回 `Err`。
-    /// 2. 获取对 `ws_send_channel` (WebSocket 发送端) 的互斥锁。
-    /// 3. 如果发送通道存在：
-    ///    a. 将传入的 `WsMessage` 对象序列化为 JSON 字符串。
-    ///    b. 使用 `sender.send()` 方法将 JSON 字符串作为文本消息发送出去。
-    ///    c. 根据发送结果，记录成功或失败日志，并返回相应的 `Result`。
-    /// 4. 如果发送通道不存在 (例如，在断开过程中被清空)，记录警告并返回 `Err`。
-    ///
-    /// # 参数
-    /// * `message`: 要发送的 `WsMessage` 实例。
-    ///
     /// # 返回
-    /// * `Result<(), String>`: 
-    ///     - `Ok(())`: 如果消息已成功序列化并交由 WebSocket 发送端处理。
-    ///     - `Err(String)`: 如果未连接、序列化失败、或发送过程中发生错误，则返回包含中文错误描述的字符串。
+    /// * `Ok(())`: 消息已交由 WebSocket 连接发送。
+    /// * `Err(String)`: 未连接或发送过程中发生错误，包含中文错误描述。
     pub async fn send_ws_message(&self, message: WsMessage) -> Result<(), String> {
         info!(
-            "[现场端移动服务] WebSocketClientService::send_ws_message 方法调用，准备发送消息: 类型='{}', ID='{}'", 
+            "[现场端移动服务] WebSocketClientService::send_ws_message 方法调用，准备发送消息: 类型='{}', ID='{}'",
             message.message_type, message.message_id
         );
-        // 1. 检查连接状态
-        if !self.is_connected().await {
-            let err_msg = "无法发送 WebSocket 消息：当前未连接到云端服务。".to_string();
-            error!("[现场端移动服务] (发送消息) {}", err_msg);
-            return Err(err_msg);
-        }
-
-        // 2. 获取发送通道的锁
-        let mut sender_guard = self.ws_send_channel.lock().await;
-        if let Some(sender) = sender_guard.as_mut() { // 3. 检查发送通道是否存在
-            // 3a. 序列化 WsMessage 为 JSON 字符串
-            match serde_json::to_string(&message) { 
-                Ok(msg_json) => {
-                    // 3b. 发送消息
-                    match sender.send(TungsteniteMessage::Text(msg_json)).await {
-                        Ok(_) => {
-                            info!(
-                                "[现场端移动服务] (发送消息) 消息 (类型: '{}', ID: '{}') 已成功发送至 WebSocket 服务器。",
-                                message.message_type, message.message_id
-                            );
-                            Ok(())
-                        }
-                        Err(e) => { // 发送失败
-                            let err_msg = format!(
-                                "发送 WebSocket 消息 (类型: '{}', ID: '{}') 失败: {}. 可能连接已中断。",
-                                message.message_type, message.message_id, e
-                            );
-                            error!("[现场端移动服务] (发送消息) {}", err_msg);
-                            // 考虑在这种情况下也设置 is_connected_status 为 false，并通知前端
-                            // *self.is_connected_status.write().await = false;
-                            // self.app_handle.emit(...);
-                            Err(err_msg)
-                        }
-                    }
-                }
-                Err(e) => { // 序列化失败
-                    let err_msg = format!(
-                        "序列化 WebSocket 消息 (类型: '{}', ID: '{}') 为 JSON 时失败: {}",
-                        message.message_type, message.message_id, e
-                    );
-                    error!("[现场端移动服务] (发送消息) {}", err_msg);
-                    Err(err_msg)
-                }
-            }
-        } else {
-            // 4. 发送通道不存在
-            let err_msg = format!(
-                "无法发送 WebSocket 消息 (类型: '{}', ID: '{}')：发送通道不可用 (可能连接已关闭或正在关闭)。",
-                message.message_type, message.message_id
-            );
-            warn!("[现场端移动服务] (发送消息) {}", err_msg);
-            Err(err_msg)
-        }
+        self.client.send(message).await.map_err(|e| {
+            error!("[现场端移动服务] (发送消息) {}", e);
+            e
+        })
     }
 
-     /// 获取当前本地缓存的最新任务调试状态 (`TaskDebugState`)。
-     ///
-     /// 此方法允许应用的其他部分（例如 Tauri 命令）查询由 WebSocket 服务维护的、
-     /// 从云端同步过来的任务状态副本。
-     ///
-     /// # 返回
-     /// * `Option<TaskDebugState>`
+    /// 获取当前本地缓存的最新任务调试状态 (`TaskDebugState`)。
     pub async fn get_cached_task_state(&self) -> Option<TaskDebugState> {
-        info!("[现场端移动服务] WebSocketClientService::get_cached_task_state 方法被调用，正在获取本地缓存的任务状态。");
-        let cache_guard = self.local_task_state_cache.read().await;
-        // 克隆 Option<TaskDebugState>。如果 Option 是 Some，则内部的 TaskDebugState 也会被克隆
-        // (因为 TaskDebugState 派生了 Clone trait)。
-        // 如果 Option 是 None，则克隆结果仍然是 None。
+        self.client.cached_task_state().await
+    }
 
-         //这里能返回为option是因为TaskDebugState 派生了 Clone trait。在克隆的时候会自动解引用返回一个option
-        cache_guard.clone()
+    /// 处理一次版本冲突：按 `resolution` 在本地缓存的当前版本之上重新提交被拒绝的业务消息，或放弃本端修改。
+    ///
+    /// # 返回
+    /// * `Ok(Some(String))`: 已重新提交，返回新业务消息的 `message_id` (其确认同样通过 `WsBusinessAckEvent` 通知)。
+    /// * `Ok(None)`: 已放弃本端修改。
+    /// * `Err(String)`: 没有该冲突、本地尚无任务状态、负载无效或发送失败；此时冲突仍保留，可再次处理。
+    pub async fn resolve_version_conflict(
+        &self,
+        request_message_id: &str,
+        resolution: ConflictResolution,
+    ) -> Result<Option<String>, String> {
+        self.client.resolve_version_conflict(request_message_id, resolution).await
+    }
+
+    /// 请求云端重新推送当前完整的任务调试状态 (`TaskStateSyncRequest`)。
+    ///
+    /// 云端回复的 `TaskStateUpdate` 同时写入本地缓存并通知前端。
+    pub async fn resync_task_state(&self) -> Result<TaskDebugState, String> {
+        self.client.resync_task_state().await
+    }
+
+    /// 不断开连接地离开当前任务组 (`LeaveGroup`)，例如完成一个任务后准备转到下一个。
+    pub async fn leave_group(&self) -> Result<GroupLeftPayload, String> {
+        self.client.leave_group().await
+    }
+
+    /// 离开当前任务组并注册到 `register` 所指的任务组 (`SwitchTask`)，连接保持不变。
+    ///
+    /// # 返回
+    /// * `Ok(RegisterResponsePayload)`: 云端的注册响应，`success` 为 `false` 时表示未能加入目标组。
+    pub async fn switch_task(&self, register: RegisterPayload) -> Result<RegisterResponsePayload, String> {
+        self.client.switch_task(register).await
     }
 
     /// 发送 Echo 消息到 WebSocket 服务器。
     pub async fn send_echo_message(&self, payload: EchoPayload) -> Result<(), String> {
-        info!(
-            "[SatOnSiteMobile] WebSocketClientService::send_echo_message 调用，内容: '{}'",
-            payload.content
-        );
-        if !self.is_connected().await {
-            let err_msg = "WebSocket 未连接，无法发送 Echo 消息。".to_string();
-            error!("[SatOnSiteMobile] {}", err_msg);
-            return Err(err_msg);
-        }
-
-        match WsMessage::new(ECHO_MESSAGE_TYPE.to_string(), &payload) {
-            Ok(ws_message) => self.send_ws_message(ws_message).await,
-            Err(e) => {
-                let err_msg = format!("创建 Echo WsMessage 失败: {:?}", e);
-                error!("[SatOnSiteMobile] {}", err_msg);
-                Err(err_msg)
-            }
-        }
+        info!("[SatOnSiteMobile] WebSocketClientService::send_echo_message 调用，内容: '{}'", payload.content);
+        self.client.echo(payload).await
     }
 
-    /// 发送特定类型的业务消息到 WebSocket 服务器。
-    /// 这是一个通用方法，用于封装构建 WsMessage 并发送的逻辑。
-    pub async fn send_specific_message<T>(
-        &self, 
-        message_type: &str, 
-        payload_obj: &T
-    ) -> Result<(), String> 
-    where 
-        T: serde::Serialize + Send + Sync + std::fmt::Debug
+    /// 发送特定类型的业务消息到 WebSocket 服务器 (不等待响应)。
+    pub async fn send_specific_message<T>(&self, message_type: &str, payload_obj: &T) -> Result<(), String>
+    where
+        T: Serialize + Send + Sync + std::fmt::Debug,
     {
         info!(
             "[SatOnSiteMobile] WebSocketClientService::send_specific_message 调用，类型: '{}', Payload: {:?}",
             message_type, payload_obj
         );
-        if !self.is_connected().await {
-            let err_msg = format!("WebSocket 未连接，无法发送类型为 '{}' 的消息。", message_type);
-            error!("[SatOnSiteMobile] {}", err_msg);
-            return Err(err_msg);
-        }
+        self.client.send_message(message_type, payload_obj).await.map(|_| ())
+    }
 
-        match WsMessage::new(message_type.to_string(), payload_obj) {
-            Ok(ws_message) => {
-                debug!("[SatOnSiteMobile] 准备发送构建好的WsMessage: ID={}, Type={}, Timestamp={}", ws_message.message_id, ws_message.message_type, ws_message.timestamp);
-                self.send_ws_message(ws_message).await
-            }
-            Err(e) => {
-                let err_msg = format!("创建类型为 '{}' 的 WsMessage 失败: {:?}", message_type, e);
-                error!("[SatOnSiteMobile] {}", err_msg);
-                Err(err_msg)
-            }
-        }
+    /// 发送一条业务消息，并在 `timeout` 内等待云端与之关联的响应 (响应的 `in_reply_to` 等于本消息的 `message_id`)。
+    pub async fn request<T>(&self, message_type: &str, payload_obj: &T, timeout: Duration) -> Result<WsMessage, String>
+    where
+        T: Serialize + Send + Sync + std::fmt::Debug,
+    {
+        self.client.request(message_type, payload_obj, timeout).await
     }
-}
\ No newline at end of file
+}
+
+/// 向前端发送一个 Tauri 事件，失败时记录日志。
+fn emit<S: Serialize>(app_handle: &AppHandle, event_name: &str, payload: &S) {
+    if let Err(e) = app_handle.emit(event_name, payload) {
+        error!("[现场端移动服务] 发送事件 ({}) 给前端失败: {}", event_name, e);
+    }
+}
@@ -0,0 +1,65 @@
+//! 用户认证相关的共享模型。
+//!
+//! 本模块定义了客户端 (控制中心、现场移动端) 调用云端 REST 登录接口时使用的请求与响应结构体。
+//! 登录成功后获得的访问令牌 (`access_token`) 需要在建立 WebSocket 连接时携带，
+//! 可以放在 `Authorization: Bearer <token>` 请求头中，也可以作为 URL 查询参数 `token=<token>`。
+
+use crate::enums::UserRole;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// 云端 REST 登录接口的路径。
+pub const LOGIN_API_PATH: &str = "/api/auth/login";
+
+/// 登录请求体 (`POST /api/auth/login`)。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LoginRequest {
+    /// 登录用户名。
+    pub username: String,
+    /// 明文密码，仅在登录请求中传输，云端只保存其哈希值。
+    pub password: String,
+}
+
+/// 登录成功时的响应体。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoginResponse {
+    /// 签名的访问令牌。
+    pub access_token: String,
+    /// 令牌类型，固定为 `"Bearer"`。
+    pub token_type: String,
+    /// 令牌的过期时间 (UTC)。
+    pub expires_at: DateTime<Utc>,
+    /// 用户的唯一标识。
+    pub user_id: String,
+    /// 用户名。
+    pub username: String,
+    /// 用户被分配的业务角色。
+    pub roles: Vec<UserRole>,
+}
+
+/// 登录失败时的响应体。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoginErrorResponse {
+    /// 失败原因的描述。
+    pub error: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_response_round_trip() {
+        let response = LoginResponse {
+            access_token: "abc.def.ghi".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: Utc::now(),
+            user_id: "u-1".to_string(),
+            username: "alice".to_string(),
+            roles: vec![UserRole::Operator],
+        };
+        let json = serde_json::to_string(&response).expect("序列化 LoginResponse 失败");
+        let parsed: LoginResponse = serde_json::from_str(&json).expect("反序列化 LoginResponse 失败");
+        assert_eq!(parsed, response);
+    }
+}
@@ -21,12 +21,28 @@ pub enum ClientRole {
     ControlCenter,
     /// 代表 `SatOnSiteMobile` (现场移动端) 类型的客户端。
     /// 通常在现场执行具体任务，如预检查、单体测试步骤，并向云端和控制中心反馈状态和数据。
+    /// 大型撬装设备可由多名现场工程师同时测试，因此每个组可以有多个现场移动端，各自负责一部分设备。
     OnSiteMobile,
+    /// 代表 QA 主管 (质量监督) 客户端。
+    /// 在控制中心确认测试步骤之后给出最终签核 (通过/驳回)；每个组至多一个。
+    Supervisor,
+    /// 代表只读的观察者客户端，例如远程旁观测试过程的管理人员。
+    /// 接收组内全部状态与伙伴通知，但不能发起任何修改任务状态的业务操作；每个组可以有任意多个。
+    Observer,
     /// 代表一个角色尚未确定或未知的客户端。
     /// 这可能是客户端刚连接尚未完成注册流程时的初始状态，或者在某些错误/异常情况下使用。
     Unknown,
 }
 
+impl ClientRole {
+    /// 该角色在组内是否独占一个槽位。
+    ///
+    /// 控制中心与 QA 主管在每个组内各至多一个；现场移动端与观察者不限数量，未知角色不能加入组。
+    pub fn has_exclusive_slot(&self) -> bool {
+        matches!(self, ClientRole::ControlCenter | ClientRole::Supervisor)
+    }
+}
+
 // 为 ClientRole 实现 Display trait
 impl fmt::Display for ClientRole {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -36,6 +52,53 @@ impl fmt::Display for ClientRole {
     }
 }
 
+/// 表示平台用户账户被分配的业务角色。
+///
+/// 与 `ClientRole` (描述一个 WebSocket 连接在任务组中的身份) 不同，
+/// `UserRole` 描述的是"这个人"在平台中的职责，云端据此决定其可以以哪些 `ClientRole` 加入任务组：
+/// - `Operator` (操作员) 使用控制中心；
+/// - `FieldEngineer` (现场工程师) 使用现场移动端；
+/// - `QaSupervisor` (QA 主管) 以 QA 主管身份签核，也可仅作为观察者旁观；
+/// - `Manager` (管理人员) 只能作为观察者旁观；
+/// - `Admin` (管理员) 可使用任意客户端。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserRole {
+    /// 操作员，在控制中心发起和监控调试任务。
+    Operator,
+    /// 现场工程师，在现场执行预检查和单体测试。
+    FieldEngineer,
+    /// QA 主管，对已确认的测试步骤给出最终签核。
+    QaSupervisor,
+    /// 管理人员，远程旁观测试过程 (只读)。
+    Manager,
+    /// 管理员，拥有全部权限。
+    Admin,
+}
+
+impl UserRole {
+    /// 返回持有该用户角色的账户被允许扮演的客户端角色列表。
+    pub fn allowed_client_roles(&self) -> &'static [ClientRole] {
+        match self {
+            UserRole::Operator => &[ClientRole::ControlCenter],
+            UserRole::FieldEngineer => &[ClientRole::OnSiteMobile],
+            UserRole::QaSupervisor => &[ClientRole::Supervisor, ClientRole::Observer],
+            UserRole::Manager => &[ClientRole::Observer],
+            UserRole::Admin => &[ClientRole::ControlCenter, ClientRole::OnSiteMobile, ClientRole::Supervisor, ClientRole::Observer],
+        }
+    }
+
+    /// 持有该用户角色的账户是否可以不经当前持有者确认，强制接管组内已被占用的角色槽位。
+    pub fn may_force_slot_takeover(&self) -> bool {
+        matches!(self, UserRole::Admin)
+    }
+}
+
+impl fmt::Display for UserRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 /// 枚举：定义了预检查项的状态。
 /// 
 /// 根据规则 2.1，共享模型需派生 Serialize, Deserialize, Debug, Clone。
@@ -51,6 +114,68 @@ pub enum PreCheckStatus {
     Skipped, 
 }
 
+/// 云端协议中机器可读的错误码。
+///
+/// 服务端在 `ErrorResponse`、被拒绝的 `Ack` 以及失败的 `RegisterResponse` 中携带此错误码，
+/// 客户端应根据错误码决定后续处理 (重新注册、重新同步状态、提示用户等)，而不是解析错误描述文本。
+/// 序列化形式为 `SCREAMING_SNAKE_CASE` 字符串，例如 `"NOT_REGISTERED"`。
+///
+/// 新增错误码时应同时补充 [`ErrorCode::message_key`]，客户端对未知错误码应按通用错误处理。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// 客户端尚未完成注册 (未加入任何任务组) 就发送了需要组上下文的消息。
+    NotRegistered,
+    /// 消息负载无法解析，或字段取值不合法。字段级原因见错误详情。
+    PayloadInvalid,
+    /// 请求的任务ID与任务组已关联的任务ID不一致。
+    TaskMismatch,
+    /// 请求的角色在任务组中已被其他客户端占用。
+    RoleSlotTaken,
+    /// 客户端基于的状态版本与服务端当前版本不一致。
+    VersionConflict,
+    /// 当前用户或角色无权执行该操作。
+    Forbidden,
+    /// 任务组对应的任务状态不存在。
+    TaskNotFound,
+    /// 服务端不支持该消息类型。
+    UnsupportedMessage,
+    /// 服务端内部错误，通常可以稍后重试。
+    InternalError,
+    /// 注册时携带的配对码不存在、已被使用或已过期 (或其对应的任务组已解散)。
+    JoinCodeInvalid,
+}
+
+impl ErrorCode {
+    /// 返回错误码在协议中的字符串形式，与序列化结果一致 (不含引号)。
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::NotRegistered => "NOT_REGISTERED",
+            ErrorCode::PayloadInvalid => "PAYLOAD_INVALID",
+            ErrorCode::TaskMismatch => "TASK_MISMATCH",
+            ErrorCode::RoleSlotTaken => "ROLE_SLOT_TAKEN",
+            ErrorCode::VersionConflict => "VERSION_CONFLICT",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::TaskNotFound => "TASK_NOT_FOUND",
+            ErrorCode::UnsupportedMessage => "UNSUPPORTED_MESSAGE",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+            ErrorCode::JoinCodeInvalid => "JOIN_CODE_INVALID",
+        }
+    }
+
+    /// 返回该错误码对应的本地化消息键，例如 `"error.not_registered"`。
+    /// 客户端据此在自己的语言资源中查找面向用户的提示文本。
+    pub fn message_key(&self) -> String {
+        format!("error.{}", self.as_str().to_ascii_lowercase())
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,6 +213,8 @@ mod tests {
         let roles_to_test = vec![
             ClientRole::ControlCenter,
             ClientRole::OnSiteMobile,
+            ClientRole::Supervisor,
+            ClientRole::Observer,
             ClientRole::Unknown,
         ];
 
@@ -108,6 +235,8 @@ mod tests {
             let expected_json_string = match role_instance {
                 ClientRole::ControlCenter => "\"ControlCenter\"",
                 ClientRole::OnSiteMobile => "\"OnSiteMobile\"",
+                ClientRole::Supervisor => "\"Supervisor\"",
+                ClientRole::Observer => "\"Observer\"",
                 ClientRole::Unknown => "\"Unknown\"",
             };
             assert_eq!(serialized_json, expected_json_string, 
@@ -133,4 +262,49 @@ mod tests {
         // 断言：HashSet 中不应包含 Unknown (因为我们没有插入它)
         assert!(!roles_set.contains(&ClientRole::Unknown), "HashSet 中不应包含 ClientRole::Unknown");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    /// 测试 `UserRole` 到允许的 `ClientRole` 的映射。
+    fn test_user_role_allowed_client_roles() {
+        assert_eq!(UserRole::Operator.allowed_client_roles(), &[ClientRole::ControlCenter]);
+        assert_eq!(UserRole::FieldEngineer.allowed_client_roles(), &[ClientRole::OnSiteMobile]);
+        // 管理员可以扮演任意业务角色，但永远不会被映射为 Unknown
+        assert!(UserRole::Admin.allowed_client_roles().contains(&ClientRole::ControlCenter));
+        assert!(UserRole::Admin.allowed_client_roles().contains(&ClientRole::OnSiteMobile));
+        assert!(!UserRole::Admin.allowed_client_roles().contains(&ClientRole::Unknown));
+        assert_eq!(UserRole::Manager.allowed_client_roles(), &[ClientRole::Observer]);
+        assert!(UserRole::Admin.may_force_slot_takeover());
+        assert!(!UserRole::Operator.may_force_slot_takeover());
+        assert!(UserRole::QaSupervisor.allowed_client_roles().contains(&ClientRole::Supervisor));
+        assert!(ClientRole::Supervisor.has_exclusive_slot());
+        assert!(!ClientRole::OnSiteMobile.has_exclusive_slot());
+        assert!(!ClientRole::Observer.has_exclusive_slot());
+        assert!(!ClientRole::Unknown.has_exclusive_slot());
+        assert_eq!(serde_json::to_string(&UserRole::FieldEngineer).unwrap(), "\"FieldEngineer\"");
+    }
+
+    #[test]
+    /// 测试 `ErrorCode` 的序列化形式、`as_str` 与本地化消息键保持一致。
+    fn test_error_code_wire_format_and_message_key() {
+        let codes = [
+            ErrorCode::NotRegistered,
+            ErrorCode::PayloadInvalid,
+            ErrorCode::TaskMismatch,
+            ErrorCode::RoleSlotTaken,
+            ErrorCode::VersionConflict,
+            ErrorCode::Forbidden,
+            ErrorCode::TaskNotFound,
+            ErrorCode::UnsupportedMessage,
+            ErrorCode::InternalError,
+            ErrorCode::JoinCodeInvalid,
+        ];
+        for code in codes {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+            assert_eq!(serde_json::from_str::<ErrorCode>(&json).unwrap(), code);
+            assert_eq!(code.to_string(), code.as_str());
+        }
+        assert_eq!(ErrorCode::RoleSlotTaken.as_str(), "ROLE_SLOT_TAKEN");
+        assert_eq!(ErrorCode::NotRegistered.message_key(), "error.not_registered");
+    }
+}
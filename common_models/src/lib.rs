@@ -25,6 +25,8 @@ pub mod ws_payloads;        // WebSocket 通信中使用的各种消息负载结
 pub mod enums;              // 项目中通用的枚举类型定义
 pub mod task_models;        // 新增：与调试任务具体状态和业务交互相关的模型 (P3.3.1)
 pub mod templates;          // 新增 templates 模块声明
+pub mod auth_models;        // 用户登录请求/响应等认证相关模型
+pub mod permissions;        // 业务操作 × 客户端角色的授权矩阵
 
 /// 一个简单的示例函数，用于演示 crate 的基本功能和测试。
 /// 在实际的 `common_models` 库中，此类通用工具函数可能较少，主要侧重于数据结构定义。
@@ -63,6 +65,9 @@ pub use task_models::*; // 新增：导出 task_models 中的所有公共项 (P3
 // 重新导出关键的结构体和枚举，使其更易于访问
 // 例如 `use common_models::ClientRole;` 而不是 `use common_models::enums::ClientRole;`
 pub use enums::ClientRole;
+pub use enums::UserRole;
+pub use enums::ErrorCode;
+pub use auth_models::{LoginRequest, LoginResponse};
 pub use task_models::{TaskDebugState, PreCheckItemStatus, SingleTestStepStatus};
 
 use serde::{Deserialize, Serialize};
@@ -103,6 +108,9 @@ mod lib_tests { // 重命名测试模块以避免与子模块中的 `tests` 冲
             task_id: "test_task".to_string(),
             client_software_version: None, // 添加 None 值
             client_display_name: None,   // 添加 None 值
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
         };
         let payload_str = serde_json::to_string(&example_payload_struct).unwrap();
 
@@ -133,9 +141,13 @@ mod lib_tests { // 重命名测试模块以避免与子模块中的 `tests` 冲
 pub use crate::ws_payloads::{ 
     EchoPayload,
     ErrorResponsePayload,
+    FieldErrorDetail,
+    AckPayload,
+    AckStatus,
     PingPayload,
     PongPayload,
     RegisterPayload,
+    SiteAssignment,
     RegisterResponsePayload,
     PartnerStatusPayload,
     ECHO_MESSAGE_TYPE,
@@ -145,11 +157,25 @@ pub use crate::ws_payloads::{
     REGISTER_MESSAGE_TYPE,
     REGISTER_RESPONSE_MESSAGE_TYPE,
     PARTNER_STATUS_UPDATE_MESSAGE_TYPE,
+    ACK_MESSAGE_TYPE,
     UPDATE_PRE_CHECK_ITEM_TYPE,
     START_SINGLE_TEST_STEP_TYPE,
     FEEDBACK_SINGLE_TEST_STEP_TYPE,
     CONFIRM_SINGLE_TEST_STEP_TYPE,
+    SIGN_OFF_SINGLE_TEST_STEP_TYPE,
     TASK_STATE_UPDATE_MESSAGE_TYPE,
+    SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE,
+    SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE,
+    SLOT_TAKEOVER_DECISION_MESSAGE_TYPE,
+    PREPARE_HANDOVER_MESSAGE_TYPE,
+    HANDOVER_TOKEN_MESSAGE_TYPE,
+    SLOT_RELEASED_MESSAGE_TYPE,
+    LEAVE_GROUP_MESSAGE_TYPE,
+    GROUP_LEFT_MESSAGE_TYPE,
+    SWITCH_TASK_MESSAGE_TYPE,
+    OPEN_GROUP_MESSAGE_TYPE,
+    GROUP_OPENED_MESSAGE_TYPE,
+    GROUP_ROSTER_MESSAGE_TYPE,
 };
 
 // 从 task_models 模块显式导出业务相关的 Payload (如果它们确实在那里定义)
@@ -0,0 +1,275 @@
+// common_models/src/permissions.rs
+
+//! 业务操作的角色授权矩阵。
+//!
+//! 以声明式的表格 ([`PERMISSION_MATRIX`]) 规定每一种业务操作 ([`BusinessAction`]) 允许哪些客户端角色发起，
+//! 以及发起的角色通过该操作可以修改 `TaskDebugState` 中的哪些字段 ([`StateField`])。
+//! 服务端在修改任务状态前据此统一校验 (未列出的组合一律拒绝)；客户端也可据此决定界面上哪些操作可用。
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enums::ClientRole;
+use crate::task_models::TaskDebugState;
+use crate::ws_payloads::{
+    CONFIRM_SINGLE_TEST_STEP_TYPE, FEEDBACK_SINGLE_TEST_STEP_TYPE, SIGN_OFF_SINGLE_TEST_STEP_TYPE, START_SINGLE_TEST_STEP_TYPE,
+    UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE, UPDATE_PRE_CHECK_ITEM_TYPE, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE,
+};
+
+/// 会修改任务状态的业务操作，与 `BusinessActionPayload` 的成员一一对应。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusinessAction {
+    UpdatePreCheckItem,
+    StartSingleTestStep,
+    FeedbackSingleTestStep,
+    ConfirmSingleTestStep,
+    SignOffSingleTestStep,
+    UpdateTaskDebugNote,
+    UpdateCustomSharedData,
+}
+
+impl BusinessAction {
+    /// 全部业务操作。
+    pub const ALL: [BusinessAction; 7] = [
+        BusinessAction::UpdatePreCheckItem,
+        BusinessAction::StartSingleTestStep,
+        BusinessAction::FeedbackSingleTestStep,
+        BusinessAction::ConfirmSingleTestStep,
+        BusinessAction::SignOffSingleTestStep,
+        BusinessAction::UpdateTaskDebugNote,
+        BusinessAction::UpdateCustomSharedData,
+    ];
+
+    /// 该操作对应的 WebSocket 消息类型。
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            BusinessAction::UpdatePreCheckItem => UPDATE_PRE_CHECK_ITEM_TYPE,
+            BusinessAction::StartSingleTestStep => START_SINGLE_TEST_STEP_TYPE,
+            BusinessAction::FeedbackSingleTestStep => FEEDBACK_SINGLE_TEST_STEP_TYPE,
+            BusinessAction::ConfirmSingleTestStep => CONFIRM_SINGLE_TEST_STEP_TYPE,
+            BusinessAction::SignOffSingleTestStep => SIGN_OFF_SINGLE_TEST_STEP_TYPE,
+            BusinessAction::UpdateTaskDebugNote => UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE,
+            BusinessAction::UpdateCustomSharedData => UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE,
+        }
+    }
+
+    /// 根据 WebSocket 消息类型查找对应的业务操作；非业务消息返回 `None`。
+    pub fn from_message_type(message_type: &str) -> Option<BusinessAction> {
+        BusinessAction::ALL.into_iter().find(|action| action.message_type() == message_type)
+    }
+}
+
+impl fmt::Display for BusinessAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message_type())
+    }
+}
+
+/// `TaskDebugState` 中可由业务操作修改的字段。
+///
+/// 预检查项与测试步骤的字段按"每一项内的字段"计，例如任意预检查项的 `status_from_control`
+/// 都对应 `PreCheckStatusFromControl`。时间戳、版本号、最后更新者等元数据不在此列。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StateField {
+    PreCheckStatusFromSite,
+    PreCheckNotesFromSite,
+    PreCheckStatusFromControl,
+    PreCheckNotesFromControl,
+    StepCommandFromControl,
+    StepParamsFromControl,
+    StepExecutionStatusFromSite,
+    StepResultDataFromSite,
+    StepFeedbackNotesFromSite,
+    StepConfirmationStatusFromControl,
+    StepSignOffStatusFromSupervisor,
+    StepSignOffNotesFromSupervisor,
+    GeneralDebugNotes,
+    CustomSharedData,
+    /// 共享数据的 JSON Schema 只能由云端管理接口设置，任何业务操作都不可修改。
+    CustomSharedDataSchema,
+}
+
+/// 授权矩阵中的一项：`role` 可以发起 `action`，并可修改 `writable_fields` 中的字段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionPermission {
+    pub action: BusinessAction,
+    pub role: ClientRole,
+    pub writable_fields: &'static [StateField],
+}
+
+/// 业务操作 × 客户端角色的授权矩阵。未列出的组合均不允许 (观察者与未知角色没有任何条目，即只读)。
+pub const PERMISSION_MATRIX: &[ActionPermission] = &[
+    ActionPermission {
+        action: BusinessAction::UpdatePreCheckItem,
+        role: ClientRole::ControlCenter,
+        writable_fields: &[StateField::PreCheckStatusFromControl, StateField::PreCheckNotesFromControl],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdatePreCheckItem,
+        role: ClientRole::OnSiteMobile,
+        writable_fields: &[StateField::PreCheckStatusFromSite, StateField::PreCheckNotesFromSite],
+    },
+    ActionPermission {
+        action: BusinessAction::StartSingleTestStep,
+        role: ClientRole::ControlCenter,
+        writable_fields: &[StateField::StepCommandFromControl, StateField::StepParamsFromControl],
+    },
+    ActionPermission {
+        action: BusinessAction::FeedbackSingleTestStep,
+        role: ClientRole::OnSiteMobile,
+        writable_fields: &[
+            StateField::StepExecutionStatusFromSite,
+            StateField::StepResultDataFromSite,
+            StateField::StepFeedbackNotesFromSite,
+        ],
+    },
+    ActionPermission {
+        action: BusinessAction::ConfirmSingleTestStep,
+        role: ClientRole::ControlCenter,
+        writable_fields: &[StateField::StepConfirmationStatusFromControl],
+    },
+    ActionPermission {
+        action: BusinessAction::SignOffSingleTestStep,
+        role: ClientRole::Supervisor,
+        writable_fields: &[StateField::StepSignOffStatusFromSupervisor, StateField::StepSignOffNotesFromSupervisor],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdateTaskDebugNote,
+        role: ClientRole::ControlCenter,
+        writable_fields: &[StateField::GeneralDebugNotes, StateField::CustomSharedData],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdateTaskDebugNote,
+        role: ClientRole::OnSiteMobile,
+        writable_fields: &[StateField::GeneralDebugNotes, StateField::CustomSharedData],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdateTaskDebugNote,
+        role: ClientRole::Supervisor,
+        writable_fields: &[StateField::GeneralDebugNotes],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdateCustomSharedData,
+        role: ClientRole::ControlCenter,
+        writable_fields: &[StateField::CustomSharedData],
+    },
+    ActionPermission {
+        action: BusinessAction::UpdateCustomSharedData,
+        role: ClientRole::OnSiteMobile,
+        writable_fields: &[StateField::CustomSharedData],
+    },
+];
+
+/// 查找 `role` 发起 `action` 的授权项；不允许时返回 `None`。
+pub fn permission_for(action: BusinessAction, role: ClientRole) -> Option<&'static ActionPermission> {
+    PERMISSION_MATRIX.iter().find(|permission| permission.action == action && permission.role == role)
+}
+
+/// 判断 `role` 是否可以发起 `action`。
+pub fn is_action_permitted(action: BusinessAction, role: ClientRole) -> bool {
+    permission_for(action, role).is_some()
+}
+
+/// 比较两个任务状态，返回其间发生变化的字段 (不含时间戳、版本号等元数据)。
+///
+/// 服务端在业务操作修改状态后据此确认修改没有超出授权矩阵允许的字段。
+pub fn changed_state_fields(before: &TaskDebugState, after: &TaskDebugState) -> BTreeSet<StateField> {
+    let mut changed = BTreeSet::new();
+    let mut mark = |field: StateField, differs: bool| {
+        if differs {
+            changed.insert(field);
+        }
+    };
+
+    let item_ids: BTreeSet<&String> = before.pre_check_items.keys().chain(after.pre_check_items.keys()).collect();
+    for item_id in item_ids {
+        let (old, new) = (before.pre_check_items.get(item_id), after.pre_check_items.get(item_id));
+        mark(StateField::PreCheckStatusFromSite, old.and_then(|i| i.status_from_site.as_ref()) != new.and_then(|i| i.status_from_site.as_ref()));
+        mark(StateField::PreCheckNotesFromSite, old.and_then(|i| i.notes_from_site.as_ref()) != new.and_then(|i| i.notes_from_site.as_ref()));
+        mark(StateField::PreCheckStatusFromControl, old.and_then(|i| i.status_from_control.as_ref()) != new.and_then(|i| i.status_from_control.as_ref()));
+        mark(StateField::PreCheckNotesFromControl, old.and_then(|i| i.notes_from_control.as_ref()) != new.and_then(|i| i.notes_from_control.as_ref()));
+    }
+
+    let step_ids: BTreeSet<&String> = before.single_test_steps.keys().chain(after.single_test_steps.keys()).collect();
+    for step_id in step_ids {
+        let (old, new) = (before.single_test_steps.get(step_id), after.single_test_steps.get(step_id));
+        mark(StateField::StepCommandFromControl, old.and_then(|s| s.command_from_control.as_ref()) != new.and_then(|s| s.command_from_control.as_ref()));
+        mark(StateField::StepParamsFromControl, old.and_then(|s| s.params_from_control.as_ref()) != new.and_then(|s| s.params_from_control.as_ref()));
+        mark(StateField::StepExecutionStatusFromSite, old.and_then(|s| s.execution_status_from_site.as_ref()) != new.and_then(|s| s.execution_status_from_site.as_ref()));
+        mark(StateField::StepResultDataFromSite, old.and_then(|s| s.result_data_from_site.as_ref()) != new.and_then(|s| s.result_data_from_site.as_ref()));
+        mark(StateField::StepFeedbackNotesFromSite, old.and_then(|s| s.feedback_notes_from_site.as_ref()) != new.and_then(|s| s.feedback_notes_from_site.as_ref()));
+        mark(
+            StateField::StepConfirmationStatusFromControl,
+            old.and_then(|s| s.confirmation_status_from_control.as_ref()) != new.and_then(|s| s.confirmation_status_from_control.as_ref()),
+        );
+        mark(
+            StateField::StepSignOffStatusFromSupervisor,
+            old.and_then(|s| s.sign_off_status_from_supervisor.as_ref()) != new.and_then(|s| s.sign_off_status_from_supervisor.as_ref()),
+        );
+        mark(
+            StateField::StepSignOffNotesFromSupervisor,
+            old.and_then(|s| s.sign_off_notes_from_supervisor.as_ref()) != new.and_then(|s| s.sign_off_notes_from_supervisor.as_ref()),
+        );
+    }
+
+    mark(StateField::GeneralDebugNotes, before.general_debug_notes != after.general_debug_notes);
+    mark(StateField::CustomSharedData, before.custom_shared_data != after.custom_shared_data);
+    mark(StateField::CustomSharedDataSchema, before.custom_shared_data_schema != after.custom_shared_data_schema);
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_models::{PreCheckItemStatus, SingleTestStepStatus};
+
+    #[test]
+    fn test_permission_matrix_lookup_and_changed_fields() {
+        // 每个 (操作, 角色) 组合至多出现一次，未知角色没有任何权限
+        for action in BusinessAction::ALL {
+            assert_eq!(BusinessAction::from_message_type(action.message_type()), Some(action));
+            for role in [ClientRole::ControlCenter, ClientRole::OnSiteMobile, ClientRole::Supervisor, ClientRole::Observer, ClientRole::Unknown] {
+                let entries = PERMISSION_MATRIX.iter().filter(|p| p.action == action && p.role == role).count();
+                assert!(entries <= 1, "{} / {:?} 在授权矩阵中重复出现", action, role);
+                assert_eq!(is_action_permitted(action, role), entries == 1);
+            }
+            assert!(!is_action_permitted(action, ClientRole::Unknown));
+            assert!(!is_action_permitted(action, ClientRole::Observer), "观察者是只读角色");
+        }
+        assert!(is_action_permitted(BusinessAction::StartSingleTestStep, ClientRole::ControlCenter));
+        assert!(!is_action_permitted(BusinessAction::StartSingleTestStep, ClientRole::OnSiteMobile));
+        assert!(!is_action_permitted(BusinessAction::ConfirmSingleTestStep, ClientRole::OnSiteMobile));
+        assert!(!is_action_permitted(BusinessAction::FeedbackSingleTestStep, ClientRole::ControlCenter));
+        assert!(is_action_permitted(BusinessAction::SignOffSingleTestStep, ClientRole::Supervisor));
+        assert!(!is_action_permitted(BusinessAction::SignOffSingleTestStep, ClientRole::ControlCenter));
+        assert_eq!(BusinessAction::from_message_type("Ping"), None);
+
+        let before = TaskDebugState::new("task-1".to_string());
+        assert!(changed_state_fields(&before, &before).is_empty());
+
+        let mut after = before.clone();
+        let mut item = PreCheckItemStatus::new("item-1".to_string());
+        item.status_from_control = Some("Confirmed".to_string());
+        after.pre_check_items.insert("item-1".to_string(), item);
+        let mut step = SingleTestStepStatus::new("step-1".to_string());
+        step.result_data_from_site = Some(serde_json::json!({"rpm": 1500}));
+        after.single_test_steps.insert("step-1".to_string(), step);
+        after.custom_shared_data_schema = Some(serde_json::json!({"type": "object"}));
+        after.version += 1; // 元数据变化不计入
+        assert_eq!(
+            changed_state_fields(&before, &after),
+            BTreeSet::from([
+                StateField::PreCheckStatusFromControl,
+                StateField::StepResultDataFromSite,
+                StateField::CustomSharedDataSchema,
+            ])
+        );
+
+        // 新建但所有字段均为空的条目不算修改
+        let mut empty_item = before.clone();
+        empty_item.pre_check_items.insert("item-2".to_string(), PreCheckItemStatus::new("item-2".to_string()));
+        assert!(changed_state_fields(&before, &empty_item).is_empty());
+    }
+}
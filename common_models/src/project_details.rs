@@ -10,8 +10,44 @@
 //! 所有在此模块中定义的结构体都应派生 `Serialize`, `Deserialize`, `Debug`, `Clone` 以支持
 //! 数据交换、调试和实例复制。
 
-// 提醒：此文件目前为骨架状态。
-// 具体的项目详情相关的数据结构将根据后续开发需求在此处逐步添加。
-// 例如，可能会定义如 `ProjectMetadata`、`HardwareConfigurationRef` 等结构体。
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-// 暂时为空，后续会根据开发步骤添加具体内容。 
\ No newline at end of file
+/// 项目点表中的一个点位定义。
+///
+/// 模板中的 [`PointIoDefinition`](crate::templates::PointIoDefinition) 通过 `point_name` 引用这里的点位。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PointDefinition {
+    /// 点位的逻辑名称，在项目内唯一。
+    pub point_name: String,
+    /// 点位描述，可选。
+    pub description: Option<String>,
+    /// 所属设备ID，可选。
+    pub device_id: Option<String>,
+    /// 点位数据类型，例如 "bool", "f32", "String"。
+    pub data_type: String,
+    /// 点位在控制系统中的地址，可选，例如 "DB10.DBX0.1" 或 "40001"。
+    pub address: Option<String>,
+    /// 工程单位，可选，例如 "℃", "kPa"。
+    pub unit: Option<String>,
+}
+
+/// 项目点表，列出一个项目中所有可被调试模板引用的点位。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProjectPointTable {
+    /// 所属项目ID。
+    pub project_id: String,
+    /// 点表版本，例如 "3" 或 "2024-05-01.1"，点表每次修订都应更换。
+    pub version: String,
+    /// 点位定义列表。
+    pub points: Vec<PointDefinition>,
+    /// 最后更新时间戳 (UTC)。
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ProjectPointTable {
+    /// 按逻辑名称查找点位。
+    pub fn point(&self, point_name: &str) -> Option<&PointDefinition> {
+        self.points.iter().find(|p| p.point_name == point_name)
+    }
+}
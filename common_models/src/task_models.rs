@@ -1,7 +1,9 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
 use crate::enums::ClientRole; // 确保 common_models/src/enums.rs 中有 ClientRole
+use crate::ws_payloads::TaskStateDeltaPayload;
 use chrono::{DateTime, Utc};
+use std::fmt;
 
 // 预检查项的状态
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -38,6 +40,12 @@ pub struct SingleTestStepStatus {
     pub result_data_from_site: Option<serde_json::Value>,
     pub feedback_notes_from_site: Option<String>,
     pub confirmation_status_from_control: Option<String>, // "Pending", "Confirmed", "Rejected"
+    /// QA 主管对已确认步骤的最终签核结果 ("Approved", "Rejected")。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign_off_status_from_supervisor: Option<String>,
+    /// QA 主管签核时的备注。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sign_off_notes_from_supervisor: Option<String>,
     pub last_updated: DateTime<Utc>,
 }
 
@@ -52,15 +60,22 @@ impl SingleTestStepStatus {
             result_data_from_site: None,
             feedback_notes_from_site: None,
             confirmation_status_from_control: None,
+            sign_off_status_from_supervisor: None,
+            sign_off_notes_from_supervisor: None,
             last_updated: Utc::now(),
         }
     }
+
+    /// 控制中心是否已确认该步骤的结果 (QA 主管只能签核已确认的步骤)。
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmation_status_from_control.as_deref() == Some("Confirmed")
+    }
 }
 
 /// 调试任务的整体共享状态模型。
 ///
 /// 此结构体在云端完整地表示一个正在进行的调试任务的全部共享状态。
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TaskDebugState {
     /// 任务的唯一标识符。
     pub task_id: String,
@@ -74,13 +89,19 @@ pub struct TaskDebugState {
     pub last_updated_by_role: Option<ClientRole>,
     /// 最后更新的时间戳 (Unix epoch milliseconds)。
     pub last_update_timestamp: DateTime<Utc>,
-    pub version: u64, // 用于乐观锁或版本跟踪
+    /// 状态版本号，每次实际变更递增 1。业务消息可通过 `expected_version` 声明其所基于的版本，
+    /// 服务端据此进行乐观并发控制 (见 [`TaskDebugState::accepts_expected_version`])。
+    pub version: u64,
 
     // 新增字段以匹配 admin_broadcast_task_state_update_cmd 命令的期望
     /// 通用的调试备注，可由管理员或特定流程设置。
     pub general_debug_notes: Option<String>,
     /// 自定义的共享JSON数据，可由管理员或特定流程注入，用于灵活的状态扩展。
     pub custom_shared_data: Option<serde_json::Value>,
+    /// 可选的 JSON Schema，约束 `custom_shared_data` 允许包含的内容；为 `None` 时不做限制。
+    /// 由服务端在应用 `UpdateCustomSharedData` 等修改前校验。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_shared_data_schema: Option<serde_json::Value>,
 }
 
 impl TaskDebugState {
@@ -99,10 +120,99 @@ impl TaskDebugState {
             // 初始化新增字段
             general_debug_notes: None,
             custom_shared_data: None,
+            custom_shared_data_schema: None,
+        }
+    }
+
+    /// 乐观并发控制：判断基于 `expected_version` 的业务操作能否应用到当前状态。
+    ///
+    /// 未声明期望版本 (`None`) 的操作总是可以应用 (兼容旧客户端，后写入者生效)；
+    /// 声明了期望版本的操作仅当其与当前版本一致时才可应用，否则服务端应以 `VERSION_CONFLICT` 拒绝。
+    pub fn accepts_expected_version(&self, expected_version: Option<u64>) -> bool {
+        expected_version.is_none_or(|expected| expected == self.version)
+    }
+
+    /// 生成从当前状态变为 `next` 的增量 (`TaskStateDeltaPayload`)。
+    ///
+    /// 由服务端在状态发生实际变更 (版本号递增) 后调用，用于向组内成员广播增量而非完整状态。
+    pub fn delta_to(&self, next: &TaskDebugState) -> Result<TaskStateDeltaPayload, serde_json::Error> {
+        let before = serde_json::to_value(self)?;
+        let after = serde_json::to_value(next)?;
+        Ok(TaskStateDeltaPayload {
+            task_id: next.task_id.clone(),
+            base_version: self.version,
+            version: next.version,
+            patch: json_patch::diff(&before, &after),
+        })
+    }
+
+    /// 将增量应用到当前状态 (通常是客户端的本地缓存)，返回应用后的新状态。
+    ///
+    /// 当前状态本身不会被修改，应用失败时调用方可继续使用原状态。
+    /// 除 `StateDeltaError::AlreadyApplied` (重复或过期的增量，可直接忽略) 外，
+    /// 其他错误都意味着本地缓存已无法通过增量追上云端，应请求完整状态。
+    pub fn apply_delta(&self, delta: &TaskStateDeltaPayload) -> Result<TaskDebugState, StateDeltaError> {
+        if delta.task_id != self.task_id {
+            return Err(StateDeltaError::TaskMismatch { cached_task_id: self.task_id.clone(), delta_task_id: delta.task_id.clone() });
+        }
+        if delta.version <= self.version {
+            return Err(StateDeltaError::AlreadyApplied { cached_version: self.version, delta_version: delta.version });
+        }
+        if delta.base_version != self.version {
+            return Err(StateDeltaError::VersionGap { cached_version: self.version, base_version: delta.base_version });
+        }
+        let mut document = serde_json::to_value(self).map_err(|e| StateDeltaError::InvalidPatch(e.to_string()))?;
+        json_patch::patch(&mut document, &delta.patch).map_err(|e| StateDeltaError::InvalidPatch(e.to_string()))?;
+        let patched: TaskDebugState = serde_json::from_value(document).map_err(|e| StateDeltaError::InvalidPatch(e.to_string()))?;
+        if patched.version != delta.version {
+            return Err(StateDeltaError::InvalidPatch(format!(
+                "应用增量后的版本号为 {}，与增量声明的版本号 {} 不一致",
+                patched.version, delta.version
+            )));
+        }
+        Ok(patched)
+    }
+}
+
+/// 将任务状态增量应用到本地缓存时可能出现的错误。
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateDeltaError {
+    /// 增量属于另一个任务。
+    TaskMismatch { cached_task_id: String, delta_task_id: String },
+    /// 增量的目标版本不高于本地缓存的版本 (重复或过期的增量)，可安全忽略。
+    AlreadyApplied { cached_version: u64, delta_version: u64 },
+    /// 本地缓存与增量的基础版本之间存在缺口 (中间的增量丢失)。
+    VersionGap { cached_version: u64, base_version: u64 },
+    /// 增量内容无法应用到本地缓存。
+    InvalidPatch(String),
+}
+
+impl StateDeltaError {
+    /// 是否需要向云端请求完整状态才能恢复一致。
+    pub fn requires_resync(&self) -> bool {
+        !matches!(self, StateDeltaError::AlreadyApplied { .. })
+    }
+}
+
+impl fmt::Display for StateDeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateDeltaError::TaskMismatch { cached_task_id, delta_task_id } => {
+                write!(f, "增量属于任务 '{}'，而本地缓存属于任务 '{}'", delta_task_id, cached_task_id)
+            }
+            StateDeltaError::AlreadyApplied { cached_version, delta_version } => {
+                write!(f, "增量版本 {} 不高于本地缓存版本 {}，已忽略", delta_version, cached_version)
+            }
+            StateDeltaError::VersionGap { cached_version, base_version } => {
+                write!(f, "本地缓存版本为 {}，但增量基于版本 {}，中间存在缺失的更新", cached_version, base_version)
+            }
+            StateDeltaError::InvalidPatch(reason) => write!(f, "增量无法应用到本地缓存: {}", reason),
         }
     }
 }
 
+impl std::error::Error for StateDeltaError {}
+
 // --- 业务 Payloads ---
 // 以下是客户端发起、意图改变共享任务状态或报告状态的业务消息 Payload 示例。
 
@@ -116,6 +226,10 @@ pub struct UpdatePreCheckItemPayload {
     /// 具体的状态值，例如 "Site_Completed", "Confirmed"。
     pub status: String,
     pub notes: Option<String>,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
     // 注意: `updated_by_role` 通常由服务器根据发送消息的客户端会话角色来确定，
     // 而不是在此 Payload 中由客户端直接指定。
 }
@@ -130,6 +244,10 @@ pub struct StartSingleTestStepPayload {
     pub step_id: String,
     /// 具体指令内容，例如 "RUN_FORWARD_5_SEC"。
     pub command: String,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 /// 反馈单体测试步骤结果的 Payload。
@@ -145,6 +263,10 @@ pub struct FeedbackSingleTestStepPayload {
     /// 测试结果数据，可以是任意 JSON 值。
     pub result_data: Option<serde_json::Value>,
     pub feedback_notes: Option<String>,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 /// 确认单体测试步骤的 Payload。
@@ -157,6 +279,27 @@ pub struct ConfirmSingleTestStepPayload {
     pub step_id: String,
     /// 确认状态，例如 "Confirmed", "Rejected"。
     pub confirmation_status: String,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+}
+
+/// QA 主管签核单体测试步骤的 Payload。
+///
+/// 由 QA 主管 (`ClientRole::Supervisor`) 发送，对控制中心已确认 (`Confirmed`) 的测试步骤给出最终签核。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignOffSingleTestStepPayload {
+    pub task_id: String,
+    pub device_id: String,
+    pub step_id: String,
+    /// 签核结果，例如 "Approved", "Rejected"。
+    pub sign_off_status: String,
+    pub notes: Option<String>,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 #[cfg(test)]
@@ -166,6 +309,41 @@ mod tests {
     use chrono::Utc;
     use serde_json;
 
+    #[test]
+    fn test_state_delta_round_trip_and_version_checks() {
+        let base = TaskDebugState::new("task-1".to_string());
+        let mut next = base.clone();
+        next.pre_check_items.insert("item-1".to_string(), PreCheckItemStatus::new("item-1".to_string()));
+        next.general_debug_notes = Some("备注".to_string());
+        next.last_updated_by_role = Some(ClientRole::OnSiteMobile);
+        next.version = 1;
+
+        let delta = base.delta_to(&next).unwrap();
+        assert_eq!((delta.base_version, delta.version), (0, 1));
+        let applied = base.apply_delta(&delta).unwrap();
+        assert_eq!(serde_json::to_value(&applied).unwrap(), serde_json::to_value(&next).unwrap());
+
+        // 重复应用：可忽略，无需重新同步
+        let err = applied.apply_delta(&delta).unwrap_err();
+        assert_eq!(err, StateDeltaError::AlreadyApplied { cached_version: 1, delta_version: 1 });
+        assert!(!err.requires_resync());
+
+        // 跳过了一个版本：检测到缺口
+        let mut later = next.clone();
+        later.pre_check_items.remove("item-1");
+        later.version = 2;
+        let gap_delta = next.delta_to(&later).unwrap();
+        let err = base.apply_delta(&gap_delta).unwrap_err();
+        assert_eq!(err, StateDeltaError::VersionGap { cached_version: 0, base_version: 1 });
+        assert!(err.requires_resync());
+        assert!(applied.apply_delta(&gap_delta).unwrap().pre_check_items.is_empty());
+
+        // 增量在网络上以 JSON 传输
+        let json = serde_json::to_string(&gap_delta).unwrap();
+        let decoded: TaskStateDeltaPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, gap_delta);
+    }
+
     #[test]
     fn test_pre_check_item_status_serialization_deserialization() {
         let original_item_status = PreCheckItemStatus {
@@ -205,6 +383,8 @@ mod tests {
             result_data_from_site: Some(serde_json::json!({"actual_duration": 5.1})),
             feedback_notes_from_site: Some("Motor ran smoothly".to_string()),
             confirmation_status_from_control: Some("Confirmed".to_string()),
+            sign_off_status_from_supervisor: Some("Approved".to_string()),
+            sign_off_notes_from_supervisor: None,
             last_updated: Utc::now(),
         };
 
@@ -215,6 +395,8 @@ mod tests {
         assert_eq!(original_step_status.step_id, deserialized.step_id);
         assert_eq!(original_step_status.command_from_control, deserialized.command_from_control);
         assert_eq!(original_step_status.params_from_control, deserialized.params_from_control);
+        assert_eq!(original_step_status.sign_off_status_from_supervisor, deserialized.sign_off_status_from_supervisor);
+        assert!(deserialized.is_confirmed());
         assert!((original_step_status.last_updated - deserialized.last_updated).num_milliseconds().abs() < 1000, "Timestamp mismatch too large for single test step");
     }
     
@@ -241,6 +423,8 @@ mod tests {
             result_data_from_site: None,
             feedback_notes_from_site: None,
             confirmation_status_from_control: None,
+            sign_off_status_from_supervisor: None,
+            sign_off_notes_from_supervisor: None,
             last_updated: Utc::now(),
         };
         original_state.single_test_steps.insert("st_001".to_string(), single_test_step1.clone());
@@ -269,4 +269,31 @@ pub struct ExpectedPointOutcome {
     /// 比较操作符，可选 (例如 "==", ">", "<=", "InRange")。
     /// 如果为None，可能表示仅关注状态变化或由 `success_criteria_logic` 处理复杂比较。
     pub comparison_operator: Option<String>,
-} 
\ No newline at end of file
+} 
+/// 一个调试任务所用到的全部模板，按模板类型分组。
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TaskTemplateSet {
+    /// 预检查模板。
+    pub pre_check: Vec<PreCheckTemplate>,
+    /// 单体设备测试模板。
+    pub single_device_tests: Vec<SingleDeviceTestTemplate>,
+    /// 联锁测试模板。
+    pub interlock_tests: Vec<InterlockTestTemplate>,
+}
+
+impl TaskTemplateSet {
+    /// 全部模板的元数据 (含 `template_version`)，按预检查、单体测试、联锁测试的顺序排列。
+    pub fn metadata(&self) -> Vec<&TemplateMetadata> {
+        self.pre_check
+            .iter()
+            .map(|t| &t.metadata)
+            .chain(self.single_device_tests.iter().map(|t| &t.metadata))
+            .chain(self.interlock_tests.iter().map(|t| &t.metadata))
+            .collect()
+    }
+
+    /// 是否不含任何模板。
+    pub fn is_empty(&self) -> bool {
+        self.pre_check.is_empty() && self.single_device_tests.is_empty() && self.interlock_tests.is_empty()
+    }
+}
@@ -7,7 +7,10 @@
 // 根据 P0.3.1 和项目规则 2.1 定义 EchoPayload
 use serde::{Serialize, Deserialize};
 use crate::enums::ClientRole; // 假设 ClientRole 在 common_models/src/enums.rs 中定义
+use crate::enums::ErrorCode;
+use crate::permissions::BusinessAction;
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 /// "Echo" 消息的消息类型常量。
 pub const ECHO_MESSAGE_TYPE: &str = "Echo";
@@ -23,6 +26,11 @@ pub const REGISTER_MESSAGE_TYPE: &str = "Register";
 pub const REGISTER_RESPONSE_MESSAGE_TYPE: &str = "RegisterResponse";
 /// 伙伴状态更新消息类型 - 服务器通知组内一个客户端其伙伴的在线状态变化。
 pub const PARTNER_STATUS_UPDATE_MESSAGE_TYPE: &str = "PartnerStatusUpdate";
+/// 组成员名册消息类型 - 组内成员加入、离开、断线、恢复或链路延迟明显变化时，服务器向全部在线成员推送完整名册。
+pub const GROUP_ROSTER_MESSAGE_TYPE: &str = "GroupRoster";
+/// 业务确认消息类型 - 服务器对每一条业务消息 (预检项更新、测试步骤、调试备注等) 回复的统一确认。
+/// 该消息的 `in_reply_to` 指向被确认的业务消息。
+pub const ACK_MESSAGE_TYPE: &str = "Ack";
 
 // --- 任务调试相关消息类型 (P3.3.1) ---
 
@@ -38,12 +46,70 @@ pub const FEEDBACK_SINGLE_TEST_STEP_TYPE: &str = "FeedbackSingleTestStep";
 /// 用于中心端确认现场端反馈的单体测试步骤结果的消息类型。
 pub const CONFIRM_SINGLE_TEST_STEP_TYPE: &str = "ConfirmSingleTestStep";
 
+/// 用于 QA 主管对已确认的单体测试步骤给出最终签核的消息类型。
+pub const SIGN_OFF_SINGLE_TEST_STEP_TYPE: &str = "SignOffSingleTestStep";
+
 /// 用于服务端主动向客户端推送完整的任务调试状态更新。
 /// 当云端权威的 `TaskDebugState` 因某一客户端的操作而发生改变后，
 /// 服务端会使用此消息类型，将更新后的整个 `TaskDebugState` 对象序列化后，
 /// 作为 payload 发送给同一任务组内的其他伙伴客户端。
 pub const TASK_STATE_UPDATE_MESSAGE_TYPE: &str = "TaskStateUpdate";
 
+/// 客户端请求重新同步完整任务调试状态的消息类型。
+/// 服务端以一条 `in_reply_to` 指向该请求的 `TaskStateUpdate` 回复当前权威的 `TaskDebugState`。
+/// (注册成功后服务端会主动推送一次完整状态，此消息用于客户端怀疑本地缓存不一致时主动校准。)
+pub const TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE: &str = "TaskStateSyncRequest";
+
+/// 用于服务端推送任务调试状态增量的消息类型。
+/// 状态每发生一次实际变更 (版本号 N -> N+1)，服务端向组内所有成员 (包括发起变更的客户端) 推送一条
+/// `TaskStateDeltaPayload`，而不再发送完整的 `TaskDebugState`。
+/// 客户端将增量应用到本地缓存；发现版本缺口时应通过 `TaskStateSyncRequest` 请求完整状态。
+pub const TASK_STATE_DELTA_MESSAGE_TYPE: &str = "TaskStateDelta";
+
+// --- 角色槽位接管与交接 ---
+/// 客户端请求接管组内已被占用的独占角色槽位 (例如原操作员的电脑宕机，其会话仍占着 `ControlCenter` 槽位)。
+/// 服务端可能立即完成接管，也可能先征求当前持有者的同意；最终结果均以一条 `in_reply_to` 指向该请求的
+/// `RegisterResponse` 告知请求方。
+pub const SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE: &str = "SlotTakeoverRequest";
+
+/// 服务端向当前槽位持有者征求接管同意的消息类型。持有者在期限内未答复时接管自动生效。
+pub const SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE: &str = "SlotTakeoverPrompt";
+
+/// 当前槽位持有者对接管请求的答复 (同意或拒绝)。
+pub const SLOT_TAKEOVER_DECISION_MESSAGE_TYPE: &str = "SlotTakeoverDecision";
+
+/// 槽位持有者请求一次性交接令牌的消息类型 (计划内换班)。服务端以 `in_reply_to` 指向该请求的
+/// `HandoverToken` 回复；拒绝时回复被拒绝的 `Ack`。
+pub const PREPARE_HANDOVER_MESSAGE_TYPE: &str = "PrepareHandover";
+
+/// 服务端签发交接令牌的消息类型。
+pub const HANDOVER_TOKEN_MESSAGE_TYPE: &str = "HandoverToken";
+
+/// 服务端通知某个客户端其角色槽位已被接管或已交接给他人的消息类型。
+/// 收到后客户端不再是组成员 (被强制接管时连接随后关闭)。
+pub const SLOT_RELEASED_MESSAGE_TYPE: &str = "SlotReleased";
+
+// --- 不断开连接地离开任务组 / 切换任务 ---
+/// 客户端主动离开当前任务组的消息类型。服务端以 `in_reply_to` 指向该请求的 `GroupLeft` 回复；
+/// 客户端尚未加入任何组时回复被拒绝的 `Ack`。离开后同一连接可以重新注册到其他组。
+pub const LEAVE_GROUP_MESSAGE_TYPE: &str = "LeaveGroup";
+
+/// 服务端确认客户端已离开任务组的消息类型。
+pub const GROUP_LEFT_MESSAGE_TYPE: &str = "GroupLeft";
+
+/// 客户端离开当前任务组并立即注册到另一个任务组的消息类型 (例如现场工程师完成一个任务后转到下一个)。
+/// 服务端以 `in_reply_to` 指向该请求的 `RegisterResponse` 回复加入新组的结果。
+pub const SWITCH_TASK_MESSAGE_TYPE: &str = "SwitchTask";
+
+// --- 配对码开组 ---
+/// 控制中心请求云端为某个任务开组的消息类型：控制中心以 `ControlCenter` 角色加入该组，
+/// 并取得一个短时有效的配对码。服务端以 `in_reply_to` 指向该请求的 `GroupOpened` 回复。
+/// 已在组内的控制中心再次发送 (不指定其他组) 时仅签发新的配对码。
+pub const OPEN_GROUP_MESSAGE_TYPE: &str = "OpenGroup";
+
+/// 服务端对 `OpenGroup` 的回复消息类型。
+pub const GROUP_OPENED_MESSAGE_TYPE: &str = "GroupOpened";
+
 // --- 新增的业务消息类型和Payload --- (P4.2.1 场景二)
 pub const UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE: &str = "UpdateTaskDebugNoteCommand";
 
@@ -57,17 +123,112 @@ pub struct UpdateTaskDebugNotePayload {
     /// (新增) 可选的自定义共享数据，应为一个有效的JSON对象字符串在进入此结构前已被解析为 Value。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom_shared_data: Option<serde_json::Value>,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 /// (新增) 消息类型: 用于客户端请求更新 TaskDebugState 中的 custom_shared_data 字段。
 pub const UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE: &str = "UpdateCustomSharedData";
 
 /// (新增) Payload: 用于客户端发送要更新到 TaskDebugState.custom_shared_data 的内容。
-/// 客户端应发送一个有效的 JSON 对象。
+///
+/// 按 `mode` 的不同，`new_data` 可以是完整的新内容、RFC 7386 合并补丁，或写入 `pointer` 所指位置的值。
+/// 结果为 `null` 时清空共享数据。
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct UpdateCustomSharedDataPayload {
-    /// 新的 custom_shared_data 内容，应为一个 JSON 对象。
+    /// 更新方式，省略时为 `Replace` (与旧版本客户端兼容)。
+    #[serde(default)]
+    pub mode: CustomSharedDataUpdateMode,
+    /// 新的内容、合并补丁或要写入的值，含义取决于 `mode`。
     pub new_data: serde_json::Value,
+    /// `JsonPointer` 方式下的目标位置 (RFC 6901)，例如 "/devices/pump-1/status"。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    /// 客户端修改时所基于的任务状态版本 (乐观并发控制)。与服务端当前版本不一致时操作被拒绝 (`VERSION_CONFLICT`)；
+    /// 省略时不做版本检查。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
+}
+
+/// `UpdateCustomSharedData` 的更新方式。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CustomSharedDataUpdateMode {
+    /// 以 `new_data` 整体替换共享数据。
+    #[default]
+    Replace,
+    /// 将 `new_data` 作为 RFC 7386 JSON Merge Patch 合并到共享数据中 (值为 `null` 的成员表示删除)。
+    MergePatch,
+    /// 将 `new_data` 写入 `pointer` 所指的位置；路径上缺失的对象成员会被自动创建，
+    /// 数组位置可使用下标或 "-" (追加到末尾)。
+    JsonPointer,
+}
+
+impl UpdateCustomSharedDataPayload {
+    /// 将本次更新应用到当前的共享数据 (`None` 视为 `null`)，返回更新后的完整内容。
+    ///
+    /// 仅 `JsonPointer` 方式可能失败 (缺少或无效的 `pointer`，或路径无法写入)，错误信息描述原因。
+    pub fn apply_to(&self, current: Option<&serde_json::Value>) -> Result<serde_json::Value, String> {
+        let mut document = current.cloned().unwrap_or(serde_json::Value::Null);
+        match self.mode {
+            CustomSharedDataUpdateMode::Replace => document = self.new_data.clone(),
+            CustomSharedDataUpdateMode::MergePatch => json_patch::merge(&mut document, &self.new_data),
+            CustomSharedDataUpdateMode::JsonPointer => {
+                let pointer = self.pointer.as_deref().ok_or_else(|| "JsonPointer 方式必须提供 pointer".to_string())?;
+                set_by_pointer(&mut document, pointer, self.new_data.clone())?;
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// 将 `value` 写入 `document` 中 `pointer` (RFC 6901) 所指的位置。空指针表示整个文档。
+fn set_by_pointer(document: &mut serde_json::Value, pointer: &str, value: serde_json::Value) -> Result<(), String> {
+    if pointer.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let Some(path) = pointer.strip_prefix('/') else {
+        return Err(format!("JSON Pointer '{}' 必须以 '/' 开头", pointer));
+    };
+    let tokens: Vec<String> = path.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect();
+    let (last, parents) = tokens.split_last().expect("非空的 JSON Pointer 至少包含一个片段");
+
+    let mut target = document;
+    for token in parents {
+        if target.is_null() {
+            *target = serde_json::Value::Object(serde_json::Map::new());
+        }
+        target = match target {
+            serde_json::Value::Object(members) => members.entry(token.clone()).or_insert(serde_json::Value::Null),
+            serde_json::Value::Array(items) => {
+                let len = items.len();
+                token
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| items.get_mut(index))
+                    .ok_or_else(|| format!("JSON Pointer '{}' 中的数组下标 '{}' 无效 (数组长度 {})", pointer, token, len))?
+            }
+            _ => return Err(format!("JSON Pointer '{}' 经过的 '{}' 不是对象或数组", pointer, token)),
+        };
+    }
+    if target.is_null() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    match target {
+        serde_json::Value::Object(members) => {
+            members.insert(last.clone(), value);
+        }
+        serde_json::Value::Array(items) if last == "-" => items.push(value),
+        serde_json::Value::Array(items) => match last.parse::<usize>() {
+            Ok(index) if index < items.len() => items[index] = value,
+            Ok(index) if index == items.len() => items.push(value),
+            _ => return Err(format!("JSON Pointer '{}' 中的数组下标 '{}' 无效 (数组长度 {})", pointer, last, items.len())),
+        },
+        _ => return Err(format!("JSON Pointer '{}' 的父位置不是对象或数组", pointer)),
+    }
+    Ok(())
 }
 
 /// 通用响应 Payload，用于许多Tauri命令的成功/失败结果。
@@ -88,11 +249,57 @@ pub enum BusinessActionPayload {
     StartSingleTestStep(crate::task_models::StartSingleTestStepPayload),
     FeedbackSingleTestStep(crate::task_models::FeedbackSingleTestStepPayload),
     ConfirmSingleTestStep(crate::task_models::ConfirmSingleTestStepPayload),
+    SignOffSingleTestStep(crate::task_models::SignOffSingleTestStepPayload),
     UpdateTaskDebugNote(UpdateTaskDebugNotePayload),
+    UpdateCustomSharedData(UpdateCustomSharedDataPayload),
     // 未来可以添加更多的业务操作类型
     // Example: UpdateInterlockCondition(crate::task_models::UpdateInterlockConditionPayload),
 }
 
+impl BusinessActionPayload {
+    /// 业务操作所基于的任务状态版本 (乐观并发控制)，未声明时为 `None`。
+    pub fn expected_version(&self) -> Option<u64> {
+        match self {
+            BusinessActionPayload::UpdatePreCheckItem(payload) => payload.expected_version,
+            BusinessActionPayload::StartSingleTestStep(payload) => payload.expected_version,
+            BusinessActionPayload::FeedbackSingleTestStep(payload) => payload.expected_version,
+            BusinessActionPayload::ConfirmSingleTestStep(payload) => payload.expected_version,
+            BusinessActionPayload::SignOffSingleTestStep(payload) => payload.expected_version,
+            BusinessActionPayload::UpdateTaskDebugNote(payload) => payload.expected_version,
+            BusinessActionPayload::UpdateCustomSharedData(payload) => payload.expected_version,
+        }
+    }
+
+    /// 该负载对应的业务操作，用于按授权矩阵 (`permissions::PERMISSION_MATRIX`) 校验发送方角色。
+    pub fn action(&self) -> BusinessAction {
+        match self {
+            BusinessActionPayload::UpdatePreCheckItem(_) => BusinessAction::UpdatePreCheckItem,
+            BusinessActionPayload::StartSingleTestStep(_) => BusinessAction::StartSingleTestStep,
+            BusinessActionPayload::FeedbackSingleTestStep(_) => BusinessAction::FeedbackSingleTestStep,
+            BusinessActionPayload::ConfirmSingleTestStep(_) => BusinessAction::ConfirmSingleTestStep,
+            BusinessActionPayload::SignOffSingleTestStep(_) => BusinessAction::SignOffSingleTestStep,
+            BusinessActionPayload::UpdateTaskDebugNote(_) => BusinessAction::UpdateTaskDebugNote,
+            BusinessActionPayload::UpdateCustomSharedData(_) => BusinessAction::UpdateCustomSharedData,
+        }
+    }
+}
+
+/// 判断消息类型是否为会修改任务状态、并由服务端以 `Ack` 确认的业务消息。
+///
+/// 客户端据此登记已发出但尚未确认的业务消息，以便在版本冲突时重新提交。
+pub fn is_business_message_type(message_type: &str) -> bool {
+    matches!(
+        message_type,
+        UPDATE_PRE_CHECK_ITEM_TYPE
+            | START_SINGLE_TEST_STEP_TYPE
+            | FEEDBACK_SINGLE_TEST_STEP_TYPE
+            | CONFIRM_SINGLE_TEST_STEP_TYPE
+            | SIGN_OFF_SINGLE_TEST_STEP_TYPE
+            | UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE
+            | UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE
+    )
+}
+
 /// EchoPayload 是一个简单的负载，用于测试 WebSocket 通信。
 /// 它包含一个字符串内容，期望被服务器回显。
 ///
@@ -112,15 +319,165 @@ pub struct ErrorResponsePayload {
     /// 例如，如果处理 "RegisterClient" 消息时出错，这里可以是 "RegisterClient"。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub original_message_type: Option<String>,
-    /// 错误的详细描述信息。
+    /// 错误的详细描述信息 (面向日志与调试，客户端不应解析此文本)。
     pub error: String,
+    /// 机器可读的错误码。旧版本服务端不会发送此字段。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    /// 本地化消息键，例如 "error.payload_invalid"，客户端据此查找面向用户的提示文本。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    /// 可选的字段级错误详情，通常在 `PAYLOAD_INVALID` 时给出。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldErrorDetail>,
 }
 
-/// PingPayload 是客户端发送到服务端的心跳消息负载。
-/// 当前为空结构体，但定义它有助于类型安全和未来的扩展。
+impl ErrorResponsePayload {
+    /// 根据错误码构造一个错误响应，`message_key` 取错误码的默认消息键。
+    pub fn new(code: ErrorCode, original_message_type: Option<String>, error: String) -> Self {
+        Self {
+            original_message_type,
+            error,
+            code: Some(code),
+            message_key: Some(code.message_key()),
+            details: Vec::new(),
+        }
+    }
+
+    /// 附加字段级错误详情。
+    pub fn with_details(mut self, details: Vec<FieldErrorDetail>) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// 字段级错误详情，指明负载中哪个字段有问题以及原因。
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldErrorDetail {
+    /// 出错的字段路径，例如 "item_id"；无法定位到具体字段时为 "$" (整个负载)。
+    pub field: String,
+    /// 出错原因的描述 (面向日志与调试)。
+    pub reason: String,
+    /// 可选的本地化消息键，例如 "error.field.required"。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+}
+
+impl FieldErrorDetail {
+    /// 构造一个不带本地化消息键的字段错误详情。
+    pub fn new(field: &str, reason: String) -> Self {
+        Self { field: field.to_string(), reason, message_key: None }
+    }
+}
+
+/// 业务消息的处理结果。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckStatus {
+    /// 业务操作已被接受，且任务状态发生了改变 (版本号已递增)。
+    Applied,
+    /// 业务操作已被接受，但任务状态没有变化 (例如重复提交了相同的值)。
+    NoChange,
+    /// 业务操作被拒绝，任务状态未被修改。具体原因见 `error_code` 和 `message`。
+    Rejected,
+}
+
+/// AckPayload 是服务端对业务消息的统一确认负载。
+///
+/// 无论业务消息处理成功与否，发送方都会收到且仅收到一条 `Ack`，
+/// 从而可以确定自己的操作是否到达服务端、是否生效，以及生效后的任务状态版本。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AckPayload {
+    /// 被确认的业务消息的消息类型，例如 "UpdatePreCheckItem"。
+    pub original_message_type: String,
+    /// 处理结果。
+    pub status: AckStatus,
+    /// 处理后的任务状态版本号。`Applied` 时为新版本，`NoChange` 时为当前版本；
+    /// 若无法确定 (例如任务状态不存在) 则为 `None`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// 被拒绝时的错误码，例如 "NOT_REGISTERED"、"PAYLOAD_INVALID"。仅在 `Rejected` 时出现。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// 可选的附加说明 (通常是被拒绝时的错误描述)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// 被拒绝时的本地化消息键。仅在 `Rejected` 时出现。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_key: Option<String>,
+    /// 被拒绝时可选的字段级错误详情。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldErrorDetail>,
+    /// 因版本冲突 (`VERSION_CONFLICT`) 被拒绝时，服务端当前完整的任务状态。
+    /// 客户端据此刷新本地缓存，并在最新状态上变基或合并本地修改后重新提交。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_state: Option<crate::task_models::TaskDebugState>,
+}
+
+impl AckPayload {
+    /// 构造一个"已生效"的确认。
+    pub fn applied(original_message_type: &str, version: u64) -> Self {
+        Self {
+            original_message_type: original_message_type.to_string(),
+            status: AckStatus::Applied,
+            version: Some(version),
+            error_code: None,
+            message: None,
+            message_key: None,
+            details: Vec::new(),
+            current_state: None,
+        }
+    }
+
+    /// 构造一个"无变化"的确认。
+    pub fn no_change(original_message_type: &str, version: u64) -> Self {
+        Self {
+            original_message_type: original_message_type.to_string(),
+            status: AckStatus::NoChange,
+            version: Some(version),
+            error_code: None,
+            message: None,
+            message_key: None,
+            details: Vec::new(),
+            current_state: None,
+        }
+    }
+
+    /// 构造一个"被拒绝"的确认。
+    pub fn rejected(original_message_type: &str, error_code: ErrorCode, message: String) -> Self {
+        Self {
+            original_message_type: original_message_type.to_string(),
+            status: AckStatus::Rejected,
+            version: None,
+            error_code: Some(error_code),
+            message: Some(message),
+            message_key: Some(error_code.message_key()),
+            details: Vec::new(),
+            current_state: None,
+        }
+    }
+
+    /// 为被拒绝的确认附加字段级错误详情。
+    pub fn with_details(mut self, details: Vec<FieldErrorDetail>) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// 为因版本冲突被拒绝的确认附加服务端当前的任务状态 (同时填入当前版本号)。
+    pub fn with_current_state(mut self, current_state: crate::task_models::TaskDebugState) -> Self {
+        self.version = Some(current_state.version);
+        self.current_state = Some(current_state);
+        self
+    }
+}
+
+/// PingPayload 是客户端发送到服务端的心跳消息负载。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 #[serde(deny_unknown_fields)]
-pub struct PingPayload {}
+pub struct PingPayload {
+    /// 客户端测得的上一次 Ping/Pong 往返时延 (毫秒)，可选。服务端据此在组成员名册中展示各成员的链路质量。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round_trip_ms: Option<u64>,
+}
 
 /// PongPayload 是服务端响应客户端心跳（Ping）的消息负载。
 /// 当前为空结构体。
@@ -128,6 +485,27 @@ pub struct PingPayload {}
 #[serde(deny_unknown_fields)]
 pub struct PongPayload {}
 
+/// 任务调试状态增量 (`TaskStateDelta`) 的消息负载。
+///
+/// `patch` 是把 `base_version` 版本的 `TaskDebugState` (按其 JSON 表示) 变为 `version` 版本的
+/// JSON Patch (RFC 6902) 操作序列。生成与应用见 `TaskDebugState::delta_to` / `TaskDebugState::apply_delta`。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaskStateDeltaPayload {
+    /// 增量所属的任务ID。
+    pub task_id: String,
+    /// 增量所基于的版本号；只有本地缓存恰好处于此版本时才能应用。
+    pub base_version: u64,
+    /// 应用增量后的版本号。
+    pub version: u64,
+    /// JSON Patch 操作序列。
+    pub patch: json_patch::Patch,
+}
+
+/// 客户端请求重新同步完整任务状态 (`TaskStateSyncRequest`) 的消息负载。
+/// 当前为空结构体，同步的目标组即客户端注册时加入的组。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct TaskStateSyncRequestPayload {}
+
 /// 客户端注册时发送的负载。
 ///
 /// 用于客户端向服务器声明其身份、希望加入或创建的组以及关联的任务。
@@ -135,11 +513,14 @@ pub struct PongPayload {}
 pub struct RegisterPayload {
     /// 客户端希望加入或创建的组的ID。
     /// 这个ID通常由用户（例如控制中心操作员）提供，或者基于任务信息生成。
+    /// 携带 `join_code` 时可以省略，由服务端根据配对码确定。
+    #[serde(default)]
     pub group_id: String,
     /// 客户端声明的角色 (例如，控制中心或现场移动端)。
     pub role: ClientRole,
     /// 客户端希望关联的调试任务的唯一ID。
-    /// 此ID用于在云端初始化或关联到特定的任务状态。
+    /// 此ID用于在云端初始化或关联到特定的任务状态。携带 `join_code` 时可以省略。
+    #[serde(default)]
     pub task_id: String,
     /// (新增) 客户端软件的版本信息，可选。
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -147,6 +528,48 @@ pub struct RegisterPayload {
     /// (新增) 客户端的显示名称，可选。用于在伙伴列表中展示。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_display_name: Option<String>,
+    /// 上一次注册成功时服务器签发的会话恢复令牌，可选。
+    /// 断线重连后携带此令牌注册，若仍在服务器的宽限期内，客户端将取回原有的客户端ID与组内槽位，
+    /// 并收到断线期间错过的消息；令牌无效或已过期时按普通注册处理。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
+    /// 现场移动端负责的设备与预检查类别，可选 (仅对 `ClientRole::OnSiteMobile` 有意义)。
+    /// 同一组内可以有多名现场工程师，但各自声明负责的设备与类别不能重叠；
+    /// 发起单体测试步骤的指令只会转发给负责该设备的工程师。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site_assignment: Option<SiteAssignment>,
+    /// 控制中心开组时取得的配对码 (见 `OpenGroup`)，可选。
+    /// 携带时服务端据此确定要加入的组与任务 (覆盖 `group_id`/`task_id`)，配对码随即失效，
+    /// 即使随后的注册因其他原因失败也不能再次使用；配对码无效或已过期时注册以 `JOIN_CODE_INVALID` 失败。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_code: Option<String>,
+}
+
+/// 现场工程师在组内负责的设备与预检查类别。
+///
+/// 两个列表均为空表示未划分职责 (通才)：未被任何工程师认领的设备的测试指令会转发给这类工程师。
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SiteAssignment {
+    /// 负责的设备ID，对应单体测试步骤负载中的 `device_id`。
+    #[serde(default)]
+    pub device_ids: Vec<String>,
+    /// 负责的预检查类别。
+    #[serde(default)]
+    pub pre_check_categories: Vec<String>,
+}
+
+impl SiteAssignment {
+    /// 是否负责指定设备。
+    pub fn owns_device(&self, device_id: &str) -> bool {
+        self.device_ids.iter().any(|owned| owned == device_id)
+    }
+
+    /// 返回与另一份分配重叠的设备与预检查类别 (用于拒绝重复认领)。
+    pub fn overlap_with(&self, other: &SiteAssignment) -> Vec<String> {
+        let devices = self.device_ids.iter().filter(|device| other.device_ids.contains(device));
+        let categories = self.pre_check_categories.iter().filter(|category| other.pre_check_categories.contains(category));
+        devices.chain(categories).cloned().collect()
+    }
 }
 
 /// 服务器对 "Register" 消息的响应负载。
@@ -170,6 +593,38 @@ pub struct RegisterResponsePayload {
     /// 通常与客户端请求的角色一致，但服务器可能有最终决定权。
     #[serde(skip_serializing_if = "Option::is_none")]
     pub effective_role: Option<ClientRole>,
+    /// 如果注册失败，机器可读的失败原因，例如 `ROLE_SLOT_TAKEN`、`TASK_MISMATCH`。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+    /// 如果注册成功，服务器签发的会话恢复令牌。
+    /// 客户端断线后在 `resume_grace_seconds` 秒内携带此令牌重新注册即可恢复原会话。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<String>,
+    /// 会话恢复的宽限期 (秒)，即断线后服务器为该客户端保留身份与组内槽位的时长。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_grace_seconds: Option<u64>,
+    /// 本次注册是否恢复了之前的会话 (沿用原客户端ID，随后会补发断线期间错过的消息)。
+    #[serde(default)]
+    pub resumed: bool,
+    /// 如果注册成功，服务器对该角色采用的心跳策略。客户端的心跳间隔应始终保持在其给出的范围内，
+    /// 以免被服务器判定为链路可疑或断线。旧版本服务器不发送此字段。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HeartbeatBoundsPayload>,
+}
+
+/// 服务器在 `RegisterResponse` 中公布的心跳策略 (`RegisterResponsePayload::heartbeat`)。
+///
+/// 客户端可根据测得的网络质量在 `min_interval_seconds` 与 `max_interval_seconds` 之间自行调整 Ping 间隔。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatBoundsPayload {
+    /// 客户端 Ping 间隔的下限 (秒)。
+    pub min_interval_seconds: u64,
+    /// 客户端 Ping 间隔的上限 (秒)，保证偶尔丢失一次 Ping 也不会让链路被标记为可疑。
+    pub max_interval_seconds: u64,
+    /// 服务器多久未收到消息就把该客户端的链路标记为可疑 (秒)。
+    pub suspect_after_seconds: u64,
+    /// 服务器多久未收到消息就判定该客户端已断线 (秒)。
+    pub timeout_seconds: u64,
 }
 
 /// 伙伴状态更新负载。
@@ -187,6 +642,194 @@ pub struct PartnerStatusPayload {
     pub is_online: bool,
     /// 发生此状态变化的伙伴所属的组ID。
     pub group_id: String,
+    /// 伙伴在线但链路可疑：服务端已有一段时间 (超过该角色的可疑阈值) 未收到其任何消息，
+    /// 若继续静默将被判定为断线。恢复通信后服务端会再发送一次 `link_suspect: false` 的通知。
+    /// 旧版本服务端不发送此字段，视为 `false`。
+    #[serde(default)]
+    pub link_suspect: bool,
+}
+
+/// 组成员名册中的一名成员 (`GroupRoster`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RosterMember {
+    /// 成员的客户端ID。
+    #[serde(with = "uuid::serde::simple")]
+    pub client_id: Uuid,
+    /// 成员在组内的角色。
+    pub role: ClientRole,
+    /// 成员注册时提供的显示名称。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// 成员注册时提供的客户端软件版本。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub software_version: Option<String>,
+    /// 成员当前连接的建立时间。
+    pub connected_at: DateTime<Utc>,
+    /// 服务端最后一次收到该成员消息的时间。
+    pub last_seen: DateTime<Utc>,
+    /// 成员最近报告的 Ping/Pong 往返时延 (毫秒)；尚未测得时省略。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub round_trip_ms: Option<u64>,
+    /// 成员是否在线。`false` 表示已断线、其槽位正处于会话恢复宽限期内。
+    pub is_online: bool,
+    /// 成员在线但链路可疑 (长时间未收到其消息，尚未达到断线判定时间)。
+    #[serde(default)]
+    pub link_suspect: bool,
+}
+
+/// 组成员名册负载 (`GroupRoster`)：组内全部成员的完整列表，客户端收到后整体替换本地名册。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupRosterPayload {
+    /// 组ID。
+    pub group_id: String,
+    /// 组关联的任务ID。
+    pub task_id: String,
+    /// 全部成员。
+    pub members: Vec<RosterMember>,
+}
+
+/// 接管角色槽位的请求负载 (`SlotTakeoverRequest`)。
+///
+/// 注册信息与普通的 `Register` 相同；接管只适用于独占角色 (`ClientRole::has_exclusive_slot`)。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SlotTakeoverRequestPayload {
+    /// 接管成功后以此身份加入组。
+    #[serde(flatten)]
+    pub register: RegisterPayload,
+    /// 是否跳过持有者确认直接接管。仅被授权的用户 (例如管理员) 可以强制接管。
+    #[serde(default)]
+    pub force: bool,
+    /// 当前持有者通过 `PrepareHandover` 取得并转交的一次性交接令牌。令牌有效时无需持有者再次确认。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handover_token: Option<String>,
+}
+
+/// 服务端向当前槽位持有者征求接管同意的负载 (`SlotTakeoverPrompt`)。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SlotTakeoverPromptPayload {
+    /// 本次接管请求的ID，答复时原样带回。
+    pub takeover_id: String,
+    /// 被请求接管的角色。
+    pub role: ClientRole,
+    /// 请求方的客户端ID。
+    #[serde(with = "uuid::serde::simple")]
+    pub requester_client_id: Uuid,
+    /// 请求方的显示名称 (如有)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requester_display_name: Option<String>,
+    /// 请求方的已认证用户 (如有)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requester_user_id: Option<String>,
+    /// 持有者在此秒数内未答复时，接管自动生效。
+    pub auto_approve_after_seconds: u64,
+}
+
+/// 当前槽位持有者对接管请求的答复负载 (`SlotTakeoverDecision`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlotTakeoverDecisionPayload {
+    /// `SlotTakeoverPrompt` 中的接管请求ID。
+    pub takeover_id: String,
+    /// `true` 表示同意交出槽位，`false` 表示拒绝。
+    pub approve: bool,
+}
+
+/// 槽位持有者请求交接令牌的负载 (`PrepareHandover`)。
+/// 当前为空结构体，交接的槽位即持有者注册时的组与角色。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PrepareHandoverPayload {}
+
+/// 服务端签发的交接令牌负载 (`HandoverToken`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HandoverTokenPayload {
+    /// 一次性交接令牌，接班的操作员在 `SlotTakeoverRequest` 中携带。
+    pub handover_token: String,
+    /// 交接的组ID。
+    pub group_id: String,
+    /// 交接的角色。
+    pub role: ClientRole,
+    /// 令牌的有效期 (秒)。
+    pub expires_in_seconds: u64,
+}
+
+/// 槽位被释放的原因。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SlotReleaseReason {
+    /// 被其他客户端接管 (持有者超时未答复、已断线或被强制接管)，原连接随后被关闭。
+    TakenOver,
+    /// 持有者同意了接管请求，或通过交接令牌计划内交给了接班的客户端；原连接保持但不再属于该组。
+    HandedOver,
+}
+
+/// 通知客户端其角色槽位已被释放的负载 (`SlotReleased`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SlotReleasedPayload {
+    /// 所在的组ID。
+    pub group_id: String,
+    /// 被释放的角色。
+    pub role: ClientRole,
+    /// 释放原因。
+    pub reason: SlotReleaseReason,
+    /// 接替该槽位的客户端ID。
+    #[serde(with = "uuid::serde::simple")]
+    pub successor_client_id: Uuid,
+}
+
+/// 客户端离开当前任务组的负载 (`LeaveGroup`)。
+/// 当前为空结构体，离开的即客户端注册时加入的组。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct LeaveGroupPayload {}
+
+/// 服务端确认客户端已离开任务组的负载 (`GroupLeft`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GroupLeftPayload {
+    /// 已离开的组ID。
+    pub group_id: String,
+    /// 客户端在该组内的角色。
+    pub role: ClientRole,
+}
+
+/// 切换任务的负载 (`SwitchTask`)：即目标任务组的注册信息。
+///
+/// 服务端先对目标组执行与 `Register` 相同的基本校验与授权检查，通过后才离开当前组，
+/// 因此无权加入目标组时客户端仍留在原组；离开原组后若目标槽位已被占用等原因导致加入失败，
+/// 客户端不再属于任何组，可重新注册。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SwitchTaskPayload {
+    /// 目标任务组的注册信息，字段与 `RegisterPayload` 相同并平铺在负载顶层。
+    #[serde(flatten)]
+    pub register: RegisterPayload,
+}
+
+/// 控制中心请求开组的负载 (`OpenGroup`)。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OpenGroupPayload {
+    /// 组要关联的任务ID。
+    pub task_id: String,
+    /// 指定的组ID，可选；省略时由服务端生成一个不会与现有组冲突的组ID。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<String>,
+    /// 客户端软件的版本信息，可选。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_software_version: Option<String>,
+    /// 客户端的显示名称，可选。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_display_name: Option<String>,
+}
+
+/// 服务端对 `OpenGroup` 的回复负载 (`GroupOpened`)。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GroupOpenedPayload {
+    /// 控制中心加入该组的结果，与 `Register` 的注册响应相同。失败时不签发配对码。
+    pub registration: RegisterResponsePayload,
+    /// 组关联的任务ID。
+    pub task_id: String,
+    /// 签发的配对码 (短字符串，便于口头或扫码传给现场)，现场端在 `RegisterPayload::join_code` 中携带。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_code: Option<String>,
+    /// 配对码的有效期 (秒)。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_code_expires_in_seconds: Option<u64>,
 }
 
 // P0.3.1_Test: EchoPayload 单元测试
@@ -228,6 +871,9 @@ mod tests {
         let original_payload = ErrorResponsePayload {
             original_message_type: Some("TestRequest".to_string()),
             error: "Something went wrong".to_string(),
+            code: None,
+            message_key: None,
+            details: Vec::new(),
         };
 
         let serialized_payload = serde_json::to_string(&original_payload);
@@ -251,6 +897,9 @@ mod tests {
         let original_payload = ErrorResponsePayload {
             original_message_type: None,
             error: "Another issue".to_string(),
+            code: None,
+            message_key: None,
+            details: Vec::new(),
         };
 
         let serialized_payload = serde_json::to_string(&original_payload);
@@ -271,13 +920,16 @@ mod tests {
 
     #[test]
     fn test_ping_payload_serialization_deserialization() {
-        let original_payload = PingPayload {};
+        let original_payload = PingPayload::default();
         let serialized_payload = serde_json::to_string(&original_payload).expect("PingPayload 序列化失败");
-        // 空结构体序列化后应为 "{}"
+        // 未测得往返时延时序列化后应为 "{}" (与旧版本服务端兼容)
         assert_eq!(serialized_payload, "{}");
 
         let deserialized_payload: PingPayload = serde_json::from_str(&serialized_payload).expect("PingPayload 反序列化失败");
         assert_eq!(original_payload, deserialized_payload);
+
+        let with_latency: PingPayload = serde_json::from_str(r#"{"round_trip_ms":42}"#).unwrap();
+        assert_eq!(with_latency.round_trip_ms, Some(42));
     }
 
     #[test]
@@ -291,6 +943,125 @@ mod tests {
         assert_eq!(original_payload, deserialized_payload);
     }
 
+    #[test]
+    fn test_ack_payload_serialization_deserialization() {
+        let applied = AckPayload::applied(UPDATE_PRE_CHECK_ITEM_TYPE, 3);
+        let json = serde_json::to_string(&applied).expect("AckPayload 序列化失败");
+        assert!(json.contains("\"status\":\"Applied\""));
+        assert!(json.contains("\"version\":3"));
+        assert!(!json.contains("error_code"), "成功的确认不应包含 error_code 字段");
+        assert_eq!(serde_json::from_str::<AckPayload>(&json).unwrap(), applied);
+
+        let rejected = AckPayload::rejected(UPDATE_PRE_CHECK_ITEM_TYPE, ErrorCode::NotRegistered, "未注册".to_string());
+        let json = serde_json::to_string(&rejected).unwrap();
+        assert!(json.contains("\"error_code\":\"NOT_REGISTERED\""));
+        let back: AckPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.status, AckStatus::Rejected);
+        assert_eq!(back.version, None);
+        assert_eq!(back.error_code, Some(ErrorCode::NotRegistered));
+        assert_eq!(back.message_key.as_deref(), Some("error.not_registered"));
+        assert!(!json.contains("current_state"), "非版本冲突的确认不应包含 current_state 字段");
+    }
+
+    #[test]
+    fn test_custom_shared_data_update_modes() {
+        let current = serde_json::json!({"devices": {"pump-1": {"status": "idle", "rpm": 0}}, "tags": ["a"]});
+        let update = |mode, new_data, pointer: Option<&str>| UpdateCustomSharedDataPayload {
+            mode,
+            new_data,
+            pointer: pointer.map(str::to_string),
+            expected_version: None,
+        };
+
+        // 旧版本负载只有 new_data：整体替换
+        let legacy: UpdateCustomSharedDataPayload = serde_json::from_str(r#"{"new_data":{"x":1}}"#).unwrap();
+        assert_eq!(legacy.mode, CustomSharedDataUpdateMode::Replace);
+        assert_eq!(legacy.apply_to(Some(&current)).unwrap(), serde_json::json!({"x": 1}));
+
+        // RFC 7386：合并对象成员，null 表示删除
+        let merged = update(
+            CustomSharedDataUpdateMode::MergePatch,
+            serde_json::json!({"devices": {"pump-1": {"status": "running", "rpm": null}}}),
+            None,
+        )
+        .apply_to(Some(&current))
+        .unwrap();
+        assert_eq!(merged, serde_json::json!({"devices": {"pump-1": {"status": "running"}}, "tags": ["a"]}));
+
+        // JSON Pointer：写入已有位置、自动创建缺失的对象成员、向数组追加
+        let pointed = update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1200), Some("/devices/pump-1/rpm"))
+            .apply_to(Some(&current))
+            .unwrap();
+        assert_eq!(pointed["devices"]["pump-1"]["rpm"], 1200);
+        let created = update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!("ok"), Some("/devices/fan~12/status"))
+            .apply_to(Some(&current))
+            .unwrap();
+        assert_eq!(created["devices"]["fan/2"]["status"], "ok");
+        let appended = update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!("b"), Some("/tags/-"))
+            .apply_to(Some(&current))
+            .unwrap();
+        assert_eq!(appended["tags"], serde_json::json!(["a", "b"]));
+        let from_empty = update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(true), Some("/flags/ready"))
+            .apply_to(None)
+            .unwrap();
+        assert_eq!(from_empty, serde_json::json!({"flags": {"ready": true}}));
+
+        // 无效的指针
+        assert!(update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1), None).apply_to(Some(&current)).is_err());
+        assert!(update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1), Some("devices")).apply_to(Some(&current)).is_err());
+        assert!(update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1), Some("/tags/5")).apply_to(Some(&current)).is_err());
+        assert!(update(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1), Some("/devices/pump-1/status/x")).apply_to(Some(&current)).is_err());
+    }
+
+    #[test]
+    fn test_version_conflict_ack_carries_current_state() {
+        let mut current = crate::task_models::TaskDebugState::new("task-1".to_string());
+        current.version = 5;
+        assert!(current.accepts_expected_version(None), "未声明期望版本的操作不做检查");
+        assert!(current.accepts_expected_version(Some(5)));
+        assert!(!current.accepts_expected_version(Some(4)));
+
+        let conflict = AckPayload::rejected(UPDATE_PRE_CHECK_ITEM_TYPE, ErrorCode::VersionConflict, "版本冲突".to_string())
+            .with_current_state(current.clone());
+        let json = serde_json::to_string(&conflict).unwrap();
+        assert!(json.contains("\"error_code\":\"VERSION_CONFLICT\""));
+        let back: AckPayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.version, Some(5));
+        assert_eq!(back.current_state, Some(current));
+
+        // 旧客户端发出的负载不带 expected_version，仍可解析且序列化时不输出该字段
+        let legacy: UpdateTaskDebugNotePayload =
+            serde_json::from_str(r#"{"group_id":"g1","new_note":"备注"}"#).unwrap();
+        assert_eq!(legacy.expected_version, None);
+        assert!(!serde_json::to_string(&legacy).unwrap().contains("expected_version"));
+        let action = BusinessActionPayload::UpdateTaskDebugNote(UpdateTaskDebugNotePayload { expected_version: Some(4), ..legacy });
+        assert_eq!(action.expected_version(), Some(4));
+        assert!(is_business_message_type(UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE));
+        assert!(!is_business_message_type(TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE));
+    }
+
+    #[test]
+    fn test_error_response_payload_with_code_and_details() {
+        let payload = ErrorResponsePayload::new(
+            ErrorCode::PayloadInvalid,
+            Some(UPDATE_PRE_CHECK_ITEM_TYPE.to_string()),
+            "负载解析失败".to_string(),
+        )
+        .with_details(vec![FieldErrorDetail::new("item_id", "missing field `item_id`".to_string())]);
+
+        let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"code\":\"PAYLOAD_INVALID\""));
+        assert!(json.contains("\"message_key\":\"error.payload_invalid\""));
+        let back: ErrorResponsePayload = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, payload);
+        assert_eq!(back.details[0].field, "item_id");
+
+        // 旧版本服务端发送的错误响应 (不含错误码等字段) 仍可被解析
+        let legacy: ErrorResponsePayload = serde_json::from_str(r#"{"error":"旧格式错误"}"#).unwrap();
+        assert_eq!(legacy.code, None);
+        assert!(legacy.details.is_empty());
+    }
+
     // 为 RegisterPayload 添加测试
     #[test]
     fn test_register_payload_serialization_deserialization() {
@@ -300,6 +1071,9 @@ mod tests {
             task_id: "task_abc_789".to_string(),
             client_software_version: None,
             client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
         };
 
         // 测试序列化
@@ -318,6 +1092,21 @@ mod tests {
         assert_eq!(payload.task_id, deserialized.task_id);
     }
 
+    #[test]
+    fn test_site_assignment_ownership_and_overlap() {
+        let pumps = SiteAssignment { device_ids: vec!["pump-1".to_string(), "pump-2".to_string()], pre_check_categories: vec!["electrical".to_string()] };
+        let valves = SiteAssignment { device_ids: vec!["valve-1".to_string()], pre_check_categories: vec!["mechanical".to_string()] };
+        assert!(pumps.owns_device("pump-2"));
+        assert!(!pumps.owns_device("valve-1"));
+        assert!(pumps.overlap_with(&valves).is_empty());
+        let greedy = SiteAssignment { device_ids: vec!["pump-2".to_string()], pre_check_categories: vec!["electrical".to_string()] };
+        assert_eq!(pumps.overlap_with(&greedy), vec!["pump-2".to_string(), "electrical".to_string()]);
+
+        // 旧客户端不携带分配字段
+        let legacy: RegisterPayload = serde_json::from_str(r#"{"group_id":"g","role":"OnSiteMobile","task_id":"t"}"#).unwrap();
+        assert!(legacy.site_assignment.is_none());
+    }
+
     #[test]
     fn test_register_payload_clone_debug() {
         let payload = RegisterPayload {
@@ -326,6 +1115,9 @@ mod tests {
             task_id: "clone_task".to_string(),
             client_software_version: None,
             client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
         };
         let cloned_payload = payload.clone();
         assert_eq!(payload.group_id, cloned_payload.group_id);
@@ -345,6 +1137,16 @@ mod tests {
             assigned_client_id: client_uuid,
             effective_group_id: Some("effective_group".to_string()),
             effective_role: Some(ClientRole::ControlCenter),
+            error_code: None,
+            resume_token: None,
+            resume_grace_seconds: None,
+            resumed: false,
+            heartbeat: Some(HeartbeatBoundsPayload {
+                min_interval_seconds: 5,
+                max_interval_seconds: 15,
+                suspect_after_seconds: 30,
+                timeout_seconds: 60,
+            }),
         };
 
         let serialized = serde_json::to_string_pretty(&payload).expect("RegisterResponsePayload serialization failed");
@@ -358,6 +1160,7 @@ mod tests {
         assert_eq!(payload.assigned_client_id, deserialized.assigned_client_id);
         assert_eq!(payload.effective_group_id, deserialized.effective_group_id);
         assert_eq!(payload.effective_role, deserialized.effective_role);
+        assert_eq!(payload.heartbeat, deserialized.heartbeat);
     }
 
     #[test]
@@ -369,6 +1172,11 @@ mod tests {
             assigned_client_id: client_uuid, 
             effective_group_id: None,
             effective_role: None,
+            error_code: Some(ErrorCode::RoleSlotTaken),
+            resume_token: None,
+            resume_grace_seconds: None,
+            resumed: false,
+            heartbeat: None,
         };
 
         let serialized = serde_json::to_string_pretty(&payload).expect("RegisterResponsePayload serialization failed");
@@ -382,6 +1190,34 @@ mod tests {
         assert_eq!(payload.assigned_client_id, deserialized.assigned_client_id);
         assert_eq!(payload.effective_group_id, deserialized.effective_group_id); // Will be None
         assert_eq!(payload.effective_role, deserialized.effective_role); // Will be None
+        assert_eq!(deserialized.error_code, Some(ErrorCode::RoleSlotTaken));
+    }
+
+    #[test]
+    fn test_register_payloads_without_resume_fields_remain_compatible() {
+        // 旧版本客户端/服务端不携带会话恢复相关字段
+        let register: RegisterPayload = serde_json::from_str(
+            r#"{"group_id":"g1","role":"OnSiteMobile","task_id":"t1"}"#,
+        )
+        .expect("旧版 RegisterPayload 应能反序列化");
+        assert_eq!(register.resume_token, None);
+        assert!(!serde_json::to_string(&register).unwrap().contains("resume_token"));
+        assert_eq!(register.join_code, None);
+
+        // 凭配对码注册时可省略组ID与任务ID
+        let by_code: RegisterPayload = serde_json::from_str(r#"{"role":"OnSiteMobile","join_code":"K7PQ2M"}"#)
+            .expect("仅携带配对码的 RegisterPayload 应能反序列化");
+        assert!(by_code.group_id.is_empty() && by_code.task_id.is_empty());
+        assert_eq!(by_code.join_code.as_deref(), Some("K7PQ2M"));
+
+        let response: RegisterResponsePayload = serde_json::from_str(&format!(
+            r#"{{"success":true,"assigned_client_id":"{}"}}"#,
+            Uuid::new_v4().simple()
+        ))
+        .expect("旧版 RegisterResponsePayload 应能反序列化");
+        assert_eq!(response.resume_token, None);
+        assert_eq!(response.resume_grace_seconds, None);
+        assert!(!response.resumed);
     }
 
     // 为 PartnerStatusPayload 编写单元测试
@@ -393,6 +1229,7 @@ mod tests {
             partner_client_id: partner_uuid,
             is_online: true,
             group_id: "group_status_xyz".to_string(),
+            link_suspect: true,
         };
 
         let serialized = serde_json::to_string_pretty(&payload).expect("PartnerStatusPayload serialization failed");
@@ -410,5 +1247,34 @@ mod tests {
         assert_eq!(payload.partner_client_id, deserialized.partner_client_id); // 核心断言
         assert_eq!(payload.is_online, deserialized.is_online);
         assert_eq!(payload.group_id, deserialized.group_id);
+        assert!(deserialized.link_suspect);
+
+        // 旧版本服务端发送的通知不含 link_suspect 字段
+        let legacy: PartnerStatusPayload = serde_json::from_str(&format!(
+            r#"{{"partner_role":"ControlCenter","partner_client_id":"{}","is_online":true,"group_id":"g"}}"#,
+            partner_uuid.simple()
+        ))
+        .unwrap();
+        assert!(!legacy.link_suspect);
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_slot_takeover_request_flattens_register_fields() {
+        let json = r#"{"group_id":"g","role":"ControlCenter","task_id":"t","handover_token":"h-1"}"#;
+        let request: SlotTakeoverRequestPayload = serde_json::from_str(json).unwrap();
+        assert_eq!(request.register.group_id, "g");
+        assert_eq!(request.register.role, ClientRole::ControlCenter);
+        assert!(!request.force, "未指定时不强制接管");
+        assert_eq!(request.handover_token.as_deref(), Some("h-1"));
+
+        let released = SlotReleasedPayload {
+            group_id: "g".to_string(),
+            role: ClientRole::ControlCenter,
+            reason: SlotReleaseReason::HandedOver,
+            successor_client_id: Uuid::new_v4(),
+        };
+        let serialized = serde_json::to_string(&released).unwrap();
+        assert!(serialized.contains(r#""reason":"HANDED_OVER""#));
+        assert_eq!(serde_json::from_str::<SlotReleasedPayload>(&serialized).unwrap(), released);
+    }
+}
@@ -0,0 +1,49 @@
+// rust_websocket_utils/src/client/error_handling.rs
+
+//! 云端错误码到客户端处理方式的映射。
+//!
+//! 云端在 `ErrorResponse`、被拒绝的 `Ack` 以及失败的 `RegisterResponse` 中携带机器可读的错误码
+//! (`common_models::enums::ErrorCode`)。本模块把错误码映射为前端可以直接据此行动的处理方式
+//! ([`ServerErrorHandling`])，前端无需也不应解析错误描述文本；面向用户的提示文本由前端根据
+//! 事件中的 `message_key` 在本地语言资源中查找。
+
+use common_models::enums::ErrorCode;
+use serde::{Deserialize, Serialize};
+
+/// 收到云端错误后建议的处理方式，随错误相关的事件一起发送给前端。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerErrorHandling {
+    /// 会话不在任务组中 (或任务组已失效)，需要重新注册。
+    Reregister,
+    /// 本地状态已过期，需要重新同步任务状态后再操作。
+    ResyncState,
+    /// 本端修改基于过期的任务状态版本被拒绝：本地缓存已刷新为云端当前状态，
+    /// 应让用户选择变基重新提交、合并或放弃 (见 `WsVersionConflictEvent`)。
+    ResolveConflict,
+    /// 请求内容不合法，应提示用户修正输入 (可结合字段级详情高亮对应字段)。
+    FixInput,
+    /// 任务ID与任务组不一致，应提示用户重新选择任务。
+    SelectAnotherTask,
+    /// 请求的角色已被其他客户端占用，应提示用户稍后再试或联系占用方。
+    RoleUnavailable,
+    /// 当前用户无权执行该操作。
+    PermissionDenied,
+    /// 云端暂时无法处理，可稍后重试。
+    RetryLater,
+    /// 无特定处理方式，仅向用户展示错误提示。
+    ShowMessage,
+}
+
+/// 根据云端返回的错误码确定处理方式。未携带错误码 (旧版本云端) 或无法识别时按 `ShowMessage` 处理。
+pub fn handling_for(code: Option<ErrorCode>) -> ServerErrorHandling {
+    match code {
+        Some(ErrorCode::NotRegistered) | Some(ErrorCode::TaskNotFound) => ServerErrorHandling::Reregister,
+        Some(ErrorCode::VersionConflict) => ServerErrorHandling::ResolveConflict,
+        Some(ErrorCode::PayloadInvalid) | Some(ErrorCode::JoinCodeInvalid) => ServerErrorHandling::FixInput,
+        Some(ErrorCode::TaskMismatch) => ServerErrorHandling::SelectAnotherTask,
+        Some(ErrorCode::RoleSlotTaken) => ServerErrorHandling::RoleUnavailable,
+        Some(ErrorCode::Forbidden) => ServerErrorHandling::PermissionDenied,
+        Some(ErrorCode::InternalError) => ServerErrorHandling::RetryLater,
+        Some(ErrorCode::UnsupportedMessage) | None => ServerErrorHandling::ShowMessage,
+    }
+}
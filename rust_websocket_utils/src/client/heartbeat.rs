@@ -0,0 +1,266 @@
+// rust_websocket_utils/src/client/heartbeat.rs
+
+//! 根据实测网络质量自适应调整的客户端心跳。
+//!
+//! 客户端每次收到 Pong 时把该次 Ping 的往返时延交给 [`AdaptiveHeartbeat::on_pong`]，
+//! 未在期限内收到 Pong 时调用 [`AdaptiveHeartbeat::on_missed_pong`]。本模块据此维护：
+//! - 平滑往返时延与抖动 (与 TCP 的 SRTT / RTTVAR 估计方法相同)；
+//! - 链路质量等级 ([`LinkQualityLevel`])，供前端展示；
+//! - 下一次 Ping 的间隔：链路良好时使用上限以节省流量与电量，链路变差时逐步缩短，
+//!   让服务端更频繁地收到活动、也让客户端更早发现连接"假死"；
+//! - 等待 Pong 的期限：随实测时延放宽，避免慢速网络上的误判。
+//!
+//! 间隔始终限制在 [`HeartbeatBounds`] 内。服务端在 `RegisterResponse` 中公布其心跳策略
+//! (`HeartbeatBoundsPayload`)，客户端据此调用 [`AdaptiveHeartbeat::apply_server_bounds`]，保证间隔不会超过服务端的判定时间。
+
+use common_models::ws_payloads::HeartbeatBoundsPayload;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 服务端未公布心跳策略 (旧版本服务端) 时使用的 Ping 间隔上限 (秒)。
+pub const DEFAULT_MAX_HEARTBEAT_INTERVAL_SECONDS: u64 = 30;
+/// 服务端未公布心跳策略时使用的 Ping 间隔下限 (秒)。
+pub const DEFAULT_MIN_HEARTBEAT_INTERVAL_SECONDS: u64 = 5;
+/// 等待 Pong 的默认期限 (秒)，也是实测时延较小时使用的期限。
+pub const DEFAULT_PONG_TIMEOUT_SECONDS: u64 = 10;
+
+/// 平滑往返时延的更新权重 (1/8)。
+const RTT_GAIN: f64 = 0.125;
+/// 抖动估计的更新权重 (1/4)。
+const JITTER_GAIN: f64 = 0.25;
+/// 平滑时延不超过此值且抖动不超过 `GOOD_JITTER_MS` 时视为链路良好 (毫秒)。
+const GOOD_RTT_MS: f64 = 300.0;
+const GOOD_JITTER_MS: f64 = 100.0;
+/// 平滑时延不超过此值且抖动不超过 `FAIR_JITTER_MS` 时视为链路一般 (毫秒)。
+const FAIR_RTT_MS: f64 = 1000.0;
+const FAIR_JITTER_MS: f64 = 500.0;
+
+/// 心跳间隔的允许范围。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatBounds {
+    /// Ping 间隔下限。
+    pub min_interval: Duration,
+    /// Ping 间隔上限。
+    pub max_interval: Duration,
+}
+
+impl Default for HeartbeatBounds {
+    fn default() -> Self {
+        Self {
+            min_interval: Duration::from_secs(DEFAULT_MIN_HEARTBEAT_INTERVAL_SECONDS),
+            max_interval: Duration::from_secs(DEFAULT_MAX_HEARTBEAT_INTERVAL_SECONDS),
+        }
+    }
+}
+
+impl From<&HeartbeatBoundsPayload> for HeartbeatBounds {
+    fn from(payload: &HeartbeatBoundsPayload) -> Self {
+        let max_interval = Duration::from_secs(payload.max_interval_seconds.max(1));
+        Self {
+            min_interval: Duration::from_secs(payload.min_interval_seconds).min(max_interval),
+            max_interval,
+        }
+    }
+}
+
+/// 链路质量等级。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkQualityLevel {
+    /// 尚未测得往返时延。
+    Unknown,
+    /// 时延低且稳定。
+    Good,
+    /// 时延或抖动偏高。
+    Fair,
+    /// 时延很高、抖动很大，或最近有 Ping 未得到回复。
+    Poor,
+}
+
+/// 一次链路质量评估的结果，可直接作为事件负载发给前端。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkQuality {
+    /// 质量等级。
+    pub level: LinkQualityLevel,
+    /// 最近一次测得的往返时延 (毫秒)。
+    pub last_rtt_ms: Option<u64>,
+    /// 平滑往返时延 (毫秒)。
+    pub smoothed_rtt_ms: Option<u64>,
+    /// 往返时延的抖动 (平均偏差，毫秒)。
+    pub jitter_ms: Option<u64>,
+    /// 连续未收到 Pong 的次数。
+    pub consecutive_missed_pongs: u32,
+    /// 当前采用的 Ping 间隔 (毫秒)。
+    pub heartbeat_interval_ms: u64,
+}
+
+/// 自适应心跳的状态。
+#[derive(Debug, Clone)]
+pub struct AdaptiveHeartbeat {
+    bounds: HeartbeatBounds,
+    last_rtt_ms: Option<f64>,
+    smoothed_rtt_ms: Option<f64>,
+    jitter_ms: f64,
+    consecutive_missed_pongs: u32,
+}
+
+impl AdaptiveHeartbeat {
+    /// 以指定的间隔范围创建。尚未测得时延时使用间隔上限。
+    pub fn new(bounds: HeartbeatBounds) -> Self {
+        Self {
+            bounds,
+            last_rtt_ms: None,
+            smoothed_rtt_ms: None,
+            jitter_ms: 0.0,
+            consecutive_missed_pongs: 0,
+        }
+    }
+
+    /// 采用服务端在 `RegisterResponse` 中公布的心跳策略。
+    pub fn apply_server_bounds(&mut self, payload: &HeartbeatBoundsPayload) {
+        self.bounds = HeartbeatBounds::from(payload);
+    }
+
+    /// 当前的间隔范围。
+    pub fn bounds(&self) -> HeartbeatBounds {
+        self.bounds
+    }
+
+    /// 收到 Pong，`rtt` 为该次 Ping 的往返时延。
+    pub fn on_pong(&mut self, rtt: Duration) -> LinkQuality {
+        let rtt_ms = rtt.as_secs_f64() * 1000.0;
+        match self.smoothed_rtt_ms {
+            None => {
+                self.smoothed_rtt_ms = Some(rtt_ms);
+                self.jitter_ms = rtt_ms / 2.0;
+            }
+            Some(smoothed) => {
+                self.jitter_ms += JITTER_GAIN * ((smoothed - rtt_ms).abs() - self.jitter_ms);
+                self.smoothed_rtt_ms = Some(smoothed + RTT_GAIN * (rtt_ms - smoothed));
+            }
+        }
+        self.last_rtt_ms = Some(rtt_ms);
+        self.consecutive_missed_pongs = 0;
+        self.quality()
+    }
+
+    /// 上一次 Ping 在期限内未收到 Pong。
+    pub fn on_missed_pong(&mut self) -> LinkQuality {
+        self.consecutive_missed_pongs = self.consecutive_missed_pongs.saturating_add(1);
+        self.quality()
+    }
+
+    /// 当前的链路质量等级。
+    pub fn level(&self) -> LinkQualityLevel {
+        if self.consecutive_missed_pongs > 0 {
+            return LinkQualityLevel::Poor;
+        }
+        match self.smoothed_rtt_ms {
+            None => LinkQualityLevel::Unknown,
+            Some(rtt) if rtt <= GOOD_RTT_MS && self.jitter_ms <= GOOD_JITTER_MS => LinkQualityLevel::Good,
+            Some(rtt) if rtt <= FAIR_RTT_MS && self.jitter_ms <= FAIR_JITTER_MS => LinkQualityLevel::Fair,
+            Some(_) => LinkQualityLevel::Poor,
+        }
+    }
+
+    /// 下一次 Ping 之前应等待的时间。链路良好 (或尚无测量) 时取上限，一般时取上下限的中点，较差时取下限。
+    pub fn interval(&self) -> Duration {
+        let HeartbeatBounds { min_interval, max_interval } = self.bounds;
+        match self.level() {
+            LinkQualityLevel::Unknown | LinkQualityLevel::Good => max_interval,
+            LinkQualityLevel::Fair => (min_interval + max_interval) / 2,
+            LinkQualityLevel::Poor => min_interval,
+        }
+    }
+
+    /// 等待 Pong 的期限：平滑时延加四倍抖动，不小于 `DEFAULT_PONG_TIMEOUT_SECONDS`，
+    /// 也不超过 Ping 间隔上限 (超过时在下一次 Ping 之前仍无法判断)。
+    pub fn pong_timeout(&self) -> Duration {
+        let floor = Duration::from_secs(DEFAULT_PONG_TIMEOUT_SECONDS).min(self.bounds.max_interval);
+        let estimate = self
+            .smoothed_rtt_ms
+            .map(|rtt| Duration::from_secs_f64((rtt + 4.0 * self.jitter_ms) / 1000.0))
+            .unwrap_or_default();
+        estimate.clamp(floor, self.bounds.max_interval.max(floor))
+    }
+
+    /// 当前的链路质量评估。
+    pub fn quality(&self) -> LinkQuality {
+        LinkQuality {
+            level: self.level(),
+            last_rtt_ms: self.last_rtt_ms.map(|ms| ms.round() as u64),
+            smoothed_rtt_ms: self.smoothed_rtt_ms.map(|ms| ms.round() as u64),
+            jitter_ms: self.smoothed_rtt_ms.map(|_| self.jitter_ms.round() as u64),
+            consecutive_missed_pongs: self.consecutive_missed_pongs,
+            heartbeat_interval_ms: self.interval().as_millis() as u64,
+        }
+    }
+}
+
+impl Default for AdaptiveHeartbeat {
+    fn default() -> Self {
+        Self::new(HeartbeatBounds::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_follows_link_quality_within_bounds() {
+        let mut heartbeat = AdaptiveHeartbeat::default();
+        assert_eq!(heartbeat.level(), LinkQualityLevel::Unknown);
+        assert_eq!(heartbeat.interval(), Duration::from_secs(30));
+
+        // 低时延、稳定：使用上限
+        for _ in 0..5 {
+            heartbeat.on_pong(Duration::from_millis(80));
+        }
+        assert_eq!(heartbeat.level(), LinkQualityLevel::Good);
+        assert_eq!(heartbeat.interval(), Duration::from_secs(30));
+
+        // 时延升高：缩短间隔
+        for _ in 0..20 {
+            heartbeat.on_pong(Duration::from_millis(700));
+        }
+        assert_eq!(heartbeat.level(), LinkQualityLevel::Fair);
+        assert_eq!(heartbeat.interval(), Duration::from_millis(17_500));
+
+        // 丢失 Pong：使用下限，收到下一次 Pong 后恢复
+        let quality = heartbeat.on_missed_pong();
+        assert_eq!(quality.level, LinkQualityLevel::Poor);
+        assert_eq!(quality.consecutive_missed_pongs, 1);
+        assert_eq!(heartbeat.interval(), Duration::from_secs(5));
+        assert_eq!(heartbeat.on_pong(Duration::from_millis(700)).consecutive_missed_pongs, 0);
+    }
+
+    #[test]
+    fn test_server_bounds_cap_interval_and_pong_timeout() {
+        let mut heartbeat = AdaptiveHeartbeat::default();
+        heartbeat.apply_server_bounds(&HeartbeatBoundsPayload {
+            min_interval_seconds: 5,
+            max_interval_seconds: 8,
+            suspect_after_seconds: 16,
+            timeout_seconds: 30,
+        });
+        assert_eq!(heartbeat.interval(), Duration::from_secs(8));
+        assert_eq!(heartbeat.pong_timeout(), Duration::from_secs(8), "等待 Pong 的期限不应超过间隔上限");
+
+        // 时延很大且抖动剧烈时，期限放宽但仍受上限约束
+        heartbeat.on_pong(Duration::from_millis(3_000));
+        heartbeat.on_pong(Duration::from_millis(200));
+        assert_eq!(heartbeat.level(), LinkQualityLevel::Poor);
+        assert_eq!(heartbeat.interval(), Duration::from_secs(5));
+        assert_eq!(heartbeat.pong_timeout(), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_jitter_reflects_rtt_variation() {
+        let mut heartbeat = AdaptiveHeartbeat::default();
+        let quality = heartbeat.on_pong(Duration::from_millis(100));
+        assert_eq!((quality.last_rtt_ms, quality.smoothed_rtt_ms, quality.jitter_ms), (Some(100), Some(100), Some(50)));
+        let quality = heartbeat.on_pong(Duration::from_millis(300));
+        // 抖动: 50 + (|100 - 300| - 50) / 4 = 87.5；平滑时延: 100 + (300 - 100) / 8 = 125
+        assert_eq!((quality.last_rtt_ms, quality.smoothed_rtt_ms, quality.jitter_ms), (Some(300), Some(125), Some(88)));
+    }
+}
@@ -13,5 +13,16 @@
 //!   提供一个更简洁、更易于使用的API给上层应用。
 //!
 //! `transport` 子模块通常包含具体的传输层实现，例如 `TransportLayer` 结构体及其相关方法。
+//! `request` 子模块提供基于 `message_id` / `in_reply_to` 的请求-响应关联 (挂起请求表)。
+//! `reconnect` 子模块提供带指数退避自动重连的客户端 (`ReconnectingClient`)。
+//! `heartbeat` 子模块根据实测往返时延与抖动自适应调整心跳间隔，并评估链路质量 (`AdaptiveHeartbeat`)。
+//! `error_handling` 与 `version_conflict` 子模块分别提供云端错误码的处理方式映射与版本冲突的变基/合并。
+//! `sat_client` 子模块在以上组件之上提供与界面框架无关的业务客户端 (`SatClient`)，中心端与现场端共用。
 
-pub mod transport; // 公开 transport 子模块，其中包含主要的客户端传输层逻辑 
\ No newline at end of file
+pub mod transport; // 公开 transport 子模块，其中包含主要的客户端传输层逻辑
+pub mod request; // 请求-响应关联辅助设施 (挂起请求表 PendingRequests)
+pub mod reconnect; // 带指数退避与抖动的自动重连客户端 (ReconnectingClient)
+pub mod heartbeat; // 根据网络质量自适应调整的心跳间隔与链路质量评估 (AdaptiveHeartbeat)
+pub mod error_handling; // 云端错误码到客户端处理方式的映射 (ServerErrorHandling)
+pub mod version_conflict; // 业务消息版本冲突的登记与变基/合并 (UnconfirmedBusinessMessages)
+pub mod sat_client; // 与界面框架无关的业务客户端：类型化的业务方法与事件流 (SatClient)
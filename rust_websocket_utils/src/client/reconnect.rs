@@ -0,0 +1,556 @@
+// rust_websocket_utils/src/client/reconnect.rs
+
+//! 带自动重连的 WebSocket 客户端。
+//!
+//! [`transport::connect_client`](super::transport::connect_client) 只做一次连接尝试，连接断开后需要调用方自行重连。
+//! 现场平板等网络不稳定的场景下，本模块提供的 [`ReconnectingClient`] 会在后台维持连接：
+//! - 连接失败或已建立的连接断开后，按照 [`ReconnectPolicy`] 以指数退避 (带随机抖动) 的间隔重试；
+//! - 连续失败次数达到策略上限后放弃，并发出 [`ConnectionState::GaveUp`]；
+//! - 连接状态的每次变化都以 [`ClientEvent::StateChanged`] 通知调用方，收到的业务消息以 [`ClientEvent::Message`] 转交；
+//! - 通过 [`ReconnectingClient::send`] 发送过的最后一条 `Register` 消息会被记住，
+//!   每次重连成功后自动以新的 `message_id` 重新发送，使会话重新加入原来的任务组；
+//! - 成功的 `RegisterResponse` 中携带的会话恢复令牌会被写入记住的 `Register` 消息，
+//!   使重连后的注册能够在服务端宽限期内恢复原会话并补收断线期间错过的消息。
+//!
+//! 心跳、请求-响应关联等上层逻辑仍由调用方负责；调用方在检测到连接"假死" (例如心跳超时) 时，
+//! 可调用 [`ReconnectingClient::force_reconnect`] 主动断开当前连接并触发重连。
+
+use crate::client::transport::{self, ClientWsStream};
+use crate::error::WsError;
+use crate::message::WsMessage;
+use common_models::ws_payloads::{REGISTER_MESSAGE_TYPE, REGISTER_RESPONSE_MESSAGE_TYPE, RegisterPayload, RegisterResponsePayload};
+use futures_util::SinkExt;
+use futures_util::stream::SplitSink;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex as TokioMutex, Notify, mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// 事件通道的容量。调用方处理事件过慢时，接收循环会等待而不是丢弃消息。
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// 重连策略：指数退避、随机抖动与最大尝试次数。
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// 第一次重试前的等待时间。
+    pub initial_delay: Duration,
+    /// 等待时间的上限 (抖动之前)。
+    pub max_delay: Duration,
+    /// 每次连续失败后等待时间的增长倍数。
+    pub multiplier: f64,
+    /// 随机抖动比例 (0.0 ~ 1.0)。实际等待时间在 `基础等待时间 × (1 ± jitter_ratio)` 之间均匀分布，
+    /// 避免大量客户端在服务端恢复后同时重连。
+    pub jitter_ratio: f64,
+    /// 连续连接失败多少次后放弃；`None` 表示永不放弃。已建立的连接断开后计数会重新开始。
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter_ratio: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 第 `attempt` 次重试 (从 1 开始) 之前的基础等待时间，不含抖动。
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63) as i32;
+        let delay_secs = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_secs_f64(delay_secs.min(self.max_delay.as_secs_f64()))
+    }
+
+    /// 在基础等待时间上叠加抖动。`unit_random` 为 [0, 1) 区间内的随机数，0.5 表示不偏移。
+    pub fn delay_with_jitter(&self, attempt: u32, unit_random: f64) -> Duration {
+        let jitter = self.jitter_ratio.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * unit_random.clamp(0.0, 1.0);
+        Duration::from_secs_f64(self.base_delay(attempt).as_secs_f64() * factor)
+    }
+
+    /// 第 `attempt` 次重试之前实际应等待的时间 (含随机抖动)。
+    pub fn next_delay(&self, attempt: u32) -> Duration {
+        self.delay_with_jitter(attempt, unit_random())
+    }
+
+    /// 在已经连续失败 `consecutive_failures` 次之后，是否还允许继续尝试。
+    pub fn allows_retry(&self, consecutive_failures: u32) -> bool {
+        self.max_attempts.is_none_or(|max| consecutive_failures < max)
+    }
+}
+
+/// 生成 [0, 1) 区间内的随机数，仅用于退避抖动，不要求密码学强度。
+fn unit_random() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// 重连客户端的连接状态。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state")]
+pub enum ConnectionState {
+    /// 正在进行第 `attempt` 次连接尝试 (自上次连接成功以来，从 1 开始计数)。
+    Connecting { attempt: u32 },
+    /// 连接已建立。`reconnected` 为 `true` 表示这是一次断线后的重连。
+    Connected { reconnected: bool },
+    /// 连接失败或断开，将在 `delay_ms` 毫秒后进行第 `attempt` 次尝试。
+    Reconnecting { attempt: u32, delay_ms: u64, reason: String },
+    /// 连续失败次数达到策略上限，不再重试。
+    GaveUp { attempts: u32, last_error: String },
+    /// 调用方主动关闭了客户端。
+    Closed,
+}
+
+/// 重连客户端向调用方发出的事件。
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    /// 连接状态发生变化。
+    StateChanged(ConnectionState),
+    /// 从服务端收到一条消息。
+    Message(WsMessage),
+}
+
+/// 在客户端句柄与后台连接任务之间共享的状态。
+struct SharedState {
+    /// 当前连接的发送端；未连接时为 `None`。
+    sender: TokioMutex<Option<SplitSink<ClientWsStream, Message>>>,
+    /// 当前是否已连接。
+    connected: AtomicBool,
+    /// 最后一次发送的 `Register` 消息，重连成功后会重新发送。
+    last_register: std::sync::Mutex<Option<WsMessage>>,
+    /// 通知连接任务断开当前连接并重连。
+    force_reconnect: Notify,
+}
+
+impl SharedState {
+    fn last_register(&self) -> Option<WsMessage> {
+        self.last_register.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    fn set_last_register(&self, message: Option<WsMessage>) {
+        *self.last_register.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = message;
+    }
+}
+
+/// 带自动重连的 WebSocket 客户端句柄。
+///
+/// 通过 [`ReconnectingClient::start`] 创建，后台任务会立即开始连接。
+/// 句柄被丢弃时后台任务也会随之关闭连接并结束；需要等待其结束时调用 [`ReconnectingClient::shutdown`]。
+pub struct ReconnectingClient {
+    shared: Arc<SharedState>,
+    shutdown_tx: watch::Sender<bool>,
+    task: TokioMutex<Option<JoinHandle<()>>>,
+}
+
+impl std::fmt::Debug for ReconnectingClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectingClient")
+            .field("connected", &self.is_connected())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReconnectingClient {
+    /// 启动重连客户端，返回客户端句柄与事件接收端。
+    ///
+    /// 事件接收端被丢弃时，后台任务会关闭连接并结束。
+    pub fn start(url: String, policy: ReconnectPolicy) -> (Self, mpsc::Receiver<ClientEvent>) {
+        let (event_tx, event_rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let shared = Arc::new(SharedState {
+            sender: TokioMutex::new(None),
+            connected: AtomicBool::new(false),
+            last_register: std::sync::Mutex::new(None),
+            force_reconnect: Notify::new(),
+        });
+        let task = tokio::spawn(run_connection_loop(url, policy, Arc::clone(&shared), event_tx, shutdown_rx));
+        let client = Self { shared, shutdown_tx, task: TokioMutex::new(Some(task)) };
+        (client, event_rx)
+    }
+
+    /// 当前是否已连接。
+    pub fn is_connected(&self) -> bool {
+        self.shared.connected.load(Ordering::SeqCst)
+    }
+
+    /// 通过当前连接发送一条消息。
+    ///
+    /// 若消息类型为 `Register`，它会被记住并在每次重连成功后自动重新发送。
+    /// 未连接时返回 `WsError::NotConnected`，消息不会被缓存 (离线缓存由上层负责)。
+    pub async fn send(&self, message: &WsMessage) -> Result<(), WsError> {
+        if message.message_type == REGISTER_MESSAGE_TYPE {
+            self.shared.set_last_register(Some(message.clone()));
+        }
+        send_on(&self.shared, message).await
+    }
+
+    /// 手动设置 (或清除) 重连后需要重新发送的 `Register` 消息，例如离开任务组之后。
+    pub fn set_register_message(&self, message: Option<WsMessage>) {
+        self.shared.set_last_register(message);
+    }
+
+    /// 断开当前连接并按重连策略重新连接 (例如心跳超时、判断连接已假死时调用)。
+    pub fn force_reconnect(&self) {
+        self.shared.force_reconnect.notify_one();
+    }
+
+    /// 关闭客户端：断开当前连接、停止重连，并等待后台任务结束。调用后将发出 [`ConnectionState::Closed`]。
+    pub async fn shutdown(&self) {
+        let _ = self.shutdown_tx.send(true);
+        let task = self.task.lock().await.take();
+        if let Some(task) = task
+            && let Err(e) = task.await
+        {
+            warn!("[重连客户端] 等待后台连接任务结束时发生错误: {:?}", e);
+        }
+    }
+}
+
+/// 通过共享状态中的发送端发送一条消息。
+async fn send_on(shared: &SharedState, message: &WsMessage) -> Result<(), WsError> {
+    let mut sender_guard = shared.sender.lock().await;
+    let Some(sender) = sender_guard.as_mut() else {
+        return Err(WsError::NotConnected);
+    };
+    let json = serde_json::to_string(message)
+        .map_err(|e| WsError::SerializationError(format!("消息序列化为JSON失败: {}", e)))?;
+    sender.send(Message::Text(json)).await.map_err(WsError::from)
+}
+
+/// 后台连接任务：连接、转发消息、断开后按策略重连，直到被关闭或放弃。
+async fn run_connection_loop(
+    url: String,
+    policy: ReconnectPolicy,
+    shared: Arc<SharedState>,
+    event_tx: mpsc::Sender<ClientEvent>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    // 自上次连接成功以来连续失败的次数
+    let mut consecutive_failures: u32 = 0;
+    let mut has_connected_before = false;
+
+    'outer: loop {
+        let attempt = consecutive_failures + 1;
+        if !emit(&event_tx, ClientEvent::StateChanged(ConnectionState::Connecting { attempt })).await {
+            break;
+        }
+        info!("[重连客户端] 第 {} 次尝试连接到 {}", attempt, url);
+
+        let connect_result = tokio::select! {
+            result = transport::connect_client(url.clone()) => result,
+            _ = shutdown_rx.changed() => break 'outer,
+        };
+
+        let reason = match connect_result {
+            Ok(connection) => {
+                consecutive_failures = 0;
+                let mut receiver = connection.ws_receiver;
+                *shared.sender.lock().await = Some(connection.ws_sender);
+                shared.connected.store(true, Ordering::SeqCst);
+                if !emit(&event_tx, ClientEvent::StateChanged(ConnectionState::Connected { reconnected: has_connected_before })).await {
+                    break 'outer;
+                }
+                if has_connected_before {
+                    resend_register(&shared).await;
+                }
+                has_connected_before = true;
+
+                // 接收循环：转发消息，直到连接断开、被要求重连或被关闭
+                let reason = loop {
+                    tokio::select! {
+                        received = transport::receive_message(&mut receiver) => match received {
+                            Some(Ok(message)) => {
+                                remember_resume_token(&shared, &message);
+                                if !emit(&event_tx, ClientEvent::Message(message)).await {
+                                    break 'outer;
+                                }
+                            }
+                            Some(Err(WsError::DeserializationError(e))) => {
+                                // 单条消息格式错误不影响连接本身
+                                warn!("[重连客户端] 忽略一条无法解析的消息: {}", e);
+                            }
+                            Some(Err(e)) => break format!("接收消息时发生错误: {}", e),
+                            None => break "连接已由对方关闭".to_string(),
+                        },
+                        _ = shared.force_reconnect.notified() => break "调用方要求重新连接".to_string(),
+                        _ = shutdown_rx.changed() => break 'outer,
+                    }
+                };
+                close_connection(&shared).await;
+                reason
+            }
+            Err(e) => {
+                consecutive_failures += 1;
+                format!("连接失败: {}", e)
+            }
+        };
+
+        if !policy.allows_retry(consecutive_failures) {
+            error!("[重连客户端] 连续 {} 次连接失败，放弃重连。最后一次错误: {}", consecutive_failures, reason);
+            emit(&event_tx, ClientEvent::StateChanged(ConnectionState::GaveUp { attempts: consecutive_failures, last_error: reason })).await;
+            return;
+        }
+
+        let next_attempt = consecutive_failures + 1;
+        let delay = policy.next_delay(next_attempt);
+        warn!("[重连客户端] {}，{:?} 后进行第 {} 次尝试", reason, delay, next_attempt);
+        let state = ConnectionState::Reconnecting { attempt: next_attempt, delay_ms: delay.as_millis() as u64, reason };
+        if !emit(&event_tx, ClientEvent::StateChanged(state)).await {
+            break;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown_rx.changed() => break 'outer,
+        }
+    }
+
+    close_connection(&shared).await;
+    let _ = event_tx.send(ClientEvent::StateChanged(ConnectionState::Closed)).await;
+    info!("[重连客户端] 后台连接任务已结束");
+}
+
+/// 向调用方发送事件；事件接收端已被丢弃时返回 `false`，连接任务随之结束。
+async fn emit(event_tx: &mpsc::Sender<ClientEvent>, event: ClientEvent) -> bool {
+    if event_tx.send(event).await.is_err() {
+        debug!("[重连客户端] 事件接收端已被丢弃，结束连接任务");
+        return false;
+    }
+    true
+}
+
+/// 关闭并清理当前连接的发送端。
+async fn close_connection(shared: &SharedState) {
+    shared.connected.store(false, Ordering::SeqCst);
+    let sender = shared.sender.lock().await.take();
+    if let Some(mut sender) = sender
+        && let Err(e) = sender.close().await
+    {
+        debug!("[重连客户端] 关闭发送端时发生错误 (连接可能已断开): {}", e);
+    }
+}
+
+/// 重连成功后重新发送最后一次的 `Register` 消息。
+///
+/// 使用新的 `message_id` 和时间戳，避免服务端把它当作已处理过的旧消息。
+async fn resend_register(shared: &SharedState) {
+    let Some(register) = shared.last_register() else {
+        return;
+    };
+    let resend = WsMessage {
+        message_id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        ..register
+    };
+    match send_on(shared, &resend).await {
+        Ok(()) => info!("[重连客户端] 重连成功，已重新发送 Register 消息 (ID: {})", resend.message_id),
+        Err(e) => warn!("[重连客户端] 重连后重新发送 Register 消息失败: {}", e),
+    }
+}
+
+/// 若收到的是携带会话恢复令牌的成功 `RegisterResponse`，把令牌写入记住的 `Register` 消息。
+fn remember_resume_token(shared: &SharedState, message: &WsMessage) {
+    if message.message_type != REGISTER_RESPONSE_MESSAGE_TYPE {
+        return;
+    }
+    let Ok(response) = message.deserialize_payload::<RegisterResponsePayload>() else {
+        return;
+    };
+    let Some(token) = response.resume_token.filter(|_| response.success) else {
+        return;
+    };
+    let mut last_register = shared.last_register.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(register) = last_register.as_ref()
+        && let Some(updated) = with_resume_token(register, token)
+    {
+        debug!("[重连客户端] 已记录服务端签发的会话恢复令牌，重连后将凭其恢复会话");
+        *last_register = Some(updated);
+    }
+}
+
+/// 返回把 `resume_token` 替换为指定令牌后的 `Register` 消息；负载无法解析时返回 `None`。
+fn with_resume_token(register: &WsMessage, token: String) -> Option<WsMessage> {
+    let mut payload = register.deserialize_payload::<RegisterPayload>().ok()?;
+    payload.resume_token = Some(token);
+    let payload = serde_json::to_string(&payload).ok()?;
+    Some(WsMessage { payload, ..register.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::enums::ClientRole;
+    use futures_util::StreamExt;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_backoff_grows_exponentially_with_cap_and_jitter() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            multiplier: 2.0,
+            jitter_ratio: 0.5,
+            max_attempts: Some(3),
+        };
+        assert_eq!(policy.base_delay(1), Duration::from_millis(100));
+        assert_eq!(policy.base_delay(2), Duration::from_millis(200));
+        assert_eq!(policy.base_delay(4), Duration::from_millis(800));
+        assert_eq!(policy.base_delay(10), Duration::from_millis(1000), "等待时间应被限制在上限内");
+
+        assert_eq!(policy.delay_with_jitter(2, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.delay_with_jitter(2, 0.5), Duration::from_millis(200));
+        for _ in 0..20 {
+            let delay = policy.next_delay(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+        }
+
+        assert!(policy.allows_retry(2));
+        assert!(!policy.allows_retry(3));
+        assert!(ReconnectPolicy::default().allows_retry(u32::MAX - 1));
+    }
+
+    #[test]
+    fn test_successful_register_response_token_is_remembered() {
+        let shared = SharedState {
+            sender: TokioMutex::new(None),
+            connected: AtomicBool::new(false),
+            last_register: std::sync::Mutex::new(None),
+            force_reconnect: Notify::new(),
+        };
+        let register = WsMessage::new(
+            REGISTER_MESSAGE_TYPE.to_string(),
+            &RegisterPayload {
+                group_id: "g1".to_string(),
+                role: ClientRole::ControlCenter,
+                task_id: "t1".to_string(),
+                client_software_version: None,
+                client_display_name: None,
+                resume_token: None,
+                site_assignment: None,
+                join_code: None,
+            },
+        )
+        .unwrap();
+        shared.set_last_register(Some(register.clone()));
+
+        let response = |success: bool, token: &str| {
+            WsMessage::new(
+                REGISTER_RESPONSE_MESSAGE_TYPE.to_string(),
+                &RegisterResponsePayload {
+                    success,
+                    message: None,
+                    assigned_client_id: uuid::Uuid::new_v4(),
+                    effective_group_id: Some("g1".to_string()),
+                    effective_role: Some(ClientRole::ControlCenter),
+                    error_code: None,
+                    resume_token: Some(token.to_string()),
+                    resume_grace_seconds: Some(60),
+                    resumed: false,
+                    heartbeat: None,
+                },
+            )
+            .unwrap()
+        };
+
+        // 失败的注册响应不应改写记住的 Register
+        remember_resume_token(&shared, &response(false, "ignored"));
+        assert_eq!(shared.last_register().unwrap().payload, register.payload);
+
+        remember_resume_token(&shared, &response(true, "token-1"));
+        let remembered = shared.last_register().unwrap();
+        assert_eq!(remembered.message_id, register.message_id);
+        let payload: RegisterPayload = remembered.deserialize_payload().unwrap();
+        assert_eq!(payload.resume_token.as_deref(), Some("token-1"));
+        assert_eq!(payload.group_id, "g1");
+    }
+
+    async fn next_state(events: &mut mpsc::Receiver<ClientEvent>) -> ConnectionState {
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), events.recv()).await.expect("等待状态事件超时") {
+                Some(ClientEvent::StateChanged(state)) => return state,
+                Some(ClientEvent::Message(_)) => continue,
+                None => panic!("事件通道意外关闭"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_resends_register() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let policy = ReconnectPolicy { initial_delay: Duration::from_millis(20), ..Default::default() };
+        let (client, mut events) = ReconnectingClient::start(url, policy);
+
+        // 第一次连接：客户端发送 Register，服务端随后主动断开
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server_ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting { attempt: 1 });
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connected { reconnected: false });
+
+        let register = WsMessage::new(
+            REGISTER_MESSAGE_TYPE.to_string(),
+            &RegisterPayload {
+                group_id: "g1".to_string(),
+                role: ClientRole::OnSiteMobile,
+                task_id: "t1".to_string(),
+                client_software_version: None,
+                client_display_name: None,
+                resume_token: None,
+                site_assignment: None,
+                join_code: None,
+            },
+        )
+        .unwrap();
+        client.send(&register).await.unwrap();
+        let first = server_ws.next().await.unwrap().unwrap();
+        let first: WsMessage = serde_json::from_str(first.to_text().unwrap()).unwrap();
+        assert_eq!(first.message_id, register.message_id);
+        drop(server_ws);
+
+        assert!(matches!(next_state(&mut events).await, ConnectionState::Reconnecting { attempt: 1, .. }));
+
+        // 重连成功后，客户端应以新的 message_id 自动重新发送 Register
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server_ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting { attempt: 1 });
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connected { reconnected: true });
+        let resent = server_ws.next().await.unwrap().unwrap();
+        let resent: WsMessage = serde_json::from_str(resent.to_text().unwrap()).unwrap();
+        assert_eq!(resent.message_type, REGISTER_MESSAGE_TYPE);
+        assert_eq!(resent.payload, register.payload);
+        assert_ne!(resent.message_id, register.message_id);
+
+        client.shutdown().await;
+        assert_eq!(next_state(&mut events).await, ConnectionState::Closed);
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        // 绑定后立即释放端口，使连接必然失败
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_millis(5),
+            max_attempts: Some(2),
+            ..Default::default()
+        };
+        let (_client, mut events) = ReconnectingClient::start(url, policy);
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting { attempt: 1 });
+        assert!(matches!(next_state(&mut events).await, ConnectionState::Reconnecting { attempt: 2, .. }));
+        assert_eq!(next_state(&mut events).await, ConnectionState::Connecting { attempt: 2 });
+        assert!(matches!(next_state(&mut events).await, ConnectionState::GaveUp { attempts: 2, .. }));
+    }
+}
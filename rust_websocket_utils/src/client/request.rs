@@ -0,0 +1,175 @@
+// rust_websocket_utils/src/client/request.rs
+
+//! 客户端"请求-响应"关联的辅助设施。
+//!
+//! WebSocket 本身是全双工的消息流，没有请求与响应的概念。本模块基于 `WsMessage` 的
+//! `message_id` / `in_reply_to` 字段，为客户端提供一个简单的挂起请求表 [`PendingRequests`]：
+//! 1. 发送请求前，以请求消息的 `message_id` 调用 [`PendingRequests::register`]，得到一个等待句柄；
+//! 2. 接收循环收到任何带有 `in_reply_to` 的消息时调用 [`PendingRequests::resolve`]，
+//!    若其指向某个挂起的请求，则把该消息交给对应的等待者；
+//! 3. 调用方通过 [`PendingRequests::wait_for_reply`] 带超时地等待响应。
+//!
+//! 连接断开时应调用 [`PendingRequests::cancel_all`]，使所有等待者立即得到 `WsError::NotConnected`，
+//! 而不是一直等到超时。
+//!
+//! 本模块不关心消息如何发送，也不持有连接，因此可以被任何客户端实现复用。
+
+use crate::error::WsError;
+use crate::message::WsMessage;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// 等待某个请求响应的句柄，由 [`PendingRequests::register`] 返回。
+#[derive(Debug)]
+pub struct PendingReply {
+    /// 被等待的请求消息的 `message_id`。
+    request_id: String,
+    /// 接收响应消息的一次性通道。
+    receiver: oneshot::Receiver<WsMessage>,
+}
+
+impl PendingReply {
+    /// 返回被等待的请求消息的 `message_id`。
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+/// 挂起请求表：`message_id` -> 等待该请求响应的一次性发送端。
+///
+/// 内部使用 `std::sync::Mutex`，所有操作都只做短暂的哈希表读写，不会跨越 `.await` 持有锁。
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    waiters: Mutex<HashMap<String, oneshot::Sender<WsMessage>>>,
+}
+
+impl PendingRequests {
+    /// 创建一个空的挂起请求表。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为即将发送的请求登记一个等待者。
+    ///
+    /// 必须在真正发送请求之前调用，以免响应先于登记到达而被丢弃。
+    /// 若同一 `request_id` 已被登记，旧的等待者会被替换 (其等待将以 `NotConnected` 结束)。
+    pub fn register(&self, request_id: &str) -> PendingReply {
+        let (sender, receiver) = oneshot::channel();
+        if self.lock().insert(request_id.to_string(), sender).is_some() {
+            warn!("[挂起请求表] 请求 {} 被重复登记，旧的等待者已被替换", request_id);
+        }
+        PendingReply { request_id: request_id.to_string(), receiver }
+    }
+
+    /// 尝试用收到的消息完成一个挂起的请求。
+    ///
+    /// 仅当消息带有 `in_reply_to` 且其值对应一个挂起请求时返回 `true`。
+    /// 传入的消息会被克隆后交给等待者，调用方仍可按原有逻辑继续处理该消息 (例如发出前端事件)。
+    pub fn resolve(&self, message: &WsMessage) -> bool {
+        let Some(request_id) = message.in_reply_to.as_deref() else {
+            return false;
+        };
+        let Some(sender) = self.lock().remove(request_id) else {
+            return false;
+        };
+        if sender.send(message.clone()).is_err() {
+            // 等待者已经放弃 (例如刚好超时)，响应被丢弃
+            debug!("[挂起请求表] 请求 {} 的等待者已不存在，响应 {} 被丢弃", request_id, message.message_type);
+        }
+        true
+    }
+
+    /// 放弃一个挂起的请求 (例如发送失败或等待超时后清理)。
+    pub fn cancel(&self, request_id: &str) {
+        self.lock().remove(request_id);
+    }
+
+    /// 取消所有挂起的请求，所有等待者将立即以 `WsError::NotConnected` 结束。应在连接断开时调用。
+    pub fn cancel_all(&self) {
+        let mut waiters = self.lock();
+        if !waiters.is_empty() {
+            debug!("[挂起请求表] 连接断开，取消 {} 个挂起的请求", waiters.len());
+        }
+        waiters.clear();
+    }
+
+    /// 当前挂起的请求数量。
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// 是否没有任何挂起的请求。
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// 带超时地等待某个已登记请求的响应。
+    ///
+    /// # Returns
+    /// * `Ok(WsMessage)` - 收到的响应消息 (可能是业务响应，也可能是 `ErrorResponse`，由调用方根据 `message_type` 判断)。
+    /// * `Err(WsError::ConnectionTimeout)` - 在 `timeout` 内未收到响应，挂起记录会被清理。
+    /// * `Err(WsError::NotConnected)` - 等待期间请求被取消 (通常是连接断开)。
+    pub async fn wait_for_reply(&self, pending: PendingReply, timeout: Duration) -> Result<WsMessage, WsError> {
+        let PendingReply { request_id, receiver } = pending;
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(reply)) => Ok(reply),
+            Ok(Err(_)) => Err(WsError::NotConnected),
+            Err(_) => {
+                self.cancel(&request_id);
+                Err(WsError::ConnectionTimeout)
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<WsMessage>>> {
+        // 表内只有通道发送端，锁中毒时继续使用内部数据是安全的
+        self.waiters.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::ws_payloads::EchoPayload;
+
+    fn echo(content: &str) -> WsMessage {
+        WsMessage::new("Echo".to_string(), &EchoPayload { content: content.to_string() }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reply_is_delivered_to_matching_request() {
+        let pending = PendingRequests::new();
+        let request = echo("请求");
+        let handle = pending.register(&request.message_id);
+        assert_eq!(handle.request_id(), request.message_id);
+
+        // 无关的消息不会完成请求
+        assert!(!pending.resolve(&echo("推送")));
+        let unrelated = WsMessage::new_reply("Echo".to_string(), &EchoPayload { content: "x".into() }, "other-id").unwrap();
+        assert!(!pending.resolve(&unrelated));
+
+        let reply = WsMessage::new_reply("Echo".to_string(), &EchoPayload { content: "响应".into() }, &request.message_id).unwrap();
+        assert!(pending.resolve(&reply));
+        assert!(pending.is_empty());
+
+        let received = pending.wait_for_reply(handle, Duration::from_secs(1)).await.unwrap();
+        assert_eq!(received.message_id, reply.message_id);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_cancellation() {
+        let pending = PendingRequests::new();
+        let handle = pending.register("req-timeout");
+        let result = pending.wait_for_reply(handle, Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(WsError::ConnectionTimeout)));
+        assert!(pending.is_empty(), "超时后挂起记录应被清理");
+
+        let handle = pending.register("req-cancel");
+        pending.cancel_all();
+        let result = pending.wait_for_reply(handle, Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(WsError::NotConnected)));
+    }
+}
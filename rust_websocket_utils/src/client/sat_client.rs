@@ -0,0 +1,1068 @@
+// rust_websocket_utils/src/client/sat_client.rs
+
+//! 与界面框架无关的 SatPlatform 业务客户端 ([`SatClient`])。
+//!
+//! 中心端与现场端都需要同一套客户端逻辑：带自动重连的连接、自适应心跳、接收分发、任务状态缓存
+//! (含增量应用与缺口时的重新同步)、请求-响应关联以及版本冲突处理。本模块把这些逻辑集中在一处，
+//! 以类型化的方法 (`register`、`update_pre_check_item`、`start_step`、`feedback_step`、`confirm_step` 等)
+//! 对外提供业务操作，并通过类型化的事件流 ([`SatClientEvent`]) 通知调用方。
+//!
+//! 本模块不依赖 Tauri 等界面框架：桌面/移动应用只需把事件转发给前端，测试工具与命令行工具也可直接使用。
+
+use crate::client::error_handling::{ServerErrorHandling, handling_for};
+use crate::client::heartbeat::{AdaptiveHeartbeat, HeartbeatBounds, LinkQuality};
+use crate::client::reconnect::{ClientEvent, ConnectionState, ReconnectPolicy, ReconnectingClient};
+use crate::client::request::PendingRequests;
+use crate::client::version_conflict::{ConflictResolution, UnconfirmedBusinessMessages, rebase_message};
+use crate::error::WsError;
+use crate::message::WsMessage;
+use common_models::TaskDebugState;
+use common_models::enums::ErrorCode;
+use common_models::task_models::{
+    ConfirmSingleTestStepPayload, FeedbackSingleTestStepPayload, SignOffSingleTestStepPayload, StartSingleTestStepPayload,
+    UpdatePreCheckItemPayload,
+};
+use common_models::ws_payloads::{
+    ACK_MESSAGE_TYPE, AckPayload, AckStatus, CONFIRM_SINGLE_TEST_STEP_TYPE, ECHO_MESSAGE_TYPE, ERROR_RESPONSE_MESSAGE_TYPE, EchoPayload,
+    ErrorResponsePayload, FEEDBACK_SINGLE_TEST_STEP_TYPE, GROUP_LEFT_MESSAGE_TYPE, GROUP_OPENED_MESSAGE_TYPE, GROUP_ROSTER_MESSAGE_TYPE,
+    GroupLeftPayload, GroupOpenedPayload, GroupRosterPayload, HANDOVER_TOKEN_MESSAGE_TYPE, HandoverTokenPayload, LEAVE_GROUP_MESSAGE_TYPE,
+    LeaveGroupPayload, OPEN_GROUP_MESSAGE_TYPE, OpenGroupPayload, PARTNER_STATUS_UPDATE_MESSAGE_TYPE, PING_MESSAGE_TYPE,
+    PONG_MESSAGE_TYPE, PREPARE_HANDOVER_MESSAGE_TYPE, PartnerStatusPayload, PingPayload, PrepareHandoverPayload,
+    REGISTER_MESSAGE_TYPE, REGISTER_RESPONSE_MESSAGE_TYPE, RegisterPayload, RegisterResponsePayload,
+    SIGN_OFF_SINGLE_TEST_STEP_TYPE, SLOT_RELEASED_MESSAGE_TYPE, SLOT_TAKEOVER_DECISION_MESSAGE_TYPE,
+    SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE, SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE, START_SINGLE_TEST_STEP_TYPE, SWITCH_TASK_MESSAGE_TYPE,
+    SlotReleaseReason, SlotReleasedPayload, SlotTakeoverDecisionPayload, SlotTakeoverPromptPayload, SlotTakeoverRequestPayload,
+    SwitchTaskPayload, TASK_STATE_DELTA_MESSAGE_TYPE, TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE, TASK_STATE_UPDATE_MESSAGE_TYPE,
+    TaskStateDeltaPayload, TaskStateSyncRequestPayload, UPDATE_PRE_CHECK_ITEM_TYPE, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE,
+    UpdateTaskDebugNotePayload,
+};
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex as TokioMutex, RwLock, mpsc};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// 等待云端响应的默认超时时间，单位：秒。
+pub const DEFAULT_REQUEST_TIMEOUT_SECONDS: u64 = 10;
+
+/// 业务客户端的配置。
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatClientConfig {
+    /// 连接失败或断开后的重连策略。
+    pub reconnect_policy: ReconnectPolicy,
+    /// 类型化请求方法等待云端响应的超时时间。
+    pub request_timeout: Duration,
+    /// 云端公布心跳策略之前使用的心跳间隔范围。
+    pub heartbeat_bounds: HeartbeatBounds,
+}
+
+impl Default for SatClientConfig {
+    fn default() -> Self {
+        Self {
+            reconnect_policy: ReconnectPolicy::default(),
+            request_timeout: Duration::from_secs(DEFAULT_REQUEST_TIMEOUT_SECONDS),
+            heartbeat_bounds: HeartbeatBounds::default(),
+        }
+    }
+}
+
+/// 业务消息因版本冲突被云端拒绝、等待用户处理的详情。
+#[derive(Debug, Clone)]
+pub struct VersionConflict {
+    /// 被拒绝的业务消息的 `message_id`，处理冲突时 ([`SatClient::resolve_version_conflict`]) 以此指定。
+    pub request_message_id: String,
+    /// 被拒绝的业务消息的类型。
+    pub original_message_type: String,
+    /// 本端提交的负载 (原样解析为 JSON)。
+    pub local_payload: serde_json::Value,
+    /// 云端当前的任务状态 (本地缓存已刷新为此状态)。
+    pub current_state: TaskDebugState,
+}
+
+/// 业务客户端发出的事件。
+///
+/// 事件按收到的顺序发出；同一条云端消息若同时是某个请求的响应，请求方法的返回值与事件都会得到它。
+#[derive(Debug, Clone)]
+pub enum SatClientEvent {
+    /// 连接状态发生变化 (每次变化都会发出，包括正在连接、等待重连等中间状态)。
+    ConnectionState(ConnectionState),
+    /// 连接已建立。`reconnected` 为 `true` 表示断线后的重连，此时会自动重新注册。
+    Connected { reconnected: bool },
+    /// 连接由"已连接"变为"未连接"，或连续失败后放弃重连。主动调用 [`SatClient::disconnect`] 时不发出。
+    Disconnected { reason: String },
+    /// 收到注册响应 (含重连后的自动重新注册与切换任务)。失败时附带建议的处理方式。
+    Registration { response: RegisterResponsePayload, handling: Option<ServerErrorHandling> },
+    /// 伙伴客户端的在线状态变化。
+    PartnerStatus(PartnerStatusPayload),
+    /// 本地缓存的任务状态已更新 (完整推送、增量应用或版本冲突时的刷新)。
+    TaskStateUpdated(TaskDebugState),
+    /// 云端返回的错误响应及建议的处理方式。
+    ServerError { error: ErrorResponsePayload, handling: ServerErrorHandling },
+    /// 云端对业务消息的确认。被拒绝时附带建议的处理方式。
+    BusinessAck { request_message_id: Option<String>, ack: AckPayload, handling: Option<ServerErrorHandling> },
+    /// 业务消息因版本冲突被拒绝，等待用户选择变基、合并或放弃。
+    VersionConflict(VersionConflict),
+    /// 有客户端请求接管本端占用的角色槽位。
+    SlotTakeoverPrompt(SlotTakeoverPromptPayload),
+    /// 本端的角色槽位已被接替，本端不再属于该任务组。
+    SlotReleased(SlotReleasedPayload),
+    /// 开组结果 (含配对码)。
+    GroupOpened(GroupOpenedPayload),
+    /// 组成员名册。
+    GroupRoster(GroupRosterPayload),
+    /// 本端已离开任务组，连接保持。
+    GroupLeft(GroupLeftPayload),
+    /// 云端转发的单体测试指令。`command_message_id` 为该指令消息的 `message_id`。
+    StartSingleTestStep { command_message_id: String, step: StartSingleTestStepPayload },
+    /// Echo 回复。
+    Echo(EchoPayload),
+    /// 心跳测得的链路质量。
+    LinkQuality(LinkQuality),
+    /// 未识别或无需特殊处理的消息，原样转交。
+    Unhandled(WsMessage),
+}
+
+/// 在客户端句柄与后台任务 (连接事件任务、心跳任务) 之间共享的会话状态。
+#[derive(Debug)]
+struct SessionState {
+    /// 事件发送端。接收端被丢弃后事件被静默丢弃，客户端照常工作。
+    events: mpsc::UnboundedSender<SatClientEvent>,
+    /// 当前是否已连接。
+    connected: AtomicBool,
+    /// 云端分配给本客户端的ID，注册成功后才有值。
+    client_id: RwLock<Option<Uuid>>,
+    /// 最后一次收到 Pong 的时刻 (单调时钟)。
+    last_pong_received_at: RwLock<Option<Instant>>,
+    /// 自适应心跳的状态：实测往返时延与抖动、链路质量以及当前采用的心跳间隔。
+    heartbeat: RwLock<AdaptiveHeartbeat>,
+    /// 本地缓存的、从云端同步过来的权威任务状态。
+    task_state: RwLock<Option<TaskDebugState>>,
+    /// 等待云端响应的挂起请求表。
+    pending_requests: PendingRequests,
+    /// 已发出但尚未确认、以及因版本冲突被拒绝而等待用户处理的业务消息。
+    unconfirmed_business_messages: UnconfirmedBusinessMessages,
+    /// 心跳任务的句柄，连接建立时启动，连接结束时停止。
+    heartbeat_task: TokioMutex<Option<JoinHandle<()>>>,
+}
+
+impl SessionState {
+    fn emit(&self, event: SatClientEvent) {
+        if self.events.send(event).is_err() {
+            debug!("[业务客户端] 事件接收端已被丢弃，忽略事件。");
+        }
+    }
+
+    /// 替换本地缓存的任务状态并发出 [`SatClientEvent::TaskStateUpdated`]。
+    async fn replace_task_state(&self, new_state: TaskDebugState) {
+        *self.task_state.write().await = Some(new_state.clone());
+        self.emit(SatClientEvent::TaskStateUpdated(new_state));
+    }
+}
+
+/// 与界面框架无关的 SatPlatform 业务客户端。
+///
+/// 通过 [`SatClient::new`] 创建，同时得到事件接收端；事件接收端在多次 `connect`/`disconnect` 之间保持有效。
+#[derive(Debug)]
+pub struct SatClient {
+    config: SatClientConfig,
+    session: Arc<SessionState>,
+    /// 带自动重连的 WebSocket 客户端。`None` 表示尚未调用 `connect` 或已主动断开。
+    ws_client: RwLock<Option<Arc<ReconnectingClient>>>,
+    /// 处理重连客户端事件 (消息与连接状态) 的后台任务。
+    connection_task: TokioMutex<Option<JoinHandle<()>>>,
+}
+
+impl SatClient {
+    /// 创建业务客户端，返回客户端与其事件接收端。此时尚未连接，需调用 [`SatClient::connect`]。
+    pub fn new(config: SatClientConfig) -> (Self, mpsc::UnboundedReceiver<SatClientEvent>) {
+        let (events, event_rx) = mpsc::unbounded_channel();
+        let session = Arc::new(SessionState {
+            events,
+            connected: AtomicBool::new(false),
+            client_id: RwLock::new(None),
+            last_pong_received_at: RwLock::new(None),
+            heartbeat: RwLock::new(AdaptiveHeartbeat::new(config.heartbeat_bounds)),
+            task_state: RwLock::new(None),
+            pending_requests: PendingRequests::new(),
+            unconfirmed_business_messages: UnconfirmedBusinessMessages::new(),
+            heartbeat_task: TokioMutex::new(None),
+        });
+        let client = Self {
+            config,
+            session,
+            ws_client: RwLock::new(None),
+            connection_task: TokioMutex::new(None),
+        };
+        (client, event_rx)
+    }
+
+    /// 连接到指定的 WebSocket 服务器。
+    ///
+    /// 启动带自动重连的客户端及其事件任务后立即返回；实际的连接结果通过 [`SatClientEvent::Connected`] 等事件通知。
+    /// 已有连接时先将其关闭。
+    pub async fn connect(&self, url: &str) {
+        info!("[业务客户端] 连接到 {}", url);
+        self.stop_connection().await;
+
+        let (client, mut client_events) = ReconnectingClient::start(url.to_string(), self.config.reconnect_policy.clone());
+        let client = Arc::new(client);
+        *self.ws_client.write().await = Some(client.clone());
+
+        let session = self.session.clone();
+        let connection_task = tokio::spawn(async move {
+            while let Some(event) = client_events.recv().await {
+                match event {
+                    ClientEvent::Message(ws_msg) => {
+                        // 若该消息是对某个请求的响应，先交给等待者；消息本身仍按常规流程处理
+                        session.pending_requests.resolve(&ws_msg);
+                        process_received_message(&session, &client, ws_msg).await;
+                    }
+                    ClientEvent::StateChanged(state) => handle_connection_state(&session, &client, state).await,
+                }
+            }
+            info!("[业务客户端] 重连客户端的事件流已结束，连接事件任务退出。");
+        });
+        *self.connection_task.lock().await = Some(connection_task);
+    }
+
+    /// 主动断开连接：关闭重连客户端 (不再自动重连)，停止后台任务并清理会话状态。
+    ///
+    /// 不会发出 [`SatClientEvent::Disconnected`]，由调用方自行决定如何通知用户。
+    pub async fn disconnect(&self) {
+        info!("[业务客户端] 主动断开连接。");
+        self.stop_connection().await;
+    }
+
+    /// 关闭当前的重连客户端 (若存在)，结束连接事件任务与心跳任务，并清理会话状态。
+    async fn stop_connection(&self) {
+        if let Some(client) = self.ws_client.write().await.take() {
+            client.shutdown().await;
+        }
+        if let Some(handle) = self.connection_task.lock().await.take() {
+            // 客户端关闭后事件流会结束，事件任务随之退出；此处直接中止以免等待
+            handle.abort();
+            if let Err(e) = handle.await
+                && !e.is_cancelled()
+            {
+                warn!("[业务客户端] 等待之前的连接事件任务结束时发生错误: {:?}", e);
+            }
+        }
+        if let Some(heartbeat_task) = self.session.heartbeat_task.lock().await.take() {
+            heartbeat_task.abort();
+        }
+        self.session.connected.store(false, Ordering::SeqCst);
+        *self.session.client_id.write().await = None;
+        *self.session.last_pong_received_at.write().await = None;
+        self.session.pending_requests.cancel_all();
+        self.session.unconfirmed_business_messages.clear_awaiting_ack();
+    }
+
+    /// 当前是否已连接。
+    pub fn is_connected(&self) -> bool {
+        self.session.connected.load(Ordering::SeqCst)
+    }
+
+    /// 云端分配给本客户端的ID (注册成功后才有值)。
+    pub async fn client_id(&self) -> Option<Uuid> {
+        *self.session.client_id.read().await
+    }
+
+    /// 本地缓存的最新任务状态。
+    pub async fn cached_task_state(&self) -> Option<TaskDebugState> {
+        self.session.task_state.read().await.clone()
+    }
+
+    /// 用持久化保存的任务状态填充尚为空的本地缓存 (例如应用重启后离线恢复)，并发出 [`SatClientEvent::TaskStateUpdated`]。
+    ///
+    /// 缓存中已有任务状态时不做任何修改并返回 `false`：已从云端同步的状态总是优先。
+    /// 恢复的状态可能已过时，注册成功后云端推送的完整状态会将其替换，版本不连续的增量会触发重新同步。
+    pub async fn restore_task_state(&self, state: TaskDebugState) -> bool {
+        let mut cache_guard = self.session.task_state.write().await;
+        if cache_guard.is_some() {
+            debug!("[业务客户端] 本地已有任务状态缓存，忽略恢复的任务状态 (任务 {}, 版本 {})。", state.task_id, state.version);
+            return false;
+        }
+        info!("[业务客户端] 已从本地持久化数据恢复任务状态: 任务 {}, 版本 {}", state.task_id, state.version);
+        *cache_guard = Some(state.clone());
+        drop(cache_guard);
+        self.session.emit(SatClientEvent::TaskStateUpdated(state));
+        true
+    }
+
+    /// 本地缓存的任务状态版本，用作业务消息的 `expected_version`。
+    pub async fn cached_task_version(&self) -> Option<u64> {
+        self.session.task_state.read().await.as_ref().map(|state| state.version)
+    }
+
+    /// 当前的链路质量评估。
+    pub async fn link_quality(&self) -> LinkQuality {
+        self.session.heartbeat.read().await.quality()
+    }
+
+    /// 发送一条消息，不等待响应。
+    ///
+    /// 业务消息在发送前登记，以便收到版本冲突的确认时找回原始修改；`Register` 消息会被记住，重连后自动重新发送。
+    pub async fn send(&self, message: WsMessage) -> Result<(), String> {
+        if !self.is_connected() {
+            return Err("发送 WebSocket 消息失败：未连接到服务器。".to_string());
+        }
+        let Some(client) = self.ws_client.read().await.clone() else {
+            return Err("发送 WebSocket 消息失败：连接客户端不可用 (可能已断开)。".to_string());
+        };
+        self.session.unconfirmed_business_messages.track(&message);
+        match client.send(&message).await {
+            Ok(()) => {
+                debug!("[业务客户端] 消息已发送: 类型='{}', ID={}", message.message_type, message.message_id);
+                Ok(())
+            }
+            Err(e) => {
+                self.session.unconfirmed_business_messages.acknowledge(&message.message_id);
+                let err_msg = format!("发送 WebSocket 消息 (类型='{}') 失败: {}", message.message_type, e);
+                error!("[业务客户端] {}", err_msg);
+                Err(err_msg)
+            }
+        }
+    }
+
+    /// 以 `payload` 构造指定类型的消息并发送，不等待响应。
+    pub async fn send_message<T: Serialize>(&self, message_type: &str, payload: &T) -> Result<WsMessage, String> {
+        let message = WsMessage::new(message_type.to_string(), payload)
+            .map_err(|e| format!("创建类型为 '{}' 的 WsMessage 失败: {}", message_type, e))?;
+        self.send(message.clone()).await?;
+        Ok(message)
+    }
+
+    /// 发送一条消息，并在 `timeout` 内等待云端与之关联的响应 (响应的 `in_reply_to` 等于本消息的 `message_id`)。
+    ///
+    /// 返回的响应可能是业务响应，也可能是 `ErrorResponse` 或被拒绝的 `Ack`，调用方应根据 `message_type` 区分。
+    pub async fn request<T: Serialize>(&self, message_type: &str, payload: &T, timeout: Duration) -> Result<WsMessage, String> {
+        let message = WsMessage::new(message_type.to_string(), payload)
+            .map_err(|e| format!("创建类型为 '{}' 的 WsMessage 失败: {}", message_type, e))?;
+        self.request_message(message, timeout).await
+    }
+
+    /// 发送一条已构造好的消息并等待与之关联的响应，消息的 `message_id` 保持不变。
+    ///
+    /// 用于重发先前保存的消息 (例如离线期间排队的业务操作)：重发时沿用原 `message_id`，
+    /// 云端据此识别重复投递。
+    pub async fn request_message(&self, message: WsMessage, timeout: Duration) -> Result<WsMessage, String> {
+        let message_type = message.message_type.clone();
+        let request_id = message.message_id.clone();
+
+        // 必须在发送前登记，避免响应先于登记到达而被丢弃
+        let pending_reply = self.session.pending_requests.register(&request_id);
+        if let Err(e) = self.send(message).await {
+            self.session.pending_requests.cancel(&request_id);
+            return Err(e);
+        }
+        match self.session.pending_requests.wait_for_reply(pending_reply, timeout).await {
+            Ok(reply) => Ok(reply),
+            Err(WsError::ConnectionTimeout) => {
+                let err_msg = format!("等待类型为 '{}' 的请求 (ID: {}) 的响应超时 ({:?})", message_type, request_id, timeout);
+                warn!("[业务客户端] {}", err_msg);
+                Err(err_msg)
+            }
+            Err(e) => {
+                let err_msg = format!("等待类型为 '{}' 的请求 (ID: {}) 的响应时连接已断开: {}", message_type, request_id, e);
+                warn!("[业务客户端] {}", err_msg);
+                Err(err_msg)
+            }
+        }
+    }
+
+    /// 以配置的默认超时发送请求，并把类型为 `expected_type` 的响应解析为 `R`。
+    ///
+    /// 云端以其他类型 (例如被拒绝的 `Ack` 或 `ErrorResponse`) 回复时，返回其中的错误描述。
+    async fn request_typed<T: Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        message_type: &str,
+        payload: &T,
+        expected_type: &str,
+    ) -> Result<R, String> {
+        let reply = self.request(message_type, payload, self.config.request_timeout).await?;
+        if reply.message_type != expected_type {
+            return Err(format!("'{}' 请求失败: {}", message_type, rejection_reason(&reply)));
+        }
+        reply
+            .deserialize_payload::<R>()
+            .map_err(|e| format!("解析云端对 '{}' 的响应 (消息类型 '{}') 失败: {}", message_type, reply.message_type, e))
+    }
+
+    /// 注册到任务组 (`Register`)。注册消息会被记住，断线重连后自动重新注册。
+    ///
+    /// 返回云端的注册响应，`success` 为 `false` 时见 `error_code`。
+    pub async fn register(&self, payload: RegisterPayload) -> Result<RegisterResponsePayload, String> {
+        self.request_typed(REGISTER_MESSAGE_TYPE, &payload, REGISTER_RESPONSE_MESSAGE_TYPE).await
+    }
+
+    /// 发送一条业务消息并返回云端的确认 (`Ack`)。被拒绝的确认同样以 `Ok` 返回，由调用方查看 `status`。
+    pub async fn send_business_action<T: Serialize>(&self, message_type: &str, payload: &T) -> Result<AckPayload, String> {
+        self.request_typed(message_type, payload, ACK_MESSAGE_TYPE).await
+    }
+
+    /// 发送一条已构造好的业务消息 (沿用其 `message_id`) 并返回云端的确认，用于重放先前保存的业务操作。
+    ///
+    /// 返回 `Err` 表示消息未能送达或未在超时内收到确认，调用方可稍后以同一消息重试。
+    pub async fn send_business_message(&self, message: WsMessage) -> Result<AckPayload, String> {
+        let message_type = message.message_type.clone();
+        let reply = self.request_message(message, self.config.request_timeout).await?;
+        if reply.message_type != ACK_MESSAGE_TYPE {
+            return Err(format!("'{}' 请求失败: {}", message_type, rejection_reason(&reply)));
+        }
+        reply
+            .deserialize_payload::<AckPayload>()
+            .map_err(|e| format!("解析云端对 '{}' 的确认失败: {}", message_type, e))
+    }
+
+    /// 更新预检查项状态 (`UpdatePreCheckItem`)。
+    pub async fn update_pre_check_item(&self, payload: UpdatePreCheckItemPayload) -> Result<AckPayload, String> {
+        self.send_business_action(UPDATE_PRE_CHECK_ITEM_TYPE, &payload).await
+    }
+
+    /// 发起单体测试步骤 (`StartSingleTestStep`)。
+    pub async fn start_step(&self, payload: StartSingleTestStepPayload) -> Result<AckPayload, String> {
+        self.send_business_action(START_SINGLE_TEST_STEP_TYPE, &payload).await
+    }
+
+    /// 反馈单体测试步骤的执行结果 (`FeedbackSingleTestStep`)。
+    pub async fn feedback_step(&self, payload: FeedbackSingleTestStepPayload) -> Result<AckPayload, String> {
+        self.send_business_action(FEEDBACK_SINGLE_TEST_STEP_TYPE, &payload).await
+    }
+
+    /// 确认单体测试步骤的结果 (`ConfirmSingleTestStep`)。
+    pub async fn confirm_step(&self, payload: ConfirmSingleTestStepPayload) -> Result<AckPayload, String> {
+        self.send_business_action(CONFIRM_SINGLE_TEST_STEP_TYPE, &payload).await
+    }
+
+    /// 签核单体测试步骤 (`SignOffSingleTestStep`)。
+    pub async fn sign_off_step(&self, payload: SignOffSingleTestStepPayload) -> Result<AckPayload, String> {
+        self.send_business_action(SIGN_OFF_SINGLE_TEST_STEP_TYPE, &payload).await
+    }
+
+    /// 更新任务的通用调试备注 (`UpdateTaskDebugNoteCommand`)。
+    pub async fn update_debug_note(&self, payload: UpdateTaskDebugNotePayload) -> Result<AckPayload, String> {
+        self.send_business_action(UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, &payload).await
+    }
+
+    /// 请求云端重新推送当前完整的任务状态 (`TaskStateSyncRequest`)。回复同时写入本地缓存。
+    pub async fn resync_task_state(&self) -> Result<TaskDebugState, String> {
+        self.request_typed(TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE, &TaskStateSyncRequestPayload {}, TASK_STATE_UPDATE_MESSAGE_TYPE).await
+    }
+
+    /// 为本端当前占用的角色槽位申请一次性交接令牌 (`PrepareHandover`)。
+    pub async fn prepare_handover(&self) -> Result<HandoverTokenPayload, String> {
+        self.request_typed(PREPARE_HANDOVER_MESSAGE_TYPE, &PrepareHandoverPayload {}, HANDOVER_TOKEN_MESSAGE_TYPE).await
+    }
+
+    /// 请求接管其他客户端占用的角色槽位 (`SlotTakeoverRequest`)。结果以注册响应的形式通过事件通知。
+    pub async fn request_slot_takeover(&self, payload: SlotTakeoverRequestPayload) -> Result<(), String> {
+        self.send_message(SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE, &payload).await.map(|_| ())
+    }
+
+    /// 答复其他客户端对本端槽位的接管请求 (`SlotTakeoverDecision`)。
+    pub async fn decide_slot_takeover(&self, payload: SlotTakeoverDecisionPayload) -> Result<(), String> {
+        self.send_message(SLOT_TAKEOVER_DECISION_MESSAGE_TYPE, &payload).await.map(|_| ())
+    }
+
+    /// 请求云端为任务开组并签发配对码 (`OpenGroup`)，本端以控制中心角色加入该组。
+    pub async fn open_group(&self, payload: OpenGroupPayload) -> Result<GroupOpenedPayload, String> {
+        self.request_typed(OPEN_GROUP_MESSAGE_TYPE, &payload, GROUP_OPENED_MESSAGE_TYPE).await
+    }
+
+    /// 不断开连接地离开当前任务组 (`LeaveGroup`)。
+    pub async fn leave_group(&self) -> Result<GroupLeftPayload, String> {
+        self.request_typed(LEAVE_GROUP_MESSAGE_TYPE, &LeaveGroupPayload {}, GROUP_LEFT_MESSAGE_TYPE).await
+    }
+
+    /// 离开当前任务组并注册到 `register` 所指的任务组 (`SwitchTask`)，连接保持不变。
+    pub async fn switch_task(&self, register: RegisterPayload) -> Result<RegisterResponsePayload, String> {
+        self.request_typed(SWITCH_TASK_MESSAGE_TYPE, &SwitchTaskPayload { register }, REGISTER_RESPONSE_MESSAGE_TYPE).await
+    }
+
+    /// 发送 Echo 消息 (连通性测试)，回复通过 [`SatClientEvent::Echo`] 通知。
+    pub async fn echo(&self, payload: EchoPayload) -> Result<(), String> {
+        self.send_message(ECHO_MESSAGE_TYPE, &payload).await.map(|_| ())
+    }
+
+    /// 处理一次版本冲突：按 `resolution` 在本地缓存的当前版本之上重新提交被拒绝的业务消息，或放弃本端修改。
+    ///
+    /// # 返回
+    /// * `Ok(Some(String))`: 已重新提交，返回新业务消息的 `message_id` (其确认同样通过事件通知)。
+    /// * `Ok(None)`: 已放弃本端修改。
+    /// * `Err(String)`: 没有该冲突、本地尚无任务状态、负载无效或发送失败；此时冲突仍保留，可再次处理。
+    pub async fn resolve_version_conflict(&self, request_message_id: &str, resolution: ConflictResolution) -> Result<Option<String>, String> {
+        let unconfirmed = &self.session.unconfirmed_business_messages;
+        let Some(rejected_message) = unconfirmed.take_conflict(request_message_id) else {
+            return Err(format!("没有等待处理的版本冲突 (业务消息 ID: {})", request_message_id));
+        };
+        let outcome = match self.cached_task_version().await {
+            Some(current_version) => rebase_message(&rejected_message, resolution, current_version),
+            None => Err("本地尚无任务状态，无法确定变基的目标版本".to_string()),
+        };
+        let resubmitted = match outcome {
+            Ok(Some(resubmitted)) => resubmitted,
+            Ok(None) => {
+                info!("[业务客户端] 已放弃业务消息 '{}' (ID: {}) 的本端修改。", rejected_message.message_type, request_message_id);
+                return Ok(None);
+            }
+            Err(e) => {
+                unconfirmed.park_conflict(rejected_message);
+                return Err(e);
+            }
+        };
+        let resubmitted_id = resubmitted.message_id.clone();
+        if let Err(e) = self.send(resubmitted).await {
+            unconfirmed.park_conflict(rejected_message);
+            return Err(e);
+        }
+        info!(
+            "[业务客户端] 业务消息 '{}' 已基于最新版本重新提交: {} -> {}",
+            rejected_message.message_type, request_message_id, resubmitted_id
+        );
+        Ok(Some(resubmitted_id))
+    }
+}
+
+/// 从非预期类型的回复中提取拒绝原因 (被拒绝的 `Ack` 或 `ErrorResponse` 中的说明)。
+fn rejection_reason(reply: &WsMessage) -> String {
+    let reason = match reply.message_type.as_str() {
+        ACK_MESSAGE_TYPE => reply.deserialize_payload::<AckPayload>().ok().and_then(|ack| ack.message),
+        ERROR_RESPONSE_MESSAGE_TYPE => reply.deserialize_payload::<ErrorResponsePayload>().ok().map(|error| error.error),
+        _ => None,
+    };
+    reason.unwrap_or_else(|| format!("云端以 '{}' 消息回复", reply.message_type))
+}
+
+/// 处理重连客户端的连接状态变化。
+///
+/// * 连接建立 (含重连)：标记已连接，丢弃之前的链路测量并启动心跳任务；
+/// * 连接结束 (等待重连、放弃重连或关闭)：停止心跳、取消挂起请求、清理会话状态，
+///   在从"已连接"变为"未连接"或最终放弃时发出 [`SatClientEvent::Disconnected`]。
+async fn handle_connection_state(session: &Arc<SessionState>, client: &Arc<ReconnectingClient>, state: ConnectionState) {
+    info!("[业务客户端] 连接状态变化: {:?}", state);
+    session.emit(SatClientEvent::ConnectionState(state.clone()));
+
+    let reason = match &state {
+        ConnectionState::Connecting { .. } => return,
+        ConnectionState::Connected { reconnected } => {
+            session.connected.store(true, Ordering::SeqCst);
+            *session.last_pong_received_at.write().await = Some(Instant::now());
+            // 新连接的网络状况可能完全不同，丢弃之前的测量，保留服务端公布的间隔范围
+            {
+                let mut heartbeat = session.heartbeat.write().await;
+                *heartbeat = AdaptiveHeartbeat::new(heartbeat.bounds());
+            }
+            let heartbeat_task = tokio::spawn(run_heartbeat_loop(session.clone(), client.clone()));
+            if let Some(old_heartbeat_task) = session.heartbeat_task.lock().await.replace(heartbeat_task) {
+                old_heartbeat_task.abort();
+            }
+            session.emit(SatClientEvent::Connected { reconnected: *reconnected });
+            return;
+        }
+        ConnectionState::Reconnecting { reason, delay_ms, .. } => format!("{}，{} 毫秒后自动重连", reason, delay_ms),
+        ConnectionState::GaveUp { attempts, last_error } => format!("连续 {} 次连接失败，已停止自动重连: {}", attempts, last_error),
+        ConnectionState::Closed => "WebSocket 连接已关闭".to_string(),
+    };
+
+    // 连接已结束，不会再有响应到达，立即取消所有挂起的请求
+    session.pending_requests.cancel_all();
+    if let Some(heartbeat_task) = session.heartbeat_task.lock().await.take() {
+        heartbeat_task.abort();
+    }
+    let was_connected = session.connected.swap(false, Ordering::SeqCst);
+    *session.client_id.write().await = None; // 重连后由新的 RegisterResponse 重新分配
+    *session.last_pong_received_at.write().await = None;
+
+    // 主动关闭时由调用方自行通知
+    let gave_up = matches!(state, ConnectionState::GaveUp { .. });
+    if (was_connected && state != ConnectionState::Closed) || gave_up {
+        session.emit(SatClientEvent::Disconnected { reason });
+    }
+}
+
+/// 解析并处理一条从云端收到的消息：更新会话状态，并发出对应的事件。
+async fn process_received_message(session: &Arc<SessionState>, client: &Arc<ReconnectingClient>, ws_msg: WsMessage) {
+    debug!("[业务客户端] 收到消息: 类型='{}', ID={}", ws_msg.message_type, ws_msg.message_id);
+    match ws_msg.message_type.as_str() {
+        PONG_MESSAGE_TYPE => {
+            *session.last_pong_received_at.write().await = Some(Instant::now());
+        }
+        REGISTER_RESPONSE_MESSAGE_TYPE => {
+            let Some(response) = parse_payload::<RegisterResponsePayload>(&ws_msg) else { return };
+            let handling = if response.success {
+                info!(
+                    "[业务客户端] 注册成功: client_id={}, group_id={:?}, role={:?}, resumed={}",
+                    response.assigned_client_id, response.effective_group_id, response.effective_role, response.resumed
+                );
+                *session.client_id.write().await = Some(response.assigned_client_id);
+                if let Some(bounds) = &response.heartbeat {
+                    info!("[业务客户端] 采用云端公布的心跳策略: {:?}", bounds);
+                    session.heartbeat.write().await.apply_server_bounds(bounds);
+                }
+                None
+            } else {
+                let handling = handling_for(response.error_code);
+                warn!("[业务客户端] 注册被云端拒绝: 错误码={:?}, 建议处理方式={:?}", response.error_code, handling);
+                Some(handling)
+            };
+            session.emit(SatClientEvent::Registration { response, handling });
+        }
+        PARTNER_STATUS_UPDATE_MESSAGE_TYPE => {
+            let Some(partner) = parse_payload::<PartnerStatusPayload>(&ws_msg) else { return };
+            info!(
+                "[业务客户端] 伙伴状态: group='{}', role={:?}, client_id={}, online={}, link_suspect={}",
+                partner.group_id, partner.partner_role, partner.partner_client_id, partner.is_online, partner.link_suspect
+            );
+            session.emit(SatClientEvent::PartnerStatus(partner));
+        }
+        TASK_STATE_UPDATE_MESSAGE_TYPE => {
+            let Some(new_state) = parse_payload::<TaskDebugState>(&ws_msg) else { return };
+            info!("[业务客户端] 收到完整任务状态: 任务ID='{}', 版本 {}", new_state.task_id, new_state.version);
+            session.replace_task_state(new_state).await;
+        }
+        TASK_STATE_DELTA_MESSAGE_TYPE => {
+            let Some(delta) = parse_payload::<TaskStateDeltaPayload>(&ws_msg) else { return };
+            apply_task_state_delta(session, client, delta).await;
+        }
+        ERROR_RESPONSE_MESSAGE_TYPE => {
+            let Some(error) = parse_payload::<ErrorResponsePayload>(&ws_msg) else { return };
+            let handling = handling_for(error.code);
+            warn!(
+                "[业务客户端] 收到来自云端的错误响应: code={:?}, message='{}', original_request_type='{:?}', 建议处理方式={:?}",
+                error.code, error.error, error.original_message_type, handling
+            );
+            session.emit(SatClientEvent::ServerError { error, handling });
+        }
+        ACK_MESSAGE_TYPE => {
+            let Some(ack) = parse_payload::<AckPayload>(&ws_msg) else { return };
+            // 收到确认后不再跟踪原业务消息；版本冲突时将其保留，等待用户选择变基、合并或放弃
+            let acknowledged_message = ws_msg.in_reply_to.as_deref().and_then(|id| session.unconfirmed_business_messages.acknowledge(id));
+            if ack.error_code == Some(ErrorCode::VersionConflict) {
+                handle_version_conflict(session, &ack, acknowledged_message).await;
+            }
+            if ack.status == AckStatus::Rejected {
+                warn!(
+                    "[业务客户端] 云端拒绝了业务消息 '{}' (ID: {:?}): 错误码={:?}, 说明={:?}",
+                    ack.original_message_type, ws_msg.in_reply_to, ack.error_code, ack.message
+                );
+            } else {
+                info!(
+                    "[业务客户端] 云端确认了业务消息 '{}' (ID: {:?}): 结果={:?}, 版本={:?}",
+                    ack.original_message_type, ws_msg.in_reply_to, ack.status, ack.version
+                );
+            }
+            let handling = (ack.status == AckStatus::Rejected).then(|| handling_for(ack.error_code));
+            session.emit(SatClientEvent::BusinessAck { request_message_id: ws_msg.in_reply_to.clone(), ack, handling });
+        }
+        SLOT_TAKEOVER_PROMPT_MESSAGE_TYPE => {
+            let Some(prompt) = parse_payload::<SlotTakeoverPromptPayload>(&ws_msg) else { return };
+            info!(
+                "[业务客户端] 客户端 {} 请求接管本端的 {:?} 槽位 (接管请求ID: {})，{} 秒内未答复将自动同意。",
+                prompt.requester_client_id, prompt.role, prompt.takeover_id, prompt.auto_approve_after_seconds
+            );
+            session.emit(SatClientEvent::SlotTakeoverPrompt(prompt));
+        }
+        SLOT_RELEASED_MESSAGE_TYPE => {
+            let Some(released) = parse_payload::<SlotReleasedPayload>(&ws_msg) else { return };
+            warn!(
+                "[业务客户端] 本端在组 '{}' 的 {:?} 槽位已由客户端 {} 接替 (原因: {:?})。",
+                released.group_id, released.role, released.successor_client_id, released.reason
+            );
+            // 本端已不属于该任务组，其任务状态缓存不再更新
+            *session.task_state.write().await = None;
+            if released.reason == SlotReleaseReason::TakenOver {
+                *session.client_id.write().await = None;
+            }
+            session.emit(SatClientEvent::SlotReleased(released));
+        }
+        GROUP_OPENED_MESSAGE_TYPE => {
+            let Some(opened) = parse_payload::<GroupOpenedPayload>(&ws_msg) else { return };
+            if opened.registration.success {
+                info!(
+                    "[业务客户端] 已为任务 '{}' 开组 '{:?}'，配对码有效期 {:?} 秒。",
+                    opened.task_id, opened.registration.effective_group_id, opened.join_code_expires_in_seconds
+                );
+                *session.client_id.write().await = Some(opened.registration.assigned_client_id);
+                if let Some(bounds) = &opened.registration.heartbeat {
+                    session.heartbeat.write().await.apply_server_bounds(bounds);
+                }
+            } else {
+                warn!(
+                    "[业务客户端] 为任务 '{}' 开组失败: 错误码={:?}, 原因={:?}",
+                    opened.task_id, opened.registration.error_code, opened.registration.message
+                );
+            }
+            session.emit(SatClientEvent::GroupOpened(opened));
+        }
+        GROUP_ROSTER_MESSAGE_TYPE => {
+            let Some(roster) = parse_payload::<GroupRosterPayload>(&ws_msg) else { return };
+            debug!("[业务客户端] 收到组 '{}' 的成员名册，共 {} 名成员。", roster.group_id, roster.members.len());
+            session.emit(SatClientEvent::GroupRoster(roster));
+        }
+        GROUP_LEFT_MESSAGE_TYPE => {
+            let Some(left) = parse_payload::<GroupLeftPayload>(&ws_msg) else { return };
+            info!("[业务客户端] 本端 ({:?}) 已离开任务组 '{}'，连接保持。", left.role, left.group_id);
+            *session.task_state.write().await = None;
+            session.emit(SatClientEvent::GroupLeft(left));
+        }
+        START_SINGLE_TEST_STEP_TYPE => {
+            let Some(step) = parse_payload::<StartSingleTestStepPayload>(&ws_msg) else { return };
+            info!("[业务客户端] 收到设备 '{}' 的单体测试指令: 步骤='{}', 指令='{}'", step.device_id, step.step_id, step.command);
+            session.emit(SatClientEvent::StartSingleTestStep { command_message_id: ws_msg.message_id.clone(), step });
+        }
+        ECHO_MESSAGE_TYPE => {
+            let Some(echo) = parse_payload::<EchoPayload>(&ws_msg) else { return };
+            session.emit(SatClientEvent::Echo(echo));
+        }
+        HANDOVER_TOKEN_MESSAGE_TYPE => {
+            // 交接令牌由 `prepare_handover()` 的等待者取走，此处只做记录
+            debug!("[业务客户端] 收到云端签发的交接令牌 (请求ID: {:?})。", ws_msg.in_reply_to);
+        }
+        _ => {
+            debug!("[业务客户端] 未识别的消息类型 '{}'，原样转交。", ws_msg.message_type);
+            session.emit(SatClientEvent::Unhandled(ws_msg));
+        }
+    }
+}
+
+/// 解析消息负载，失败时记录日志并返回 `None`。
+fn parse_payload<T: serde::de::DeserializeOwned>(ws_msg: &WsMessage) -> Option<T> {
+    match ws_msg.deserialize_payload::<T>() {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            error!("[业务客户端] 解析 '{}' 消息的负载失败: {}, 原始 payload: {}", ws_msg.message_type, e, ws_msg.payload);
+            None
+        }
+    }
+}
+
+/// 将云端推送的任务状态增量应用到本地缓存。
+///
+/// 本地缓存为空、与增量之间存在版本缺口或增量无法应用时，向云端发送 `TaskStateSyncRequest`，
+/// 云端回复的完整 `TaskStateUpdate` 会覆盖本地缓存。重复或过期的增量直接忽略。
+async fn apply_task_state_delta(session: &Arc<SessionState>, client: &Arc<ReconnectingClient>, delta: TaskStateDeltaPayload) {
+    let mut cache_guard = session.task_state.write().await;
+    let outcome = match cache_guard.as_ref() {
+        Some(cached) => cached.apply_delta(&delta),
+        None => {
+            drop(cache_guard);
+            warn!("[业务客户端] 收到任务状态增量 (版本 {})，但本地尚无任务状态缓存，将向云端请求完整状态。", delta.version);
+            request_task_state_resync(client).await;
+            return;
+        }
+    };
+    match outcome {
+        Ok(new_state) => {
+            debug!(
+                "[业务客户端] 已应用任务状态增量: 版本 {} -> {} ({} 个操作)",
+                delta.base_version, delta.version, delta.patch.0.len()
+            );
+            *cache_guard = Some(new_state.clone());
+            drop(cache_guard);
+            session.emit(SatClientEvent::TaskStateUpdated(new_state));
+        }
+        Err(e) if !e.requires_resync() => debug!("[业务客户端] 忽略任务状态增量: {}", e),
+        Err(e) => {
+            drop(cache_guard);
+            warn!("[业务客户端] 无法应用任务状态增量: {}。将向云端请求完整状态。", e);
+            request_task_state_resync(client).await;
+        }
+    }
+}
+
+/// 向云端发送 `TaskStateSyncRequest`，不等待回复 (回复的 `TaskStateUpdate` 由接收流程写入本地缓存)。
+async fn request_task_state_resync(client: &Arc<ReconnectingClient>) {
+    match WsMessage::new(TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE.to_string(), &TaskStateSyncRequestPayload {}) {
+        Ok(sync_request) => {
+            if let Err(e) = client.send(&sync_request).await {
+                error!("[业务客户端] 发送 TaskStateSyncRequest 失败: {}", e);
+            }
+        }
+        Err(e) => error!("[业务客户端] 创建 TaskStateSyncRequest 消息失败: {:?}", e),
+    }
+}
+
+/// 处理被云端以 `VERSION_CONFLICT` 拒绝的业务消息。
+///
+/// 用确认中附带的云端当前状态刷新本地缓存，保留本端被拒绝的消息，
+/// 再发出 [`SatClientEvent::VersionConflict`]，等待调用方通过 [`SatClient::resolve_version_conflict`] 处理。
+async fn handle_version_conflict(session: &Arc<SessionState>, ack: &AckPayload, rejected_message: Option<WsMessage>) {
+    let Some(current_state) = ack.current_state.clone() else {
+        warn!("[业务客户端] 版本冲突的确认未携带云端当前状态 (业务消息 '{}')，无法提供变基/合并。", ack.original_message_type);
+        return;
+    };
+    let is_newer = session
+        .task_state
+        .read()
+        .await
+        .as_ref()
+        .is_none_or(|cached| cached.task_id != current_state.task_id || cached.version < current_state.version);
+    if is_newer {
+        session.replace_task_state(current_state.clone()).await;
+    }
+    let Some(rejected_message) = rejected_message else {
+        warn!(
+            "[业务客户端] 业务消息 '{}' 因版本冲突被拒绝，但本端未登记其原始内容，仅刷新了本地状态 (版本 {})。",
+            ack.original_message_type, current_state.version
+        );
+        return;
+    };
+    info!(
+        "[业务客户端] 业务消息 '{}' (ID: {}) 基于过期版本被拒绝，云端当前版本 {}，等待用户处理冲突。",
+        rejected_message.message_type, rejected_message.message_id, current_state.version
+    );
+    let conflict = VersionConflict {
+        request_message_id: rejected_message.message_id.clone(),
+        original_message_type: rejected_message.message_type.clone(),
+        local_payload: serde_json::from_str(&rejected_message.payload).unwrap_or(serde_json::Value::Null),
+        current_state,
+    };
+    session.unconfirmed_business_messages.park_conflict(rejected_message);
+    session.emit(SatClientEvent::VersionConflict(conflict));
+}
+
+/// 心跳循环。
+///
+/// 按自适应的间隔 (`AdaptiveHeartbeat::interval`) 发送 Ping，并根据 Pong 测量往返时延与抖动：
+/// 1. 每次醒来先评估尚未得到结论的 Ping：收到了 Pong 则记录往返时延，超过等待期限仍未收到则记为一次丢失，
+///    评估结果以 [`SatClientEvent::LinkQuality`] 发出。心跳间隔始终不超出服务端公布的范围。
+/// 2. 自上次收到 Pong 起超过"心跳间隔 + 等待期限"仍无任何 Pong 时，认为连接已"半死"，
+///    调用 `client.force_reconnect()` 断开并按退避策略重连，本任务随即结束；重连成功后重新启动。
+/// 3. 连接断开或发送 Ping 失败时，循环终止。
+async fn run_heartbeat_loop(session: Arc<SessionState>, client: Arc<ReconnectingClient>) {
+    {
+        let heartbeat = session.heartbeat.read().await;
+        info!(
+            "[业务客户端] 心跳任务已启动，间隔范围 {:?} ~ {:?}，当前间隔 {:?}。",
+            heartbeat.bounds().min_interval, heartbeat.bounds().max_interval, heartbeat.interval()
+        );
+    }
+    // 尚未得到结论 (既未收到 Pong 也未超过等待期限) 的最早一次 Ping 的发送时刻
+    let mut outstanding_ping_sent_at: Option<Instant> = None;
+
+    loop {
+        let heartbeat_interval = session.heartbeat.read().await.interval();
+        tokio::time::sleep(heartbeat_interval).await;
+        if !session.connected.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // 评估上一次 Ping：收到 Pong 则记录往返时延，超过等待期限则记为丢失
+        let last_pong = *session.last_pong_received_at.read().await;
+        let now = Instant::now();
+        let (quality, pong_timeout, heartbeat_interval) = {
+            let mut heartbeat = session.heartbeat.write().await;
+            let pong_timeout = heartbeat.pong_timeout();
+            let quality = match (outstanding_ping_sent_at, last_pong) {
+                (Some(sent_at), Some(pong_at)) if pong_at >= sent_at => {
+                    outstanding_ping_sent_at = None;
+                    Some(heartbeat.on_pong(pong_at.duration_since(sent_at)))
+                }
+                (Some(sent_at), _) if now.duration_since(sent_at) >= pong_timeout => {
+                    outstanding_ping_sent_at = None;
+                    Some(heartbeat.on_missed_pong())
+                }
+                _ => None,
+            };
+            (quality, pong_timeout, heartbeat.interval())
+        };
+        if let Some(quality) = &quality {
+            debug!("[业务客户端] 链路质量: {:?}", quality);
+            session.emit(SatClientEvent::LinkQuality(quality.clone()));
+        }
+
+        // 检查 Pong 超时
+        if let Some(last_pong) = last_pong {
+            let dead_after = heartbeat_interval + pong_timeout;
+            if now.duration_since(last_pong) > dead_after {
+                warn!("[业务客户端] 超过 {:?} 未收到 Pong，连接可能已假死，将强制重连。", dead_after);
+                *session.last_pong_received_at.write().await = None;
+                client.force_reconnect();
+                break;
+            }
+        }
+
+        // 发送 Ping，并在其中报告最近一次测得的往返时延
+        let round_trip_ms = quality.and_then(|quality| quality.last_rtt_ms);
+        match WsMessage::new(PING_MESSAGE_TYPE.to_string(), &PingPayload { round_trip_ms }) {
+            Ok(ping) => {
+                let sent_at = Instant::now();
+                if let Err(e) = client.send(&ping).await {
+                    // 发送失败通常意味着连接已断开，重连由重连客户端负责
+                    error!("[业务客户端] 发送 Ping 失败: {}。心跳任务将终止。", e);
+                    break;
+                }
+                outstanding_ping_sent_at.get_or_insert(sent_at);
+            }
+            Err(e) => error!("[业务客户端] 构建 Ping 消息失败: {:?}", e),
+        }
+    }
+    info!("[业务客户端] 心跳任务已停止。");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::enums::ClientRole;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::protocol::Message;
+
+    async fn next_event(events: &mut mpsc::UnboundedReceiver<SatClientEvent>) -> SatClientEvent {
+        tokio::time::timeout(Duration::from_secs(5), events.recv()).await.expect("等待事件超时").expect("事件通道意外关闭")
+    }
+
+    async fn next_message<S>(server_ws: &mut S) -> WsMessage
+    where
+        S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        let frame = server_ws.next().await.unwrap().unwrap();
+        serde_json::from_str(frame.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_typed_requests_state_cache_and_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (client, mut events) = SatClient::new(SatClientConfig::default());
+        client.connect(&url).await;
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server_ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::ConnectionState(ConnectionState::Connecting { .. })));
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::ConnectionState(ConnectionState::Connected { .. })));
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::Connected { reconnected: false }));
+        assert!(client.is_connected());
+
+        // 注册：请求方法返回类型化的响应，同时发出事件并记录客户端ID
+        let client = Arc::new(client);
+        let register = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .register(RegisterPayload {
+                        group_id: "g1".to_string(),
+                        role: ClientRole::OnSiteMobile,
+                        task_id: "t1".to_string(),
+                        client_software_version: None,
+                        client_display_name: None,
+                        resume_token: None,
+                        site_assignment: None,
+                        join_code: None,
+                    })
+                    .await
+            })
+        };
+        let request = next_message(&mut server_ws).await;
+        assert_eq!(request.message_type, REGISTER_MESSAGE_TYPE);
+        let client_id = Uuid::new_v4();
+        let response = RegisterResponsePayload {
+            success: true,
+            message: None,
+            assigned_client_id: client_id,
+            effective_group_id: Some("g1".to_string()),
+            effective_role: Some(ClientRole::OnSiteMobile),
+            error_code: None,
+            resume_token: None,
+            resume_grace_seconds: None,
+            resumed: false,
+            heartbeat: None,
+        };
+        let reply = WsMessage::new_reply(REGISTER_RESPONSE_MESSAGE_TYPE.to_string(), &response, &request.message_id).unwrap();
+        server_ws.send(Message::Text(serde_json::to_string(&reply).unwrap())).await.unwrap();
+        assert_eq!(register.await.unwrap().unwrap().assigned_client_id, client_id);
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::Registration { handling: None, .. }));
+        assert_eq!(client.client_id().await, Some(client_id));
+
+        // 完整任务状态写入本地缓存
+        let mut state = TaskDebugState::new("t1".to_string());
+        state.version = 4;
+        let push = WsMessage::new(TASK_STATE_UPDATE_MESSAGE_TYPE.to_string(), &state).unwrap();
+        server_ws.send(Message::Text(serde_json::to_string(&push).unwrap())).await.unwrap();
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::TaskStateUpdated(updated) if updated.version == 4));
+        assert_eq!(client.cached_task_version().await, Some(4));
+
+        // 业务操作：返回云端的确认
+        let feedback = {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .feedback_step(FeedbackSingleTestStepPayload {
+                        task_id: "t1".to_string(),
+                        device_id: "d1".to_string(),
+                        step_id: "s1".to_string(),
+                        execution_status: "Completed".to_string(),
+                        result_data: None,
+                        feedback_notes: None,
+                        expected_version: Some(4),
+                    })
+                    .await
+            })
+        };
+        let request = next_message(&mut server_ws).await;
+        assert_eq!(request.message_type, FEEDBACK_SINGLE_TEST_STEP_TYPE);
+        let ack = AckPayload::applied(FEEDBACK_SINGLE_TEST_STEP_TYPE, 5);
+        let reply = WsMessage::new_reply(ACK_MESSAGE_TYPE.to_string(), &ack, &request.message_id).unwrap();
+        server_ws.send(Message::Text(serde_json::to_string(&reply).unwrap())).await.unwrap();
+        assert_eq!(feedback.await.unwrap().unwrap(), ack);
+        match next_event(&mut events).await {
+            SatClientEvent::BusinessAck { request_message_id, ack: event_ack, handling } => {
+                assert_eq!(request_message_id.as_deref(), Some(request.message_id.as_str()));
+                assert_eq!(event_ack, ack);
+                assert_eq!(handling, None);
+            }
+            other => panic!("预期业务确认事件，实际为 {:?}", other),
+        }
+
+        // 重放已保存的业务消息：沿用原 message_id
+        let replay = {
+            let client = client.clone();
+            let saved = request.clone();
+            tokio::spawn(async move { client.send_business_message(saved).await })
+        };
+        let replayed = next_message(&mut server_ws).await;
+        assert_eq!(replayed.message_id, request.message_id);
+        let reply = WsMessage::new_reply(ACK_MESSAGE_TYPE.to_string(), &ack, &replayed.message_id).unwrap();
+        server_ws.send(Message::Text(serde_json::to_string(&reply).unwrap())).await.unwrap();
+        assert_eq!(replay.await.unwrap().unwrap(), ack);
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::BusinessAck { .. }));
+
+        // 服务端断开：发出 Disconnected，会话状态被清理
+        drop(server_ws);
+        loop {
+            if let SatClientEvent::Disconnected { .. } = next_event(&mut events).await {
+                break;
+            }
+        }
+        assert!(!client.is_connected());
+        assert_eq!(client.client_id().await, None);
+        assert!(client.update_pre_check_item(UpdatePreCheckItemPayload {
+            task_id: "t1".to_string(),
+            item_id: "i1".to_string(),
+            status: "Site_Completed".to_string(),
+            notes: None,
+            expected_version: None,
+        })
+        .await
+        .is_err());
+        client.disconnect().await;
+    }
+
+    #[tokio::test]
+    async fn test_restore_task_state_only_fills_empty_cache() {
+        let (client, mut events) = SatClient::new(SatClientConfig::default());
+        let mut saved = TaskDebugState::new("t1".to_string());
+        saved.version = 2;
+        assert!(client.restore_task_state(saved).await);
+        assert!(matches!(next_event(&mut events).await, SatClientEvent::TaskStateUpdated(restored) if restored.version == 2));
+        assert_eq!(client.cached_task_version().await, Some(2));
+
+        let mut other = TaskDebugState::new("t2".to_string());
+        other.version = 7;
+        assert!(!client.restore_task_state(other).await);
+        assert_eq!(client.cached_task_state().await.map(|state| state.task_id), Some("t1".to_string()));
+    }
+}
@@ -102,7 +102,7 @@ pub async fn connect_client(url_str: String) -> Result<ClientConnection, WsError
         Err(e) => {
             // 连接失败
             error!("客户端：连接到 {} 失败，错误: {}", url_str, e);
-            Err(WsError::WebSocketProtocolError(e)) // 将底层的 TungsteniteError 包装在 WsError 中
+            Err(WsError::from(e)) // 将底层的 TungsteniteError 包装在 WsError 中
         }
     }
 }
@@ -177,7 +177,7 @@ pub async fn receive_message(
                     _ => {
                         // 其他类型的 TungsteniteError，例如协议错误、IO错误等
                         error!("客户端：从 WebSocket 流接收消息时发生底层错误: {}", e);
-                        break Some(Err(WsError::WebSocketProtocolError(e))); // 将其包装并作为错误返回
+                        break Some(Err(WsError::from(e))); // 将其包装并作为错误返回
                     }
                 },
             },
@@ -0,0 +1,127 @@
+// rust_websocket_utils/src/client/version_conflict.rs
+
+//! 业务消息的版本冲突处理 (乐观并发控制下的变基/合并流程)。
+//!
+//! 本端发出的业务消息携带 `expected_version` (发送时本地缓存的任务状态版本)。若在此期间另一端已修改了
+//! 任务状态，云端会以 `VERSION_CONFLICT` 拒绝该消息，并在 `Ack` 中附带当前完整的任务状态。本模块负责：
+//! - 登记已发出但尚未确认的业务消息 ([`UnconfirmedBusinessMessages`])，冲突时将其保留，等待用户决定；
+//! - 按用户选择的处理方式 ([`ConflictResolution`]) 在最新版本之上重新构造业务消息 ([`rebase_message`])。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use common_models::ws_payloads::is_business_message_type;
+use crate::message::WsMessage;
+use serde::{Deserialize, Serialize};
+
+/// 用户对一次版本冲突的处理方式，由前端在收到 `WsVersionConflictEvent` 后选择。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// 变基：保持本端原有的修改内容，基于云端当前版本重新提交 (覆盖对方的修改)。
+    Rebase,
+    /// 合并：提交前端合并后的负载 (与原业务消息同类型)，基于云端当前版本。
+    Merge { payload: serde_json::Value },
+    /// 放弃本端的修改，以云端当前状态为准。
+    Discard,
+}
+
+/// 已发出但尚未收到确认的业务消息，以及因版本冲突被拒绝、等待用户处理的业务消息，均以 `message_id` 为键。
+#[derive(Debug, Default)]
+pub struct UnconfirmedBusinessMessages {
+    awaiting_ack: Mutex<HashMap<String, WsMessage>>,
+    conflicted: Mutex<HashMap<String, WsMessage>>,
+}
+
+impl UnconfirmedBusinessMessages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记一条即将发出的消息。非业务消息 (心跳、注册等) 不会被登记。
+    pub fn track(&self, message: &WsMessage) {
+        if is_business_message_type(&message.message_type) {
+            self.awaiting_ack.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(message.message_id.clone(), message.clone());
+        }
+    }
+
+    /// 收到确认 (或发送失败) 后移除登记，返回原业务消息。
+    pub fn acknowledge(&self, message_id: &str) -> Option<WsMessage> {
+        self.awaiting_ack.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(message_id)
+    }
+
+    /// 保留一条因版本冲突被拒绝的业务消息，直到用户通过 [`Self::take_conflict`] 处理它。
+    pub fn park_conflict(&self, message: WsMessage) {
+        self.conflicted.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(message.message_id.clone(), message);
+    }
+
+    /// 取出一条等待处理的冲突业务消息。
+    pub fn take_conflict(&self, message_id: &str) -> Option<WsMessage> {
+        self.conflicted.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(message_id)
+    }
+
+    /// 主动断开连接时清空尚未确认的登记 (不会再收到它们的确认)；等待用户处理的冲突保留。
+    pub fn clear_awaiting_ack(&self) {
+        self.awaiting_ack.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+}
+
+/// 按处理方式在云端当前版本 `current_version` 之上重新构造被拒绝的业务消息。
+///
+/// # 返回
+/// * `Ok(Some(WsMessage))`: 待发送的新业务消息 (新的 `message_id`，`expected_version` 为当前版本)。
+/// * `Ok(None)`: 用户选择放弃，无需发送。
+/// * `Err(String)`: 原负载或合并后的负载不是 JSON 对象，无法重新构造。
+pub fn rebase_message(original: &WsMessage, resolution: ConflictResolution, current_version: u64) -> Result<Option<WsMessage>, String> {
+    let mut payload = match resolution {
+        ConflictResolution::Discard => return Ok(None),
+        ConflictResolution::Rebase => serde_json::from_str::<serde_json::Value>(&original.payload)
+            .map_err(|e| format!("解析原业务消息负载失败: {}", e))?,
+        ConflictResolution::Merge { payload } => payload,
+    };
+    let Some(fields) = payload.as_object_mut() else {
+        return Err(format!("'{}' 消息的负载必须是 JSON 对象", original.message_type));
+    };
+    fields.insert("expected_version".to_string(), serde_json::Value::from(current_version));
+    WsMessage::new(original.message_type.clone(), &payload)
+        .map(Some)
+        .map_err(|e| format!("创建 '{}' 消息失败: {:?}", original.message_type, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::ws_payloads::{PING_MESSAGE_TYPE, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE};
+
+    #[test]
+    fn test_conflicted_message_is_rebased_onto_current_version() {
+        let unconfirmed = UnconfirmedBusinessMessages::new();
+        let note = WsMessage::new(
+            UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string(),
+            &serde_json::json!({ "task_id": "t1", "new_note": "本端备注", "expected_version": 3 }),
+        )
+        .unwrap();
+        let ping = WsMessage::new(PING_MESSAGE_TYPE.to_string(), &serde_json::json!({})).unwrap();
+        unconfirmed.track(&note);
+        unconfirmed.track(&ping);
+        assert!(unconfirmed.acknowledge(&ping.message_id).is_none(), "非业务消息不应被登记");
+
+        let rejected = unconfirmed.acknowledge(&note.message_id).unwrap();
+        unconfirmed.park_conflict(rejected);
+        let rejected = unconfirmed.take_conflict(&note.message_id).unwrap();
+
+        let rebased = rebase_message(&rejected, ConflictResolution::Rebase, 5).unwrap().unwrap();
+        assert_ne!(rebased.message_id, note.message_id);
+        let payload: serde_json::Value = serde_json::from_str(&rebased.payload).unwrap();
+        assert_eq!(payload["expected_version"], 5);
+        assert_eq!(payload["new_note"], "本端备注");
+
+        let merged = ConflictResolution::Merge { payload: serde_json::json!({ "task_id": "t1", "new_note": "合并后" }) };
+        let merged = rebase_message(&rejected, merged, 5).unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&merged.payload).unwrap()["new_note"], "合并后");
+
+        assert!(rebase_message(&rejected, ConflictResolution::Discard, 5).unwrap().is_none());
+        let invalid = ConflictResolution::Merge { payload: serde_json::json!("不是对象") };
+        assert!(rebase_message(&rejected, invalid, 5).is_err());
+    }
+}
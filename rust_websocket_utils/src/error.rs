@@ -119,9 +119,10 @@ pub enum WsError {
     /// 表示在 WebSocket 协议层面发生的错误。
     /// 这通常是由底层的 `tokio-tungstenite` 库报告的错误，例如连接握手失败、
     /// 无效的 WebSocket 帧、连接被意外关闭等。
-    /// 使用 `#[from]` 属性可以直接将 `tokio_tungstenite::tungstenite::Error` 转换为此变体。
+    /// 可以通过 `From` 直接将 `tokio_tungstenite::tungstenite::Error` 转换为此变体。
+    /// 底层错误体积较大 (含 HTTP 响应)，装箱存放以免 `WsError` 随之变大。
     #[error("WebSocket 协议层错误: {0}")] // 中文错误信息
-    WebSocketProtocolError(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocketProtocolError(Box<tokio_tungstenite::tungstenite::Error>),
 
     /// 表示在执行底层输入/输出 (I/O) 操作时发生的错误。
     /// 例如，网络连接无法建立、读取或写入 TCP 流失败等。
@@ -159,5 +160,11 @@ pub enum WsError {
     Message(String),
 }
 
+impl From<tokio_tungstenite::tungstenite::Error> for WsError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        WsError::WebSocketProtocolError(Box::new(e))
+    }
+}
+
 // 移除最后的占位注释
 // // 暂时为空，后续会根据开发步骤添加具体错误枚举或结构。 
\ No newline at end of file
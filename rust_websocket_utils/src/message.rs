@@ -26,6 +26,8 @@ use crate::error::WsError; // 引入本库定义的错误类型，用于处理
 /// - `payload`: 消息的实际数据负载，表示为一个 JSON 格式的字符串。其内部的具体数据结构由 `message_type` 决定。
 ///   发送方负责将具体的业务数据结构序列化为此 JSON 字符串，接收方则负责将其反序列化回相应的结构体。
 /// - `timestamp`: 一个 `i64` 类型的值，表示消息创建时的 UTC 时间戳 (自 Unix 纪元以来的毫秒数)。
+/// - `in_reply_to`: 可选字段。当此消息是对另一条消息的响应时，填入被响应消息的 `message_id`，
+///   使发送方能够把响应 (包括 `ErrorResponse`) 与自己发出的请求对应起来。
 ///
 /// 此结构体遵循项目规则 4.1 (WebSocket 消息结构) 和 1.2 (相关依赖库如 `uuid`, `chrono`, `serde`)。
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -45,6 +47,11 @@ pub struct WsMessage {
     /// 消息在创建时的时间戳，以自 Unix 纪元 (1970-01-01T00:00:00Z) 以来的毫秒数表示 (UTC 时间)。
     /// 可用于消息排序、超时检测或审计日志。
     pub timestamp: i64,
+
+    /// 被响应消息的 `message_id`。仅在响应类消息中出现，主动推送的消息此字段为 `None`。
+    /// 序列化时若为 `None` 则省略该字段，反序列化时缺失则视为 `None`，以兼容不带此字段的旧版本对端。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
 }
 
 impl WsMessage {
@@ -71,9 +78,25 @@ impl WsMessage {
             message_type, // 使用传入的消息类型
             payload: payload_str, // 使用序列化后的 JSON 字符串作为载荷
             timestamp: Utc::now().timestamp_millis(), // 获取当前 UTC 时间的毫秒级时间戳
+            in_reply_to: None, // 普通消息不关联任何请求
         })
     }
 
+    /// 创建一条响应消息，其 `in_reply_to` 字段指向被响应消息的 `message_id`。
+    ///
+    /// 除 `in_reply_to` 外，其余字段的生成方式与 [`WsMessage::new`] 相同。
+    ///
+    /// # Arguments
+    ///
+    /// * `message_type` - 响应消息的业务类型。
+    /// * `payload_data` - 响应负载，将被序列化为 JSON 字符串。
+    /// * `in_reply_to` - 被响应消息的 `message_id`。
+    pub fn new_reply<T: Serialize>(message_type: String, payload_data: &T, in_reply_to: &str) -> Result<WsMessage, WsError> {
+        let mut reply = Self::new(message_type, payload_data)?;
+        reply.in_reply_to = Some(in_reply_to.to_string());
+        Ok(reply)
+    }
+
     /// 将内部存储的 JSON 字符串载荷反序列化为指定的目标类型 `T`。
     ///
     /// 此方法提供了一种便捷的方式来从 `WsMessage` 的 `payload` 字段中提取并转换出具体的业务数据结构。
@@ -180,6 +203,7 @@ mod tests {
         assert_eq!(original_ws_message.message_type, deserialized_ws_message.message_type, "message_type 在序列化/反序列化周期后不一致");
         assert_eq!(original_ws_message.message_id, deserialized_ws_message.message_id, "message_id 在序列化/反序列化周期后不一致");
         assert_eq!(original_ws_message.timestamp, deserialized_ws_message.timestamp, "timestamp 在序列化/反序列化周期后不一致");
+        assert_eq!(deserialized_ws_message.in_reply_to, None, "普通消息的 in_reply_to 应为 None");
 
         // 步骤 4: 验证反序列化后的 WsMessage 实例中的 payload 字段，看它是否能被正确地反序列化回原始的 EchoPayload
         let payload_from_deserialized_result: Result<EchoPayload, WsError> = deserialized_ws_message.deserialize_payload();
@@ -187,6 +211,25 @@ mod tests {
         assert_eq!(payload_from_deserialized_result.unwrap(), original_echo_payload, "从反序列化后的 WsMessage 中提取的 EchoPayload 与原始实例不相等");
     }
 
+    #[test]
+    /// 测试 `WsMessage::new_reply` 是否正确填写 `in_reply_to`，
+    /// 以及不带 `in_reply_to` 字段的旧格式 JSON 是否仍能被反序列化。
+    fn test_ws_message_reply_correlation_and_backward_compatibility() {
+        let request = WsMessage::new(TEST_MESSAGE_TYPE.to_string(), &EchoPayload { content: "请求".to_string() }).unwrap();
+        let reply = WsMessage::new_reply(TEST_MESSAGE_TYPE.to_string(), &EchoPayload { content: "响应".to_string() }, &request.message_id).unwrap();
+        assert_eq!(reply.in_reply_to.as_deref(), Some(request.message_id.as_str()));
+        assert_ne!(reply.message_id, request.message_id, "响应消息应有自己的 message_id");
+
+        // 普通消息序列化后不应包含 in_reply_to 字段
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(!json.contains("in_reply_to"));
+
+        // 旧格式 (无 in_reply_to) 的消息应能正常解析
+        let legacy_json = r#"{"message_id":"m1","message_type":"Echo","payload":"{}","timestamp":1}"#;
+        let legacy: WsMessage = serde_json::from_str(legacy_json).unwrap();
+        assert_eq!(legacy.in_reply_to, None);
+    }
+
     #[test]
     /// 测试当尝试将 `WsMessage` 的 `payload` 反序列化为一个不匹配的类型时，
     /// `deserialize_payload` 方法是否能正确返回一个 `WsError::DeserializationError`。
@@ -0,0 +1,193 @@
+// rust_websocket_utils/src/server/auth.rs
+
+//! WebSocket 握手阶段的身份认证扩展点。
+//!
+//! `start_server` 在完成 WebSocket 升级之前，会把客户端发来的 HTTP 升级请求 (`Request`)
+//! 交给一个实现了 [`HandshakeAuthenticator`] 的认证器检查：
+//! - 认证通过时，认证器可以返回一个 [`AuthenticatedPrincipal`] (已认证主体)，
+//!   该主体会随 `ConnectionHandler` 一起交给上层应用，由应用将其绑定到自身的会话对象上。
+//! - 认证失败时，认证器返回 [`HandshakeRejection`]，握手将以 HTTP 401 或 403 状态码被拒绝，
+//!   连接不会进入 WebSocket 阶段。
+//!
+//! 本模块只定义扩展点和通用的辅助函数，不包含任何具体的令牌格式或用户存储逻辑；
+//! 这些应由使用本库的应用自行实现。
+
+use common_models::enums::ClientRole;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request};
+use tokio_tungstenite::tungstenite::http::{header, StatusCode};
+
+/// 表示"任意项目"的通配符。若 `allowed_projects` 中包含此值，则主体可访问所有项目。
+pub const ANY_PROJECT: &str = "*";
+
+/// 握手认证通过后得到的已认证主体。
+///
+/// 该结构体描述了"谁"建立了这个连接，以及该用户被允许以哪些角色、在哪些项目 (任务组) 中活动。
+/// 具体的授权判断 (例如注册时是否允许以某个角色加入某个组) 由上层应用基于这些信息完成。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedPrincipal {
+    /// 用户的唯一标识 (例如用户名或用户ID)。
+    pub user_id: String,
+    /// 该用户被允许扮演的客户端角色列表。
+    pub allowed_roles: Vec<ClientRole>,
+    /// 该用户被允许访问的项目 (任务组) 标识列表。包含 [`ANY_PROJECT`] 时表示不限制。
+    pub allowed_projects: Vec<String>,
+    /// 该用户是否可以不经当前持有者确认，强制接管任务组内已被占用的角色槽位。
+    pub may_force_slot_takeover: bool,
+}
+
+impl AuthenticatedPrincipal {
+    /// 判断该主体是否被允许扮演指定的客户端角色。
+    pub fn may_take_role(&self, role: ClientRole) -> bool {
+        self.allowed_roles.contains(&role)
+    }
+
+    /// 判断该主体是否被允许访问指定的项目 (任务组)。
+    pub fn may_access_project(&self, project_id: &str) -> bool {
+        self.allowed_projects
+            .iter()
+            .any(|p| p == ANY_PROJECT || p == project_id)
+    }
+}
+
+/// 握手被认证器拒绝的原因。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeRejection {
+    /// 未提供凭证或凭证无效，对应 HTTP 401 Unauthorized。
+    Unauthorized(String),
+    /// 凭证有效但无权建立连接，对应 HTTP 403 Forbidden。
+    Forbidden(String),
+}
+
+impl HandshakeRejection {
+    /// 返回此拒绝原因对应的 HTTP 状态码。
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            HandshakeRejection::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            HandshakeRejection::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    /// 返回拒绝原因的文本描述。
+    pub fn reason(&self) -> &str {
+        match self {
+            HandshakeRejection::Unauthorized(reason) | HandshakeRejection::Forbidden(reason) => reason,
+        }
+    }
+
+    /// 将拒绝原因转换为 tungstenite 握手回调所需的 HTTP 错误响应。
+    pub fn into_error_response(self) -> ErrorResponse {
+        let status = self.status_code();
+        let mut response = ErrorResponse::new(Some(self.reason().to_string()));
+        *response.status_mut() = status;
+        if status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+        }
+        response
+    }
+}
+
+/// 握手认证器 trait。
+///
+/// 实现者在 WebSocket 握手回调中被同步调用 (tungstenite 的握手回调本身是同步的)，
+/// 因此实现中不应执行阻塞 I/O；需要查库的场景应预先加载数据或使用可同步校验的签名令牌。
+///
+/// 返回值含义：
+/// - `Ok(Some(principal))`: 认证通过，并附带已认证主体。
+/// - `Ok(None)`: 允许匿名连接 (不附带主体)。
+/// - `Err(rejection)`: 拒绝握手，返回 401/403。
+pub trait HandshakeAuthenticator: Send + Sync + 'static {
+    /// 检查 HTTP 升级请求并给出认证结果。
+    fn authenticate(&self, request: &Request) -> Result<Option<AuthenticatedPrincipal>, HandshakeRejection>;
+}
+
+/// 默认的认证器：接受所有连接且不附带主体，保持与引入认证扩展点之前相同的行为。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllowAnonymous;
+
+impl HandshakeAuthenticator for AllowAnonymous {
+    fn authenticate(&self, _request: &Request) -> Result<Option<AuthenticatedPrincipal>, HandshakeRejection> {
+        Ok(None)
+    }
+}
+
+/// 从升级请求中提取 Bearer 令牌。
+///
+/// 依次检查：
+/// 1. `Authorization: Bearer <token>` 请求头；
+/// 2. URL 查询参数 `token=<token>` (浏览器中的 WebSocket API 无法设置自定义请求头，只能通过查询参数传递)。
+pub fn extract_bearer_token(request: &Request) -> Option<String> {
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+    if let Some(token) = header_token {
+        return Some(token.to_string());
+    }
+    request.uri().query().and_then(|query| {
+        query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            (key == "token" && !value.is_empty()).then(|| value.to_string())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with(uri: &str, authorization: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(auth) = authorization {
+            builder = builder.header(header::AUTHORIZATION, auth);
+        }
+        builder.body(()).expect("构造测试请求失败")
+    }
+
+    #[test]
+    fn test_extract_bearer_token_from_header_and_query() {
+        let req = request_with("/ws", Some("Bearer abc.def"));
+        assert_eq!(extract_bearer_token(&req), Some("abc.def".to_string()));
+
+        let req = request_with("/ws?foo=1&token=xyz", None);
+        assert_eq!(extract_bearer_token(&req), Some("xyz".to_string()));
+
+        let req = request_with("/ws", Some("Basic dXNlcjpwYXNz"));
+        assert_eq!(extract_bearer_token(&req), None);
+    }
+
+    #[test]
+    fn test_rejection_status_codes() {
+        let resp = HandshakeRejection::Unauthorized("缺少令牌".to_string()).into_error_response();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let resp = HandshakeRejection::Forbidden("账户已停用".to_string()).into_error_response();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_principal_permissions() {
+        let principal = AuthenticatedPrincipal {
+            user_id: "alice".to_string(),
+            allowed_roles: vec![ClientRole::ControlCenter],
+            allowed_projects: vec!["group-1".to_string()],
+            may_force_slot_takeover: false,
+        };
+        assert!(principal.may_take_role(ClientRole::ControlCenter));
+        assert!(!principal.may_take_role(ClientRole::OnSiteMobile));
+        assert!(principal.may_access_project("group-1"));
+        assert!(!principal.may_access_project("group-2"));
+
+        let admin = AuthenticatedPrincipal {
+            allowed_projects: vec![ANY_PROJECT.to_string()],
+            ..principal
+        };
+        assert!(admin.may_access_project("group-2"));
+    }
+}
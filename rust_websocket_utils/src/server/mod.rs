@@ -16,4 +16,5 @@
 //!
 //! `transport` 子模块通常包含具体的传输层实现，例如 `start_server` 函数和 `ConnectionHandler` 结构体等。
 
-pub mod transport; // 公开 transport 子模块，其中包含了主要的服务器端传输层逻辑和核心功能实现 
\ No newline at end of file
+pub mod transport; // 公开 transport 子模块，其中包含了主要的服务器端传输层逻辑和核心功能实现
+pub mod auth; // 握手认证扩展点：认证器 trait、已认证主体以及拒绝原因 (401/403)
@@ -4,12 +4,14 @@
 
 use crate::error::WsError;
 use crate::message::WsMessage;
+use crate::server::auth::{AllowAnonymous, AuthenticatedPrincipal, HandshakeAuthenticator};
 use futures_util::{
     stream::{SplitSink, SplitStream},
     SinkExt,
     StreamExt,
 };
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use std::sync::{Arc, Mutex};
 // use std::net::SocketAddr; // 已注释
 use tokio::net::TcpStream; // 只导入 TcpStream，因为 TcpListener 是通过完整路径使用的
 use tokio_tungstenite::{
@@ -32,9 +34,22 @@ use tokio_tungstenite::{
 /// 它负责处理来自该连接的消息接收、分发以及向该连接发送消息。
 pub struct ConnectionHandler {
     ws_sender: SplitSink<WebSocketStream<TcpStream>, Message>,
+    /// 握手阶段由认证器确认的主体。使用默认的 `AllowAnonymous` 认证器时为 `None`。
+    principal: Option<AuthenticatedPrincipal>,
 }
 
 impl ConnectionHandler {
+    /// 返回握手阶段认证得到的主体 (如有)。
+    pub fn principal(&self) -> Option<&AuthenticatedPrincipal> {
+        self.principal.as_ref()
+    }
+
+    /// 取出握手阶段认证得到的主体 (如有)，之后 `principal()` 将返回 `None`。
+    /// 便于上层应用将主体的所有权转移到自身的会话对象中。
+    pub fn take_principal(&mut self) -> Option<AuthenticatedPrincipal> {
+        self.principal.take()
+    }
+
     /// 发送一个 WsMessage 到客户端
     pub async fn send_message(&mut self, message: &WsMessage) -> Result<(), WsError> {
         let msg_json = serde_json::to_string(message)
@@ -48,6 +63,8 @@ impl ConnectionHandler {
 
 /// 监听并接受新的 WebSocket 连接
 ///
+/// 等价于使用 `AllowAnonymous` 认证器调用 [`start_server_with_authenticator`]，即接受所有握手请求。
+///
 /// # Arguments
 /// * `addr` - 服务器绑定的地址字符串，例如 "127.0.0.1:8080"。
 /// * `on_new_connection` - 一个回调闭包，当新的 WebSocket 连接建立并成功握手后被异步调用。
@@ -63,17 +80,60 @@ where
     F: FnMut(ConnectionHandler, SplitStream<WebSocketStream<TcpStream>>) -> Fut + Send + Clone + 'static, 
     Fut: std::future::Future<Output = ()> + Send + 'static,
 {
-    let listener = tokio::net::TcpListener::bind(&addr).await.map_err(|e| WsError::IoError(e))?;
+    start_server_with_authenticator(addr, Arc::new(AllowAnonymous), on_new_connection).await
+}
+
+/// 监听并接受新的 WebSocket 连接，并在握手阶段调用认证器。
+///
+/// 每个握手请求都会先交给 `authenticator` 检查：
+/// - 认证通过时，返回的主体会存入 `ConnectionHandler`，可通过 `principal()` / `take_principal()` 获取；
+/// - 认证失败时，握手以 401/403 响应被拒绝，`on_new_connection` 不会被调用。
+///
+/// # Arguments
+/// * `addr` - 服务器绑定的地址字符串。
+/// * `authenticator` - 握手认证器，在所有连接任务间共享。
+/// * `on_new_connection` - 与 [`start_server`] 中的含义相同。
+pub async fn start_server_with_authenticator<F, Fut>(
+    addr: String,
+    authenticator: Arc<dyn HandshakeAuthenticator>,
+    on_new_connection: F,
+) -> Result<(), WsError>
+where
+    F: FnMut(ConnectionHandler, SplitStream<WebSocketStream<TcpStream>>) -> Fut + Send + Clone + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind(&addr).await.map_err(WsError::IoError)?;
     info!("WebSocket 服务端正在监听地址: {}", addr);
 
     while let Ok((stream, client_addr)) = listener.accept().await {
         info!("新的 TCP 连接来自: {}", client_addr);
         let mut on_new_connection_for_task = on_new_connection.clone();
+        let authenticator_for_task = Arc::clone(&authenticator);
         tokio::spawn(async move {
-            let callback = |req: &Request, mut response: Response|
+            // 握手回调是同步的 FnOnce，通过共享槽位把认证结果带出回调。
+            let principal_slot: Arc<Mutex<Option<AuthenticatedPrincipal>>> = Arc::new(Mutex::new(None));
+            let principal_slot_for_cb = Arc::clone(&principal_slot);
+            let callback = move |req: &Request, mut response: Response|
                 -> Result<Response, ErrorResponse>
             {
                 info!("[握手回调] 收到来自 {} 的新 WebSocket 握手请求，路径: {}", client_addr, req.uri().path());
+                match authenticator_for_task.authenticate(req) {
+                    Ok(principal) => {
+                        if let Some(p) = &principal {
+                            info!("[握手回调] 来自 {} 的握手认证通过，用户: {}", client_addr, p.user_id);
+                        }
+                        *principal_slot_for_cb.lock().expect("握手主体槽位锁已中毒") = principal;
+                    }
+                    Err(rejection) => {
+                        warn!(
+                            "[握手回调] 拒绝来自 {} 的握手请求 (HTTP {}): {}",
+                            client_addr,
+                            rejection.status_code(),
+                            rejection.reason()
+                        );
+                        return Err(rejection.into_error_response());
+                    }
+                }
                 response.headers_mut().append(
                     "Access-Control-Allow-Origin",
                     HeaderValue::from_static("*")
@@ -85,7 +145,8 @@ where
                 Ok(ws_stream) => {
                     info!("WebSocket 连接已建立: {}", client_addr);
                     let (ws_sender, ws_receiver) = ws_stream.split();
-                    let handler = ConnectionHandler { ws_sender };
+                    let principal = principal_slot.lock().expect("握手主体槽位锁已中毒").take();
+                    let handler = ConnectionHandler { ws_sender, principal };
                     (on_new_connection_for_task)(handler, ws_receiver).await;
                     info!("与 {} 的连接已关闭", client_addr);
                 }
@@ -147,7 +208,7 @@ pub async fn receive_message(
                     }
                     _ => {
                         error!("从 WebSocket 流接收消息时发生错误: {}", e);
-                        break Some(Err(WsError::WebSocketProtocolError(e)));
+                        break Some(Err(WsError::from(e)));
                     }
                 },
             },
@@ -276,4 +337,65 @@ mod tests {
         }
         server_handle.abort();
     }
-} 
\ No newline at end of file
+    /// 测试用认证器：只接受携带 `token=good` 的请求，`token=banned` 返回 403，其余返回 401。
+    struct TokenAuthenticator;
+
+    impl HandshakeAuthenticator for TokenAuthenticator {
+        fn authenticate(&self, request: &Request) -> Result<Option<AuthenticatedPrincipal>, crate::server::auth::HandshakeRejection> {
+            use crate::server::auth::{extract_bearer_token, HandshakeRejection};
+            match extract_bearer_token(request).as_deref() {
+                Some("good") => Ok(Some(AuthenticatedPrincipal {
+                    user_id: "tester".to_string(),
+                    allowed_roles: vec![common_models::enums::ClientRole::ControlCenter],
+                    allowed_projects: vec!["*".to_string()],
+                    may_force_slot_takeover: false,
+                })),
+                Some("banned") => Err(HandshakeRejection::Forbidden("账户已停用".to_string())),
+                _ => Err(HandshakeRejection::Unauthorized("缺少或无效的令牌".to_string())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_handshake_authenticator() {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let server_bind_addr = "127.0.0.1:12347".to_string();
+        let principals_seen = Arc::new(Mutex::new(Vec::new()));
+        let principals_seen_clone = principals_seen.clone();
+
+        let server_handle = tokio::spawn(start_server_with_authenticator(
+            server_bind_addr.clone(),
+            Arc::new(TokenAuthenticator),
+            move |handler, _receiver| {
+                let seen = principals_seen_clone.clone();
+                async move {
+                    seen.lock().await.push(handler.principal().map(|p| p.user_id.clone()));
+                }
+            },
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let expect_status = |result: Result<crate::client::transport::ClientConnection, WsError>, status: u16| {
+            match result {
+                Err(WsError::WebSocketProtocolError(e)) => match *e {
+                    TungsteniteError::Http(resp) => assert_eq!(resp.status().as_u16(), status),
+                    other => panic!("期望 HTTP {} 握手拒绝，实际错误: {}", status, other),
+                },
+                Err(other) => panic!("期望 HTTP {} 握手拒绝，实际错误: {}", status, other),
+                Ok(_) => panic!("期望 HTTP {} 握手拒绝，但连接成功", status),
+            }
+        };
+
+        expect_status(connect_client(format!("ws://{}/ws", server_bind_addr)).await, 401);
+        expect_status(connect_client(format!("ws://{}/ws?token=banned", server_bind_addr)).await, 403);
+
+        let conn = connect_client(format!("ws://{}/ws?token=good", server_bind_addr)).await;
+        assert!(conn.is_ok(), "携带有效令牌的连接应被接受");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let seen = principals_seen.lock().await;
+        assert_eq!(seen.as_slice(), &[Some("tester".to_string())], "只有通过认证的连接会进入回调，且应携带主体");
+        server_handle.abort();
+    }
+}
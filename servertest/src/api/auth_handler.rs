@@ -0,0 +1,126 @@
+// SatCloudService/src-tauri/src/api/auth_handler.rs
+
+//! 用户认证相关的 HTTP API 处理器。
+//!
+//! 提供 `POST /api/auth/login` 接口：校验用户名和密码，成功后签发带过期时间的访问令牌。
+//! 客户端随后在建立 WebSocket 连接时携带该令牌，由 `auth::TokenHandshakeAuthenticator` 在握手阶段校验。
+
+use crate::auth::TokenService;
+use crate::db::UserRepository;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use common_models::auth_models::{LoginErrorResponse, LoginRequest, LoginResponse, LOGIN_API_PATH};
+use log::{error, info, warn};
+use std::sync::Arc;
+
+/// 认证 API 处理器共享的状态。
+#[derive(Clone)]
+pub struct AuthApiState {
+    /// 用户账户仓库。
+    pub user_repository: Arc<UserRepository>,
+    /// 访问令牌服务。
+    pub token_service: Arc<TokenService>,
+}
+
+/// 构建认证相关的路由。
+pub fn auth_router(state: AuthApiState) -> Router {
+    Router::new()
+        .route(LOGIN_API_PATH, post(login_handler))
+        .with_state(state)
+}
+
+type ApiError = (StatusCode, Json<LoginErrorResponse>);
+
+fn api_error(status: StatusCode, message: &str) -> ApiError {
+    (status, Json(LoginErrorResponse { error: message.to_string() }))
+}
+
+/// 处理登录请求。
+///
+/// - 用户名或密码错误、账户停用：返回 401，且不区分具体原因，避免泄露账户是否存在。
+/// - 内部错误 (数据库、令牌签发)：返回 500。
+pub async fn login_handler(
+    State(state): State<AuthApiState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let username = request.username.clone();
+    let repo = Arc::clone(&state.user_repository);
+    // 密码哈希校验是 CPU 密集型操作，且仓库访问是同步的，放到阻塞线程池中执行
+    let verify_result = tokio::task::spawn_blocking(move || repo.verify_credentials(&request.username, &request.password))
+        .await
+        .map_err(|e| {
+            error!("[认证API] 校验用户 '{}' 凭证的任务执行失败: {}", username, e);
+            api_error(StatusCode::INTERNAL_SERVER_ERROR, "服务器内部错误")
+        })?;
+
+    let account = match verify_result {
+        Ok(Some(account)) => account,
+        Ok(None) => {
+            warn!("[认证API] 用户 '{}' 登录失败：用户名或密码错误", username);
+            return Err(api_error(StatusCode::UNAUTHORIZED, "用户名或密码错误"));
+        }
+        Err(e) => {
+            error!("[认证API] 校验用户 '{}' 凭证时发生错误: {}", username, e);
+            return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, "服务器内部错误"));
+        }
+    };
+
+    let (access_token, expires_at) = state.token_service.issue(&account).map_err(|e| {
+        error!("[认证API] 为用户 '{}' 签发令牌失败: {}", username, e);
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, "服务器内部错误")
+    })?;
+
+    info!("[认证API] 用户 '{}' 登录成功，令牌有效期至 {}", account.username, expires_at);
+    Ok(Json(LoginResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_at,
+        user_id: account.user_id,
+        username: account.username,
+        roles: account.roles,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use common_models::enums::UserRole;
+
+    fn test_state() -> AuthApiState {
+        let repo = UserRepository::open_in_memory().unwrap();
+        repo.create_user("operator", "pw", &[UserRole::Operator], &["*".to_string()]).unwrap();
+        AuthApiState {
+            user_repository: Arc::new(repo),
+            token_service: Arc::new(TokenService::new(b"secret", Duration::hours(1))),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_success_issues_verifiable_token() {
+        let state = test_state();
+        let Json(response) = login_handler(
+            State(state.clone()),
+            Json(LoginRequest { username: "operator".to_string(), password: "pw".to_string() }),
+        )
+        .await
+        .expect("正确的凭证应登录成功");
+        assert_eq!(response.token_type, "Bearer");
+        assert_eq!(response.roles, vec![UserRole::Operator]);
+        let claims = state.token_service.verify(&response.access_token).unwrap();
+        assert_eq!(claims.username, "operator");
+    }
+
+    #[tokio::test]
+    async fn test_login_wrong_password_is_unauthorized() {
+        let result = login_handler(
+            State(test_state()),
+            Json(LoginRequest { username: "operator".to_string(), password: "bad".to_string() }),
+        )
+        .await;
+        let (status, _) = result.expect_err("错误的密码应登录失败");
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -1,4 +1,20 @@
-// api/mod.rs - APIu6a21u5757
-// u6b64u6a21u5757u8d1fu8d23u63d0u4f9bu5916u90e8HTTP APIu63a5u53e3
+// api/mod.rs - API模块
+// 此模块负责提供外部HTTP API接口
 
-// u540eu7eedu5b9eu73b0APIu76f8u5173u529fu80fd 
\ No newline at end of file
+/// 用户认证相关的 API (登录、令牌签发)。
+pub mod auth_handler;
+
+use anyhow::Context;
+use axum::Router;
+use log::info;
+
+/// 在指定地址上启动 HTTP API 服务，并持续运行直到出错。
+pub async fn start_api_server(addr: String, router: Router) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(&addr)
+        .await
+        .with_context(|| format!("[HTTP API] 绑定地址 '{}' 失败", addr))?;
+    info!("[HTTP API] HTTP API 服务正在监听地址: {}", addr);
+    axum::serve(listener, router)
+        .await
+        .with_context(|| format!("[HTTP API] 地址 '{}' 上的 HTTP API 服务异常终止", addr))
+}
@@ -0,0 +1,183 @@
+// SatCloudService/src-tauri/src/auth/token.rs
+
+//! 访问令牌的签发与校验。
+//!
+//! 令牌采用 HS256 签名的 JWT 格式，载荷 (`TokenClaims`) 中包含用户标识、业务角色、
+//! 允许访问的项目以及签发/过期时间。令牌校验是纯计算操作，不需要访问数据库，
+//! 因此可以在同步的 WebSocket 握手回调中安全地调用。
+
+use crate::db::UserAccount;
+use crate::error::AppError;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common_models::enums::{ClientRole, UserRole};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rust_websocket_utils::server::auth::AuthenticatedPrincipal;
+use serde::{Deserialize, Serialize};
+
+/// 访问令牌的载荷。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TokenClaims {
+    /// 用户的唯一标识 (JWT 标准字段 `sub`)。
+    pub sub: String,
+    /// 用户名。
+    pub username: String,
+    /// 用户被分配的业务角色。
+    pub roles: Vec<UserRole>,
+    /// 用户允许访问的项目 (任务组) 列表。
+    pub projects: Vec<String>,
+    /// 签发时间 (Unix 秒)。
+    pub iat: i64,
+    /// 过期时间 (Unix 秒)。
+    pub exp: i64,
+}
+
+impl TokenClaims {
+    /// 将令牌载荷转换为 WebSocket 层使用的已认证主体。
+    ///
+    /// 允许的 `ClientRole` 由用户的全部 `UserRole` 映射后去重得到。
+    pub fn to_principal(&self) -> AuthenticatedPrincipal {
+        let mut allowed_roles: Vec<ClientRole> = Vec::new();
+        for role in self.roles.iter().flat_map(|r| r.allowed_client_roles()) {
+            if !allowed_roles.contains(role) {
+                allowed_roles.push(*role);
+            }
+        }
+        AuthenticatedPrincipal {
+            user_id: self.sub.clone(),
+            allowed_roles,
+            allowed_projects: self.projects.clone(),
+            may_force_slot_takeover: self.roles.iter().any(|role| role.may_force_slot_takeover()),
+        }
+    }
+}
+
+/// 访问令牌服务，负责签发和校验令牌。
+pub struct TokenService {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    ttl: Duration,
+}
+
+impl std::fmt::Debug for TokenService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 不输出密钥内容
+        f.debug_struct("TokenService").field("ttl", &self.ttl).finish_non_exhaustive()
+    }
+}
+
+impl TokenService {
+    /// 使用给定的签名密钥和令牌有效期创建令牌服务。
+    pub fn new(secret: &[u8], ttl: Duration) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+            ttl,
+        }
+    }
+
+    /// 为指定用户签发访问令牌，返回令牌字符串及其过期时间。
+    pub fn issue(&self, account: &UserAccount) -> Result<(String, DateTime<Utc>), AppError> {
+        let now = Utc::now();
+        let expires_at = now + self.ttl;
+        let claims = TokenClaims {
+            sub: account.user_id.clone(),
+            username: account.username.clone(),
+            roles: account.roles.clone(),
+            projects: account.allowed_projects.clone(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let token = self.encode_claims(&claims)?;
+        // 令牌中的过期时间精确到秒，这里返回截断后的值以与令牌内容保持一致
+        let expires_at = Utc.timestamp_opt(claims.exp, 0).single().unwrap_or(expires_at);
+        Ok((token, expires_at))
+    }
+
+    /// 校验令牌的签名和有效期，成功时返回其载荷。
+    pub fn verify(&self, token: &str) -> Result<TokenClaims, AppError> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        decode::<TokenClaims>(token, &self.decoding_key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| AppError::AuthenticationError(format!("访问令牌无效: {}", e)))
+    }
+
+    fn encode_claims(&self, claims: &TokenClaims) -> Result<String, AppError> {
+        encode(&Header::new(Algorithm::HS256), claims, &self.encoding_key)
+            .map_err(|e| AppError::AuthenticationError(format!("签发访问令牌失败: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_account(roles: Vec<UserRole>) -> UserAccount {
+        UserAccount {
+            user_id: "u-1".to_string(),
+            username: "alice".to_string(),
+            roles,
+            allowed_projects: vec!["group-1".to_string()],
+            is_active: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_issue_and_verify_token() {
+        let service = TokenService::new(b"test-secret", Duration::hours(1));
+        let (token, expires_at) = service.issue(&test_account(vec![UserRole::Operator])).unwrap();
+        assert!(expires_at > Utc::now());
+
+        let claims = service.verify(&token).expect("有效令牌应校验通过");
+        assert_eq!(claims.username, "alice");
+        assert_eq!(claims.roles, vec![UserRole::Operator]);
+
+        let principal = claims.to_principal();
+        assert_eq!(principal.user_id, "u-1", "主体标识应取自签名的 sub 字段而不是用户名");
+        assert_eq!(principal.allowed_roles, vec![ClientRole::ControlCenter]);
+        assert!(principal.may_access_project("group-1"));
+        assert!(!principal.may_force_slot_takeover);
+    }
+
+    #[test]
+    fn test_reject_tampered_wrong_key_and_expired_tokens() {
+        let service = TokenService::new(b"test-secret", Duration::hours(1));
+        let (token, _) = service.issue(&test_account(vec![UserRole::Admin])).unwrap();
+
+        let other_service = TokenService::new(b"another-secret", Duration::hours(1));
+        assert!(other_service.verify(&token).is_err(), "其他密钥签发的令牌应被拒绝");
+        assert!(service.verify(&format!("{}x", token)).is_err(), "被篡改的令牌应被拒绝");
+
+        let now = Utc::now().timestamp();
+        let expired = service
+            .encode_claims(&TokenClaims {
+                sub: "u-1".to_string(),
+                username: "alice".to_string(),
+                roles: vec![UserRole::Admin],
+                projects: vec![],
+                iat: now - 120,
+                exp: now - 60,
+            })
+            .unwrap();
+        assert!(service.verify(&expired).is_err(), "过期令牌应被拒绝");
+    }
+
+    #[test]
+    fn test_admin_principal_has_all_client_roles() {
+        let claims = TokenClaims {
+            sub: "u-2".to_string(),
+            username: "root".to_string(),
+            roles: vec![UserRole::Admin, UserRole::Operator],
+            projects: vec!["*".to_string()],
+            iat: 0,
+            exp: 0,
+        };
+        let principal = claims.to_principal();
+        assert_eq!(
+            principal.allowed_roles,
+            vec![ClientRole::ControlCenter, ClientRole::OnSiteMobile, ClientRole::Supervisor, ClientRole::Observer]
+        );
+        assert!(principal.may_force_slot_takeover, "管理员可强制接管角色槽位");
+    }
+}
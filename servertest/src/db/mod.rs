@@ -1,4 +1,7 @@
 // db/mod.rs - 数据库模块
 // 此模块负责数据库访问和操作
 
-// 后续实现数据库相关功能 
\ No newline at end of file
+/// 用户账户数据仓库 (用户名、密码哈希、角色分配)。
+pub mod user_repo;
+
+pub use user_repo::{UserAccount, UserRepository};
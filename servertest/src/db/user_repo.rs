@@ -0,0 +1,275 @@
+// SatCloudService/src-tauri/src/db/user_repo.rs
+
+//! 用户账户数据仓库。
+//!
+//! 本模块负责用户账户的持久化：用户名、密码哈希、被分配的业务角色 (`UserRole`)
+//! 以及允许访问的项目 (任务组) 列表。数据存储在 SQLite 数据库的 `users` 表中。
+//!
+//! 密码从不以明文保存，而是使用 Argon2id 算法加盐哈希后以 PHC 字符串格式存储。
+//!
+//! `rusqlite::Connection` 不是 `Sync` 的，因此仓库内部使用 `std::sync::Mutex` 保护连接。
+//! 所有方法都是同步的；在异步上下文 (例如 axum 处理器) 中调用时，
+//! 应通过 `tokio::task::spawn_blocking` 避免阻塞运行时 (密码哈希校验本身也是 CPU 密集型操作)。
+
+use crate::error::AppError;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use common_models::enums::UserRole;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// 一个用户账户的完整信息 (不包含密码哈希)。
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserAccount {
+    /// 用户的唯一标识 (UUID 字符串)。
+    pub user_id: String,
+    /// 登录用户名，全局唯一。
+    pub username: String,
+    /// 用户被分配的业务角色。
+    pub roles: Vec<UserRole>,
+    /// 用户允许访问的项目 (任务组) 列表。包含 `"*"` 时表示不限制。
+    pub allowed_projects: Vec<String>,
+    /// 账户是否处于启用状态。停用的账户无法登录。
+    pub is_active: bool,
+    /// 账户创建时间。
+    pub created_at: DateTime<Utc>,
+}
+
+/// 用户账户数据仓库，封装对 `users` 表的所有访问。
+#[derive(Debug)]
+pub struct UserRepository {
+    /// 受互斥锁保护的 SQLite 连接。
+    conn: Mutex<Connection>,
+}
+
+impl UserRepository {
+    /// 打开 (或创建) 指定路径的 SQLite 数据库文件，并确保 `users` 表存在。
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .map_err(|e| AppError::DatabaseError(format!("打开数据库文件 {:?} 失败: {}", path, e)))?;
+        info!("[用户仓库] 已打开数据库文件 {:?}", path);
+        Self::with_connection(conn)
+    }
+
+    /// 创建一个基于内存数据库的仓库，主要用于测试。
+    pub fn open_in_memory() -> Result<Self, AppError> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| AppError::DatabaseError(format!("创建内存数据库失败: {}", e)))?;
+        Self::with_connection(conn)
+    }
+
+    /// 使用已有连接初始化仓库并执行建表语句。
+    fn with_connection(conn: Connection) -> Result<Self, AppError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS users (
+                user_id          TEXT PRIMARY KEY,
+                username         TEXT NOT NULL UNIQUE,
+                password_hash    TEXT NOT NULL,
+                roles            TEXT NOT NULL,
+                allowed_projects TEXT NOT NULL,
+                is_active        INTEGER NOT NULL DEFAULT 1,
+                created_at       TEXT NOT NULL
+            );",
+        )
+        .map_err(|e| AppError::DatabaseError(format!("初始化 users 表失败: {}", e)))?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// 创建一个新用户。密码会被 Argon2id 哈希后存储。
+    ///
+    /// # 错误
+    /// - 用户名为空、密码为空或未分配任何角色时返回 `AppError::AuthenticationError`。
+    /// - 用户名已存在或数据库操作失败时返回 `AppError::DatabaseError`。
+    pub fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        roles: &[UserRole],
+        allowed_projects: &[String],
+    ) -> Result<UserAccount, AppError> {
+        if username.trim().is_empty() || password.is_empty() {
+            return Err(AppError::AuthenticationError("用户名和密码不能为空".to_string()));
+        }
+        if roles.is_empty() {
+            return Err(AppError::AuthenticationError(format!("用户 '{}' 至少需要分配一个角色", username)));
+        }
+
+        let password_hash = hash_password(password)?;
+        let account = UserAccount {
+            user_id: Uuid::new_v4().to_string(),
+            username: username.to_string(),
+            roles: roles.to_vec(),
+            allowed_projects: allowed_projects.to_vec(),
+            is_active: true,
+            created_at: Utc::now(),
+        };
+
+        let conn = self.lock_conn()?;
+        conn.execute(
+            "INSERT INTO users (user_id, username, password_hash, roles, allowed_projects, is_active, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6)",
+            params![
+                account.user_id,
+                account.username,
+                password_hash,
+                to_json(&account.roles)?,
+                to_json(&account.allowed_projects)?,
+                account.created_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| AppError::DatabaseError(format!("创建用户 '{}' 失败: {}", username, e)))?;
+
+        info!("[用户仓库] 已创建用户 '{}' (ID: {}), 角色: {:?}", account.username, account.user_id, account.roles);
+        Ok(account)
+    }
+
+    /// 按用户名查找用户。
+    pub fn find_by_username(&self, username: &str) -> Result<Option<UserAccount>, AppError> {
+        Ok(self.find_with_hash(username)?.map(|(account, _)| account))
+    }
+
+    /// 校验用户名和密码。
+    ///
+    /// 仅当用户存在、账户处于启用状态且密码匹配时返回 `Ok(Some(account))`；
+    /// 其余情况返回 `Ok(None)`，调用方不应向客户端区分具体的失败原因。
+    pub fn verify_credentials(&self, username: &str, password: &str) -> Result<Option<UserAccount>, AppError> {
+        let Some((account, password_hash)) = self.find_with_hash(username)? else {
+            return Ok(None);
+        };
+        if !account.is_active {
+            warn!("[用户仓库] 已停用的用户 '{}' 尝试登录", username);
+            return Ok(None);
+        }
+        let parsed_hash = PasswordHash::new(&password_hash)
+            .map_err(|e| AppError::DatabaseError(format!("用户 '{}' 的密码哈希格式无效: {}", username, e)))?;
+        if Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok() {
+            Ok(Some(account))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 启用或停用用户账户。
+    pub fn set_user_active(&self, username: &str, is_active: bool) -> Result<bool, AppError> {
+        let conn = self.lock_conn()?;
+        let changed = conn
+            .execute(
+                "UPDATE users SET is_active = ?1 WHERE username = ?2",
+                params![is_active as i64, username],
+            )
+            .map_err(|e| AppError::DatabaseError(format!("更新用户 '{}' 状态失败: {}", username, e)))?;
+        Ok(changed > 0)
+    }
+
+    /// 返回用户总数。
+    pub fn user_count(&self) -> Result<u64, AppError> {
+        let conn = self.lock_conn()?;
+        conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get::<_, i64>(0))
+            .map(|count| count as u64)
+            .map_err(|e| AppError::DatabaseError(format!("统计用户数量失败: {}", e)))
+    }
+
+    /// 查找用户并同时返回其密码哈希。
+    fn find_with_hash(&self, username: &str) -> Result<Option<(UserAccount, String)>, AppError> {
+        let conn = self.lock_conn()?;
+        let row = conn
+            .query_row(
+                "SELECT user_id, username, password_hash, roles, allowed_projects, is_active, created_at
+                 FROM users WHERE username = ?1",
+                params![username],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, i64>(5)?,
+                        row.get::<_, String>(6)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(|e| AppError::DatabaseError(format!("查询用户 '{}' 失败: {}", username, e)))?;
+
+        let Some((user_id, username, password_hash, roles, allowed_projects, is_active, created_at)) = row else {
+            return Ok(None);
+        };
+        let account = UserAccount {
+            user_id,
+            username,
+            roles: from_json(&roles)?,
+            allowed_projects: from_json(&allowed_projects)?,
+            is_active: is_active != 0,
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|e| AppError::DatabaseError(format!("解析用户创建时间失败: {}", e)))?,
+        };
+        Ok(Some((account, password_hash)))
+    }
+
+    /// 获取数据库连接的互斥锁。
+    fn lock_conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>, AppError> {
+        self.conn
+            .lock()
+            .map_err(|_| AppError::DatabaseError("数据库连接锁已中毒".to_string()))
+    }
+}
+
+/// 使用 Argon2id 和随机盐对密码进行哈希，返回 PHC 格式字符串。
+fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::AuthenticationError(format!("密码哈希失败: {}", e)))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, AppError> {
+    serde_json::to_string(value).map_err(|e| AppError::DatabaseError(format!("序列化字段失败: {}", e)))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, AppError> {
+    serde_json::from_str(text).map_err(|e| AppError::DatabaseError(format!("反序列化字段失败: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_verify_user() {
+        let repo = UserRepository::open_in_memory().expect("创建内存仓库失败");
+        let created = repo
+            .create_user("alice", "s3cret", &[UserRole::Operator], &["group-1".to_string()])
+            .expect("创建用户失败");
+        assert_eq!(repo.user_count().unwrap(), 1);
+
+        let verified = repo.verify_credentials("alice", "s3cret").unwrap();
+        assert_eq!(verified, Some(created.clone()));
+        assert_eq!(repo.verify_credentials("alice", "wrong").unwrap(), None);
+        assert_eq!(repo.verify_credentials("nobody", "s3cret").unwrap(), None);
+
+        // 重复的用户名应被拒绝
+        assert!(repo.create_user("alice", "other", &[UserRole::Admin], &[]).is_err());
+
+        // 停用后无法登录
+        assert!(repo.set_user_active("alice", false).unwrap());
+        assert_eq!(repo.verify_credentials("alice", "s3cret").unwrap(), None);
+    }
+
+    #[test]
+    fn test_password_is_not_stored_in_plaintext() {
+        let repo = UserRepository::open_in_memory().unwrap();
+        repo.create_user("bob", "plain-password", &[UserRole::FieldEngineer], &[]).unwrap();
+        let (_, hash) = repo.find_with_hash("bob").unwrap().unwrap();
+        assert!(!hash.contains("plain-password"));
+        assert!(hash.starts_with("$argon2"));
+    }
+}
@@ -5,6 +5,7 @@
 //! 
 //! 主要模块包括：
 //! - `api`: 定义和处理外部 HTTP API 请求。
+//! - `auth`: 用户认证与授权 (访问令牌签发与校验、WebSocket 握手认证)。
 //! - `config`: 管理应用的配置信息加载与访问。
 //! - `db`: (规划中) 数据库交互逻辑。
 //! - `error`: 定义应用特定的错误类型。
@@ -13,6 +14,7 @@
 //! - `ws_server`: 实现 WebSocket 服务端，处理客户端连接、消息路由和实时通信。
 
 pub mod api;
+pub mod auth;
 pub mod config;
 pub mod db;
 pub mod error;
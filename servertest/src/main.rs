@@ -1,4 +1,4 @@
-use log::{error, info, LevelFilter};
+use log::{error, info, warn, LevelFilter};
 use servertest::ws_server::service::WsService;
 use servertest::ws_server::connection_manager::ConnectionManager;
 use servertest::ws_server::task_state_manager::TaskStateManager;
@@ -6,6 +6,8 @@ use servertest::ws_server::heartbeat_monitor::HeartbeatMonitor;
 use std::sync::Arc;
 use std::time::Duration;
 use servertest::config::WebSocketConfig;
+use servertest::api::{self, auth_handler::{auth_router, AuthApiState}};
+use servertest::auth::{self, TokenHandshakeAuthenticator};
 
 #[tokio::main]
 async fn main() {
@@ -22,6 +24,8 @@ async fn main() {
         port: 8088,
         heartbeat_check_interval_seconds: 15,
         client_timeout_seconds: 60,
+        resume_grace_seconds: 60,
+        ..WebSocketConfig::default()
     };
     
     // 初始化应用配置（仅用于其他可能的配置）
@@ -33,24 +37,62 @@ async fn main() {
     let task_state_manager = Arc::new(TaskStateManager::new());
     info!("[主程序] 任务状态管理器 (TaskStateManager) 已创建。");
 
-    // 创建连接管理器
-    let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+    // 创建连接管理器，配置两阶段心跳策略与会话恢复宽限期，并按角色覆盖
+    let mut connection_manager = ConnectionManager::new(task_state_manager.clone())
+        .with_resume_grace(Duration::from_secs(ws_config.resume_grace_seconds))
+        .with_heartbeat_policy(ws_config.heartbeat_policy());
+    for (role, policy) in &ws_config.role_heartbeat_policies {
+        connection_manager = connection_manager.with_role_heartbeat_policy(*role, policy.heartbeat_policy());
+        if let Some(resume_grace_seconds) = policy.resume_grace_seconds {
+            connection_manager = connection_manager.with_role_resume_grace(*role, Duration::from_secs(resume_grace_seconds));
+        }
+    }
+    let connection_manager = Arc::new(connection_manager);
     info!("[主程序] WebSocket 连接管理器 (ConnectionManager) 已创建，并已注入任务状态管理器。");
 
-    // 为 WebSocket 服务创建一个新的 WsService 实例，使用硬编码配置
+    // 初始化用户仓库与令牌服务
+    let (user_repository, token_service) = match auth::init_auth(&app_config.auth, &app_config.database) {
+        Ok(pair) => pair,
+        Err(e) => {
+            error!("[主程序] 致命错误：初始化用户认证模块失败: {}", e);
+            return;
+        }
+    };
+    info!(
+        "[主程序] 用户认证模块已初始化。数据库: {}, 接受匿名连接: {}",
+        app_config.database.path, app_config.auth.allow_anonymous
+    );
+    if app_config.auth.allow_anonymous {
+        warn!("[主程序] 已开启 auth.allow_anonymous：未携带访问令牌的连接将被接受，但只能以 Observer (只读观察者) 角色注册。");
+    }
+
+    // 启动 HTTP API 服务 (登录接口)
+    let api_addr = format!("{}:{}", app_config.http_api.host, app_config.http_api.port);
+    let api_router = auth_router(AuthApiState {
+        user_repository: user_repository.clone(),
+        token_service: token_service.clone(),
+    });
+    tokio::spawn(async move {
+        if let Err(e) = api::start_api_server(api_addr, api_router).await {
+            error!("[主程序] HTTP API 服务异常终止: {:#}", e);
+        }
+    });
+
+    // 为 WebSocket 服务创建一个新的 WsService 实例，使用硬编码配置，并在握手阶段校验访问令牌
     let ws_service_instance = WsService::new(
-        ws_config, 
+        ws_config.clone(),
         connection_manager.clone(),
         task_state_manager.clone(),
-    );
+    )
+    .with_authenticator(Arc::new(TokenHandshakeAuthenticator::new(
+        token_service,
+        app_config.auth.allow_anonymous,
+    )));
     
-    // 创建心跳监视器，使用硬编码的心跳配置
-    let heartbeat_check_interval = Duration::from_secs(15);
-    let client_timeout = Duration::from_secs(60);
+    // 创建心跳监视器，按连接管理器中配置的各角色心跳策略检查客户端
     let heartbeat_monitor = HeartbeatMonitor::new(
         connection_manager.clone(),
-        client_timeout,
-        heartbeat_check_interval,
+        Duration::from_secs(ws_config.heartbeat_check_interval_seconds),
     );
 
     // 启动心跳监视器
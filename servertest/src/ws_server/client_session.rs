@@ -5,7 +5,9 @@ use std::net::SocketAddr;
 use chrono::{DateTime, Utc};
 use common_models::enums::ClientRole;
 use rust_websocket_utils::message::WsMessage;
-use std::sync::atomic::AtomicBool;
+use rust_websocket_utils::server::auth::AuthenticatedPrincipal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 /// 代表一个已连接到服务器的 WebSocket 客户端的会话状态及相关句柄。
 ///
@@ -49,6 +51,15 @@ pub struct ClientSession {
     /// 使用 `Arc<RwLock<...>>` 包装，以实现线程安全的更新。
     pub last_seen: Arc<RwLock<DateTime<Utc>>>,
 
+    /// 服务端最后一次收到该客户端任何消息的单调时钟时刻。
+    /// 与 `last_seen` 同时更新 (见 `record_activity`)；`HeartbeatMonitor` 据此计算不活动时长，
+    /// 不受服务器系统时间被调整 (NTP 校时、手动修改) 的影响。`last_seen` 仅用于展示。
+    pub last_activity: Arc<RwLock<Instant>>,
+
+    /// 链路是否处于"可疑"状态：不活动时长已超过该角色心跳策略的可疑阈值，但尚未达到断线判定时间。
+    /// 由 `HeartbeatMonitor` 经 `ConnectionManager::set_link_suspect` 置位，收到该客户端的下一条消息时清除。
+    pub link_suspect: Arc<AtomicBool>,
+
     /// 客户端当前所属的调试任务组的ID (字符串类型)。
     /// 如果客户端尚未加入任何组，或者已从组中离开，则此值为 `None`。
     /// 此状态在客户端通过 "Register" 消息成功加入一个组后，由 `ConnectionManager` 设置。
@@ -64,6 +75,21 @@ pub struct ClientSession {
     /// 
     /// 此标志通常由管理连接生命周期的模块设置，而被连接的读写循环任务会定期检查它。
     pub connection_should_close: Arc<AtomicBool>,
+
+    /// WebSocket 握手阶段由认证器确认的已认证主体 (用户、允许的角色与项目)。
+    /// 在会话创建时由 `ConnectionManager::add_client` 写入，之后不再变化。
+    /// 若服务未配置认证器 (使用默认的匿名认证器)，则为 `None`。
+    pub principal: Option<AuthenticatedPrincipal>,
+
+    /// 客户端注册时提供的显示名称 (`RegisterPayload::client_display_name`)，用于组成员名册。
+    /// 加入组前为 `None`。
+    pub display_name: Arc<RwLock<Option<String>>>,
+
+    /// 客户端注册时提供的软件版本 (`RegisterPayload::client_software_version`)，用于组成员名册。
+    pub software_version: Arc<RwLock<Option<String>>>,
+
+    /// 客户端在 Ping 中报告的最近一次 Ping/Pong 往返时延 (毫秒)。尚未报告时为 `None`。
+    pub round_trip_ms: Arc<RwLock<Option<u64>>>,
 }
 
 impl ClientSession {
@@ -79,6 +105,7 @@ impl ClientSession {
     ///   用于将出站 WebSocket 消息发送给此客户端的专用发送任务。
     /// * `connection_should_close`: `Arc<AtomicBool>` - 一个共享的原子布尔标志，
     ///   允许其他部分（如连接管理器或心跳监视器）请求关闭此客户端的连接。
+    /// * `principal`: `Option<AuthenticatedPrincipal>` - 握手阶段认证得到的主体 (如有)。
     ///
     /// # 返回
     /// 返回一个初始化完成的 `ClientSession` 实例。
@@ -86,8 +113,22 @@ impl ClientSession {
         addr: SocketAddr,
         sender: mpsc::Sender<WsMessage>,
         connection_should_close: Arc<AtomicBool>,
+        principal: Option<AuthenticatedPrincipal>,
+    ) -> Self {
+        Self::with_client_id(Uuid::new_v4(), addr, sender, connection_should_close, principal)
+    }
+
+    /// 使用指定的 `client_id` 创建一个 `ClientSession` 实例。
+    ///
+    /// 用于会话恢复：断线客户端在宽限期内的占位会话，以及凭恢复令牌重连后沿用原客户端ID的新会话，
+    /// 都需要保留原有的 `client_id`，而不是像 `new` 那样生成新的ID。其余字段的初始化与 `new` 相同。
+    pub fn with_client_id(
+        client_id: Uuid,
+        addr: SocketAddr,
+        sender: mpsc::Sender<WsMessage>,
+        connection_should_close: Arc<AtomicBool>,
+        principal: Option<AuthenticatedPrincipal>,
     ) -> Self {
-        let client_id = Uuid::new_v4();
         let now = Utc::now();
         Self {
             client_id,
@@ -96,8 +137,30 @@ impl ClientSession {
             addr,
             creation_time: now,
             last_seen: Arc::new(RwLock::new(now)),
+            last_activity: Arc::new(RwLock::new(Instant::now())),
+            link_suspect: Arc::new(AtomicBool::new(false)),
             group_id: Arc::new(RwLock::new(None)),
             connection_should_close,
+            principal,
+            display_name: Arc::new(RwLock::new(None)),
+            software_version: Arc::new(RwLock::new(None)),
+            round_trip_ms: Arc::new(RwLock::new(None)),
         }
     }
+
+    /// 记录收到了该客户端的一条消息：同时更新 `last_seen` (UTC 时间) 与 `last_activity` (单调时钟)。
+    pub async fn record_activity(&self) {
+        *self.last_seen.write().await = Utc::now();
+        *self.last_activity.write().await = Instant::now();
+    }
+
+    /// 自最后一次收到该客户端消息以来经过的时长 (单调时钟)。
+    pub async fn idle_for(&self) -> Duration {
+        self.last_activity.read().await.elapsed()
+    }
+
+    /// 链路当前是否处于"可疑"状态。
+    pub fn is_link_suspect(&self) -> bool {
+        self.link_suspect.load(Ordering::SeqCst)
+    }
 } 
\ No newline at end of file
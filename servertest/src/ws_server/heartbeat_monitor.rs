@@ -3,43 +3,71 @@
 //! 心跳监视器模块。
 //!
 //! 该模块的核心职责是定期检查所有已连接 WebSocket 客户端的活跃状态。
-//! 如果某个客户端在预设定的不活动时间（超时阈值）内没有任何通信（包括 Ping 消息），
-//! 心跳监视器会认为该客户端已失联或不再响应，并会启动断开该客户端连接的流程。
+//! 超时判断分两个阶段，阈值可按客户端角色分别配置 (`HeartbeatPolicy`)：
+//! 客户端静默超过可疑阈值时，链路被标记为"可疑"并通知其组内伙伴；
+//! 静默超过超时阈值 (包括 Ping 消息在内没有任何通信) 时，心跳监视器认为该客户端已失联，并启动断开该客户端连接的流程。
+//! 不活动时长基于单调时钟计算，不受服务器系统时间调整的影响。
 //! 这样做有助于及时释放服务器资源，防止因大量僵尸连接耗尽系统能力，从而维护整体服务的稳定性和健康。
 
 use crate::ws_server::connection_manager::ConnectionManager; // 引入连接管理器，用于获取客户端列表和移除客户端
-// use crate::ws_server::client_session::ClientSession; // 移除未使用的导入
 // use crate::config::WebSocketConfig; // 配置信息现在通过构造函数传入，而不是直接从模块读取
-use chrono::Utc; // 引入 chrono 库的 Utc 时间，用于获取当前时间戳，进行时间比较
+use common_models::ws_payloads::HeartbeatBoundsPayload; // 在 RegisterResponse 中公布给客户端的心跳策略
 use log::{info, warn, debug}; // 引入日志宏 (info, warn, debug)，用于在程序不同阶段输出诊断和状态信息
 use std::sync::Arc; // 引入原子引用计数 Arc，用于在不同异步任务间安全地共享对 ConnectionManager 等状态的所有权
 use std::time::Duration; // 引入标准库的时间间隔类型 Duration，用于表示超时和检查周期等
 use tokio::time::sleep; // 引入 Tokio 的异步睡眠功能，用于在主循环中实现定时执行检查任务
 
+/// 公布给客户端的 Ping 间隔下限 (秒)。客户端在网络质量较差时以此频率发送心跳。
+pub const MIN_CLIENT_HEARTBEAT_INTERVAL_SECONDS: u64 = 5;
+
+/// 单个角色的两阶段心跳超时策略。
+///
+/// 客户端的不活动时长 (单调时钟) 超过 `suspect_after` 时进入"可疑"状态，服务端通知其组内伙伴链路已降级；
+/// 超过 `timeout` 时判定为断线并移除。断线后其组内槽位是否保留、保留多久由 `ConnectionManager` 的会话恢复宽限期决定。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeartbeatPolicy {
+    /// 进入"可疑"状态的不活动时长。不小于 `timeout` 时不会进入可疑状态。
+    pub suspect_after: Duration,
+    /// 判定为断线的不活动时长。
+    pub timeout: Duration,
+}
+
+impl HeartbeatPolicy {
+    /// 仅指定断线判定时长，可疑阈值取其一半。
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self { suspect_after: timeout / 2, timeout }
+    }
+
+    /// 公布给客户端的心跳策略：Ping 间隔上限取可疑阈值的一半，使偶尔丢失一次 Ping 不会让链路被标记为可疑。
+    pub fn advertised_bounds(&self) -> HeartbeatBoundsPayload {
+        let suspect_after = self.suspect_after.min(self.timeout);
+        let max_interval_seconds = (suspect_after.as_secs() / 2).max(1);
+        HeartbeatBoundsPayload {
+            min_interval_seconds: MIN_CLIENT_HEARTBEAT_INTERVAL_SECONDS.min(max_interval_seconds),
+            max_interval_seconds,
+            suspect_after_seconds: suspect_after.as_secs(),
+            timeout_seconds: self.timeout.as_secs(),
+        }
+    }
+}
+
 /// `HeartbeatMonitor` 结构体定义。
 /// 
 /// 它封装了心跳检测机制所需的所有状态和依赖项，包括：
-/// - 对 `ConnectionManager` 的共享引用，以便访问客户端列表并请求移除超时的客户端。
-/// - 客户端允许的最大不活动时间 (`client_timeout_duration`)。
+/// - 对 `ConnectionManager` 的共享引用，以便访问客户端列表与各角色的心跳策略、标记可疑链路并请求移除超时的客户端。
 /// - 执行超时检查任务的周期性时间间隔 (`check_interval`)。
 // 注意：如果 `ConnectionManager` 未实现 `Debug` trait，则直接在此派生 `#[derive(Debug)]` 可能会导致编译错误。
 // 如果需要调试打印 `HeartbeatMonitor` 实例，应确保其所有成员都支持 `Debug`。
 pub struct HeartbeatMonitor {
     /// 对 `ConnectionManager` 的共享引用 (`Arc<ConnectionManager>`)。
     /// `HeartbeatMonitor` 通过此引用来获取当前所有活动客户端的会话列表，
-    /// 并在检测到客户端超时后，调用 `ConnectionManager` 的方法来移除相应的客户端会话。
+    /// 并在检测到客户端链路可疑或超时后，调用 `ConnectionManager` 的相应方法。
     connection_manager: Arc<ConnectionManager>,
     
-    /// 客户端被判断为超时的最大不活动持续时间 (`std::time::Duration`)。
-    /// 如果一个客户端在超过此设定时长的时间内没有向服务器发送任何消息
-    /// (其 `ClientSession` 中的 `last_seen` 时间戳未得到更新)，
-    /// 则该客户端将被视为超时。
-    client_timeout_duration: Duration,
-    
     /// 心跳监视器执行其周期性超时检查任务的时间间隔 (`std::time::Duration`)。
     /// `HeartbeatMonitor` 的主运行循环会每隔这么长时间唤醒一次，
     /// 然后调用 `check_for_timed_out_clients` 方法来遍历所有客户端并检查其是否超时。
-    /// 此间隔通常应远小于 `client_timeout_duration`，以确保能及时发现超时。
+    /// 此间隔通常应远小于各策略的可疑阈值，以确保能及时发现链路问题。
     check_interval: Duration,
 }
 
@@ -49,24 +77,17 @@ impl HeartbeatMonitor {
     /// # 参数
     /// * `connection_manager`: `Arc<ConnectionManager>` - 对 `ConnectionManager` 实例的共享原子引用计数指针。
     ///   这是心跳监视器执行其职责所必需的核心依赖。
-    /// * `client_timeout_duration`: `Duration` - 客户端在被判断为超时之前允许的最大不活动时间。
     /// * `check_interval`: `Duration` - 心跳监视器执行其超时检查逻辑的频率。
     ///
     /// # 返回
     /// 返回一个根据传入参数初始化完成的 `HeartbeatMonitor` 实例。
     pub fn new(
         connection_manager: Arc<ConnectionManager>,
-        client_timeout_duration: Duration,
         check_interval: Duration,
     ) -> Self {
-        info!(
-            "[心跳监视器] 正在创建并初始化新的 HeartbeatMonitor 实例。客户端超时阈值: {:?}，检查周期: {:?}",
-            client_timeout_duration,
-            check_interval
-        );
+        info!("[心跳监视器] 正在创建并初始化新的 HeartbeatMonitor 实例。检查周期: {:?}", check_interval);
         Self {
             connection_manager, // 存储对连接管理器的共享引用
-            client_timeout_duration, // 设置客户端超时时长
             check_interval,      // 设置检查间隔
         }
     }
@@ -93,102 +114,183 @@ impl HeartbeatMonitor {
         }
     }
 
-    /// 异步地检查所有当前活动的客户端，识别并处理那些可能已经超时的连接。
+    /// 异步地检查所有当前活动的客户端，标记链路可疑的连接并处理已超时的连接。
     ///
     /// 此方法是心跳监视器的核心工作单元，其主要步骤包括：
-    /// 1. 调用 `ConnectionManager::get_all_client_sessions()` 来获取当前所有活动客户端会话的一个快照列表。
-    ///    这确保了检查过程基于一个一致的客户端集合，即使在检查期间有新的连接或断开发生。
-    /// 2. 如果没有活动的客户端，则直接返回，避免不必要的工作。
-    /// 3. 获取当前的UTC时间戳，作为比较的基准。
-    /// 4. 遍历快照中的每一个 `ClientSession`：
-    ///    a. 异步读取该会话中记录的 `last_seen` 时间戳（表示服务器最后一次收到该客户端消息的时间）。
-    ///    b. 将配置的 `client_timeout_duration` (类型为 `std::time::Duration`) 转换为 `chrono::Duration`，
-    ///       以便能与 `chrono::DateTime<Utc>` 类型的时间戳进行比较。如果转换失败（理论上不应发生，除非配置值异常），
-    ///       则会记录警告并使用一个安全的默认超时值。
-    ///    c. 计算当前时间与 `last_seen` 时间戳之间的时间差。
-    ///    d. 如果这个时间差大于转换后的 `chrono_timeout`，则判定该客户端已超时。
-    /// 5. 对于每个被判定为超时的客户端：
-    ///    a. 记录一条警告级别的日志，包含客户端的地址、ID、最后活跃时间以及配置的超时时长，以便追踪。
-    ///    b. 异步调用 `self.connection_manager.remove_client(&client_id)` 方法来处理该客户端的移除。
-    ///       `remove_client` 方法会负责将会话从活动列表中移除，通知同组伙伴（如果存在）该客户端已下线，
-    ///       并最终请求关闭与该客户端关联的底层 WebSocket 连接。
-    ///    c. 记录一条信息日志，表明对该超时客户端的移除请求已发出。
-    /// 6. 如果客户端未超时，则仅在调试日志级别记录其仍然活跃的信息。
+    /// 1. 调用 `ConnectionManager::get_all_client_sessions()` 获取当前所有活动客户端会话的快照列表。
+    /// 2. 对每个会话，按其当前角色从 `ConnectionManager` 取得心跳策略，并读取其不活动时长 (`ClientSession::idle_for`，单调时钟，
+    ///    不受服务器系统时间调整的影响)。
+    /// 3. 不活动时长超过策略的 `timeout`：判定为断线，调用 `ConnectionManager::remove_client`。
+    ///    若该客户端持有会话恢复令牌，其组内槽位会在恢复宽限期内保留，等待其重连。
+    /// 4. 不活动时长超过策略的 `suspect_after`：将链路标记为可疑 (`ConnectionManager::set_link_suspect`)，
+    ///    组内伙伴会收到 `link_suspect: true` 的伙伴状态通知。客户端再次发来消息时由 `MessageRouter` 清除该标记。
+    /// 5. 最后清理过期的断线会话、接管请求与配对码。
     async fn check_for_timed_out_clients(&self) {
         // 从 ConnectionManager 获取当前所有活动客户端会话的快照（一个 Vec<Arc<ClientSession>>）。
         // 这允许我们在一个固定的客户端列表上进行操作，即使在检查过程中 ConnectionManager 的状态发生变化。
         let clients_snapshot = self.connection_manager.get_all_client_sessions();
 
-        if clients_snapshot.is_empty() {
-            debug!("[心跳监视器] 当前系统中没有活动的客户端连接，无需执行超时检查。");
-            return; // 如果没有活动客户端，则提前返回，避免不必要的后续操作。
-        }
-
-        debug!(
-            "[心跳监视器] 开始对 {} 个当前活动的客户端连接进行超时状态检查。配置的超时阈值: {:?}",
-            clients_snapshot.len(),
-            self.client_timeout_duration
-        );
-
-        let now = Utc::now(); // 获取当前的协调世界时 (UTC) 时间戳，作为所有比较的基准。
+        debug!("[心跳监视器] 开始对 {} 个当前活动的客户端连接进行超时状态检查。", clients_snapshot.len());
 
         for client_session in &clients_snapshot { // 遍历从快照中获取的每个客户端会话的共享引用 - 使用 & 避免移动
             let client_id = client_session.client_id; // 获取当前正在检查的客户端的唯一ID
-            
-            // 异步读取此客户端会话中记录的最后活跃时间 (`last_seen`)。
-            // `last_seen` 是一个被 `RwLock` 保护的 `DateTime<Utc>`，因此读取需要 `.await`。
-            let last_seen_dt = *client_session.last_seen.read().await; 
-            
-            // 将配置中 `std::time::Duration` 类型的 `client_timeout_duration` 转换为 `chrono::Duration` 类型，
-            // 以便能与 `chrono::DateTime<Utc>` 类型的时间戳 (如 `now` 和 `last_seen_dt`) 进行安全的算术运算和比较。
-            // `chrono::Duration::from_std` 可能会因输入 `Duration` 为负或过大而返回错误。
-            // 假设我们的 `client_timeout_duration` 配置总是正数且在合理范围内。
-            let chrono_timeout_threshold = match chrono::Duration::from_std(self.client_timeout_duration) {
-                Ok(duration) => duration, // 转换成功，使用配置的超时时长
-                Err(e) => { // 如果转换失败 (例如，配置值异常导致无法转换为有效的 chrono::Duration)
-                    warn!(
-                        "[心跳监视器] 无法将配置的 client_timeout_duration ({:?}) 从 std::time::Duration 转换为 chrono::Duration: {}. \
-                        本次检查将针对客户端 {} (ID: {}) 使用一个备用的60秒超时阈值。请检查配置。",
-                        self.client_timeout_duration, e, client_session.addr, client_id
-                    );
-                    chrono::Duration::seconds(60) // 提供一个安全的、硬编码的备用超时值 (例如60秒)
-                }
-            };
+            let role = *client_session.role.read().await;
+            let policy = self.connection_manager.heartbeat_policy_for(role);
+            let idle = client_session.idle_for().await;
 
-            // 核心超时判断逻辑：
-            // 计算当前时间 `now` 与客户端最后活跃时间 `last_seen_dt` 之间的时间差 (`signed_duration_since`)。
-            // 如果这个差值大于我们确定的超时阈值 `chrono_timeout_threshold`，则认为客户端已超时。
-            if now.signed_duration_since(last_seen_dt) > chrono_timeout_threshold {
+            if idle > policy.timeout {
                 // 客户端被判定为超时
                 warn!(
-                    "[心跳监视器] 检测到客户端 {} (ID: {}) 已超时！最后活跃时间: {:?} (UTC), 当前时间: {:?} (UTC), 配置超时阈值: {:?}. 将启动移除流程...",
-                    client_session.addr, // 客户端的网络地址，有助于定位问题
-                    client_id,           // 客户端的唯一标识符
-                    last_seen_dt,        // 记录的客户端最后一次发送消息的时间
-                    now,                 // 当前检查时间
-                    self.client_timeout_duration // 用户在配置中设定的原始超时时长 (std::time::Duration)
+                    "[心跳监视器] 检测到客户端 {} (ID: {}, 角色: {:?}) 已超时！已 {:?} 未收到其消息 (超时阈值: {:?})。将启动移除流程...",
+                    client_session.addr, client_id, role, idle, policy.timeout
                 );
                 
-                // 异步调用 ConnectionManager 的 `remove_client` 方法来处理此超时客户端的移除。
-                // `remove_client` 方法负责将会话从活动列表中删除，通知组内伙伴（如果存在），
-                // 并最终请求关闭与该客户端关联的底层 WebSocket 连接。
-                // 这是一个异步操作，所以我们使用 `.await` 来等待它完成（或在其内部派生任务）。
+                // `remove_client` 负责将会话从活动列表中删除、请求关闭底层连接，
+                // 并在客户端持有恢复令牌时让其进入会话恢复宽限期 (保留组内槽位)，否则通知伙伴其已下线。
                 self.connection_manager.remove_client(&client_id).await;
                 
-                // 在 `remove_client` 调用完成后记录日志，表明对该超时客户端的处理请求已发出。
-                // 注意：`remove_client` 本身可能只是启动了清理过程，物理连接的关闭可能稍有延迟。
                 info!(
                     "[心跳监视器] 已为超时客户端 {} (ID: {}) 调用 ConnectionManager 的移除处理方法。",
                     client_session.addr, client_id
                 );
+            } else if idle > policy.suspect_after {
+                if !client_session.is_link_suspect() {
+                    info!(
+                        "[心跳监视器] 客户端 {} (ID: {}, 角色: {:?}) 已 {:?} 未发送任何消息 (可疑阈值: {:?})，标记为链路可疑。",
+                        client_session.addr, client_id, role, idle, policy.suspect_after
+                    );
+                }
+                self.connection_manager.set_link_suspect(client_session, true).await;
             } else {
                 // 如果客户端未超时，仅在调试日志级别记录其仍然保持活跃的信息，以避免在正常情况下产生过多日志。
                 debug!(
-                    "[心跳监视器] 客户端 {} (ID: {}) 状态正常，未超时。最后活跃于: {:?} (UTC), 当前时间: {:?} (UTC).",
-                    client_session.addr, client_id, last_seen_dt, now
+                    "[心跳监视器] 客户端 {} (ID: {}) 状态正常。不活动时长: {:?}",
+                    client_session.addr, client_id, idle
                 );
             }
         }
         debug!("[心跳监视器] 对所有 {} 个活动客户端的超时状态检查已全部完成。", clients_snapshot.len());
+
+        // 顺带清理已超过会话恢复宽限期仍未重连的断线会话，释放其保留的组内槽位。
+        let expired_count = self.connection_manager.expire_detached_sessions().await;
+        if expired_count > 0 {
+            info!("[心跳监视器] 已清理 {} 个超过恢复宽限期的断线会话。", expired_count);
+        }
+
+        // 完成超过答复期限 (或持有者已断开) 的角色槽位接管请求。
+        let takeover_count = self.connection_manager.expire_slot_takeovers().await;
+        if takeover_count > 0 {
+            info!("[心跳监视器] 已自动完成 {} 个未获持有者答复的槽位接管请求。", takeover_count);
+        }
+
+        // 丢弃已过期而未被使用的配对码。
+        let join_code_count = self.connection_manager.expire_join_codes();
+        if join_code_count > 0 {
+            debug!("[心跳监视器] 已丢弃 {} 个过期未使用的配对码。", join_code_count);
+        }
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_server::client_session::ClientSession;
+    use crate::ws_server::connection_manager::test_principal;
+    use common_models::enums::ClientRole;
+    use common_models::ws_payloads::{PartnerStatusPayload, RegisterPayload, RegisterResponsePayload, PARTNER_STATUS_UPDATE_MESSAGE_TYPE};
+    use rust_websocket_utils::message::WsMessage;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Instant;
+    use tokio::sync::mpsc;
+
+    async fn join(manager: &ConnectionManager, role: ClientRole) -> (Arc<ClientSession>, mpsc::Receiver<WsMessage>, RegisterResponsePayload) {
+        let (tx, rx) = mpsc::channel(32);
+        let session = manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+        let payload = RegisterPayload {
+            group_id: "group-1".to_string(),
+            role,
+            task_id: "task-1".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        let response = manager.join_group(session.clone(), payload).await.unwrap();
+        (session, rx, response)
+    }
+
+    fn drain_partner_updates(rx: &mut mpsc::Receiver<WsMessage>) -> Vec<PartnerStatusPayload> {
+        let mut updates = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            if message.message_type == PARTNER_STATUS_UPDATE_MESSAGE_TYPE {
+                updates.push(message.deserialize_payload().unwrap());
+            }
+        }
+        updates
+    }
+
+    async fn set_idle(session: &ClientSession, idle: Duration) {
+        *session.last_activity.write().await = Instant::now() - idle;
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_mobile_link_goes_suspect_before_eviction_with_role_policy() {
+        let manager = Arc::new(
+            ConnectionManager::default()
+                .with_resume_grace(Duration::ZERO)
+                .with_role_resume_grace(ClientRole::OnSiteMobile, Duration::from_secs(300))
+                .with_heartbeat_policy(HeartbeatPolicy::with_timeout(Duration::from_secs(60)))
+                .with_role_heartbeat_policy(
+                    ClientRole::OnSiteMobile,
+                    HeartbeatPolicy { suspect_after: Duration::from_secs(45), timeout: Duration::from_secs(150) },
+                ),
+        );
+        let monitor = HeartbeatMonitor::new(manager.clone(), Duration::from_secs(15));
+        let (control, mut control_rx, control_response) = join(&manager, ClientRole::ControlCenter).await;
+        let (mobile, _mobile_rx, mobile_response) = join(&manager, ClientRole::OnSiteMobile).await;
+        assert_eq!(control_response.resume_grace_seconds, None);
+        assert_eq!(mobile_response.resume_grace_seconds, Some(300), "现场端应使用按角色覆盖的宽限期");
+
+        // 注册响应中公布各自角色的心跳策略
+        let control_bounds = control_response.heartbeat.expect("注册成功应公布心跳策略");
+        assert_eq!((control_bounds.max_interval_seconds, control_bounds.timeout_seconds), (15, 60));
+        let mobile_bounds = mobile_response.heartbeat.expect("注册成功应公布心跳策略");
+        assert_eq!(
+            mobile_bounds,
+            HeartbeatBoundsPayload { min_interval_seconds: 5, max_interval_seconds: 22, suspect_after_seconds: 45, timeout_seconds: 150 }
+        );
+        drain_partner_updates(&mut control_rx);
+
+        // 超过默认超时但未超过现场端策略的超时：仅标记为可疑并通知伙伴
+        set_idle(&mobile, Duration::from_secs(90)).await;
+        monitor.check_for_timed_out_clients().await;
+        assert!(mobile.is_link_suspect());
+        let updates = drain_partner_updates(&mut control_rx);
+        assert_eq!(updates.len(), 1);
+        assert!(updates[0].is_online && updates[0].link_suspect);
+
+        // 再次检查不重复通知
+        monitor.check_for_timed_out_clients().await;
+        assert!(drain_partner_updates(&mut control_rx).is_empty());
+
+        // 收到消息后链路恢复
+        mobile.record_activity().await;
+        manager.set_link_suspect(&mobile, false).await;
+        let updates = drain_partner_updates(&mut control_rx);
+        assert!(updates.len() == 1 && !updates[0].link_suspect);
+
+        // 超过现场端策略的超时：断线，槽位进入宽限期
+        set_idle(&mobile, Duration::from_secs(151)).await;
+        monitor.check_for_timed_out_clients().await;
+        assert!(manager.get_all_client_sessions().iter().all(|s| s.client_id != mobile.client_id));
+        let updates = drain_partner_updates(&mut control_rx);
+        assert!(updates.iter().any(|u| u.partner_client_id == mobile.client_id && !u.is_online));
+
+        // 控制中心使用默认策略，同样的静默时长直接断线
+        set_idle(&control, Duration::from_secs(61)).await;
+        monitor.check_for_timed_out_clients().await;
+        assert!(manager.get_all_client_sessions().is_empty());
+    }
+}
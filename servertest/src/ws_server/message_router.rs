@@ -24,12 +24,12 @@
 use std::sync::Arc; // 原子引用计数 Arc，用于在异步任务间安全地共享对象所有权，如 ClientSession, ConnectionManager 等。
 use anyhow::Result; // anyhow 提供的 Result 类型，用于简化错误处理链，允许返回多种错误类型。
 use log::{debug, warn, error, info}; // 标准日志宏，用于在不同级别记录程序运行信息。
-use chrono::Utc; // chrono 库的 Utc 时间模块，用于获取当前协调世界时 (UTC) 时间戳。
 
 use common_models::ws_payloads::{ // 从共享模型库引入 WebSocket 消息负载 (payload) 定义
     self, // 引入整个 ws_payloads 模块本身，使得可以通过 ws_payloads::CONSTANT_NAME 访问常量
     EchoPayload, // 用于 "Echo" (回声) 请求和响应的负载结构体定义。
     ErrorResponsePayload, // 用于向客户端发送标准格式错误信息的负载结构体定义。
+    AckPayload, // 服务端对业务消息的统一确认负载。
     PingPayload, // 用于客户端 "Ping" (心跳) 请求的负载结构体定义 (P1.4.1 新增)。
     PongPayload, // 用于服务端对 "Ping" (心跳) 请求的 "Pong" 响应的负载结构体定义 (P1.4.1 新增)。
     RegisterPayload, // 用于客户端发起注册或加入调试任务组请求的负载结构体定义 (P3.1.2 新增)。
@@ -45,6 +45,8 @@ use rust_websocket_utils::message::WsMessage; // 从公司内部的 WebSocket 
 use super::client_session::ClientSession; // 引入同一模块层级下的 `client_session` 子模块中定义的 `ClientSession` 结构体。
 use super::connection_manager::ConnectionManager; // 引入同一模块层级下的 `connection_manager` 子模块中定义的 `ConnectionManager` 结构体 (P3.1.2 新增)。
 use super::task_state_manager::TaskStateManager; // P3.3.2: 引入 TaskStateManager
+use crate::error::CloudError; // 带有机器可读错误码的协议错误类型
+use common_models::permissions::BusinessAction; // 业务操作 (需要回复 Ack 的消息类型)
 
 /// 异步处理从特定客户端接收到的单个 WebSocket 消息 (`WsMessage`)。
 ///
@@ -68,6 +70,9 @@ use super::task_state_manager::TaskStateManager; // P3.3.2: 引入 TaskStateMana
 ///     (例如，`PongPayload` 作为对 "Ping" 的响应，`RegisterResponsePayload` 作为对 "Register" 的响应，
 ///     或一个通用的 `ErrorResponsePayload` 来指示错误)，然后通过 `client_session.sender` 
 ///     将此响应消息异步地发送回原始请求的客户端。
+///     对于业务消息 (预检项更新、测试步骤、调试备注等)，无论成功与否都会回复且仅回复一条 `Ack`
+///     (`AckPayload`: 已生效 / 无变化 / 被拒绝，附带处理后的任务状态版本号及错误码)。
+///     所有响应消息的 `in_reply_to` 都指向被响应消息的 `message_id`。
 /// 5.  **错误处理与日志记录**: 
 ///     a.  如果遇到无法识别的 `message_type`，或者在尝试反序列化已知类型的 `payload` 时失败
 ///         (例如，JSON格式错误或字段不匹配)，会记录详细的警告或错误日志。
@@ -99,14 +104,20 @@ pub async fn handle_message(
     connection_manager: Arc<ConnectionManager>, // P3.1.2: 添加 ConnectionManager 作为参数
     task_state_manager: Arc<TaskStateManager>, // P3.3.2: 添加 TaskStateManager 作为参数
 ) -> Result<(), anyhow::Error> {
-    // 步骤 1: 更新客户端会话的 `last_seen` 时间戳，记录其最近的活跃时间。
-    // 这是心跳机制 (`HeartbeatMonitor`) 判断客户端是否超时的关键依据。
-    let now = Utc::now(); // 获取当前的UTC时间。
-    *client_session.last_seen.write().await = now; // 异步获取 `last_seen` 字段的写锁并更新其值。
+    // 若该连接已凭恢复令牌恢复了之前的会话，则后续消息均以恢复后的会话 (原客户端ID) 身份处理。
+    let client_session = connection_manager.resolve_session(client_session);
+
+    // 步骤 1: 记录客户端的最近活跃时间 (`last_seen` 与单调时钟 `last_activity`)。
+    // 这是心跳机制 (`HeartbeatMonitor`) 判断客户端链路是否可疑或已超时的关键依据。
+    client_session.record_activity().await;
     debug!(
-        "[消息路由] 客户端 {} (地址: {})：已将其 last_seen (最后活跃) 时间戳更新为: {} (UTC)",
-        client_session.client_id, client_session.addr, now.to_rfc3339() // 使用 RFC3339 格式化时间，更易读且标准化
+        "[消息路由] 客户端 {} (地址: {})：已更新其最后活跃时间。",
+        client_session.client_id, client_session.addr
     );
+    // 链路此前被标记为可疑时，收到任何消息都说明链路已恢复，通知伙伴。
+    if client_session.is_link_suspect() {
+        connection_manager.set_link_suspect(&client_session, false).await;
+    }
 
     // 记录接收到消息的基本信息，便于追踪和调试。
     info!(
@@ -119,6 +130,27 @@ pub async fn handle_message(
         client_session.client_id, client_session.addr, message.payload
     );
 
+    // 重复投递的业务消息 (重连后的重试或离线重放，`message_id` 不变) 不再处理，原样回复第一次的确认。
+    // 处理期间持有预留：同一消息同时从旧连接与新连接到达时，后到的一条等待前者的结果；
+    // 预留在本函数返回时释放，未得到最终确认 (见 `is_final_ack`) 的消息仍可重试。
+    let mut _processing_reservation = None;
+    if BusinessAction::from_message_type(&message.message_type).is_some() {
+        let group_id = client_session.group_id.read().await.clone();
+        if let Some(group_id) = group_id {
+            match task_state_manager.begin_processing(&group_id, &message.message_id).await {
+                Ok(reservation) => _processing_reservation = Some(reservation),
+                Err(ack) => {
+                    info!(
+                        "[消息路由] 客户端 {} (地址: {})：业务消息 '{}' (ID: {}) 已处理过，回复原确认而不再次应用。",
+                        client_session.client_id, client_session.addr, message.message_type, message.message_id
+                    );
+                    send_ack(&client_session, &message.message_id, ack).await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
     // 步骤 2: 根据 `WsMessage` 中的 `message_type` 字符串，将消息路由到相应的处理分支。
     match message.message_type.as_str() { // 使用 as_str() 将 String 转换为 &str 以便匹配常量
         // 分支 2.1: 处理 "Echo" (回声) 类型的消息。
@@ -138,7 +170,7 @@ pub async fn handle_message(
 
                     // 准备 Echo (回声) 响应消息。对于 Echo，响应负载与请求负载相同。
                     // 使用 `ws_payloads::ECHO_MESSAGE_TYPE` 作为响应的消息类型。
-                    match WsMessage::new(ws_payloads::ECHO_MESSAGE_TYPE.to_string(), &echo_payload) {
+                    match WsMessage::new_reply(ws_payloads::ECHO_MESSAGE_TYPE.to_string(), &echo_payload, &message.message_id) {
                         Ok(response_msg) => { // 如果成功创建了 `WsMessage` 实例...
                             // 通过此客户端会话的 `sender` (一个MPSC通道的发送端) 将响应消息异步发送回该客户端。
                             if let Err(e) = client_session.sender.send(response_msg).await {
@@ -170,9 +202,9 @@ pub async fn handle_message(
                     );
                     // 向客户端发送一个标准的错误响应，告知其请求的负载无效。
                     send_error_response(
-                        &client_session, // 目标客户端会话
+                        &client_session, &message.message_id, // 目标客户端会话
                         Some(ws_payloads::ECHO_MESSAGE_TYPE.to_string()), // 指明原始请求的消息类型是 "Echo" (回声)
-                        format!("Echo (回声) 请求的负载 (payload) 格式无效: {}. 请确保提供符合 EchoPayload 结构的JSON对象。", e), // 具体的错误信息
+                        &CloudError::payload_invalid(ws_payloads::ECHO_MESSAGE_TYPE, &e), // 带错误码和字段详情的错误
                     )
                     .await; // 等待错误响应发送完成（或失败）
                 }
@@ -186,20 +218,21 @@ pub async fn handle_message(
                 "[消息路由] 客户端 {} (地址: {})：收到 Ping (心跳) 请求。",
                 client_session.client_id, client_session.addr
             );
-            // `PingPayload` 当前定义为一个空结构体，但仍尝试进行反序列化，
-            // 以保持处理流程的一致性，并为未来可能向 Ping 消息添加负载内容留出扩展空间。
+            // `PingPayload` 可携带客户端测得的上一次往返时延，用于组成员名册中的链路质量展示。
             match serde_json::from_str::<PingPayload>(&message.payload) {
-                Ok(_ping_payload) => { // 如果反序列化成功 (对于空结构体，通常意味着负载是 `{}` 或空的JSON兼容结构)
-                                      // `_ping_payload` 当前未使用，所以用下划线 `_` 前缀忽略它以避免编译器警告。
+                Ok(ping_payload) => {
                     debug!(
-                        "[消息路由] 客户端 {}：PingPayload 解析成功 (由于其为空结构体，通常表示负载符合预期)。",
-                        client_session.client_id
+                        "[消息路由] 客户端 {}：PingPayload 解析成功，报告的往返时延: {:?} ms。",
+                        client_session.client_id, ping_payload.round_trip_ms
                     );
+                    if let Some(round_trip_ms) = ping_payload.round_trip_ms {
+                        connection_manager.record_round_trip(&client_session, round_trip_ms).await;
+                    }
                     
                     // 准备 Pong (心跳响应) 消息。
                     let pong_payload = PongPayload {}; // `PongPayload` 当前也定义为一个空结构体。
                     // 使用 `ws_payloads::PONG_MESSAGE_TYPE` 作为响应的消息类型。
-                    match WsMessage::new(ws_payloads::PONG_MESSAGE_TYPE.to_string(), &pong_payload) {
+                    match WsMessage::new_reply(ws_payloads::PONG_MESSAGE_TYPE.to_string(), &pong_payload, &message.message_id) {
                         Ok(pong_msg) => { // 如果成功创建了 `WsMessage` 实例...
                             if let Err(e) = client_session.sender.send(pong_msg).await {
                                 error!(
@@ -290,9 +323,10 @@ pub async fn handle_message(
 
                     // 根据 `join_group` 的处理结果 (封装在 `final_response_payload` 中)，
                     // 创建并发送 `RegisterResponse` (注册响应) 消息给原始请求的客户端。
-                    match WsMessage::new(
+                    match WsMessage::new_reply(
                         ws_payloads::REGISTER_RESPONSE_MESSAGE_TYPE.to_string(), // 消息类型为 "RegisterResponse" (注册响应)
                         &final_response_payload, // 使用 `join_group` 返回的最终响应负载
+                        &message.message_id, // 关联到客户端发出的 Register 请求
                     ) {
                         Ok(response_ws_msg) => { // 如果成功创建了 `WsMessage` 实例...
                             if let Err(e) = client_session.sender.send(response_ws_msg).await {
@@ -305,6 +339,14 @@ pub async fn handle_message(
                                     "[消息路由] 客户端 {} (地址: {})：RegisterResponse (注册响应) 已成功发送。响应中 success 标志为: {}.",
                                     client_session.client_id, client_session.addr, final_response_payload.success
                                 );
+                                // 会话恢复成功时，紧随注册响应补发断线期间错过的消息。
+                                if final_response_payload.resumed {
+                                    connection_manager.deliver_missed_messages(&client_session).await;
+                                }
+                                // 注册成功后立即推送一次完整的任务状态，使客户端 (包括中途加入的客户端) 无需等待伙伴的下一次操作即可获得权威状态。
+                                if let (true, Some(group_id)) = (final_response_payload.success, final_response_payload.effective_group_id.as_deref()) {
+                                    connection_manager.send_task_state_snapshot(&client_session, group_id, None).await;
+                                }
                             }
                         }
                         Err(e) => { // 如果 `WsMessage::new` 创建 RegisterResponse 消息失败...
@@ -328,11 +370,17 @@ pub async fn handle_message(
                         assigned_client_id: client_session.client_id, // 即使失败，也告知客户端其当前的会话ID，便于调试
                         effective_group_id: None, // 未能加入任何组
                         effective_role: None,     // 未能分配任何角色
+                        error_code: Some(common_models::enums::ErrorCode::PayloadInvalid),
+                        resume_token: None,
+                        resume_grace_seconds: None,
+                        resumed: false,
+                        heartbeat: None,
                     };
                     // 尝试创建并发送这个包含解析错误的 RegisterResponse (注册响应) 消息。
-                    match WsMessage::new(
+                    match WsMessage::new_reply(
                         ws_payloads::REGISTER_RESPONSE_MESSAGE_TYPE.to_string(), // 消息类型仍为 "RegisterResponse" (注册响应)
                         &error_response, // 使用我们构造的错误响应负载
+                        &message.message_id, // 关联到客户端发出的 Register 请求
                     ) {
                         Ok(response_ws_msg) => {
                             if let Err(send_err) = client_session.sender.send(response_ws_msg).await {
@@ -354,33 +402,169 @@ pub async fn handle_message(
         }
 
         // 分支 2.4 (P3.3.2 新增): 处理 "UpdatePreCheckItem" 类型的消息
-        ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE => {
+        // 分支: 处理 "TaskStateSyncRequest" (请求重新同步完整任务状态) 类型的消息。
+        // 以一条关联到该请求的 `TaskStateUpdate` 回复客户端所在组当前的权威状态。
+        ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE => {
             info!(
-                "[消息路由] 客户端 {} (地址: {}): 正在处理 UpdatePreCheckItem 请求。",
-                client_session.client_id, client_session.addr
+                "[消息路由] 客户端 {} (地址: {})：正在处理 {} 请求。",
+                client_session.client_id, client_session.addr, ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE
             );
+            let group_id = client_session.group_id.read().await.clone();
+            match group_id {
+                Some(group_id) => {
+                    if !connection_manager.send_task_state_snapshot(&client_session, &group_id, Some(&message.message_id)).await {
+                        send_rejected_ack(
+                            &client_session,
+                            &message.message_id,
+                            ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE,
+                            &CloudError::Internal(format!("组 '{}' 的任务状态不可用", group_id)),
+                        )
+                        .await;
+                    }
+                }
+                None => {
+                    send_unregistered_error(&client_session, &message.message_id, ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE).await;
+                }
+            }
+        }
 
-            let group_id_clone;
-            let client_role_clone;
-            {
-                let group_id_guard = client_session.group_id.read().await;
-                let role_guard = client_session.role.read().await;
-                if group_id_guard.is_none() || *role_guard == common_models::enums::ClientRole::Unknown {
-                    warn!(
-                        "[消息路由] 客户端 {} (地址: {}): 尝试在未注册或未分配角色的情况下发送 UpdatePreCheckItem。忽略。",
-                        client_session.client_id, client_session.addr
+        // 接管组内已被占用的独占角色槽位：结果 (RegisterResponse) 由 ConnectionManager 直接发送给请求方，
+        // 可能立即给出，也可能在当前持有者答复或超时后给出。
+        ws_payloads::SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE => {
+            info!(
+                "[消息路由] 客户端 {} (地址: {})：收到 {} 请求。",
+                client_session.client_id, client_session.addr, ws_payloads::SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE
+            );
+            match serde_json::from_str::<ws_payloads::SlotTakeoverRequestPayload>(&message.payload) {
+                Ok(request) => {
+                    let outcome = connection_manager
+                        .request_slot_takeover(client_session.clone(), &message.message_id, request)
+                        .await;
+                    debug!("[消息路由] 客户端 {} 的接管请求处理结果: {:?}", client_session.client_id, outcome);
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::SLOT_TAKEOVER_REQUEST_MESSAGE_TYPE, &e, &message.payload).await;
+                }
+            }
+        }
+
+        // 当前槽位持有者对接管请求的答复。处理成功时不单独回复 (同意时持有者会收到 SlotReleased)。
+        ws_payloads::SLOT_TAKEOVER_DECISION_MESSAGE_TYPE => {
+            match serde_json::from_str::<ws_payloads::SlotTakeoverDecisionPayload>(&message.payload) {
+                Ok(decision) => {
+                    if let Err(e) = connection_manager.answer_slot_takeover(&client_session, decision).await {
+                        warn!("[消息路由] 客户端 {} 对接管请求的答复被拒绝: {}", client_session.client_id, e);
+                        send_rejected_ack(&client_session, &message.message_id, ws_payloads::SLOT_TAKEOVER_DECISION_MESSAGE_TYPE, &e).await;
+                    }
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::SLOT_TAKEOVER_DECISION_MESSAGE_TYPE, &e, &message.payload).await;
+                }
+            }
+        }
+
+        // 槽位持有者为计划内换班申请一次性交接令牌，以关联到请求的 HandoverToken 回复。
+        ws_payloads::PREPARE_HANDOVER_MESSAGE_TYPE => {
+            match connection_manager.prepare_handover(&client_session).await {
+                Ok(handover) => {
+                    match WsMessage::new_reply(ws_payloads::HANDOVER_TOKEN_MESSAGE_TYPE.to_string(), &handover, &message.message_id) {
+                        Ok(reply) => {
+                            if let Err(e) = client_session.sender.send(reply).await {
+                                error!("[消息路由] 向客户端 {} 发送交接令牌失败: {}", client_session.client_id, e);
+                            }
+                        }
+                        Err(e) => error!("[消息路由] 创建交接令牌消息失败: {}", e),
+                    }
+                }
+                Err(e) => {
+                    warn!("[消息路由] 客户端 {} 申请交接令牌被拒绝: {}", client_session.client_id, e);
+                    send_rejected_ack(&client_session, &message.message_id, ws_payloads::PREPARE_HANDOVER_MESSAGE_TYPE, &e).await;
+                }
+            }
+        }
+
+        // 不断开连接地离开当前任务组，以关联到请求的 GroupLeft 回复；未加入任何组时回复被拒绝的 Ack。
+        ws_payloads::LEAVE_GROUP_MESSAGE_TYPE => {
+            match connection_manager.leave_group(&client_session).await {
+                Ok(left) => {
+                    match WsMessage::new_reply(ws_payloads::GROUP_LEFT_MESSAGE_TYPE.to_string(), &left, &message.message_id) {
+                        Ok(reply) => {
+                            if let Err(e) = client_session.sender.send(reply).await {
+                                error!("[消息路由] 向客户端 {} 发送离开组确认失败: {}", client_session.client_id, e);
+                            }
+                        }
+                        Err(e) => error!("[消息路由] 创建离开组确认消息失败: {}", e),
+                    }
+                }
+                Err(e) => {
+                    warn!("[消息路由] 客户端 {} 离开组的请求被拒绝: {}", client_session.client_id, e);
+                    send_rejected_ack(&client_session, &message.message_id, ws_payloads::LEAVE_GROUP_MESSAGE_TYPE, &e).await;
+                }
+            }
+        }
+
+        // 离开当前任务组并注册到另一个任务组，以关联到请求的 RegisterResponse 回复加入新组的结果；
+        // 成功时与 Register 一样随后推送一次完整任务状态。
+        ws_payloads::SWITCH_TASK_MESSAGE_TYPE => {
+            match serde_json::from_str::<ws_payloads::SwitchTaskPayload>(&message.payload) {
+                Ok(switch) => {
+                    let response = connection_manager
+                        .switch_task(client_session.clone(), switch.register)
+                        .await
+                        .unwrap_or_else(|failure| failure);
+                    info!(
+                        "[消息路由] 客户端 {} 切换任务的结果: success={}, 组={:?}",
+                        client_session.client_id, response.success, response.effective_group_id
                     );
-                    send_error_response(
-                        &client_session,
-                        Some(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string()),
-                        "客户端未注册到有效调试组或角色未知，无法处理此业务请求。".to_string(),
-                    )
-                    .await;
-                    return Ok(()); // 提前返回，不继续处理
+                    match WsMessage::new_reply(ws_payloads::REGISTER_RESPONSE_MESSAGE_TYPE.to_string(), &response, &message.message_id) {
+                        Ok(reply) => {
+                            if let Err(e) = client_session.sender.send(reply).await {
+                                error!("[消息路由] 向客户端 {} 发送切换任务结果失败: {}", client_session.client_id, e);
+                            } else if let (true, Some(group_id)) = (response.success, response.effective_group_id.as_deref()) {
+                                connection_manager.send_task_state_snapshot(&client_session, group_id, None).await;
+                            }
+                        }
+                        Err(e) => error!("[消息路由] 创建切换任务结果消息失败: {}. Payload: {:?}", e, response),
+                    }
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::SWITCH_TASK_MESSAGE_TYPE, &e, &message.payload).await;
+                }
+            }
+        }
+
+        // 控制中心请求为任务开组并取得配对码，以关联到请求的 GroupOpened 回复；
+        // 加入组成功时与 Register 一样随后推送一次完整任务状态。
+        ws_payloads::OPEN_GROUP_MESSAGE_TYPE => {
+            match serde_json::from_str::<ws_payloads::OpenGroupPayload>(&message.payload) {
+                Ok(request) => {
+                    let opened = connection_manager.open_group(client_session.clone(), request).await;
+                    match WsMessage::new_reply(ws_payloads::GROUP_OPENED_MESSAGE_TYPE.to_string(), &opened, &message.message_id) {
+                        Ok(reply) => {
+                            if let Err(e) = client_session.sender.send(reply).await {
+                                error!("[消息路由] 向客户端 {} 发送开组结果失败: {}", client_session.client_id, e);
+                            } else if let (true, Some(group_id)) = (opened.registration.success, opened.registration.effective_group_id.as_deref()) {
+                                connection_manager.send_task_state_snapshot(&client_session, group_id, None).await;
+                            }
+                        }
+                        Err(e) => error!("[消息路由] 创建开组结果消息失败: {}", e),
+                    }
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::OPEN_GROUP_MESSAGE_TYPE, &e, &message.payload).await;
                 }
-                group_id_clone = group_id_guard.as_ref().unwrap().clone();
-                client_role_clone = role_guard.clone();
             }
+        }
+
+        ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE => {
+            info!(
+                "[消息路由] 客户端 {} (地址: {}): 正在处理 UpdatePreCheckItem 请求。",
+                client_session.client_id, client_session.addr
+            );
+            let (group_id_clone, client_role_clone) = 
+                if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE).await; return Ok(()); };
 
             match serde_json::from_str::<common_models::task_models::UpdatePreCheckItemPayload>(&message.payload) {
                 Ok(parsed_payload) => {
@@ -388,80 +572,22 @@ pub async fn handle_message(
                         "[消息路由] 客户端 {}: UpdatePreCheckItemPayload 解析成功: {:?}",
                         client_session.client_id, parsed_payload
                     );
-
                     let action_payload = common_models::ws_payloads::BusinessActionPayload::UpdatePreCheckItem(parsed_payload);
-
-                    match task_state_manager.update_state_and_get_updated(&group_id_clone, client_role_clone, action_payload).await {
-                        Some(updated_task_state) => {
-                            info!(
-                                "[消息路由] group_id '{}' 的 TaskDebugState 已更新。版本: {}. 准备通知伙伴客户端。", 
-                                group_id_clone, updated_task_state.version
-                            );
-                            // 状态已更新，需要通知同组的其他伙伴客户端
-                            if let Some(group_guard) = connection_manager.get_group(&group_id_clone).await {
-                                let mut recipients_found = false;
-
-                                // 确定伙伴客户端并发送消息
-                                let partner_sessions_to_notify: Vec<Arc<ClientSession>> = match client_role_clone {
-                                    common_models::enums::ClientRole::ControlCenter => {
-                                        group_guard.on_site_mobile_client.iter().cloned().collect()
-                                    }
-                                    common_models::enums::ClientRole::OnSiteMobile => {
-                                        group_guard.control_center_client.iter().cloned().collect()
-                                    }
-                                    _ => Vec::new(), // 其他角色目前不处理伙伴通知
-                                };
-
-                                for partner_session in partner_sessions_to_notify {
-                                    // 确保不会给自己发送通知 (虽然按角色区分已避免大部分情况，但作为双重检查)
-                                    if partner_session.client_id != client_session.client_id {
-                                        recipients_found = true;
-                                        match WsMessage::new(ws_payloads::TASK_STATE_UPDATE_MESSAGE_TYPE.to_string(), &updated_task_state) {
-                                            Ok(state_update_msg) => {
-                                                if let Err(e) = partner_session.sender.send(state_update_msg).await {
-                                                    error!(
-                                                        "[消息路由] 向伙伴客户端 {} (组 '{}') 发送 TaskStateUpdate 失败: {}",
-                                                        partner_session.client_id, group_id_clone, e
-                                                    );
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!(
-                                                    "[消息路由] 为伙伴客户端 {} (组 '{}') 创建 TaskStateUpdate 消息失败: {}",
-                                                    partner_session.client_id, group_id_clone, e
-                                                );
-                                            }
-                                        }
-                                    }
-                                }
-                                if recipients_found {
-                                    info!("[消息路由] 已向组 '{}' 内的伙伴客户端发送 TaskStateUpdate。", group_id_clone);
-                                } else {
-                                    info!("[消息路由] 组 '{}' 内没有需要通知的伙伴客户端。", group_id_clone);
-                                }
-                            } else {
-                                warn!("[消息路由] 未找到 group_id '{}' 对应的组信息，无法通知伙伴。", group_id_clone);
-                            }
-                        }
-                        None => {
-                            info!(
-                                "[消息路由] group_id '{}' 的 TaskDebugState 未发生变化，无需通知伙伴。",
-                                group_id_clone
-                            );
-                        }
-                    }
+                    process_business_action_and_notify_partners(
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
+                        action_payload,
+                        &task_state_manager,
+                        &connection_manager,
+                        ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE
+                    ).await;
                 }
                 Err(e) => {
-                    warn!(
-                        "[消息路由] 客户端 {} (地址: {}): 解析 UpdatePreCheckItem 请求的负载 (UpdatePreCheckItemPayload) 失败: {}. 原始JSON负载: '{}'",
-                        client_session.client_id, client_session.addr, e, message.payload
-                    );
-                    send_error_response(
-                        &client_session,
-                        Some(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string()),
-                        format!("UpdatePreCheckItem 请求的负载格式无效: {}.", e),
-                    )
-                    .await;
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE, &e, &message.payload).await;
                 }
             }
         }
@@ -474,8 +600,8 @@ pub async fn handle_message(
             );
             let (group_id_clone, client_role_clone) = 
                 if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
-                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, ws_payloads::START_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
-                } else { send_unregistered_error(&client_session, ws_payloads::START_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::START_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::START_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
 
             match serde_json::from_str::<common_models::task_models::StartSingleTestStepPayload>(&message.payload) {
                 Ok(parsed_payload) => {
@@ -483,19 +609,27 @@ pub async fn handle_message(
                         "[消息路由] 客户端 {}: StartSingleTestStepPayload 解析成功: {:?}",
                         client_session.client_id, parsed_payload
                     );
+                    let device_id = parsed_payload.device_id.clone();
                     let action_payload = common_models::ws_payloads::BusinessActionPayload::StartSingleTestStep(parsed_payload);
-                    process_business_action_and_notify_partners(
-                        &client_session,
-                        &group_id_clone,
-                        client_role_clone,
+                    let accepted = process_business_action_and_notify_partners(
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
                         action_payload,
                         &task_state_manager,
                         &connection_manager,
                         ws_payloads::START_SINGLE_TEST_STEP_TYPE
                     ).await;
+                    // 状态增量已广播给全组；执行指令本身只转发给负责该设备的现场工程师
+                    if accepted {
+                        forward_to_site_engineers(&message, &group_id_clone, &device_id, &connection_manager).await;
+                    }
                 }
                 Err(e) => {
-                    send_payload_parse_error(&client_session, ws_payloads::START_SINGLE_TEST_STEP_TYPE, &e.to_string(), &message.payload).await;
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::START_SINGLE_TEST_STEP_TYPE, &e, &message.payload).await;
                 }
             }
         }
@@ -508,8 +642,8 @@ pub async fn handle_message(
             );
             let (group_id_clone, client_role_clone) = 
                 if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
-                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
-                } else { send_unregistered_error(&client_session, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
 
             match serde_json::from_str::<common_models::task_models::FeedbackSingleTestStepPayload>(&message.payload) {
                 Ok(parsed_payload) => {
@@ -519,9 +653,12 @@ pub async fn handle_message(
                     );
                     let action_payload = common_models::ws_payloads::BusinessActionPayload::FeedbackSingleTestStep(parsed_payload);
                     process_business_action_and_notify_partners(
-                        &client_session,
-                        &group_id_clone,
-                        client_role_clone,
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
                         action_payload,
                         &task_state_manager,
                         &connection_manager,
@@ -529,7 +666,7 @@ pub async fn handle_message(
                     ).await;
                 }
                 Err(e) => {
-                    send_payload_parse_error(&client_session, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE, &e.to_string(), &message.payload).await;
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::FEEDBACK_SINGLE_TEST_STEP_TYPE, &e, &message.payload).await;
                 }
             }
         }
@@ -542,8 +679,8 @@ pub async fn handle_message(
             );
             let (group_id_clone, client_role_clone) = 
                 if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
-                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
-                } else { send_unregistered_error(&client_session, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
 
             match serde_json::from_str::<common_models::task_models::ConfirmSingleTestStepPayload>(&message.payload) {
                 Ok(parsed_payload) => {
@@ -553,9 +690,12 @@ pub async fn handle_message(
                     );
                     let action_payload = common_models::ws_payloads::BusinessActionPayload::ConfirmSingleTestStep(parsed_payload);
                     process_business_action_and_notify_partners(
-                        &client_session,
-                        &group_id_clone,
-                        client_role_clone,
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
                         action_payload,
                         &task_state_manager,
                         &connection_manager,
@@ -563,7 +703,44 @@ pub async fn handle_message(
                     ).await;
                 }
                 Err(e) => {
-                    send_payload_parse_error(&client_session, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE, &e.to_string(), &message.payload).await;
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::CONFIRM_SINGLE_TEST_STEP_TYPE, &e, &message.payload).await;
+                }
+            }
+        }
+
+        // 处理 "SignOffSingleTestStep" 类型的消息 (QA 主管对已确认步骤的最终签核)
+        ws_payloads::SIGN_OFF_SINGLE_TEST_STEP_TYPE => {
+            info!(
+                "[消息路由] 客户端 {} (地址: {}): 正在处理 SignOffSingleTestStep 请求。",
+                client_session.client_id, client_session.addr
+            );
+            let (group_id_clone, client_role_clone) = 
+                if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::SIGN_OFF_SINGLE_TEST_STEP_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::SIGN_OFF_SINGLE_TEST_STEP_TYPE).await; return Ok(()); };
+
+            match serde_json::from_str::<common_models::task_models::SignOffSingleTestStepPayload>(&message.payload) {
+                Ok(parsed_payload) => {
+                    debug!(
+                        "[消息路由] 客户端 {}: SignOffSingleTestStepPayload 解析成功: {:?}",
+                        client_session.client_id, parsed_payload
+                    );
+                    let action_payload = common_models::ws_payloads::BusinessActionPayload::SignOffSingleTestStep(parsed_payload);
+                    process_business_action_and_notify_partners(
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
+                        action_payload,
+                        &task_state_manager,
+                        &connection_manager,
+                        ws_payloads::SIGN_OFF_SINGLE_TEST_STEP_TYPE
+                    ).await;
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::SIGN_OFF_SINGLE_TEST_STEP_TYPE, &e, &message.payload).await;
                 }
             }
         }
@@ -611,11 +788,10 @@ pub async fn handle_message(
                                 match serde_json::to_value(specific_payload) {
                                     Ok(val) => val,
                                     Err(e_ser) => {
-                                        send_payload_parse_error(
-                                            &client_session, 
-                                            ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, 
-                                            &format!("Failed to re-serialize parsed payload: {}", e_ser),
-                                            &message.payload
+                                        send_rejected_ack(
+                                            &client_session, &message.message_id,
+                                            ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE,
+                                            &CloudError::Internal(format!("重新序列化已解析的负载失败: {}", e_ser)),
                                         ).await;
                                         return Ok(());
                                     }
@@ -623,9 +799,9 @@ pub async fn handle_message(
                             }
                             Err(e_de) => {
                                 send_payload_parse_error(
-                                    &client_session, 
+                                    &client_session, &message.message_id, 
                                     ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, 
-                                    &e_de.to_string(),
+                                    &e_de,
                                     &message.payload
                                 ).await;
                                 return Ok(()); // 结束处理此消息
@@ -644,28 +820,32 @@ pub async fn handle_message(
                 )
                 .await
                 {
-                    Ok(_) => {
+                    Ok(updated_task_state) => {
                         info!(
                             "[消息路由] 客户端 {}：业务消息 '{}' 已成功由 TaskStateManager 处理。",
                             client_session.client_id, ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE
                         );
-                        // 通常，业务消息的直接响应由 TaskStateUpdate (如果状态改变) 隐式处理，
-                        // 或者如果需要特定的命令回执，则可以在此处发送。
-                        // 例如，如果 send_debug_note_from_site_cmd 需要一个 GenericResponse,
-                        // 那么这个成功信息应该由 TaskStateManager 或其调用的命令本身返回给客户端,
-                        // 而不是由 MessageRouter 在这里构造。但 Tauri 命令直接返回了 GenericResponse。
-                        // 这里的日志是针对云端内部处理的。
+                        // 向发送方回复统一的业务确认 (Ack)，告知其操作是否生效以及生效后的版本号
+                        let ack = ack_for_outcome(
+                            &task_state_manager,
+                            group_id,
+                            ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE,
+                            updated_task_state.as_ref(),
+                        ).await;
+                        send_business_ack(&client_session, &task_state_manager, group_id, &message.message_id, ack).await;
                     }
                     Err(e) => {
                         error!(
                             "[消息路由] 客户端 {}：TaskStateManager 在处理业务消息 '{}' 时发生错误: {}.",
                             client_session.client_id, ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, e
                         );
-                        // 可以向客户端发送一个更通用的业务处理错误消息
-                        send_error_response(
+                        // 以"被拒绝"的业务确认告知发送方 (版本冲突时附带当前完整状态)
+                        send_business_ack(
                             &client_session,
-                            Some(ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string()),
-                            format!("处理业务消息时服务端发生内部错误: {}", e),
+                            &task_state_manager,
+                            group_id,
+                            &message.message_id,
+                            e.to_rejected_ack(ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE),
                         )
                         .await;
                     }
@@ -676,7 +856,44 @@ pub async fn handle_message(
                     "[消息路由] 客户端 {} (地址: {})：尝试在未注册到任何组的情况下发送业务消息 '{}'。",
                     client_session.client_id, client_session.addr, ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE
                 );
-                send_unregistered_error(&client_session, ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE).await;
+                send_unregistered_error(&client_session, &message.message_id, ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE).await;
+            }
+        }
+
+        // 处理 "UpdateCustomSharedData" (更新自定义共享数据) 类型的消息：整体替换、RFC 7386 合并补丁或按 JSON Pointer 局部写入
+        ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE => {
+            info!(
+                "[消息路由] 客户端 {} (地址: {}): 正在处理 UpdateCustomSharedData 请求。",
+                client_session.client_id, client_session.addr
+            );
+            let (group_id_clone, client_role_clone) = 
+                if let (Some(gid), role) = (client_session.group_id.read().await.as_ref(), *client_session.role.read().await) {
+                    if role != common_models::enums::ClientRole::Unknown { (gid.clone(), role) } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE).await; return Ok(()); }
+                } else { send_unregistered_error(&client_session, &message.message_id, ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE).await; return Ok(()); };
+
+            match serde_json::from_str::<ws_payloads::UpdateCustomSharedDataPayload>(&message.payload) {
+                Ok(parsed_payload) => {
+                    debug!(
+                        "[消息路由] 客户端 {}: UpdateCustomSharedDataPayload 解析成功: {:?}",
+                        client_session.client_id, parsed_payload
+                    );
+                    let action_payload = common_models::ws_payloads::BusinessActionPayload::UpdateCustomSharedData(parsed_payload);
+                    process_business_action_and_notify_partners(
+                        BusinessRequestContext {
+                            client_session: &client_session,
+                            in_reply_to: &message.message_id,
+                            group_id: &group_id_clone,
+                            updater_role: client_role_clone,
+                        },
+                        action_payload,
+                        &task_state_manager,
+                        &connection_manager,
+                        ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE
+                    ).await;
+                }
+                Err(e) => {
+                    send_payload_parse_error(&client_session, &message.message_id, ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE, &e, &message.payload).await;
+                }
             }
         }
 
@@ -698,7 +915,14 @@ pub async fn handle_message(
 
             if !is_registered_with_role {
                 // 如果客户端未注册或角色未知，则发送通用未注册错误。
-                send_unregistered_error(&client_session, actual_message_type_str).await;
+                // 未知消息类型不属于业务消息，因此回复 ErrorResponse 而不是 Ack。
+                send_error_response(
+                    &client_session,
+                    &message.message_id,
+                    Some(actual_message_type_str.to_string()),
+                    &CloudError::NotRegistered,
+                )
+                .await;
             } else {
                 // 客户端已注册，但消息类型不是 TaskStateManager 设计用来处理的已知业务操作。
                 warn!(
@@ -707,9 +931,9 @@ pub async fn handle_message(
                 );
                 
                 send_error_response(
-                    &client_session,
+                    &client_session, &message.message_id,
                     Some(actual_message_type_str.to_string()),
-                    format!("不支持的消息类型 '{}'，或该类型不被识别为有效的业务操作。", actual_message_type_str),
+                    &CloudError::UnsupportedMessage(actual_message_type_str.to_string()),
                 )
                 .await;
             }
@@ -725,9 +949,12 @@ pub async fn handle_message(
 /// # 参数
 /// * `client_session`: `&Arc<ClientSession>` - 对目标客户端 `ClientSession` 实例的共享引用。
 ///   错误响应将通过此会话的 `sender` 发送。
+/// * `in_reply_to`: `&str` - 导致此错误的原始请求消息的 `message_id`。它会被写入响应消息的 `in_reply_to` 字段，
+///   使客户端能够准确地把错误与自己发出的某条命令对应起来。
 /// * `original_message_type`: `Option<String>` - 可选的字符串，表示导致错误的原始请求的消息类型。
 ///   如果提供，它将被包含在 `ErrorResponsePayload` 中，以帮助客户端关联错误与其原始请求。
-/// * `error_message_text`: `String` - 描述错误的具体文本信息。这将作为 `ErrorResponsePayload` 中 `error` 字段的值。
+/// * `error`: `&CloudError` - 具体的协议错误。其错误码、本地化消息键和字段级详情会写入 `ErrorResponsePayload`，
+///   其描述文本作为 `error` 字段的值。
 ///
 /// # 注意
 /// 此函数是异步的 (`async`)，因为它内部调用了异步的 `client_session.sender.send(...).await`。
@@ -735,21 +962,19 @@ pub async fn handle_message(
 /// 此时仅记录错误，不会进一步传播错误，以保持消息处理的健壮性。
 async fn send_error_response(
     client_session: &Arc<ClientSession>,    // 目标客户端会话
+    in_reply_to: &str,                      // 导致此错误的请求消息的 message_id
     original_message_type: Option<String>, // 可选的原始消息类型，用于帮助客户端关联错误来源
-    error_message_text: String,            // 描述错误的具体文本信息
+    error: &CloudError,                    // 具体的协议错误
 ) {
-    // 构造标准的 ErrorResponsePayload，包含原始消息类型（如果提供）和错误文本。
-    let error_payload = ErrorResponsePayload {
-        original_message_type, // 正确的字段名
-        error: error_message_text.clone(), // 正确的字段名
-    };
+    // 构造标准的 ErrorResponsePayload，包含原始消息类型（如果提供）、错误码和错误文本。
+    let error_payload: ErrorResponsePayload = error.to_error_response(original_message_type);
     info!(
-        "[消息路由::错误响应] 正在向客户端 {} (地址: {}) 发送错误响应。原始消息类型 (如果提供): {:?}, 错误文本: '{}'",
-        client_session.client_id, client_session.addr, error_payload.original_message_type, error_payload.error
+        "[消息路由::错误响应] 正在向客户端 {} (地址: {}) 发送错误响应。原始消息类型 (如果提供): {:?}, 错误码: {:?}, 错误文本: '{}'",
+        client_session.client_id, client_session.addr, error_payload.original_message_type, error_payload.code, error_payload.error
     );
 
-    // 使用 WsMessage::new 来构造消息，它会处理 message_id 和 timestamp
-    match WsMessage::new(ws_payloads::ERROR_RESPONSE_MESSAGE_TYPE.to_string(), &error_payload) {
+    // 使用 WsMessage::new_reply 来构造消息，它会处理 message_id 和 timestamp，并填写 in_reply_to
+    match WsMessage::new_reply(ws_payloads::ERROR_RESPONSE_MESSAGE_TYPE.to_string(), &error_payload, in_reply_to) {
         Ok(ws_message) => {
             // 尝试通过客户端的 sender 将 WsMessage 发送出去。
             if let Err(e) = client_session.sender.send(ws_message).await {
@@ -766,100 +991,649 @@ async fn send_error_response(
         }
         Err(e) => { // 如果 WsMessage::new 创建失败 (例如内部序列化失败)
             error!(
-                "[消息路由::错误响应] 严重内部错误：为 ErrorResponsePayload 创建 WsMessage 失败: {}. 错误详情: {:?}. 原始错误文本: '{}'. 客户端 {} 未能收到错误响应。",
-                e, error_payload, error_message_text, client_session.client_id
+                "[消息路由::错误响应] 严重内部错误：为 ErrorResponsePayload 创建 WsMessage 失败: {}. 错误详情: {:?}. 客户端 {} 未能收到错误响应。",
+                e, error_payload, client_session.client_id
             );
         }
     }
 }
 
-// 提取的辅助函数，用于处理业务Action并通知伙伴
-async fn process_business_action_and_notify_partners(
-    client_session: &Arc<ClientSession>,
-    group_id: &str,
+/// 一条业务消息的请求上下文：发送方会话、其所在的组与角色，以及回复 Ack 时引用的消息ID。
+struct BusinessRequestContext<'a> {
+    client_session: &'a Arc<ClientSession>,
+    in_reply_to: &'a str, // 业务消息的 message_id，用于回复 Ack
+    group_id: &'a str,
     updater_role: common_models::enums::ClientRole,
+}
+
+// 提取的辅助函数，用于处理业务Action并向组内成员广播状态增量。
+// 返回该操作是否被接受 (未因版本冲突或权限等原因被拒绝)。
+async fn process_business_action_and_notify_partners(
+    request: BusinessRequestContext<'_>,
     action_payload: common_models::ws_payloads::BusinessActionPayload,
     task_state_manager: &Arc<TaskStateManager>,
     connection_manager: &Arc<ConnectionManager>,
     message_type_for_log: &str, // 用于日志记录原始消息类型
-) {
-    match task_state_manager.update_state_and_get_updated(group_id, updater_role, action_payload).await {
+) -> bool {
+    let BusinessRequestContext { client_session, in_reply_to, group_id, updater_role } = request;
+    // 状态发生实际变更时，TaskStateManager 会向组内所有成员 (包括发起者本身) 广播版本化的状态增量，
+    // 使每个客户端的本地缓存都能按版本号连续地跟上云端状态。
+    let updated_task_state = match task_state_manager
+        .update_state_and_get_updated(group_id, updater_role, action_payload, connection_manager)
+        .await
+    {
+        Ok(updated_task_state) => updated_task_state,
+        Err(e) => {
+            // 基于过期版本的写入：状态未被修改，以附带当前完整状态的 VERSION_CONFLICT 确认告知发送方
+            warn!(
+                "[消息路由 - {}] group_id '{}' 拒绝客户端 {} 的业务操作: {}",
+                message_type_for_log, group_id, client_session.client_id, e
+            );
+            send_business_ack(client_session, task_state_manager, group_id, in_reply_to, e.to_rejected_ack(message_type_for_log)).await;
+            return false;
+        }
+    };
+    match &updated_task_state {
         Some(updated_task_state) => {
             info!(
-                "[消息路由 - {}] group_id '{}' 的 TaskDebugState 已更新。版本: {}. 准备通知伙伴客户端。", 
+                "[消息路由 - {}] group_id '{}' 的 TaskDebugState 已更新至版本 {}，状态增量已广播给组内成员。",
                 message_type_for_log, group_id, updated_task_state.version
             );
-            if let Some(group_guard) = connection_manager.get_group(group_id).await {
-                let mut recipients_found = false;
-                let partner_sessions_to_notify: Vec<Arc<ClientSession>> = match updater_role {
-                    common_models::enums::ClientRole::ControlCenter => group_guard.on_site_mobile_client.iter().cloned().collect(),
-                    common_models::enums::ClientRole::OnSiteMobile => group_guard.control_center_client.iter().cloned().collect(),
-                    _ => Vec::new(),
-                };
-
-                for partner_session in partner_sessions_to_notify {
-                    if partner_session.client_id != client_session.client_id {
-                        recipients_found = true;
-                        match WsMessage::new(ws_payloads::TASK_STATE_UPDATE_MESSAGE_TYPE.to_string(), &updated_task_state) {
-                            Ok(state_update_msg) => {
-                                if let Err(e) = partner_session.sender.send(state_update_msg).await {
-                                    error!(
-                                        "[消息路由 - {}] 向伙伴客户端 {} (组 '{}') 发送 TaskStateUpdate 失败: {}",
-                                        message_type_for_log, partner_session.client_id, group_id, e
-                                    );
-                                }
-                            }
-                            Err(e) => {
-                                error!(
-                                    "[消息路由 - {}] 为伙伴客户端 {} (组 '{}') 创建 TaskStateUpdate 消息失败: {}",
-                                    message_type_for_log, partner_session.client_id, group_id, e
-                                );
-                            }
-                        }
-                    }
-                }
-                if recipients_found {
-                    info!("[消息路由 - {}] 已向组 '{}' 内的伙伴客户端发送 TaskStateUpdate。", message_type_for_log, group_id);
-                } else {
-                    info!("[消息路由 - {}] 组 '{}' 内没有需要通知的伙伴客户端。", message_type_for_log, group_id);
-                }
-            } else {
-                warn!("[消息路由 - {}] 未找到 group_id '{}' 对应的组信息，无法通知伙伴。", message_type_for_log, group_id);
-            }
         }
         None => {
             info!(
-                "[消息路由 - {}] group_id '{}' 的 TaskDebugState 未发生变化，无需通知伙伴。",
+                "[消息路由 - {}] group_id '{}' 的 TaskDebugState 未发生变化，无需广播。",
                 message_type_for_log, group_id
             );
         }
     }
+
+    // 无论状态是否变化，都向发送方回复统一的业务确认 (Ack)
+    let ack = ack_for_outcome(task_state_manager, group_id, message_type_for_log, updated_task_state.as_ref()).await;
+    send_business_ack(client_session, task_state_manager, group_id, in_reply_to, ack).await;
+    true
+}
+
+/// 把原始指令消息转发给组内负责 `device_id` 的现场工程师 (规则见 `ConnectionManager::site_engineers_for_device`)。
+///
+/// 断线宽限期内的工程师由其占位会话缓存该消息，重连后补发。
+async fn forward_to_site_engineers(message: &WsMessage, group_id: &str, device_id: &str, connection_manager: &Arc<ConnectionManager>) {
+    let engineers = connection_manager.site_engineers_for_device(group_id, device_id).await;
+    if engineers.is_empty() {
+        warn!(
+            "[消息路由 - {}] 组 '{}' 中没有负责设备 '{}' 的现场工程师，指令未转发。",
+            message.message_type, group_id, device_id
+        );
+        return;
+    }
+    for engineer in engineers {
+        if let Err(e) = engineer.sender.send(message.clone()).await {
+            error!(
+                "[消息路由 - {}] 向现场工程师 {} 转发设备 '{}' 的指令失败: {}",
+                message.message_type, engineer.client_id, device_id, e
+            );
+        } else {
+            debug!(
+                "[消息路由 - {}] 设备 '{}' 的指令已转发给现场工程师 {}。",
+                message.message_type, device_id, engineer.client_id
+            );
+        }
+    }
+}
+
+/// 根据业务操作的处理结果构造对应的业务确认。
+///
+/// * 状态已改变 -> `Applied`，携带新版本号；
+/// * 状态未改变 -> `NoChange`，携带当前版本号；
+/// * 找不到任务状态 (无法确定版本) -> `Rejected`。
+async fn ack_for_outcome(
+    task_state_manager: &Arc<TaskStateManager>,
+    group_id: &str,
+    original_message_type: &str,
+    updated_task_state: Option<&common_models::TaskDebugState>,
+) -> AckPayload {
+    if let Some(state) = updated_task_state {
+        return AckPayload::applied(original_message_type, state.version);
+    }
+    match task_state_manager.current_version(group_id).await {
+        Some(version) => AckPayload::no_change(original_message_type, version),
+        None => CloudError::TaskNotFound(group_id.to_string()).to_rejected_ack(original_message_type),
+    }
 }
 
-// 辅助函数，用于发送未注册错误
-async fn send_unregistered_error(client_session: &Arc<ClientSession>, original_message_type: &str) {
+/// 回复业务操作已交给 `TaskStateManager` 处理后的确认；结果确定时同时记录该消息已在该组处理过，
+/// 之后重复投递的同一消息将直接得到这一确认 (见 [`is_final_ack`])。
+///
+/// 未注册等在处理之前的拒绝不经由此函数，例如重新注册后重放同一消息仍会被处理。
+async fn send_business_ack(
+    client_session: &Arc<ClientSession>,
+    task_state_manager: &Arc<TaskStateManager>,
+    group_id: &str,
+    in_reply_to: &str,
+    ack: AckPayload,
+) {
+    if is_final_ack(&ack) {
+        task_state_manager.record_processed(group_id, in_reply_to, &ack);
+    }
+    send_ack(client_session, in_reply_to, ack).await;
+}
+
+/// 确认是否是该业务消息的最终结果，即以同一 `message_id` 重试必然得到相同结果。
+///
+/// * 已生效 / 无变化：重复应用会使状态 (版本号) 再次改变，必须原样回复。
+/// * 无权操作、负载不合法：与服务端状态无关，重试结果相同。
+/// * 版本冲突不算：客户端可能以同一 `message_id` 在新版本上重新提交 (例如离线待发队列重放时变基)。
+/// * 内部错误、任务不存在等：可能是暂时性的，应允许重试。
+fn is_final_ack(ack: &AckPayload) -> bool {
+    match ack.status {
+        ws_payloads::AckStatus::Applied | ws_payloads::AckStatus::NoChange => true,
+        ws_payloads::AckStatus::Rejected => matches!(
+            ack.error_code,
+            Some(common_models::enums::ErrorCode::Forbidden) | Some(common_models::enums::ErrorCode::PayloadInvalid)
+        ),
+    }
+}
+
+/// 向发送业务消息的客户端回复业务确认 (`Ack`)，其 `in_reply_to` 指向被确认的业务消息。
+async fn send_ack(client_session: &Arc<ClientSession>, in_reply_to: &str, ack: AckPayload) {
+    debug!(
+        "[消息路由::业务确认] 向客户端 {} 回复 {} 的确认: 状态={:?}, 版本={:?}, 错误码={:?}",
+        client_session.client_id, ack.original_message_type, ack.status, ack.version, ack.error_code
+    );
+    match WsMessage::new_reply(ws_payloads::ACK_MESSAGE_TYPE.to_string(), &ack, in_reply_to) {
+        Ok(ack_msg) => {
+            if let Err(e) = client_session.sender.send(ack_msg).await {
+                error!(
+                    "[消息路由::业务确认] 向客户端 {} (地址: {}) 发送 Ack 失败: {}. 可能原因：客户端已断开连接。",
+                    client_session.client_id, client_session.addr, e
+                );
+            }
+        }
+        Err(e) => {
+            error!(
+                "[消息路由::业务确认] 为 {:?} 创建 Ack 消息失败: {}. 客户端 {} 未能收到确认。",
+                ack, e, client_session.client_id
+            );
+        }
+    }
+}
+
+/// 以"被拒绝"的业务确认回复业务消息，错误码与字段详情取自 `CloudError`。
+async fn send_rejected_ack(client_session: &Arc<ClientSession>, in_reply_to: &str, original_message_type: &str, error: &CloudError) {
+    send_ack(client_session, in_reply_to, error.to_rejected_ack(original_message_type)).await;
+}
+
+// 辅助函数，用于以"被拒绝"的业务确认回复未注册客户端发来的业务消息
+async fn send_unregistered_error(client_session: &Arc<ClientSession>, in_reply_to: &str, original_message_type: &str) {
     warn!(
         "[消息路由] 客户端 {} (地址: {}): 尝试在未注册或未分配角色的情况下发送 {}。忽略。",
         client_session.client_id, client_session.addr, original_message_type
     );
-    send_error_response(
-        client_session,
-        Some(original_message_type.to_string()),
-        "客户端未注册到有效调试组或角色未知，无法处理此业务请求。".to_string(),
-    )
-    .await;
+    send_rejected_ack(client_session, in_reply_to, original_message_type, &CloudError::NotRegistered).await;
 }
 
-// 辅助函数，用于发送Payload解析错误
-async fn send_payload_parse_error(client_session: &Arc<ClientSession>, original_message_type: &str, error_text: &str, original_payload: &str) {
+// 辅助函数，用于以"被拒绝"的业务确认回复负载无法解析的业务消息
+async fn send_payload_parse_error(client_session: &Arc<ClientSession>, in_reply_to: &str, original_message_type: &str, parse_error: &serde_json::Error, original_payload: &str) {
     warn!(
         "[消息路由] 客户端 {} (地址: {}): 解析 {} 请求的负载失败: {}. 原始JSON负载: '{}'",
-        client_session.client_id, client_session.addr, original_message_type, error_text, original_payload
+        client_session.client_id, client_session.addr, original_message_type, parse_error, original_payload
     );
-    send_error_response(
+    send_rejected_ack(
         client_session,
-        Some(original_message_type.to_string()),
-        format!("{} 请求的负载格式无效: {}.", original_message_type, error_text),
+        in_reply_to,
+        original_message_type,
+        &CloudError::payload_invalid(original_message_type, parse_error),
     )
     .await;
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common_models::enums::ErrorCode;
+    use common_models::ws_payloads::{AckStatus, TaskStateSyncRequestPayload};
+    use crate::ws_server::connection_manager::test_principal;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_replies_carry_in_reply_to() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+
+        // 成功响应：Echo
+        let echo = WsMessage::new(ws_payloads::ECHO_MESSAGE_TYPE.to_string(), &EchoPayload { content: "hi".to_string() }).unwrap();
+        handle_message(session.clone(), echo.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let reply = rx.recv().await.expect("应收到 Echo 响应");
+        assert_eq!(reply.message_type, ws_payloads::ECHO_MESSAGE_TYPE);
+        assert_eq!(reply.in_reply_to.as_deref(), Some(echo.message_id.as_str()));
+
+        // 未注册客户端发送业务消息：回复被拒绝的 Ack
+        let note = WsMessage::new(ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string(), &serde_json::json!({})).unwrap();
+        handle_message(session.clone(), note.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let reply = rx.recv().await.expect("应收到 Ack");
+        assert_eq!(reply.message_type, ws_payloads::ACK_MESSAGE_TYPE);
+        assert_eq!(reply.in_reply_to.as_deref(), Some(note.message_id.as_str()));
+        let ack: AckPayload = reply.deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::Rejected);
+        assert_eq!(ack.error_code, Some(ErrorCode::NotRegistered));
+
+        // 未知消息类型不属于业务消息：回复 ErrorResponse
+        let unknown = WsMessage::new("NoSuchMessage".to_string(), &serde_json::json!({})).unwrap();
+        handle_message(session.clone(), unknown.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let reply = rx.recv().await.expect("应收到 ErrorResponse");
+        assert_eq!(reply.message_type, ws_payloads::ERROR_RESPONSE_MESSAGE_TYPE);
+        assert_eq!(reply.in_reply_to.as_deref(), Some(unknown.message_id.as_str()));
+        let error: ErrorResponsePayload = reply.deserialize_payload().unwrap();
+        assert_eq!(error.code, Some(ErrorCode::NotRegistered));
+        assert_eq!(error.message_key.as_deref(), Some("error.not_registered"));
+    }
+
+    #[tokio::test]
+    async fn test_full_task_state_is_sent_after_registration_and_on_resync() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+
+        // 未注册时请求同步：回复被拒绝的 Ack
+        let sync = WsMessage::new(ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE.to_string(), &TaskStateSyncRequestPayload {}).unwrap();
+        handle_message(session.clone(), sync.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.error_code, Some(ErrorCode::NotRegistered));
+
+        // 注册成功：注册响应之后紧跟一次完整任务状态推送
+        let register = RegisterPayload {
+            group_id: "group-sync".to_string(),
+            role: common_models::enums::ClientRole::OnSiteMobile,
+            task_id: "task-sync".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        let register = WsMessage::new(ws_payloads::REGISTER_MESSAGE_TYPE.to_string(), &register).unwrap();
+        handle_message(session.clone(), register, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::GROUP_ROSTER_MESSAGE_TYPE);
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::REGISTER_RESPONSE_MESSAGE_TYPE);
+        let snapshot = rx.recv().await.expect("注册成功后应收到完整任务状态");
+        assert_eq!(snapshot.message_type, ws_payloads::TASK_STATE_UPDATE_MESSAGE_TYPE);
+        assert_eq!(snapshot.in_reply_to, None);
+        let state: common_models::task_models::TaskDebugState = snapshot.deserialize_payload().unwrap();
+        assert_eq!(state.task_id, "task-sync");
+
+        // 主动请求同步：以关联到请求的 TaskStateUpdate 回复
+        let sync = WsMessage::new(ws_payloads::TASK_STATE_SYNC_REQUEST_MESSAGE_TYPE.to_string(), &TaskStateSyncRequestPayload {}).unwrap();
+        handle_message(session.clone(), sync.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let reply = rx.recv().await.unwrap();
+        assert_eq!(reply.message_type, ws_payloads::TASK_STATE_UPDATE_MESSAGE_TYPE);
+        assert_eq!(reply.in_reply_to.as_deref(), Some(sync.message_id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_business_messages_are_acknowledged_with_version() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+        let register = RegisterPayload {
+            group_id: "group-ack".to_string(),
+            role: common_models::enums::ClientRole::OnSiteMobile,
+            task_id: "task-ack".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        connection_manager.join_group(session.clone(), register).await.expect("注册应成功");
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::GROUP_ROSTER_MESSAGE_TYPE, "加入组后应先收到成员名册");
+        let initial_version = task_state_manager.current_version("group-ack").await.expect("注册后应已初始化任务状态");
+
+        let update = common_models::task_models::UpdatePreCheckItemPayload {
+            task_id: "task-ack".to_string(),
+            item_id: "item-1".to_string(),
+            status: "Site_Completed".to_string(),
+            notes: None,
+            expected_version: None,
+        };
+        let msg = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &update).unwrap();
+
+        // 首次提交：状态改变，版本递增；发起者自身也会先收到该版本的状态增量
+        handle_message(session.clone(), msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let delta_msg = rx.recv().await.expect("应收到状态增量");
+        assert_eq!(delta_msg.message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+        let delta: ws_payloads::TaskStateDeltaPayload = delta_msg.deserialize_payload().unwrap();
+        assert_eq!((delta.base_version, delta.version), (initial_version, initial_version + 1));
+        let reply = rx.recv().await.expect("应收到 Ack");
+        assert_eq!(reply.in_reply_to.as_deref(), Some(msg.message_id.as_str()));
+        let ack: AckPayload = reply.deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::Applied);
+        assert_eq!(ack.version, Some(initial_version + 1));
+
+        // 重复提交相同内容：无变化，返回当前版本
+        let again = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &update).unwrap();
+        handle_message(session.clone(), again, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::NoChange);
+        assert_eq!(ack.version, Some(initial_version + 1));
+
+        // 负载无法解析：被拒绝
+        let bad = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &serde_json::json!({"x": 1})).unwrap();
+        handle_message(session.clone(), bad, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::Rejected);
+        assert_eq!(ack.error_code, Some(ErrorCode::PayloadInvalid));
+        assert!(!ack.details.is_empty(), "负载无效的确认应携带字段级详情");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_business_message_returns_original_ack_without_reapplying() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+
+        // 未注册时发送：被拒绝且不记录，注册后重放同一消息仍会被处理
+        let note = UpdateTaskDebugNotePayload {
+            group_id: "group-dup".to_string(),
+            new_note: "第一次备注".to_string(),
+            custom_shared_data: None,
+            expected_version: None,
+        };
+        let note_msg = WsMessage::new(ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string(), &note).unwrap();
+        handle_message(session.clone(), note_msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.error_code, Some(ErrorCode::NotRegistered));
+
+        let register = RegisterPayload {
+            group_id: "group-dup".to_string(),
+            role: common_models::enums::ClientRole::OnSiteMobile,
+            task_id: "task-dup".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        connection_manager.join_group(session.clone(), register).await.expect("注册应成功");
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::GROUP_ROSTER_MESSAGE_TYPE);
+        let initial_version = task_state_manager.current_version("group-dup").await.unwrap();
+
+        handle_message(session.clone(), note_msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+        let note_ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!((note_ack.status, note_ack.version), (AckStatus::Applied, Some(initial_version + 1)));
+
+        let update = common_models::task_models::UpdatePreCheckItemPayload {
+            task_id: "task-dup".to_string(),
+            item_id: "item-1".to_string(),
+            status: "Site_Completed".to_string(),
+            notes: None,
+            expected_version: Some(initial_version + 1),
+        };
+        let update_msg = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &update).unwrap();
+        handle_message(session.clone(), update_msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+        let update_ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!((update_ack.status, update_ack.version), (AckStatus::Applied, Some(initial_version + 2)));
+
+        // 基于过期版本提交：版本冲突不被记录，以同一 message_id 在新版本上重新提交仍会被处理
+        let stale = common_models::task_models::UpdatePreCheckItemPayload {
+            item_id: "item-2".to_string(),
+            expected_version: Some(initial_version),
+            ..update.clone()
+        };
+        let mut stale_msg = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &stale).unwrap();
+        handle_message(session.clone(), stale_msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.error_code, Some(ErrorCode::VersionConflict));
+        let rebased = common_models::task_models::UpdatePreCheckItemPayload { expected_version: Some(initial_version + 2), ..stale };
+        stale_msg.payload = serde_json::to_string(&rebased).unwrap();
+        handle_message(session.clone(), stale_msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!((ack.status, ack.version), (AckStatus::Applied, Some(initial_version + 3)));
+
+        // 重复投递 (同一 message_id)：直接回复原确认，不再广播增量，版本号不变
+        for duplicate in [note_msg, update_msg] {
+            handle_message(session.clone(), duplicate.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+            let reply = rx.recv().await.expect("应收到 Ack");
+            assert_eq!(reply.message_type, ws_payloads::ACK_MESSAGE_TYPE, "重复消息不应再次广播状态增量");
+            assert_eq!(reply.in_reply_to.as_deref(), Some(duplicate.message_id.as_str()));
+            let ack: AckPayload = reply.deserialize_payload().unwrap();
+            let original = if duplicate.message_type == ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE { &update_ack } else { &note_ack };
+            assert_eq!(&ack, original);
+        }
+        assert_eq!(task_state_manager.current_version("group-dup").await, Some(initial_version + 3));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_start_single_test_step_is_routed_only_to_the_owning_site_engineer() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let join = |role: common_models::enums::ClientRole, device_ids: &[&str]| {
+            let connection_manager = connection_manager.clone();
+            let site_assignment = (!device_ids.is_empty()).then(|| ws_payloads::SiteAssignment {
+                device_ids: device_ids.iter().map(|device| device.to_string()).collect(),
+                pre_check_categories: Vec::new(),
+            });
+            async move {
+                let (tx, rx) = mpsc::channel(16);
+                let session = connection_manager
+                    .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+                    .await;
+                let register = RegisterPayload {
+                    group_id: "group-site".to_string(),
+                    role,
+                    task_id: "task-site".to_string(),
+                    client_software_version: None,
+                    client_display_name: None,
+                    resume_token: None,
+                    site_assignment,
+                    join_code: None,
+                };
+                connection_manager.join_group(session.clone(), register).await.expect("注册应成功");
+                (session, rx)
+            }
+        };
+        let (control, mut control_rx) = join(common_models::enums::ClientRole::ControlCenter, &[]).await;
+        let (_pump_engineer, mut pump_rx) = join(common_models::enums::ClientRole::OnSiteMobile, &["pump-1"]).await;
+        let (_valve_engineer, mut valve_rx) = join(common_models::enums::ClientRole::OnSiteMobile, &["valve-1"]).await;
+        let drain = |rx: &mut mpsc::Receiver<WsMessage>| {
+            let mut types = Vec::new();
+            while let Ok(message) = rx.try_recv() {
+                types.push(message.message_type);
+            }
+            types
+        };
+        drain(&mut control_rx);
+        drain(&mut pump_rx);
+        drain(&mut valve_rx);
+
+        let start = common_models::task_models::StartSingleTestStepPayload {
+            task_id: "task-site".to_string(),
+            device_id: "pump-1".to_string(),
+            step_id: "step-1".to_string(),
+            command: "RUN_FORWARD_5_SEC".to_string(),
+            expected_version: None,
+        };
+        let msg = WsMessage::new(ws_payloads::START_SINGLE_TEST_STEP_TYPE.to_string(), &start).unwrap();
+        handle_message(control.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+
+        let delta = ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE.to_string();
+        let start_type = ws_payloads::START_SINGLE_TEST_STEP_TYPE.to_string();
+        assert_eq!(drain(&mut pump_rx), vec![delta.clone(), start_type], "设备负责人收到状态增量与指令");
+        assert_eq!(drain(&mut valve_rx), vec![delta.clone()], "其他工程师只收到状态增量");
+        assert_eq!(drain(&mut control_rx), vec![delta, ws_payloads::ACK_MESSAGE_TYPE.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_stale_business_write_is_rejected_with_version_conflict_and_current_state() {
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+        let register = RegisterPayload {
+            group_id: "group-occ".to_string(),
+            role: common_models::enums::ClientRole::ControlCenter,
+            task_id: "task-occ".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        connection_manager.join_group(session.clone(), register).await.expect("注册应成功");
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::GROUP_ROSTER_MESSAGE_TYPE, "加入组后应先收到成员名册");
+        let base_version = task_state_manager.current_version("group-occ").await.unwrap();
+
+        // 基于当前版本的写入：生效
+        let first = common_models::task_models::UpdatePreCheckItemPayload {
+            task_id: "task-occ".to_string(),
+            item_id: "item-1".to_string(),
+            status: "Confirmed".to_string(),
+            notes: None,
+            expected_version: Some(base_version),
+        };
+        let msg = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &first).unwrap();
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!((ack.status, ack.version), (AckStatus::Applied, Some(base_version + 1)));
+
+        // 仍基于旧版本的写入 (例如另一端同时修改了同一预检项)：被拒绝，状态不变，不广播增量
+        let stale = common_models::task_models::UpdatePreCheckItemPayload {
+            status: "Rejected".to_string(),
+            ..first
+        };
+        let msg = WsMessage::new(ws_payloads::UPDATE_PRE_CHECK_ITEM_TYPE.to_string(), &stale).unwrap();
+        handle_message(session.clone(), msg.clone(), connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let reply = rx.recv().await.expect("应收到 Ack");
+        assert_eq!(reply.message_type, ws_payloads::ACK_MESSAGE_TYPE);
+        assert_eq!(reply.in_reply_to.as_deref(), Some(msg.message_id.as_str()));
+        let ack: AckPayload = reply.deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::Rejected);
+        assert_eq!(ack.error_code, Some(ErrorCode::VersionConflict));
+        assert_eq!(ack.version, Some(base_version + 1));
+        let current_state = ack.current_state.expect("版本冲突的确认应携带当前完整状态");
+        assert_eq!(current_state.version, base_version + 1);
+        assert_eq!(
+            current_state.pre_check_items["item-1"].status_from_control.as_deref(),
+            Some("Confirmed")
+        );
+        assert_eq!(task_state_manager.current_version("group-occ").await, Some(base_version + 1));
+
+        // 调试备注同样遵循版本检查
+        let note = UpdateTaskDebugNotePayload {
+            group_id: "group-occ".to_string(),
+            new_note: "过期备注".to_string(),
+            custom_shared_data: None,
+            expected_version: Some(base_version),
+        };
+        let msg = WsMessage::new(ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE.to_string(), &note).unwrap();
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.error_code, Some(ErrorCode::VersionConflict));
+        assert!(ack.current_state.is_some());
+        assert!(rx.try_recv().is_err(), "被拒绝的写入不应广播状态增量");
+    }
+
+    #[tokio::test]
+    async fn test_custom_shared_data_updates_are_routed_and_governed_by_schema() {
+        use ws_payloads::{CustomSharedDataUpdateMode, UpdateCustomSharedDataPayload};
+
+        let task_state_manager = Arc::new(TaskStateManager::new());
+        let connection_manager = Arc::new(ConnectionManager::new(task_state_manager.clone()));
+        let (tx, mut rx) = mpsc::channel(8);
+        let session = connection_manager
+            .add_client("127.0.0.1:0".parse().unwrap(), tx, Arc::new(AtomicBool::new(false)), Some(test_principal()))
+            .await;
+        let register = RegisterPayload {
+            group_id: "group-shared".to_string(),
+            role: common_models::enums::ClientRole::ControlCenter,
+            task_id: "task-shared".to_string(),
+            client_software_version: None,
+            client_display_name: None,
+            resume_token: None,
+            site_assignment: None,
+            join_code: None,
+        };
+        connection_manager.join_group(session.clone(), register).await.expect("注册应成功");
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::GROUP_ROSTER_MESSAGE_TYPE, "加入组后应先收到成员名册");
+        let send = |mode, new_data: serde_json::Value, pointer: Option<&str>| {
+            let payload = UpdateCustomSharedDataPayload { mode, new_data, pointer: pointer.map(str::to_string), expected_version: None };
+            WsMessage::new(ws_payloads::UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE.to_string(), &payload).unwrap()
+        };
+        let shared_data = |state: Option<Arc<tokio::sync::RwLock<common_models::TaskDebugState>>>| async move {
+            state.unwrap().read().await.custom_shared_data.clone()
+        };
+
+        // 合并补丁：只修改给出的成员
+        let msg = send(CustomSharedDataUpdateMode::Replace, serde_json::json!({"pump": {"status": "idle", "rpm": 0}}), None);
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let msg = send(CustomSharedDataUpdateMode::MergePatch, serde_json::json!({"pump": {"status": "running"}}), None);
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        // JSON Pointer：局部写入
+        let msg = send(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1500), Some("/pump/rpm"));
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        for _ in 0..3 {
+            assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+            let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+            assert_eq!(ack.status, AckStatus::Applied);
+        }
+        assert_eq!(
+            shared_data(task_state_manager.get_task_state("group-shared").await).await,
+            Some(serde_json::json!({"pump": {"status": "running", "rpm": 1500}}))
+        );
+
+        // 为任务配置 JSON Schema：rpm 必须是非负整数
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"pump": {"type": "object", "properties": {"rpm": {"type": "integer", "minimum": 0}}}}
+        });
+        assert!(task_state_manager
+            .set_custom_shared_data_schema("group-shared", Some(serde_json::json!({"type": "array"})), &connection_manager)
+            .await
+            .is_err(), "现有共享数据不符合的 Schema 应被拒绝");
+        task_state_manager
+            .set_custom_shared_data_schema("group-shared", Some(schema), &connection_manager)
+            .await
+            .expect("设置 Schema 应成功");
+        assert_eq!(rx.recv().await.unwrap().message_type, ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE);
+
+        // 违反 Schema 的写入被拒绝，字段详情指向出错位置，状态不变
+        let msg = send(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(-5), Some("/pump/rpm"));
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.status, AckStatus::Rejected);
+        assert_eq!(ack.error_code, Some(ErrorCode::PayloadInvalid));
+        assert_eq!(ack.details[0].field, "custom_shared_data/pump/rpm");
+
+        // 无效的 JSON Pointer 同样以 PAYLOAD_INVALID 拒绝
+        let msg = send(CustomSharedDataUpdateMode::JsonPointer, serde_json::json!(1), Some("pump"));
+        handle_message(session.clone(), msg, connection_manager.clone(), task_state_manager.clone()).await.unwrap();
+        let ack: AckPayload = rx.recv().await.unwrap().deserialize_payload().unwrap();
+        assert_eq!(ack.error_code, Some(ErrorCode::PayloadInvalid));
+        assert_eq!(ack.details[0].field, "pointer");
+        assert_eq!(
+            shared_data(task_state_manager.get_task_state("group-shared").await).await,
+            Some(serde_json::json!({"pump": {"status": "running", "rpm": 1500}}))
+        );
+        assert!(rx.try_recv().is_err(), "被拒绝的写入不应广播状态增量");
+    }
+}
@@ -45,10 +45,11 @@ use tracing::{debug, error, info, warn}; // 从 tracing 日志库中引入不同
 use rust_websocket_utils::{ // 从公司内部自定义的 `rust_websocket_utils` WebSocket 工具库导入所需组件。
     message::WsMessage as ActualWsMessage, // WebSocket 消息的标准结构体定义。使用 `as ActualWsMessage` 重命名是为了避免与项目中其他可能名为 `WsMessage` 的类型产生命名冲突，确保使用的是工具库中的定义。
     server::transport::{ // 从工具库的服务端传输层模块 (`server::transport`) 导入。
-        start_server, // 一个函数，用于根据指定配置启动底层的 WebSocket 服务器并开始监听连接。
+        start_server_with_authenticator, // 一个函数，用于根据指定配置启动底层的 WebSocket 服务器并开始监听连接，握手阶段会调用认证器。
         ConnectionHandler as WsConnectionHandler, // 一个结构体或类型别名，封装了与单个已建立的 WebSocket 连接进行交互（主要是发送消息）的逻辑。
         receive_message, // 一个异步函数，用于从给定的 WebSocket 流（的接收端）尝试接收单个完整的消息。
     },
+    server::auth::{AllowAnonymous, HandshakeAuthenticator}, // 握手认证扩展点：认证器 trait 及默认的匿名认证器。
     error::WsError, // `rust_websocket_utils` 库定义的标准错误枚举类型，用于表示 WebSocket 操作中可能发生的各种错误。
 };
 use std::sync::Arc; // 标准库的原子引用计数类型 (`Arc`)，用于在多个线程或异步任务之间安全地共享对象所有权。
@@ -68,6 +69,7 @@ use std::net::SocketAddr;
 ///   `ConnectionManager` (连接管理器) 负责管理所有活动的客户端会话及其所属的组。
 /// - `task_state_manager`: 对全局 `TaskStateManager` (任务状态管理器) 实例的共享、线程安全的引用。
 ///   `MessageRouter` (消息路由器) 将使用它来处理与任务相关的业务消息并更新共享的任务状态。
+/// - `authenticator`: WebSocket 握手阶段使用的认证器，默认为接受所有连接的 `AllowAnonymous`。
 pub struct WsService {
     /// WebSocket 服务的具体配置信息，例如监听的主机地址和端口号。
     /// 这些配置通常在应用启动时从外部文件或环境变量加载。
@@ -81,6 +83,10 @@ pub struct WsService {
     /// (P3.3.2 新增) 对全局 `TaskStateManager` (任务状态管理器) 实例的共享、线程安全的引用。
     /// `MessageRouter` (消息路由器) 将使用它来处理与任务相关的业务消息并更新共享的任务状态。
     task_state_manager: Arc<TaskStateManager>,
+
+    /// WebSocket 握手阶段使用的认证器。认证通过得到的主体会经 `ConnectionManager::add_client`
+    /// 存储到对应的 `ClientSession` 上；认证失败的握手将以 HTTP 401/403 被拒绝。
+    authenticator: Arc<dyn HandshakeAuthenticator>,
 }
 
 impl WsService {
@@ -106,14 +112,27 @@ impl WsService {
             config, // 存储传入的配置
             connection_manager, // 存储对连接管理器的共享引用
             task_state_manager, // P3.3.2 新增：存储对任务状态管理器的共享引用
+            authenticator: Arc::new(AllowAnonymous), // 默认不做握手认证，可通过 `with_authenticator` 替换
         }
     }
 
+    /// 为服务设置 WebSocket 握手认证器，替换默认的 `AllowAnonymous`。
+    ///
+    /// # 参数
+    /// * `authenticator`: `Arc<dyn HandshakeAuthenticator>` - 在所有连接间共享的握手认证器。
+    ///
+    /// # 返回值
+    /// 返回设置了认证器的 `WsService` 实例，便于链式调用。
+    pub fn with_authenticator(mut self, authenticator: Arc<dyn HandshakeAuthenticator>) -> Self {
+        self.authenticator = authenticator;
+        self
+    }
+
     /// 异步启动 WebSocket 服务端并开始监听连接。
     ///
     /// 此方法是 `WsService` (WebSocket 服务) 的核心入口点。一旦调用，它将：
     /// 1.  记录启动信息和配置详情。
-    /// 2.  调用 `rust_websocket_utils::server::transport::start_server` (启动服务器) 函数来启动底层的
+    /// 2.  调用 `rust_websocket_utils::server::transport::start_server_with_authenticator` (带握手认证的启动服务器) 函数来启动底层的
     ///     WebSocket 服务器。此函数需要一个监听地址和一个回调闭包 (`on_new_connection_cb` - 新连接回调)。
     /// 3.  `start_server` (启动服务器) 函数会在后台开始监听指定的网络地址和端口。当有新的客户端
     ///     尝试建立 WebSocket 连接时，它会接受连接，然后为这个新连接执行提供的
@@ -150,7 +169,7 @@ impl WsService {
             let task_manager_for_cb = Arc::clone(&self.task_state_manager);
             
             // `move` 关键字确保闭包捕获其使用的外部变量 (如 `conn_manager_for_cb`) 的所有权 (对于 `Arc` 来说是克隆的引用)。
-            move |ws_conn_handler: WsConnectionHandler, mut ws_receiver: SplitStream<WebSocketStream<TcpStream>>| {
+            move |mut ws_conn_handler: WsConnectionHandler, mut ws_receiver: SplitStream<WebSocketStream<TcpStream>>| {
                 // 再次为派生的 `async` 块克隆 `Arc<ConnectionManager>`。
                 let connection_manager_clone_for_async_block = Arc::clone(&conn_manager_for_cb);
                 // P3.3.2: 再次为派生的 `async` 块克隆 `Arc<TaskStateManager>`
@@ -208,11 +227,14 @@ impl WsService {
                     // 2. 为该会话生成一个唯一的 `client_id` (客户端ID，通常是UUID)。
                     // 3. 将此 `ClientSession` (客户端会话) 注册到 `ConnectionManager` (连接管理器) 的内部状态中 (例如，一个并发安全的哈希映射)。
                     // `add_client` (添加客户端) 需要客户端的地址 (当前是占位符)、用于向客户端发送消息的 MPSC 通道的发送端 (`tx_to_client_session`)，
-                    // 以及我们本地创建的、用于逻辑上请求关闭此会话相关任务的原子布尔标志 (`close_handle`)。
+                    // 以及我们本地创建的、用于逻辑上请求关闭此会话相关任务的原子布尔标志 (`close_handle`)，
+                    // 还有握手阶段由认证器确认的主体 (如有)。
+                    let principal = ws_conn_handler.take_principal();
                     let client_session = connection_manager_clone_for_async_block.add_client(
                         actual_addr,             // 客户端网络地址 (当前为占位符，见P3.1.1问题记录)。
                         tx_to_client_session,    // MPSC 通道的发送端。`ClientSession` (客户端会话) 将持有此发送端，以便其他模块可以将消息路由给它。
-                        close_handle             // 本地创建的、用于从外部请求关闭此会话相关任务的原子布尔标志 (逻辑关闭信号)。
+                        close_handle,            // 本地创建的、用于从外部请求关闭此会话相关任务的原子布尔标志 (逻辑关闭信号)。
+                        principal                // 握手阶段认证得到的主体，将被存储到 `ClientSession` 上。
                     ).await; // `add_client` (添加客户端) 是一个异步方法。
                     
                     info!(
@@ -495,7 +517,7 @@ impl WsService {
                         "[WebSocket服务层-连接处理 {}] 客户端 {} 的消息发送和接收任务均已结束或已被通知结束。现在将从 ConnectionManager (连接管理器) 中正式移除此客户端的会话。",
                         client_session.client_id, client_session.client_id
                     );
-                    connection_manager_clone_for_async_block.remove_client(&client_session.client_id).await;
+                    connection_manager_clone_for_async_block.remove_connection(&client_session).await;
                     info!(
                         "[WebSocket服务层-连接处理 {}] 客户端 {} 的会话已成功从 ConnectionManager (连接管理器) 中移除。此客户端连接的完整处理与清理流程至此结束。",
                         client_session.client_id, client_session.client_id
@@ -506,13 +528,14 @@ impl WsService {
             } // on_new_connection_cb (新连接回调) 闭包定义的结束
         }; // 回调闭包赋值的结束
 
-        // 调用 `rust_websocket_utils::server::transport::start_server` (启动服务器) 函数来实际启动 WebSocket 服务器。
+        // 调用 `rust_websocket_utils::server::transport::start_server_with_authenticator` (带握手认证的启动服务器) 函数来实际启动 WebSocket 服务器。
         // 此函数需要一个格式为 "host:port" (例如 "127.0.0.1:8088") 的监听地址字符串，以及我们上面定义的 `on_new_connection_cb` (新连接回调) 回调闭包。
         // `start_server` (启动服务器) 本身是一个异步函数，并且设计上通常会在其内部持续运行 (或阻塞当前任务) 以循环接受新的客户端连接。
         // 因此，调用者通常会在 `.await` 之后主要处理其返回的 `Result`，该 `Result` 主要用于指示启动服务器时是否立即发生了错误 (例如端口被占用)。
         // 如果服务器成功启动，`start_server` 在理论上不会"正常"返回，除非整个服务器被外部信号终止或发生不可恢复的内部错误。
-        start_server(
+        start_server_with_authenticator(
             format!("{}:{}", self.config.host, self.config.port), // 构造监听地址字符串，例如 "127.0.0.1:8088"
+            Arc::clone(&self.authenticator), // 握手阶段使用的认证器
             on_new_connection_cb, // 传递我们精心定义的、用于处理每一个新WebSocket连接的回调闭包
         )
         .await // 等待 `start_server` (启动服务器) 的完成 (如上所述，它通常会一直运行，除非出错或服务被终止)
@@ -29,15 +29,222 @@
 use log::{info, warn, error}; // 移除了未使用的 'debug' 和 'error'
 use std::sync::Arc; // `Arc` (原子引用计数) 将用于安全地共享 TaskStateManager 实例以及单个 TaskDebugState 实例的所有权。
 use dashmap::DashMap; // `DashMap` 是一个高性能的并发哈希映射库，计划用于存储 `group_id` 到 `TaskDebugState` 的映射。
-use tokio::sync::RwLock; // Tokio 提供的异步读写锁 (`RwLock`)，将用于保护对单个 `TaskDebugState` 实例内部数据的并发读写访问，确保数据一致性。
+use tokio::sync::{watch, RwLock}; // Tokio 提供的异步读写锁 (`RwLock`)，将用于保护对单个 `TaskDebugState` 实例内部数据的并发读写访问，确保数据一致性；`watch` 用于唤醒等待同一业务消息处理结果的重复投递。
 use common_models::TaskDebugState; // 从 `common_models` (公共模型) crate 引入任务调试状态的详细结构体定义。
 use common_models::enums::ClientRole; // 从 `common_models` (公共模型) crate 引入客户端角色枚举，在实现 P3.3.1 的状态更新方法时可能会根据角色进行权限检查或逻辑分支。
-use common_models::ws_payloads::{self, BusinessActionPayload, UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE}; // 引入业务Action Payload, UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE
-use common_models::task_models::PreCheckItemStatus; // Keep PreCheckItemStatus
+use common_models::ws_payloads::{self, BusinessActionPayload, UpdateCustomSharedDataPayload, UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE}; // 引入业务Action Payload, UpdateTaskDebugNotePayload, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE
+use common_models::task_models::{PreCheckItemStatus, SingleTestStepStatus};
 use chrono::Utc; // 引入Utc以获取当前时间
 use serde_json; // serde_json is used
 use uuid; // uuid is used
 use crate::ws_server::connection_manager::ConnectionManager; // 确保引入 ConnectionManager
+use crate::error::CloudError; // 带有机器可读错误码的协议错误类型
+use common_models::ws_payloads::{AckPayload, FieldErrorDetail, SIGN_OFF_SINGLE_TEST_STEP_TYPE, UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE};
+use common_models::permissions::{changed_state_fields, is_action_permitted, permission_for, BusinessAction};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// 每个任务组默认保留的已处理业务消息数量 (见 [`TaskStateManager::processed_ack`])。
+pub const DEFAULT_PROCESSED_MESSAGE_WINDOW: usize = 256;
+
+/// 一个任务组最近处理过的业务消息及其确认，按处理顺序保留最近 `capacity` 条。
+#[derive(Debug, Default)]
+struct ProcessedMessages {
+    /// 按处理顺序排列的 `message_id`，用于淘汰最早的记录。
+    order: VecDeque<String>,
+    /// `message_id` -> 当时回复的确认。
+    acks: HashMap<String, AckPayload>,
+    /// 正在处理中的 `message_id`。发送端被移除 (处理结束) 时，等待同一消息的重复投递被唤醒。
+    in_flight: HashMap<String, watch::Sender<()>>,
+}
+
+// 正在处理中的预留属于原管理器，副本只复制已处理的记录
+impl Clone for ProcessedMessages {
+    fn clone(&self) -> Self {
+        Self { order: self.order.clone(), acks: self.acks.clone(), in_flight: HashMap::new() }
+    }
+}
+
+impl ProcessedMessages {
+    fn record(&mut self, message_id: &str, ack: AckPayload, capacity: usize) {
+        self.in_flight.remove(message_id);
+        if self.acks.insert(message_id.to_string(), ack).is_none() {
+            self.order.push_back(message_id.to_string());
+        }
+        while self.order.len() > capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.acks.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// 对一条业务消息的处理预留 (见 [`TaskStateManager::begin_processing`])。
+///
+/// 持有期间同一组内以相同 `message_id` 重复投递的消息会等待，而不会被并发地再次应用。
+/// 以最终确认调用 [`TaskStateManager::record_processed`] 后，等待者得到该确认；
+/// 未记录最终确认就被丢弃时 (例如版本冲突或暂时性错误)，预留被撤销，等待者中的一个重新处理该消息。
+pub struct ProcessingReservation {
+    task_state_manager: Arc<TaskStateManager>,
+    group_id: String,
+    message_id: String,
+}
+
+impl Drop for ProcessingReservation {
+    fn drop(&mut self) {
+        if let Some(mut processed) = self.task_state_manager.processed_messages.get_mut(&self.group_id) {
+            processed.in_flight.remove(&self.message_id);
+        }
+    }
+}
+
+/// 业务操作未能应用到任务状态的原因。
+#[derive(Debug, Clone)]
+pub enum StateUpdateError {
+    /// 操作声明的 `expected_version` 与当前版本不一致 (乐观并发控制)，携带服务端当前完整的任务状态，
+    /// 以便发起方在最新状态上变基或合并后重新提交。
+    VersionConflict {
+        expected_version: u64,
+        current_state: Box<TaskDebugState>,
+    },
+    /// 操作内容不被接受 (例如共享数据不符合任务的 JSON Schema)，状态未被修改。
+    Rejected(CloudError),
+    /// 其他处理失败 (任务状态不存在、负载无法解析、不支持的消息类型等)。
+    Failed(String),
+}
+
+impl StateUpdateError {
+    /// 构造回复给发起方的"被拒绝"业务确认。版本冲突时确认中附带服务端当前的任务状态。
+    pub fn to_rejected_ack(&self, original_message_type: &str) -> AckPayload {
+        match self {
+            StateUpdateError::VersionConflict { expected_version, current_state } => CloudError::VersionConflict {
+                expected: *expected_version,
+                current: current_state.version,
+            }
+            .to_rejected_ack(original_message_type)
+            .with_current_state((**current_state).clone()),
+            StateUpdateError::Rejected(error) => error.to_rejected_ack(original_message_type),
+            StateUpdateError::Failed(reason) => CloudError::Internal(reason.clone()).to_rejected_ack(original_message_type),
+        }
+    }
+}
+
+impl fmt::Display for StateUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateUpdateError::VersionConflict { expected_version, current_state } => write!(
+                f,
+                "版本冲突：操作基于版本 {}，当前版本为 {}",
+                expected_version, current_state.version
+            ),
+            StateUpdateError::Rejected(error) => write!(f, "{}", error),
+            StateUpdateError::Failed(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// 乐观并发控制：操作声明的期望版本与当前状态版本不一致时返回 `VersionConflict`。
+fn check_expected_version(task_state: &TaskDebugState, expected_version: Option<u64>) -> Result<(), StateUpdateError> {
+    match expected_version {
+        Some(expected_version) if !task_state.accepts_expected_version(Some(expected_version)) => {
+            Err(StateUpdateError::VersionConflict {
+                expected_version,
+                current_state: Box::new(task_state.clone()),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 取得 (必要时创建) 指定测试步骤的状态条目，以 `step_id` 为键。
+fn single_test_step_entry<'a>(task_state: &'a mut TaskDebugState, step_id: &str) -> &'a mut SingleTestStepStatus {
+    task_state
+        .single_test_steps
+        .entry(step_id.to_string())
+        .or_insert_with(|| SingleTestStepStatus::new(step_id.to_string()))
+}
+
+/// 按授权矩阵 (`common_models::permissions::PERMISSION_MATRIX`) 校验角色能否发起该业务操作，须在修改状态之前调用。
+fn authorize_action(action: BusinessAction, role: ClientRole) -> Result<(), StateUpdateError> {
+    if is_action_permitted(action, role) {
+        Ok(())
+    } else {
+        Err(StateUpdateError::Rejected(CloudError::Forbidden(format!(
+            "角色 {:?} 无权发起 '{}' 操作",
+            role, action
+        ))))
+    }
+}
+
+/// 确认业务操作实际修改的字段都在授权矩阵为该角色列出的可写字段之内。
+///
+/// 作为 [`authorize_action`] 之后的第二道防线：即使处理分支的实现有误，也不会让某个角色越权改写其他角色的字段。
+fn check_writable_fields(
+    action: BusinessAction,
+    role: ClientRole,
+    before: &TaskDebugState,
+    after: &TaskDebugState,
+) -> Result<(), StateUpdateError> {
+    let writable_fields = permission_for(action, role).map(|permission| permission.writable_fields).unwrap_or(&[]);
+    let forbidden_fields: Vec<_> = changed_state_fields(before, after)
+        .into_iter()
+        .filter(|field| !writable_fields.contains(field))
+        .collect();
+    if forbidden_fields.is_empty() {
+        Ok(())
+    } else {
+        Err(StateUpdateError::Rejected(CloudError::Forbidden(format!(
+            "角色 {:?} 无权通过 '{}' 操作修改字段 {:?}",
+            role, action, forbidden_fields
+        ))))
+    }
+}
+
+/// 按任务配置的 JSON Schema (`custom_shared_data_schema`) 校验即将写入的自定义共享数据。
+///
+/// 未配置 Schema 时不做限制；不符合时返回 `PAYLOAD_INVALID`，字段详情指向共享数据中出错的位置。
+fn validate_custom_shared_data(
+    task_state: &TaskDebugState,
+    message_type: &str,
+    data: &serde_json::Value,
+) -> Result<(), StateUpdateError> {
+    let Some(schema) = task_state.custom_shared_data_schema.as_ref() else {
+        return Ok(());
+    };
+    let validator = jsonschema::validator_for(schema)
+        .map_err(|e| StateUpdateError::Failed(format!("任务 '{}' 配置的共享数据 JSON Schema 无效: {}", task_state.task_id, e)))?;
+    let details: Vec<FieldErrorDetail> = validator
+        .iter_errors(data)
+        .map(|e| FieldErrorDetail::new(&format!("custom_shared_data{}", e.instance_path), e.to_string()))
+        .collect();
+    if details.is_empty() {
+        return Ok(());
+    }
+    Err(StateUpdateError::Rejected(CloudError::PayloadInvalid {
+        message_type: message_type.to_string(),
+        reason: format!("自定义共享数据不符合任务的 JSON Schema ({} 处错误)", details.len()),
+        details,
+    }))
+}
+
+/// 计算 `UpdateCustomSharedData` 应用后的共享数据 (结果为 `null` 时表示清空)，并按任务的 JSON Schema 校验。
+fn updated_custom_shared_data(
+    task_state: &TaskDebugState,
+    payload: &UpdateCustomSharedDataPayload,
+) -> Result<Option<serde_json::Value>, StateUpdateError> {
+    let updated = payload.apply_to(task_state.custom_shared_data.as_ref()).map_err(|reason| {
+        StateUpdateError::Rejected(CloudError::PayloadInvalid {
+            message_type: UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE.to_string(),
+            details: vec![FieldErrorDetail::new("pointer", reason.clone())],
+            reason,
+        })
+    })?;
+    if updated.is_null() {
+        return Ok(None);
+    }
+    validate_custom_shared_data(task_state, UPDATE_CUSTOM_SHARED_DATA_MESSAGE_TYPE, &updated)?;
+    Ok(Some(updated))
+}
 
 /// `TaskStateManager` (任务状态管理器) 结构体的定义 (当前为P3.1.2阶段的骨架实现)。
 /// 
@@ -65,6 +272,10 @@ pub struct TaskStateManager {
     /// 键: `group_id` (String)
     /// 值: `Arc<RwLock<TaskDebugState>>` - 对任务状态的线程安全引用，允许并发读写。
     active_task_states: DashMap<String, Arc<RwLock<TaskDebugState>>>,
+    /// 每个任务组最近处理过的业务消息 (键: `group_id`)，用于识别重连或离线重放造成的重复投递。
+    processed_messages: DashMap<String, ProcessedMessages>,
+    /// 每个任务组保留的已处理业务消息数量上限。
+    processed_message_window: usize,
 }
 
 impl TaskStateManager {
@@ -80,9 +291,66 @@ impl TaskStateManager {
         info!("[任务状态管理器] 正在创建一个新的 TaskStateManager (任务状态管理器) 实例。(当前为P3.1.2骨架实现，内部实际状态存储尚未初始化)。");
         Self {
             active_task_states: DashMap::new(),
+            processed_messages: DashMap::new(),
+            processed_message_window: DEFAULT_PROCESSED_MESSAGE_WINDOW,
         }
     }
 
+    /// 设置每个任务组保留的已处理业务消息数量 (默认 [`DEFAULT_PROCESSED_MESSAGE_WINDOW`]，最小为 1)。
+    pub fn with_processed_message_window(mut self, capacity: usize) -> Self {
+        self.processed_message_window = capacity.max(1);
+        self
+    }
+
+    /// 若 `message_id` 所指的业务消息最近已在该组处理过，返回当时回复的确认。
+    ///
+    /// 客户端重连后的重试或离线重放可能把同一条 `WsMessage` 投递两次；重复的消息不应再次应用
+    /// (否则例如同一次步骤反馈会使版本号递增两次)，而应原样回复第一次的确认。
+    /// 每组只保留最近 `processed_message_window` 条记录，更早的消息不再能被识别为重复。
+    pub fn processed_ack(&self, group_id: &str, message_id: &str) -> Option<AckPayload> {
+        self.processed_messages.get(group_id).and_then(|processed| processed.acks.get(message_id).cloned())
+    }
+
+    /// 开始处理该组中 `message_id` 所指的业务消息。
+    ///
+    /// 消息最近已处理过时返回 `Err`，携带当时回复的确认 (见 [`TaskStateManager::processed_ack`])；
+    /// 同一消息正在处理时 (例如旧连接尚未关闭、客户端已在新连接上重发) 等待其处理结束后再判断，
+    /// 因此同一 `message_id` 不会被两个连接同时应用。
+    /// 否则返回处理预留，在其被丢弃之前重复投递的同一消息都会等待。
+    pub async fn begin_processing(self: &Arc<Self>, group_id: &str, message_id: &str) -> Result<ProcessingReservation, AckPayload> {
+        loop {
+            let mut in_progress = {
+                let mut processed = self.processed_messages.entry(group_id.to_string()).or_default();
+                if let Some(ack) = processed.acks.get(message_id) {
+                    return Err(ack.clone());
+                }
+                match processed.in_flight.get(message_id) {
+                    Some(sender) => sender.subscribe(),
+                    None => {
+                        processed.in_flight.insert(message_id.to_string(), watch::channel(()).0);
+                        return Ok(ProcessingReservation {
+                            task_state_manager: Arc::clone(self),
+                            group_id: group_id.to_string(),
+                            message_id: message_id.to_string(),
+                        });
+                    }
+                }
+            };
+            info!("[任务状态管理器] 组 '{}' 的业务消息 {} 正在处理中，等待其结果。", group_id, message_id);
+            // 发送端在处理结束时被移除，`changed` 随即返回 (错误)
+            let _ = in_progress.changed().await;
+        }
+    }
+
+    /// 记录该组已处理 `message_id` 所指的业务消息及回复的确认，超出窗口时淘汰最早的记录。
+    /// 等待同一消息处理结果的重复投递随之得到这一确认。
+    pub fn record_processed(&self, group_id: &str, message_id: &str, ack: &AckPayload) {
+        self.processed_messages
+            .entry(group_id.to_string())
+            .or_default()
+            .record(message_id, ack.clone(), self.processed_message_window);
+    }
+
     /// 初始化或关联特定调试任务的状态 (当前为P3.1.2阶段的骨架实现，主要功能是记录日志)。
     /// 
     /// 在 P3.3.1 阶段的完整功能实现中，此异步方法 (`async fn`) 将承担以下关键职责：
@@ -148,6 +416,15 @@ impl TaskStateManager {
         }
     }
 
+    /// 返回指定组当前任务状态的版本号；若该组没有任务状态则返回 `None`。
+    ///
+    /// 主要用于在业务操作未引起状态变化时，仍能在确认 (`Ack`) 中告知客户端当前版本。
+    pub async fn current_version(&self, group_id: &str) -> Option<u64> {
+        let task_state_arc = self.active_task_states.get(group_id).map(|entry| entry.value().clone())?;
+        let version = task_state_arc.read().await.version;
+        Some(version)
+    }
+
     /// 此方法从内部的 `active_task_states` 集合中异步移除与指定 `group_id` 关联的 `TaskDebugState`。
     /// 如果成功找到并移除了状态，或者即使未找到（也认为操作已"完成"），则返回 `Ok(())`。
     /// 如果在尝试移除过程中遇到内部错误（例如，锁获取问题，尽管当前实现不太可能），则返回 `Err(String)`。
@@ -161,6 +438,7 @@ impl TaskStateManager {
         info!("[任务状态管理器] 尝试为 group_id '{}' 移除任务状态...", group_id);
         // DashMap 的 remove 方法返回 Option<(K, V)>，这里是 Option<(String, Arc<RwLock<TaskDebugState>>)>
         let removed_entry = self.active_task_states.remove(group_id);
+        self.processed_messages.remove(group_id);
 
         if removed_entry.is_some() {
             info!("[任务状态管理器] 任务状态已成功为 group_id '{}' 移除。", group_id);
@@ -187,23 +465,44 @@ impl TaskStateManager {
     /// * `payload` - 具体的业务消息 Payload (在P3.3.2的初始实现中，我们临时使用 serde_json::Value 以便灵活处理，
     ///   未来应演化为更具体的业务 Payload 枚举或 Trait 对象)。
     ///
+    /// * `conn_manager` - 连接管理器，状态发生实际变更时用于向组内成员广播增量。
+    ///
+    /// 状态发生实际变更时，会在持有该任务自身状态写锁 (而非 `active_task_states` 的分片锁) 期间向组内所有成员 (包括发起变更的客户端) 广播
+    /// 从旧版本到新版本的 `TaskStateDelta`，保证各成员收到的增量按版本号连续有序。
+    ///
+    /// 操作声明了 `expected_version` 时，在持有写锁期间与当前版本比较，不一致则不做任何修改。
+    ///
     /// # Returns
-    /// * `Some(TaskDebugState)` 如果状态被成功修改，则返回修改后状态的一个克隆。
-    /// * `None` 如果状态没有发生实际改变，或者找不到对应的任务状态。
+    /// * `Ok(Some(TaskDebugState))` 如果状态被成功修改，则返回修改后状态的一个克隆。
+    /// * `Ok(None)` 如果状态没有发生实际改变，或者找不到对应的任务状态。
+    /// * `Err(StateUpdateError::VersionConflict)` 如果操作基于的版本已过期，携带当前完整状态。
     pub async fn update_state_and_get_updated(
         &self,
         group_id: &str,
         updater_role: ClientRole,
         action_payload: BusinessActionPayload, 
-    ) -> Option<TaskDebugState> {
+        conn_manager: &ConnectionManager,
+    ) -> Result<Option<TaskDebugState>, StateUpdateError> {
         info!(
             "[任务状态管理器] 尝试为 group_id '{}' 更新任务状态。更新者角色: {:?}, ActionPayload: {:?}",
             group_id, updater_role, action_payload
         );
+        let action = action_payload.action();
+        if let Err(denied) = authorize_action(action, updater_role) {
+            warn!("[任务状态管理器] group_id '{}' 拒绝越权操作: {}", group_id, denied);
+            return Err(denied);
+        }
 
-        match self.active_task_states.get_mut(group_id) { // 使用 get_mut 获取可写锁
-            Some(mut task_state_entry) => {
-                let mut task_state = task_state_entry.value_mut().write().await; // 获取写锁
+        // 先取出该组状态的 Arc 再加锁：广播增量需要等待每个成员的发送通道，
+        // 不能在持有 DashMap 分片锁期间等待，否则同一分片上的其他任务组都会被阻塞。
+        match self.get_task_state(group_id).await {
+            Some(task_state_arc) => {
+                let mut task_state = task_state_arc.write().await; // 获取写锁
+                if let Err(conflict) = check_expected_version(&task_state, action_payload.expected_version()) {
+                    warn!("[任务状态管理器] group_id '{}' 拒绝角色 {:?} 的过期写入: {}", group_id, updater_role, conflict);
+                    return Err(conflict);
+                }
+                let previous_state = task_state.clone(); // 用于在状态变更后生成增量
                 let mut state_changed = false;
 
                 match action_payload {
@@ -241,22 +540,61 @@ impl TaskStateManager {
                         }
                     }
                     BusinessActionPayload::StartSingleTestStep(payload) => {
-                        // TODO: 实现 StartSingleTestStep 的逻辑
-                        info!("[任务状态管理器] TODO: 处理 StartSingleTestStep: {:?}", payload);
-                        // state_changed = ...;
+                        info!("[任务状态管理器] 处理 StartSingleTestStep: {:?}", payload);
+                        let step = single_test_step_entry(&mut task_state, &payload.step_id);
+                        if step.command_from_control.as_deref() != Some(payload.command.as_str()) {
+                            step.command_from_control = Some(payload.command);
+                            step.last_updated = Utc::now();
+                            state_changed = true;
+                        }
                     }
                     BusinessActionPayload::FeedbackSingleTestStep(payload) => {
-                        // TODO: 实现 FeedbackSingleTestStep 的逻辑
-                        info!("[任务状态管理器] TODO: 处理 FeedbackSingleTestStep: {:?}", payload);
-                        // state_changed = ...;
+                        info!("[任务状态管理器] 处理 FeedbackSingleTestStep: {:?}", payload);
+                        let step = single_test_step_entry(&mut task_state, &payload.step_id);
+                        if step.execution_status_from_site.as_deref() != Some(payload.execution_status.as_str())
+                            || step.result_data_from_site != payload.result_data
+                            || step.feedback_notes_from_site != payload.feedback_notes
+                        {
+                            step.execution_status_from_site = Some(payload.execution_status);
+                            step.result_data_from_site = payload.result_data;
+                            step.feedback_notes_from_site = payload.feedback_notes;
+                            step.last_updated = Utc::now();
+                            state_changed = true;
+                        }
                     }
                     BusinessActionPayload::ConfirmSingleTestStep(payload) => {
-                        // TODO: 实现 ConfirmSingleTestStep 的逻辑
-                        info!("[任务状态管理器] TODO: 处理 ConfirmSingleTestStep: {:?}", payload);
-                        // state_changed = ...;
+                        info!("[任务状态管理器] 处理 ConfirmSingleTestStep: {:?}", payload);
+                        let step = single_test_step_entry(&mut task_state, &payload.step_id);
+                        if step.confirmation_status_from_control.as_deref() != Some(payload.confirmation_status.as_str()) {
+                            step.confirmation_status_from_control = Some(payload.confirmation_status);
+                            step.last_updated = Utc::now();
+                            state_changed = true;
+                        }
+                    }
+                    BusinessActionPayload::SignOffSingleTestStep(payload) => {
+                        info!("[任务状态管理器] 处理 SignOffSingleTestStep: {:?}", payload);
+                        // QA 主管只能签核控制中心已确认的步骤
+                        let Some(step) = task_state.single_test_steps.get_mut(&payload.step_id).filter(|step| step.is_confirmed()) else {
+                            return Err(StateUpdateError::Rejected(CloudError::PayloadInvalid {
+                                message_type: SIGN_OFF_SINGLE_TEST_STEP_TYPE.to_string(),
+                                reason: format!("测试步骤 '{}' 尚未被控制中心确认，不能签核", payload.step_id),
+                                details: vec![FieldErrorDetail::new("step_id", "步骤不存在或尚未确认".to_string())],
+                            }));
+                        };
+                        if step.sign_off_status_from_supervisor.as_deref() != Some(payload.sign_off_status.as_str())
+                            || step.sign_off_notes_from_supervisor != payload.notes
+                        {
+                            step.sign_off_status_from_supervisor = Some(payload.sign_off_status);
+                            step.sign_off_notes_from_supervisor = payload.notes;
+                            step.last_updated = Utc::now();
+                            state_changed = true;
+                        }
                     }
                     BusinessActionPayload::UpdateTaskDebugNote(payload) => {
                         info!("[任务状态管理器] 处理 UpdateTaskDebugNote: {:?}", payload);
+                        if let Some(custom_shared_data) = payload.custom_shared_data.as_ref() {
+                            validate_custom_shared_data(&task_state, UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, custom_shared_data)?;
+                        }
                         // 调用新的私有方法来处理
                         match self.priv_handle_update_task_debug_note(&mut task_state, payload, updater_role).await {
                             Ok(changed) => state_changed = changed,
@@ -266,9 +604,22 @@ impl TaskStateManager {
                             }
                         }
                     }
+                    BusinessActionPayload::UpdateCustomSharedData(payload) => {
+                        info!("[任务状态管理器] 处理 UpdateCustomSharedData: 方式={:?}, 路径={:?}", payload.mode, payload.pointer);
+                        let updated = updated_custom_shared_data(&task_state, &payload)?;
+                        if task_state.custom_shared_data != updated {
+                            task_state.custom_shared_data = updated;
+                            state_changed = true;
+                        }
+                    }
                 }
 
                 if state_changed {
+                    if let Err(denied) = check_writable_fields(action, updater_role, &previous_state, &task_state) {
+                        warn!("[任务状态管理器] group_id '{}' 撤销越权修改: {}", group_id, denied);
+                        *task_state = previous_state;
+                        return Err(denied);
+                    }
                     task_state.last_updated_by_role = Some(updater_role);
                     task_state.last_update_timestamp = Utc::now();
                     task_state.version += 1; // 版本号递增
@@ -276,13 +627,14 @@ impl TaskStateManager {
                         "[任务状态管理器] group_id '{}' 的任务状态已更新。新版本: {}. 最后更新者: {:?}",
                         group_id, task_state.version, updater_role
                     );
-                    Some(task_state.clone()) // 返回更新后状态的克隆
+                    self.priv_broadcast_state_delta(group_id, &previous_state, &task_state, conn_manager).await;
+                    Ok(Some(task_state.clone())) // 返回更新后状态的克隆
                 } else {
                     info!(
                         "[任务状态管理器] group_id '{}' 的任务状态未发生实际改变。",
                         group_id
                     );
-                    None
+                    Ok(None)
                 }
             }
             None => {
@@ -290,11 +642,59 @@ impl TaskStateManager {
                     "[任务状态管理器] 尝试更新不存在的 group_id '{}' 的任务状态。",
                     group_id
                 );
-                None
+                Ok(None)
             }
         }
     }
 
+    /// 设置 (或以 `None` 清除) 指定组任务的自定义共享数据 JSON Schema。
+    ///
+    /// Schema 本身必须有效，且任务当前的共享数据必须符合新的 Schema，否则不做修改。
+    /// 设置成功后版本号递增，并向组内所有成员广播状态增量。
+    ///
+    /// # Returns
+    /// * `Ok(TaskDebugState)` - 更新后状态的克隆。
+    /// * `Err(String)` - 找不到任务状态、Schema 无效或现有共享数据不符合 Schema。
+    pub async fn set_custom_shared_data_schema(
+        &self,
+        group_id: &str,
+        schema: Option<serde_json::Value>,
+        conn_manager: &ConnectionManager,
+    ) -> Result<TaskDebugState, String> {
+        let task_state_arc = self
+            .get_task_state(group_id)
+            .await
+            .ok_or_else(|| format!("组 '{}' 没有对应的任务状态", group_id))?;
+        let mut task_state = task_state_arc.write().await;
+        if let Some(schema) = schema.as_ref() {
+            let validator = jsonschema::validator_for(schema).map_err(|e| format!("JSON Schema 无效: {}", e))?;
+            if let Some(current) = task_state.custom_shared_data.as_ref() {
+                let violations: Vec<String> = validator
+                    .iter_errors(current)
+                    .map(|e| format!("custom_shared_data{}: {}", e.instance_path, e))
+                    .collect();
+                if !violations.is_empty() {
+                    return Err(format!("现有共享数据不符合新的 JSON Schema: {}", violations.join("; ")));
+                }
+            }
+        }
+        if task_state.custom_shared_data_schema == schema {
+            return Ok(task_state.clone());
+        }
+        let previous_state = task_state.clone();
+        task_state.custom_shared_data_schema = schema;
+        task_state.last_update_timestamp = Utc::now();
+        task_state.version += 1;
+        info!(
+            "[任务状态管理器] group_id '{}' 的共享数据 JSON Schema 已{}。新版本: {}",
+            group_id,
+            if task_state.custom_shared_data_schema.is_some() { "更新" } else { "清除" },
+            task_state.version
+        );
+        self.priv_broadcast_state_delta(group_id, &previous_state, &task_state, conn_manager).await;
+        Ok(task_state.clone())
+    }
+
     /// 强制广播指定组的当前任务状态。
     /// 此方法主要用于特殊情况，例如由Tauri命令直接触发的状态更新后的广播。
     pub async fn force_broadcast_state(
@@ -359,6 +759,7 @@ impl TaskStateManager {
             message_type: common_ws_msg.message_type.clone(),
             payload: common_ws_msg.payload.clone(),
             timestamp: common_ws_msg.timestamp,
+            in_reply_to: None, // 广播推送不是对某个请求的响应
         };
 
         for client_session_arc in clients_in_group {
@@ -372,6 +773,54 @@ impl TaskStateManager {
         info!("[任务状态管理器] 已完成向组 '{}' 的客户端广播 TaskDebugState。", group_id);
     }
 
+    /// 私有辅助方法：向指定组内的所有成员广播任务状态从 `previous` 到 `current` 的增量 (`TaskStateDelta`)。
+    ///
+    /// 调用方应在持有该状态写锁期间调用，使增量按版本号顺序进入各成员的发送队列。
+    /// 增量生成失败 (理论上不会发生) 时退化为广播完整状态。
+    async fn priv_broadcast_state_delta(
+        &self,
+        group_id: &str,
+        previous: &TaskDebugState,
+        current: &TaskDebugState,
+        conn_manager: &ConnectionManager,
+    ) {
+        let delta = match previous.delta_to(current) {
+            Ok(delta) => delta,
+            Err(e) => {
+                error!(
+                    "[任务状态管理器] 为组 '{}' 生成版本 {} -> {} 的状态增量失败: {:?}，改为广播完整状态。",
+                    group_id, previous.version, current.version, e
+                );
+                self.priv_broadcast_task_state(group_id, current, conn_manager).await;
+                return;
+            }
+        };
+        let clients_in_group = conn_manager.get_group_members_for_broadcast(group_id, None).await;
+        if clients_in_group.is_empty() {
+            warn!("[任务状态管理器] 组 '{}' 中没有活动的客户端，取消广播 TaskStateDelta。", group_id);
+            return;
+        }
+        let delta_msg = match rust_websocket_utils::message::WsMessage::new(ws_payloads::TASK_STATE_DELTA_MESSAGE_TYPE.to_string(), &delta) {
+            Ok(msg) => msg,
+            Err(e) => {
+                error!("[任务状态管理器] 序列化组 '{}' 的 TaskStateDelta 失败: {:?}", group_id, e);
+                return;
+            }
+        };
+        info!(
+            "[任务状态管理器] 向组 '{}' 的 {} 个客户端广播版本 {} -> {} 的状态增量 ({} 个操作)。",
+            group_id, clients_in_group.len(), delta.base_version, delta.version, delta.patch.0.len()
+        );
+        for client_session_arc in clients_in_group {
+            if let Err(e) = client_session_arc.sender.send(delta_msg.clone()).await {
+                error!(
+                    "[任务状态管理器] 向客户端 {} (组 '{}') 广播 TaskStateDelta 失败: {:?}",
+                    client_session_arc.client_id, group_id, e
+                );
+            }
+        }
+    }
+
     /// 处理业务消息并返回处理结果。
     ///
     /// # Arguments
@@ -383,7 +832,10 @@ impl TaskStateManager {
     /// * `conn_manager` - 连接管理器实例。
     ///
     /// # Returns
-    /// * `Result<(), String>` - 如果处理成功，则返回 `Ok(())`，否则返回包含错误描述的 `Err(String)`。
+    /// * `Ok(Some(TaskDebugState))` - 处理成功且状态发生了改变 (版本号已递增)，返回更新后状态的克隆。
+    /// * `Ok(None)` - 处理成功但状态没有变化。
+    /// * `Err(StateUpdateError::VersionConflict)` - 负载声明的 `expected_version` 已过期，状态未被修改。
+    /// * `Err(StateUpdateError::Failed)` - 处理失败，包含错误描述。
     pub async fn process_business_message(
         &self,
         group_id: &str,
@@ -392,26 +844,26 @@ impl TaskStateManager {
         source_role: ClientRole,
         source_client_id: &str,
         conn_manager: Arc<ConnectionManager>, // 新增参数
-    ) -> Result<(), String> {
+    ) -> Result<Option<TaskDebugState>, StateUpdateError> {
         info!(
             "[TaskStateManager] Processing business message. GroupID: '{}', Type: '{}', SourceRole: {:?}, SourceClientID: '{}'",
             group_id, message_type, source_role, source_client_id
         );
-        // 权限检查（P7.1.3 待实现）
-        // info!("权限检查点: 角色 {:?} 正在尝试对组 '{}' 执行操作 '{}'", source_role, group_id, message_type);
+        // 权限检查：按授权矩阵在修改状态之前拒绝越权操作 (非业务消息类型在下方作为不支持的类型处理)
+        let action = BusinessAction::from_message_type(message_type);
+        if let Some(action) = action {
+            authorize_action(action, source_role)?;
+        }
 
         let task_state_arc = self.get_task_state(group_id).await.ok_or_else(|| {
             let err_msg = format!("TaskDebugState not found for group_id: {}", group_id);
             error!("{}", err_msg);
-            err_msg
+            StateUpdateError::Failed(err_msg)
         })?;
 
         let mut task_state_guard = task_state_arc.write().await;
-        // 更新元数据
-        // task_state_guard.last_updated_by_client_id = Some(source_client_id.to_string()); // Field does not exist in TaskDebugState
-        task_state_guard.last_updated_by_role = Some(source_role.clone());
-        // last_update_timestamp 会在 priv_handle 方法中如果 state_changed 则更新，或者在这里统一更新
-        // task_state_guard.last_update_timestamp = Utc::now(); // 或者在 priv_handle_* 中处理
+        let previous_state = task_state_guard.clone(); // 用于在状态变更后生成增量
+        // 元数据 (最后更新者、时间戳、版本号) 仅在状态确实改变后统一更新，见下方
 
         let mut state_changed = false;
 
@@ -419,15 +871,19 @@ impl TaskStateManager {
             ws_payloads::UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE => {
                 match serde_json::from_value::<UpdateTaskDebugNotePayload>(payload.clone()) {
                     Ok(parsed_payload) => {
+                        check_expected_version(&task_state_guard, parsed_payload.expected_version)?;
+                        if let Some(custom_shared_data) = parsed_payload.custom_shared_data.as_ref() {
+                            validate_custom_shared_data(&task_state_guard, message_type, custom_shared_data)?;
+                        }
                         match self.priv_handle_update_task_debug_note(&mut task_state_guard, parsed_payload, source_role.clone()).await {
                             Ok(changed) => state_changed = changed,
-                            Err(e) => return Err(format!("Error in priv_handle_update_task_debug_note: {}", e)),
+                            Err(e) => return Err(StateUpdateError::Failed(format!("Error in priv_handle_update_task_debug_note: {}", e))),
                         }
                     }
                     Err(e) => {
                         let err_msg = format!("Failed to parse UpdateTaskDebugNotePayload for group '{}': {}. Payload: {:?}", group_id, e, payload);
                         error!("{}", err_msg);
-                        return Err(err_msg);
+                        return Err(StateUpdateError::Failed(err_msg));
                     }
                 }
             }
@@ -438,17 +894,27 @@ impl TaskStateManager {
             _ => {
                 let err_msg = format!("Unknown or unsupported business message type '{}' received for group '{}'.", message_type, group_id);
                 warn!("{}", err_msg);
-                return Err(err_msg);
+                return Err(StateUpdateError::Failed(err_msg));
             }
         }
 
         if state_changed {
-            // 更新时间戳应在确认状态改变之后，在广播之前
+            if let Some(action) = action {
+                if let Err(denied) = check_writable_fields(action, source_role, &previous_state, &task_state_guard) {
+                    warn!("[TaskStateManager] Reverting unauthorized change in group '{}': {}", group_id, denied);
+                    *task_state_guard = previous_state;
+                    return Err(denied);
+                }
+            }
+            // 更新时间戳和版本号应在确认状态改变之后，在广播之前
+            task_state_guard.last_updated_by_role = Some(source_role);
             task_state_guard.last_update_timestamp = Utc::now();
-            self.priv_broadcast_task_state(group_id, &*task_state_guard, &conn_manager).await; // 调用广播
+            task_state_guard.version += 1; // 与 update_state_and_get_updated 保持一致：每次实际变更递增版本号
+            self.priv_broadcast_state_delta(group_id, &previous_state, &task_state_guard, &conn_manager).await; // 广播增量
+            return Ok(Some(task_state_guard.clone()));
         }
 
-        Ok(())
+        Ok(None)
     }
 
     // P4.2.1: 新增私有方法用于处理更新任务调试备注的逻辑
@@ -584,4 +1050,207 @@ mod tests {
         assert!(true, "这是一个针对P3.1.2骨架实现的占位断言，其设计目的是始终通过测试。它主要验证代码的基本可执行性，而非具体功能逻辑。");
         info!("[单元测试 - TaskStateManager骨架] === TaskStateManager (任务状态管理器) 创建和 init_task_state (初始化任务状态) 调用测试已成功完成。骨架功能按预期执行 (无实际状态操作，主要依赖日志进行验证)。===");
     }
+
+    /// 为每种业务操作构造一个会实际改写状态 (若处理分支已实现) 的示例负载。
+    fn sample_business_payload(action: BusinessAction) -> BusinessActionPayload {
+        use common_models::task_models::{
+            ConfirmSingleTestStepPayload, FeedbackSingleTestStepPayload, SignOffSingleTestStepPayload, StartSingleTestStepPayload,
+            UpdatePreCheckItemPayload,
+        };
+        match action {
+            BusinessAction::UpdatePreCheckItem => BusinessActionPayload::UpdatePreCheckItem(UpdatePreCheckItemPayload {
+                task_id: "task-perm".to_string(),
+                item_id: "item-1".to_string(),
+                status: "Passed".to_string(),
+                notes: Some("ok".to_string()),
+                expected_version: None,
+            }),
+            BusinessAction::StartSingleTestStep => BusinessActionPayload::StartSingleTestStep(StartSingleTestStepPayload {
+                task_id: "task-perm".to_string(),
+                device_id: "pump-1".to_string(),
+                step_id: "step-1".to_string(),
+                command: "RUN_FORWARD_5_SEC".to_string(),
+                expected_version: None,
+            }),
+            BusinessAction::FeedbackSingleTestStep => BusinessActionPayload::FeedbackSingleTestStep(FeedbackSingleTestStepPayload {
+                task_id: "task-perm".to_string(),
+                device_id: "pump-1".to_string(),
+                step_id: "step-1".to_string(),
+                execution_status: "Completed".to_string(),
+                result_data: None,
+                feedback_notes: None,
+                expected_version: None,
+            }),
+            BusinessAction::ConfirmSingleTestStep => BusinessActionPayload::ConfirmSingleTestStep(ConfirmSingleTestStepPayload {
+                task_id: "task-perm".to_string(),
+                device_id: "pump-1".to_string(),
+                step_id: "step-1".to_string(),
+                confirmation_status: "Confirmed".to_string(),
+                expected_version: None,
+            }),
+            BusinessAction::SignOffSingleTestStep => BusinessActionPayload::SignOffSingleTestStep(SignOffSingleTestStepPayload {
+                task_id: "task-perm".to_string(),
+                device_id: "pump-1".to_string(),
+                step_id: "step-1".to_string(),
+                sign_off_status: "Approved".to_string(),
+                notes: None,
+                expected_version: None,
+            }),
+            BusinessAction::UpdateTaskDebugNote => BusinessActionPayload::UpdateTaskDebugNote(UpdateTaskDebugNotePayload {
+                group_id: "group-perm".to_string(),
+                new_note: "note".to_string(),
+                custom_shared_data: None,
+                expected_version: None,
+            }),
+            BusinessAction::UpdateCustomSharedData => BusinessActionPayload::UpdateCustomSharedData(UpdateCustomSharedDataPayload {
+                mode: Default::default(),
+                new_data: serde_json::json!({"k": 2}),
+                pointer: None,
+                expected_version: None,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_permission_matrix_is_enforced_for_every_action_and_role() {
+        for action in BusinessAction::ALL {
+            for role in [ClientRole::ControlCenter, ClientRole::OnSiteMobile, ClientRole::Supervisor, ClientRole::Observer, ClientRole::Unknown] {
+                let manager = Arc::new(TaskStateManager::new());
+                let conn_manager = ConnectionManager::new(manager.clone());
+                manager.init_task_state("group-perm".to_string(), "task-perm".to_string()).await;
+                // 预置一个已被控制中心确认的步骤，使签核操作在授权时可以成功
+                let mut confirmed_step = SingleTestStepStatus::new("step-1".to_string());
+                confirmed_step.confirmation_status_from_control = Some("Confirmed".to_string());
+                manager.get_task_state("group-perm").await.unwrap().write().await.single_test_steps.insert("step-1".to_string(), confirmed_step);
+                let before = manager.get_task_state("group-perm").await.unwrap().read().await.clone();
+
+                let result = manager
+                    .update_state_and_get_updated("group-perm", role, sample_business_payload(action), &conn_manager)
+                    .await;
+                let after = manager.get_task_state("group-perm").await.unwrap().read().await.clone();
+
+                if is_action_permitted(action, role) {
+                    assert!(result.is_ok(), "{} / {:?} 应被允许，实际: {:?}", action, role, result.err().map(|e| e.to_string()));
+                    let writable = permission_for(action, role).unwrap().writable_fields;
+                    for field in changed_state_fields(&before, &after) {
+                        assert!(writable.contains(&field), "{} / {:?} 修改了不可写字段 {:?}", action, role, field);
+                    }
+                } else {
+                    assert!(
+                        matches!(result, Err(StateUpdateError::Rejected(CloudError::Forbidden(_)))),
+                        "{} / {:?} 应以 FORBIDDEN 拒绝",
+                        action,
+                        role
+                    );
+                    assert_eq!(before, after, "{} / {:?} 被拒绝后状态不应改变", action, role);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_signs_off_only_confirmed_steps() {
+        let manager = Arc::new(TaskStateManager::new());
+        let conn_manager = ConnectionManager::new(manager.clone());
+        manager.init_task_state("group-qa".to_string(), "task-qa".to_string()).await;
+        let sign_off = || sample_business_payload(BusinessAction::SignOffSingleTestStep);
+
+        // 未确认的步骤不能签核
+        let result = manager.update_state_and_get_updated("group-qa", ClientRole::Supervisor, sign_off(), &conn_manager).await;
+        assert!(matches!(result, Err(StateUpdateError::Rejected(CloudError::PayloadInvalid { .. }))));
+
+        // 控制中心确认后，QA 主管签核成功
+        manager
+            .update_state_and_get_updated("group-qa", ClientRole::ControlCenter, sample_business_payload(BusinessAction::ConfirmSingleTestStep), &conn_manager)
+            .await
+            .unwrap()
+            .expect("确认应改变状态");
+        let signed = manager
+            .update_state_and_get_updated("group-qa", ClientRole::Supervisor, sign_off(), &conn_manager)
+            .await
+            .unwrap()
+            .expect("签核应改变状态");
+        assert_eq!(signed.single_test_steps["step-1"].sign_off_status_from_supervisor.as_deref(), Some("Approved"));
+        assert_eq!(signed.last_updated_by_role, Some(ClientRole::Supervisor));
+    }
+
+    #[test]
+    fn test_writable_field_violation_is_forbidden() {
+        let before = TaskDebugState::new("task-perm".to_string());
+        let mut after = before.clone();
+        let mut item = PreCheckItemStatus::new("item-1".to_string());
+        item.status_from_control = Some("Passed".to_string());
+        after.pre_check_items.insert("item-1".to_string(), item);
+
+        assert!(check_writable_fields(BusinessAction::UpdatePreCheckItem, ClientRole::ControlCenter, &before, &after).is_ok());
+        assert!(matches!(
+            check_writable_fields(BusinessAction::UpdatePreCheckItem, ClientRole::OnSiteMobile, &before, &after),
+            Err(StateUpdateError::Rejected(CloudError::Forbidden(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_note_cannot_touch_custom_shared_data() {
+        let manager = Arc::new(TaskStateManager::new());
+        let conn_manager = ConnectionManager::new(manager.clone());
+        manager.init_task_state("group-qa".to_string(), "task-qa".to_string()).await;
+        let note_with_data = BusinessActionPayload::UpdateTaskDebugNote(UpdateTaskDebugNotePayload {
+            group_id: "group-qa".to_string(),
+            new_note: "QA 备注".to_string(),
+            custom_shared_data: Some(serde_json::json!({"k": 1})),
+            expected_version: None,
+        });
+
+        // QA 主管可以写备注，但不能借备注操作改写自定义共享数据；越权修改被整体撤销
+        let result = manager.update_state_and_get_updated("group-qa", ClientRole::Supervisor, note_with_data, &conn_manager).await;
+        assert!(matches!(result, Err(StateUpdateError::Rejected(CloudError::Forbidden(_)))));
+        let state = manager.get_task_state("group-qa").await.unwrap().read().await.clone();
+        assert_eq!(state.version, 0);
+        assert!(state.general_debug_notes.is_none() && state.custom_shared_data.is_none());
+    }
+
+    #[test]
+    fn test_processed_message_window_keeps_latest_acks_per_group() {
+        let manager = TaskStateManager::new().with_processed_message_window(2);
+        let ack = |version| AckPayload::applied(UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, version);
+        manager.record_processed("g1", "m1", &ack(1));
+        manager.record_processed("g1", "m2", &ack(2));
+        manager.record_processed("g2", "m1", &ack(9));
+        assert_eq!(manager.processed_ack("g1", "m1"), Some(ack(1)));
+        assert_eq!(manager.processed_ack("g2", "m1"), Some(ack(9)));
+
+        // 超出窗口后淘汰该组最早的记录
+        manager.record_processed("g1", "m3", &ack(3));
+        assert_eq!(manager.processed_ack("g1", "m1"), None);
+        assert_eq!(manager.processed_ack("g1", "m3"), Some(ack(3)));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_of_in_flight_message_waits_for_its_outcome() {
+        let manager = Arc::new(TaskStateManager::new());
+        let ack = |version| AckPayload::applied(UPDATE_TASK_DEBUG_NOTE_MESSAGE_TYPE, version);
+
+        // 同一消息从另一个连接到达时等待第一条的最终确认，而不是再次应用
+        let reservation = manager.begin_processing("g1", "m1").await.unwrap();
+        let duplicate = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.begin_processing("g1", "m1").await.err() }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!duplicate.is_finished(), "第一条仍在处理中时重复的消息应等待");
+        assert!(manager.begin_processing("g1", "m2").await.is_ok(), "其他消息不受影响");
+        manager.record_processed("g1", "m1", &ack(1));
+        drop(reservation);
+        assert_eq!(duplicate.await.unwrap(), Some(ack(1)));
+
+        // 未得到最终确认 (例如版本冲突) 时预留被撤销，等待者重新处理该消息
+        let reservation = manager.begin_processing("g1", "m3").await.unwrap();
+        let retry = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.begin_processing("g1", "m3").await.is_ok() }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        drop(reservation);
+        assert!(retry.await.unwrap());
+    }
 } // 单元测试模块结束 
\ No newline at end of file